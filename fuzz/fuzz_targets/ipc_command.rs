@@ -0,0 +1,22 @@
+//! Socket command lines: parsing must never panic, and anything that parses
+//! must come back unchanged from its wire form.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use sway_alttab_gui::ipc::{InputCommand, IpcCommand};
+
+fuzz_target!(|line: &str| {
+    if let Ok(command) = line.parse::<IpcCommand>() {
+        let reparsed: IpcCommand = command
+            .to_string()
+            .parse()
+            .expect("wire form of a parsed command parses");
+        assert_eq!(reparsed, command);
+    }
+
+    // Lines sent on a `watch` connection
+    if let Ok(input) = line.parse::<InputCommand>() {
+        assert_eq!(input.to_string().parse::<InputCommand>().ok(), Some(input));
+    }
+});
@@ -0,0 +1,18 @@
+//! Raw socket lines: framing must never panic, and a line that decodes
+//! goes on to the command parser like the socket server does.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use sway_alttab_gui::ipc::{InputCommand, IpcCommand};
+use sway_alttab_gui::socket_server::{decode_line, MAX_LINE};
+
+fuzz_target!(|data: &[u8]| {
+    // The server never reads more than MAX_LINE bytes of one line
+    let buf = data[..data.len().min(MAX_LINE)].to_vec();
+    if let Ok(line) = decode_line(buf) {
+        assert!(!line.ends_with('\n'));
+        let _ = line.parse::<IpcCommand>();
+        let _ = line.parse::<InputCommand>();
+    }
+});
@@ -0,0 +1,18 @@
+//! Frontend protocol lines: decoding must never panic, and any event that
+//! decodes must encode back to the same event.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use sway_alttab_gui::ipc::SwitcherEvent;
+
+fuzz_target!(|data: &[u8]| {
+    for line in data.split(|&b| b == b'\n') {
+        if let Ok(event) = serde_json::from_slice::<SwitcherEvent>(line) {
+            let encoded = serde_json::to_string(&event).expect("event encodes");
+            let decoded: SwitcherEvent =
+                serde_json::from_str(&encoded).expect("encoded event decodes");
+            assert_eq!(decoded, event);
+        }
+    }
+});
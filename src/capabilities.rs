@@ -0,0 +1,222 @@
+//! Startup probe for what the compositor and system provide. Features that
+//! need something missing are turned off before the first show, with a log
+//! message saying why, instead of failing halfway through it.
+
+use crate::config::{Config, Placement, SwitcherAction};
+use crate::ipc::Trigger;
+use anyhow::Result;
+use std::path::Path;
+use tracing::{debug, warn};
+use wayland_client::globals::{registry_queue_init, GlobalListContents};
+use wayland_client::protocol::wl_registry::{self, WlRegistry};
+use wayland_client::{Connection, Dispatch, QueueHandle};
+
+/// Wayland globals grim can capture the screen through
+const SCREENCOPY_GLOBALS: [&str; 2] =
+    ["zwlr_screencopy_manager_v1", "ext_image_copy_capture_manager_v1"];
+
+/// What the switcher's optional features depend on.
+///
+/// Foreign-toplevel management isn't probed: the window list and every
+/// window command go through sway's IPC, which knows more than it does.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Capabilities {
+    /// The compositor supports wlr-layer-shell, for the overlay and the dock
+    pub layer_shell: bool,
+    /// The compositor lets clients copy the screen, which grim needs for
+    /// previews and screenshots
+    pub screencopy: bool,
+    /// grim is installed, for previews and screenshots
+    pub grim: bool,
+}
+
+impl Capabilities {
+    /// Probe the running session. GTK must be initialized.
+    pub fn probe() -> Self {
+        let screencopy = match wayland_globals() {
+            Ok(globals) => has_screencopy(&globals),
+            Err(e) => {
+                // Leave it to grim to report what is wrong
+                debug!("Couldn't list the Wayland globals: {:#}", e);
+                true
+            }
+        };
+        Capabilities {
+            layer_shell: gtk4_layer_shell::is_supported(),
+            screencopy,
+            grim: program_in_path("grim"),
+        }
+    }
+}
+
+/// Keeps no state: the globals are only listed once
+struct RegistryProbe;
+
+impl Dispatch<WlRegistry, GlobalListContents> for RegistryProbe {
+    fn event(
+        _: &mut Self,
+        _: &WlRegistry,
+        _: wl_registry::Event,
+        _: &GlobalListContents,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+/// Interface names of the globals the compositor advertises, from a
+/// connection of our own
+fn wayland_globals() -> Result<Vec<String>> {
+    let connection = Connection::connect_to_env()?;
+    let (globals, _queue) = registry_queue_init::<RegistryProbe>(&connection)?;
+    Ok(globals
+        .contents()
+        .clone_list()
+        .into_iter()
+        .map(|global| global.interface)
+        .collect())
+}
+
+/// Whether one of `globals` is a screen copy protocol grim speaks
+fn has_screencopy(globals: &[String]) -> bool {
+    globals
+        .iter()
+        .any(|interface| SCREENCOPY_GLOBALS.contains(&interface.as_str()))
+}
+
+/// Turn off the features whose requirements are missing, returning why
+/// for each
+#[must_use]
+pub fn degrade(config: &mut Config, capabilities: Capabilities) -> Vec<String> {
+    let mut notes = Vec::new();
+    if !capabilities.layer_shell {
+        if config.placement == Placement::LayerShell {
+            config.placement = Placement::Floating;
+            notes.push(
+                "The compositor has no layer shell, using a floating window \
+                 (see `sway-alttab-gui sway-rules`)"
+                    .to_string(),
+            );
+        }
+        if config.file.dock.size > 0 {
+            config.file.dock.size = 0;
+            notes.push("The compositor has no layer shell, the dock is disabled".to_string());
+        }
+        if config.file.taskbar.enabled {
+            config.file.taskbar.enabled = false;
+            notes.push("The compositor has no layer shell, the taskbar is disabled".to_string());
+        }
+    }
+    let missing = if !capabilities.screencopy {
+        Some("The compositor doesn't allow screen copies")
+    } else if !capabilities.grim {
+        Some("grim is not installed")
+    } else {
+        None
+    };
+    if let Some(missing) = missing {
+        if config.file.preview.enabled {
+            config.file.preview.enabled = false;
+            notes.push(format!("{}, previews are disabled", missing));
+        }
+        let actions = &config.file.actions;
+        let screenshot_bound = [
+            Trigger::Enter,
+            Trigger::ShiftEnter,
+            Trigger::CtrlEnter,
+            Trigger::Release,
+            Trigger::ShiftRelease,
+        ]
+        .into_iter()
+        .any(|trigger| actions.action_for(trigger) == SwitcherAction::Screenshot);
+        if screenshot_bound {
+            notes.push(format!("{}, screenshots will fail", missing));
+        }
+    }
+    notes
+}
+
+/// Probe the session and turn off what it can't support, logging each change
+pub fn probe_and_degrade(config: &mut Config) {
+    for note in degrade(config, Capabilities::probe()) {
+        warn!("{}", note);
+    }
+}
+
+/// Whether an executable with this name is on PATH
+fn program_in_path(program: &str) -> bool {
+    std::env::var_os("PATH").is_some_and(|path| {
+        std::env::split_paths(&path).any(|dir| is_executable(&dir.join(program)))
+    })
+}
+
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    path.metadata()
+        .is_ok_and(|meta| meta.is_file() && meta.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+
+    fn config_with(toml: &str) -> Config {
+        let mut config = Config::parse_from(["sway-alttab-gui", "daemon"]);
+        config.file = crate::config::FileConfig::from_toml(toml).unwrap();
+        config
+    }
+
+    #[test]
+    fn test_degrade_nothing_missing() {
+        let mut config = config_with("[dock]\nsize = 3\n[preview]\nenabled = true");
+        let all = Capabilities {
+            layer_shell: true,
+            screencopy: true,
+            grim: true,
+        };
+        assert!(degrade(&mut config, all).is_empty());
+        assert_eq!(config.file.dock.size, 3);
+        assert!(config.file.preview.enabled);
+    }
+
+    #[test]
+    fn test_degrade_without_layer_shell_or_grim() {
+        let mut config = config_with(
+            "[dock]\nsize = 3\n[taskbar]\nenabled = true\n[preview]\nenabled = true\n\
+             [actions]\nenter = \"screenshot\"",
+        );
+        let none = Capabilities {
+            layer_shell: false,
+            screencopy: false,
+            grim: false,
+        };
+        let notes = degrade(&mut config, none);
+        assert_eq!(notes.len(), 5);
+        assert_eq!(config.placement, Placement::Floating);
+        assert_eq!(config.file.dock.size, 0);
+        assert!(!config.file.taskbar.enabled);
+        assert!(!config.file.preview.enabled);
+    }
+
+    #[test]
+    fn test_degrade_without_screencopy() {
+        let mut config = config_with("[preview]\nenabled = true");
+        let no_screencopy = Capabilities {
+            layer_shell: true,
+            screencopy: false,
+            grim: true,
+        };
+        let notes = degrade(&mut config, no_screencopy);
+        assert_eq!(notes, ["The compositor doesn't allow screen copies, previews are disabled"]);
+        assert!(!config.file.preview.enabled);
+    }
+
+    #[test]
+    fn test_has_screencopy() {
+        let globals = |names: &[&str]| names.iter().map(|n| n.to_string()).collect::<Vec<_>>();
+        assert!(has_screencopy(&globals(&["wl_shm", "zwlr_screencopy_manager_v1"])));
+        assert!(has_screencopy(&globals(&["ext_image_copy_capture_manager_v1"])));
+        assert!(!has_screencopy(&globals(&["wl_shm", "zwlr_layer_shell_v1"])));
+    }
+}
@@ -0,0 +1,356 @@
+//! The compositor the daemon talks to: tree queries, commands and the
+//! window event stream behind one trait, so a backend is a single
+//! implementation. Sway's is [`RealSwayClient`](crate::sway_client::RealSwayClient).
+//!
+//! The types are swayipc's, which i3 shares; other compositors would map
+//! their state onto them.
+
+use crate::event_bus::WindowEvent;
+use anyhow::Result;
+use futures_lite::Stream;
+use std::future::Future;
+use std::pin::Pin;
+use swayipc::{Node, Output, Workspace};
+
+/// Window events as the daemon sees them; an error ends the stream
+pub type WindowEvents = Pin<Box<dyn Stream<Item = Result<WindowEvent>> + Send>>;
+
+/// Subscribing to window events, which finishes on the event loop
+pub type Subscription = Pin<Box<dyn Future<Output = Result<WindowEvents>> + Send>>;
+
+/// Queries, commands and events of a compositor.
+///
+/// Queries and commands block; events come from a connection of their own.
+pub trait Compositor: Send + Sized + 'static {
+    /// Get the full window tree
+    fn get_tree(&mut self) -> Result<Node>;
+
+    /// Get the list of workspaces
+    fn get_workspaces(&mut self) -> Result<Vec<Workspace>>;
+
+    /// Get the list of outputs
+    fn get_outputs(&mut self) -> Result<Vec<Output>>;
+
+    /// Focus a window by its container ID.
+    ///
+    /// Returns a [`CommandError`](crate::sway_client::CommandError) if the
+    /// compositor reports that the command failed (e.g. the window no
+    /// longer exists).
+    fn focus_window(&mut self, window_id: i64) -> Result<()>;
+
+    /// Switch to a workspace and then focus a window on it, as a single
+    /// command list, so the workspace switch is recorded in the history.
+    fn focus_window_on_workspace(&mut self, window_id: i64, workspace: &str) -> Result<()>;
+
+    /// Move a window to a workspace and focus it
+    fn move_window_to_workspace(&mut self, window_id: i64, workspace: &str) -> Result<()>;
+
+    /// Toggle a window between tiling and floating, and focus it
+    fn toggle_floating(&mut self, window_id: i64) -> Result<()>;
+
+    /// Ask a window to close
+    fn close_window(&mut self, window_id: i64) -> Result<()>;
+
+    /// Run a shell command through the compositor's `exec`
+    fn exec(&mut self, command: &str) -> Result<()>;
+
+    /// Open another connection to the same compositor, e.g. for a
+    /// background thread, or to replace one that broke
+    fn connect_again(&self) -> Result<Self>;
+
+    /// Subscribe to focus and new window events
+    fn window_events(&self) -> Subscription;
+}
+
+/// Everything a window list refresh reads from the compositor
+#[derive(Debug)]
+pub struct Snapshot {
+    pub tree: Node,
+    /// None if they couldn't be listed; the last known values are kept
+    pub workspaces: Option<Vec<Workspace>>,
+    pub outputs: Option<Vec<Output>>,
+}
+
+/// Read a snapshot through `compositor`. Only the tree is required.
+pub fn fetch_snapshot(compositor: &mut impl Compositor) -> Result<Snapshot> {
+    Ok(Snapshot {
+        tree: compositor.get_tree()?,
+        workspaces: compositor.get_workspaces().ok(),
+        outputs: compositor.get_outputs().ok(),
+    })
+}
+
+/// Reads snapshots on tokio's blocking pool, so the event loop can carry
+/// on while a slow compositor answers. Keeps its connection between
+/// reads and opens a new one after a failure.
+pub struct BackgroundReader<C: Compositor> {
+    /// Taken while a read is in flight
+    connection: Option<C>,
+    /// Whether the last read failed, so the connection may be broken
+    broken: bool,
+}
+
+impl<C: Compositor> BackgroundReader<C> {
+    pub fn new(connection: C) -> Self {
+        BackgroundReader {
+            connection: Some(connection),
+            broken: false,
+        }
+    }
+
+    pub async fn fetch(&mut self) -> Result<Snapshot> {
+        let Some(mut connection) = self.connection.take() else {
+            anyhow::bail!("The last background read never finished");
+        };
+        if self.broken {
+            match connection.connect_again() {
+                Ok(fresh) => connection = fresh,
+                Err(e) => {
+                    self.connection = Some(connection);
+                    return Err(e);
+                }
+            }
+        }
+        let (connection, snapshot) = tokio::task::spawn_blocking(move || {
+            let snapshot = fetch_snapshot(&mut connection);
+            (connection, snapshot)
+        })
+        .await?;
+        self.broken = snapshot.is_err();
+        self.connection = Some(connection);
+        snapshot
+    }
+}
+
+/// A compositor kept in memory, for tests of the daemon. Commands change
+/// its state like sway would and are recorded in sway's syntax.
+#[cfg(test)]
+pub mod mock {
+    use super::*;
+    use crate::sway_client::CommandError;
+    use serde_json::{json, Value};
+    use std::sync::{Arc, Mutex};
+
+    /// A window in a [`MockCompositor`]
+    #[derive(Debug, Clone)]
+    pub struct MockWindow {
+        pub id: i64,
+        pub app_id: String,
+        pub workspace: String,
+        pub fullscreen: bool,
+    }
+
+    impl MockWindow {
+        pub fn new(id: i64, app_id: &str, workspace: &str) -> Self {
+            MockWindow {
+                id,
+                app_id: app_id.to_string(),
+                workspace: workspace.to_string(),
+                fullscreen: false,
+            }
+        }
+    }
+
+    #[derive(Debug, Default)]
+    pub struct MockState {
+        /// Most recently focused first; the first window has the focus
+        pub windows: Vec<MockWindow>,
+        /// Commands run so far
+        pub commands: Vec<String>,
+        /// Reject the next command with this error
+        pub fail_next: Option<CommandError>,
+    }
+
+    /// Clones share their state, so a test keeps a handle on what the
+    /// daemon did
+    #[derive(Debug, Clone, Default)]
+    pub struct MockCompositor {
+        pub state: Arc<Mutex<MockState>>,
+    }
+
+    impl MockCompositor {
+        pub fn new(windows: Vec<MockWindow>) -> Self {
+            let state = MockState {
+                windows,
+                ..MockState::default()
+            };
+            MockCompositor {
+                state: Arc::new(Mutex::new(state)),
+            }
+        }
+
+        /// The commands run so far
+        pub fn commands(&self) -> Vec<String> {
+            self.state.lock().unwrap().commands.clone()
+        }
+
+        /// Make the next command fail with `error`
+        pub fn fail_next(&self, error: CommandError) {
+            self.state.lock().unwrap().fail_next = Some(error);
+        }
+
+        /// The focused window
+        pub fn focused(&self) -> Option<i64> {
+            self.state.lock().unwrap().windows.first().map(|w| w.id)
+        }
+
+        fn run(&mut self, command: String, change: impl FnOnce(&mut MockState)) -> Result<()> {
+            let mut state = self.state.lock().unwrap();
+            state.commands.push(command.clone());
+            if let Some(error) = state.fail_next.take() {
+                return Err(error.into());
+            }
+            change(&mut state);
+            Ok(())
+        }
+
+        fn workspace_names(&self) -> Vec<String> {
+            let mut names: Vec<String> = Vec::new();
+            for window in &self.state.lock().unwrap().windows {
+                if !names.contains(&window.workspace) {
+                    names.push(window.workspace.clone());
+                }
+            }
+            names
+        }
+    }
+
+    fn rect() -> Value {
+        json!({"x": 0, "y": 0, "width": 0, "height": 0})
+    }
+
+    /// swayipc's types are #[non_exhaustive], so build them from sway's JSON
+    fn node(id: i64, node_type: &str, name: &str, extra: Value) -> Value {
+        let mut node = json!({
+            "id": id,
+            "name": name,
+            "type": node_type,
+            "border": "none",
+            "current_border_width": 0,
+            "layout": "splith",
+            "rect": rect(),
+            "window_rect": rect(),
+            "deco_rect": rect(),
+            "geometry": rect(),
+            "urgent": false,
+            "focused": false,
+            "focus": [],
+            "nodes": [],
+            "floating_nodes": [],
+            "sticky": false,
+        });
+        node.as_object_mut()
+            .unwrap()
+            .extend(extra.as_object().unwrap().clone());
+        node
+    }
+
+    impl Compositor for MockCompositor {
+        fn get_tree(&mut self) -> Result<Node> {
+            let names = self.workspace_names();
+            let state = self.state.lock().unwrap();
+            let workspaces: Vec<Value> = names
+                .iter()
+                .zip(1000..)
+                .map(|(name, id)| {
+                    let windows: Vec<&MockWindow> =
+                        state.windows.iter().filter(|w| &w.workspace == name).collect();
+                    let nodes: Vec<Value> = windows
+                        .iter()
+                        .map(|w| {
+                            let extra = json!({
+                                "pid": 1,
+                                "app_id": w.app_id,
+                                "focused": Some(w.id) == state.windows.first().map(|f| f.id),
+                                "fullscreen_mode": u8::from(w.fullscreen),
+                            });
+                            node(w.id, "con", &format!("{} window", w.app_id), extra)
+                        })
+                        .collect();
+                    let focus: Vec<i64> = windows.iter().map(|w| w.id).collect();
+                    node(id, "workspace", name, json!({"nodes": nodes, "focus": focus}))
+                })
+                .collect();
+            let focus: Vec<i64> = (1000..).take(workspaces.len()).collect();
+            // Ids well clear of the windows', so a lookup by id finds the window
+            let output =
+                node(902, "output", "MOCK-1", json!({"nodes": workspaces, "focus": focus}));
+            let root = node(901, "root", "root", json!({"nodes": [output], "focus": [902]}));
+            Ok(serde_json::from_value(root)?)
+        }
+
+        fn get_workspaces(&mut self) -> Result<Vec<Workspace>> {
+            let names = self.workspace_names();
+            let workspaces: Vec<Value> = names
+                .iter()
+                .zip(1000..)
+                .enumerate()
+                .map(|(i, (name, id))| {
+                    json!({
+                        "id": id,
+                        "num": -1,
+                        "name": name,
+                        "visible": i == 0,
+                        "focused": i == 0,
+                        "urgent": false,
+                        "representation": null,
+                        "rect": rect(),
+                        "output": "MOCK-1",
+                    })
+                })
+                .collect();
+            Ok(serde_json::from_value(Value::Array(workspaces))?)
+        }
+
+        fn get_outputs(&mut self) -> Result<Vec<Output>> {
+            anyhow::bail!("The mock compositor has no outputs")
+        }
+
+        fn focus_window(&mut self, window_id: i64) -> Result<()> {
+            self.run(format!("[con_id={}] focus", window_id), |state| {
+                if let Some(index) = state.windows.iter().position(|w| w.id == window_id) {
+                    let window = state.windows.remove(index);
+                    state.windows.insert(0, window);
+                }
+            })
+        }
+
+        fn focus_window_on_workspace(&mut self, window_id: i64, workspace: &str) -> Result<()> {
+            self.run(format!("workspace {}; [con_id={}] focus", workspace, window_id), |_| {})?;
+            // Recorded once, as the command list it is
+            self.state.lock().unwrap().commands.pop();
+            self.focus_window(window_id)
+        }
+
+        fn move_window_to_workspace(&mut self, window_id: i64, workspace: &str) -> Result<()> {
+            let command = format!("[con_id={}] move to workspace {}", window_id, workspace);
+            self.run(command, |state| {
+                if let Some(window) = state.windows.iter_mut().find(|w| w.id == window_id) {
+                    window.workspace = workspace.to_string();
+                }
+            })
+        }
+
+        fn toggle_floating(&mut self, window_id: i64) -> Result<()> {
+            self.run(format!("[con_id={}] floating toggle", window_id), |_| {})
+        }
+
+        fn close_window(&mut self, window_id: i64) -> Result<()> {
+            self.run(format!("[con_id={}] kill", window_id), |state| {
+                state.windows.retain(|w| w.id != window_id);
+            })
+        }
+
+        fn exec(&mut self, command: &str) -> Result<()> {
+            self.run(format!("exec {}", command), |_| {})
+        }
+
+        fn connect_again(&self) -> Result<Self> {
+            Ok(self.clone())
+        }
+
+        fn window_events(&self) -> Subscription {
+            Box::pin(async { Ok(Box::pin(futures_lite::stream::empty()) as WindowEvents) })
+        }
+    }
+}
@@ -1,20 +1,1103 @@
+use crate::ipc::{InputCommand, LayoutFormat, Trigger};
+use crate::window_manager::WindowInfo;
+use anyhow::{Context, Result};
 use clap::{Parser, Subcommand, ValueEnum};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
 
-#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum, Deserialize)]
+#[serde(rename_all = "kebab-case")]
 pub enum WorkspaceMode {
     /// Show windows from current workspace only
     #[default]
     Current,
     /// Show windows from all workspaces
     All,
+    /// Show windows from all workspaces, current workspace first with a
+    /// separator before the rest
+    Hybrid,
+    /// Show windows from all workspaces, the current workspace's in a row
+    /// above the rest
+    Split,
+}
+
+impl WorkspaceMode {
+    /// Whether the current workspace's windows lead the list
+    #[must_use]
+    pub fn current_first(self) -> bool {
+        matches!(self, WorkspaceMode::Hybrid | WorkspaceMode::Split)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SortMode {
+    /// Most recently used first
+    #[default]
+    Mru,
+    /// Group by output, ordered by layout position (left monitor first),
+    /// most recently used first within each output
+    Output,
+    /// By workspace number (named workspaces last), most recently used
+    /// first within each workspace
+    Workspace,
+}
+
+/// Which sway tree nodes count as windows
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum WindowDetection {
+    /// Views with a pid
+    #[default]
+    Pid,
+    /// Views with a pid, an app_id or X11 window properties
+    Lenient,
+}
+
+/// How the GTK switcher window is placed on screen
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum Placement {
+    /// A layer-shell overlay (falls back to `floating` if unsupported)
+    #[default]
+    LayerShell,
+    /// A normal window, placed by the rules from `sway-rules`
+    Floating,
+}
+
+/// How the daemon draws the switcher
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum Frontend {
+    /// GTK layer-shell window
+    #[default]
+    Gtk,
+    /// The terminal the daemon runs in (needs the `tui` feature)
+    Tui,
+    /// None; external frontends draw the switcher from `watch`
+    None,
+}
+
+impl Frontend {
+    /// Name used on the command line
+    #[must_use]
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Frontend::Gtk => "gtk",
+            Frontend::Tui => "tui",
+            Frontend::None => "none",
+        }
+    }
+}
+
+/// What the switcher offers to switch between
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SwitchTarget {
+    /// Every window
+    #[default]
+    Windows,
+    /// One entry per application (its most recently used window)
+    Apps,
+    /// One entry per workspace (its most recently used window)
+    Workspaces,
+}
+
+impl SwitchTarget {
+    /// Name used on the command line and in IPC commands
+    #[must_use]
+    pub fn as_str(self) -> &'static str {
+        match self {
+            SwitchTarget::Windows => "windows",
+            SwitchTarget::Apps => "apps",
+            SwitchTarget::Workspaces => "workspaces",
+        }
+    }
+}
+
+/// What to do with the selected window when the switcher is activated
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SwitcherAction {
+    /// Focus the selected window
+    Focus,
+    /// Move the selected window to the current workspace and focus it
+    MoveHere,
+    /// Launch a new instance of the selected window's application
+    LaunchNew,
+    /// Close the switcher without doing anything
+    Cancel,
+    /// Focus the selected window and capture it with grim
+    Screenshot,
+}
+
+impl SwitcherAction {
+    /// The input command the daemon handles for this action
+    #[must_use]
+    pub fn input_command(self) -> InputCommand {
+        match self {
+            SwitcherAction::Focus => InputCommand::Select,
+            SwitcherAction::MoveHere => InputCommand::MoveHere,
+            SwitcherAction::LaunchNew => InputCommand::LaunchNew,
+            SwitcherAction::Cancel => InputCommand::Cancel,
+            SwitcherAction::Screenshot => InputCommand::Screenshot,
+        }
+    }
+}
+
+/// Maps each way of activating the switcher to an action
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+pub struct ActionMap {
+    pub enter: SwitcherAction,
+    pub shift_enter: SwitcherAction,
+    pub ctrl_enter: SwitcherAction,
+    pub release: SwitcherAction,
+    pub shift_release: SwitcherAction,
+}
+
+impl Default for ActionMap {
+    fn default() -> Self {
+        ActionMap {
+            enter: SwitcherAction::Focus,
+            shift_enter: SwitcherAction::MoveHere,
+            ctrl_enter: SwitcherAction::LaunchNew,
+            release: SwitcherAction::Focus,
+            shift_release: SwitcherAction::Focus,
+        }
+    }
+}
+
+impl ActionMap {
+    /// Get the action bound to a trigger
+    #[must_use]
+    pub fn action_for(&self, trigger: Trigger) -> SwitcherAction {
+        match trigger {
+            Trigger::Enter => self.enter,
+            Trigger::ShiftEnter => self.shift_enter,
+            Trigger::CtrlEnter => self.ctrl_enter,
+            Trigger::Release => self.release,
+            Trigger::ShiftRelease => self.shift_release,
+        }
+    }
+}
+
+/// Default file for the screenshot action; `{app_id}` and `{time}` (Unix
+/// seconds) are filled in
+pub const DEFAULT_SCREENSHOT_PATH: &str = "~/Pictures/{app_id}-{time}.png";
+
+/// Where the screenshot action saves its captures
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+pub struct ScreenshotConfig {
+    /// File to write; `~` is the home directory
+    pub path: String,
+    /// Copy the image to the clipboard with wl-copy instead of saving it
+    pub clipboard: bool,
+}
+
+impl Default for ScreenshotConfig {
+    fn default() -> Self {
+        ScreenshotConfig {
+            path: DEFAULT_SCREENSHOT_PATH.to_string(),
+            clipboard: false,
+        }
+    }
+}
+
+impl ScreenshotConfig {
+    /// The shell command that captures `geometry` (grim's `x,y wxh`)
+    #[must_use]
+    pub fn grim_command(&self, geometry: &str, app_id: &str, time: u64) -> String {
+        let geometry = shell_quote(geometry);
+        if self.clipboard {
+            return format!("grim -g {} - | wl-copy --type image/png", geometry);
+        }
+        let path = self
+            .path
+            .replace("{app_id}", app_id)
+            .replace("{time}", &time.to_string());
+        let path = expand_home(&path).to_string_lossy().into_owned();
+        format!("grim -g {} {}", geometry, shell_quote(&path))
+    }
+}
+
+/// Theme sounds for a finished switch, for audio confirmation. They are
+/// played with libcanberra's `canberra-gtk-play`.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+pub struct SoundConfig {
+    pub enabled: bool,
+    /// Sound theme event played when a window is picked
+    pub select: String,
+    /// Sound theme event played when the switcher is cancelled
+    pub cancel: String,
+}
+
+impl Default for SoundConfig {
+    fn default() -> Self {
+        SoundConfig {
+            enabled: false,
+            select: "complete".to_string(),
+            cancel: "bell".to_string(),
+        }
+    }
+}
+
+impl SoundConfig {
+    /// The shell command playing the sound for a pick or a cancel, None
+    /// if sounds are off
+    #[must_use]
+    pub fn command(&self, cancelled: bool) -> Option<String> {
+        if !self.enabled {
+            return None;
+        }
+        let event = if cancelled { &self.cancel } else { &self.select };
+        Some(format!(
+            "canberra-gtk-play -i {} -d sway-alttab-gui",
+            shell_quote(event)
+        ))
+    }
+}
+
+/// Where each finished switch is recorded as a JSON line
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+pub struct SelectionLogConfig {
+    /// File to append to, or a FIFO to write to; `~` is the home
+    /// directory. None (the default) records nothing.
+    pub path: Option<String>,
+}
+
+/// `path` with a leading `~/` replaced by the home directory
+#[must_use]
+pub fn expand_home(path: &str) -> PathBuf {
+    match (path.strip_prefix("~/"), dirs::home_dir()) {
+        (Some(rest), Some(home)) => home.join(rest),
+        _ => PathBuf::from(path),
+    }
+}
+
+/// Which windows the switcher leaves out
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+pub struct FilterConfig {
+    /// Hide windows not focused for this many minutes until the show-all
+    /// key is pressed; 0 (the default) hides nothing
+    pub hide_idle_after: u64,
+}
+
+/// What `show` does while the focused window is fullscreen
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum FullscreenAction {
+    /// Show the switcher as usual
+    #[default]
+    Show,
+    /// Ignore `show`, as if the daemon was paused
+    Pause,
+    /// Switch to the most recently used other window right away, without
+    /// showing the switcher
+    Toggle,
+}
+
+/// How the switcher behaves over fullscreen windows (games, videos)
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+pub struct FullscreenConfig {
+    pub action: FullscreenAction,
+}
+
+/// Which focus changes move a window to the front of the MRU order
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum MruUpdate {
+    /// Every focus change, taking the last of a burst (e.g. a workspace
+    /// switch focusing another window on the way)
+    #[default]
+    Focus,
+    /// Only a focus that lasted `settle-ms`, so windows focused briefly on
+    /// the way (by scripts, or a pointer crossing them) are left out
+    Settled,
+    /// Only windows picked in the switcher (or focused by the daemon's
+    /// other commands)
+    Selection,
+}
+
+/// How the MRU order follows focus
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+pub struct MruConfig {
+    pub update: MruUpdate,
+    /// How long a focus must last to count with `update = "settled"`
+    pub settle_ms: u64,
+}
+
+impl Default for MruConfig {
+    fn default() -> Self {
+        MruConfig {
+            update: MruUpdate::Focus,
+            settle_ms: 300,
+        }
+    }
+}
+
+/// Thumbnail of the selected window shown above the tiles while switching.
+/// Not live: it is the capture taken when the window was last picked.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+pub struct PreviewConfig {
+    pub enabled: bool,
+    /// Scale of the captured thumbnails, passed to grim's `-s`
+    pub scale: f64,
+}
+
+impl Default for PreviewConfig {
+    fn default() -> Self {
+        PreviewConfig {
+            enabled: false,
+            scale: 0.25,
+        }
+    }
+}
+
+/// A command bound to a key in the switcher, run for the selected window.
+/// `{con_id}`, `{app_id}`, `{pid}`, `{title}` and `{workspace}` in the
+/// command are replaced with the window's values.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct CustomAction {
+    /// GDK key name, e.g. `s` or `F2`
+    pub key: String,
+    /// Shell command, run through sway's `exec`
+    pub command: String,
+}
+
+impl CustomAction {
+    /// The command with the window's fields filled in. Text fields are
+    /// shell-quoted so titles can't inject commands.
+    #[must_use]
+    pub fn command_for(&self, window: &WindowInfo, pid: Option<i32>) -> String {
+        self.command
+            .replace("{con_id}", &window.id.to_string())
+            .replace("{pid}", &pid.map(|pid| pid.to_string()).unwrap_or_default())
+            .replace("{app_id}", &shell_quote(window.app_key().unwrap_or_default()))
+            .replace("{workspace}", &shell_quote(&window.workspace))
+            .replace("{title}", &shell_quote(&window.title))
+    }
+}
+
+/// Quote a string as a single shell word
+#[must_use]
+pub fn shell_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "'\\''"))
+}
+
+/// A named set of overrides for the command line settings.
+/// Unset fields fall back to the command line (or its defaults).
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Profile {
+    pub mode: Option<WorkspaceMode>,
+    pub sort: Option<SortMode>,
+}
+
+/// Default layer-shell namespace of the switcher surface
+pub const DEFAULT_NAMESPACE: &str = "sway-alttab-gui";
+
+/// Default title for windows without one
+pub const DEFAULT_UNTITLED: &str = "Untitled — {app}";
+
+/// When to use the high-contrast style
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum HighContrast {
+    /// While the desktop uses a high-contrast GTK theme
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+/// When to turn off the switcher's transitions and animations
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ReducedMotion {
+    /// While the desktop turns animations off (`gtk-enable-animations`)
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+/// How the switcher surface looks
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+pub struct Appearance {
+    /// Opacity of the switcher background, from 0.0 (clear) to 1.0 (solid)
+    pub opacity: f64,
+    /// Layer-shell namespace, for compositor rules such as blur
+    pub namespace: String,
+    /// Briefly flash the selected tile when cycling wraps around the list
+    pub wrap_cue: bool,
+    /// Title shown for windows without one; `{app}` is replaced by the app
+    pub untitled: String,
+    /// Caption tiles with how long ago the window was last focused
+    pub last_used: bool,
+    /// Dim windows not focused for this many minutes; 0 turns dimming off
+    pub dim_idle_after: u64,
+    /// Thicker selection border, larger labels and no transparency
+    pub high_contrast: HighContrast,
+    /// No transitions, and no flash for the wrap cue
+    pub reduced_motion: ReducedMotion,
+    /// Font size of the tile labels, relative to the theme's
+    pub label_scale: f64,
+    /// Font size of the position indicator ("3 / 12"), relative to its
+    /// default (a little smaller than the theme's)
+    pub position_scale: f64,
+    /// Background tints for the tiles of matching apps; the first match wins
+    pub tints: Vec<TintRule>,
+    /// Show each window's workspace number on its tile
+    pub workspace_numbers: bool,
+    /// Wrap more tiles than this into a grid this many columns wide;
+    /// 0 (the default) keeps them in a single row
+    pub grid_columns: usize,
+}
+
+/// Tints the tiles of matching apps, e.g. terminals green
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct TintRule {
+    /// App to match (app_id or WM_CLASS, case-insensitive); `*` matches
+    /// any run of characters, e.g. `*term*`
+    pub app_id: String,
+    /// A CSS color, e.g. `green` or `#3465a4`
+    pub color: String,
+}
+
+impl TintRule {
+    #[must_use]
+    pub fn matches(&self, window: &WindowInfo) -> bool {
+        window
+            .icon_candidates()
+            .iter()
+            .any(|id| glob_match(&self.app_id.to_ascii_lowercase(), &id.to_ascii_lowercase()))
+    }
+}
+
+/// Whether `text` matches `pattern`, where `*` stands for any run of
+/// characters
+#[must_use]
+pub fn glob_match(pattern: &str, text: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or_default();
+    let Some(mut rest) = text.strip_prefix(first) else {
+        return false;
+    };
+    let parts: Vec<&str> = parts.collect();
+    let Some((last, middle)) = parts.split_last() else {
+        // No `*`: the whole text must match
+        return rest.is_empty();
+    };
+    for part in middle {
+        match rest.find(part) {
+            Some(index) => rest = &rest[index + part.len()..],
+            None => return false,
+        }
+    }
+    rest.len() >= last.len() && rest.ends_with(last)
+}
+
+/// Whether `color` looks like a CSS color (a name, `#hex` or a function
+/// like `rgb(...)`), so it can go into the stylesheet as it is
+#[must_use]
+fn is_css_color(color: &str) -> bool {
+    !color.is_empty()
+        && color
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "#(),. %".contains(c))
+}
+
+/// Largest font scale accepted, which already fills a 4K screen
+pub const MAX_FONT_SCALE: f64 = 4.0;
+
+impl Default for Appearance {
+    fn default() -> Self {
+        Appearance {
+            opacity: 1.0,
+            namespace: DEFAULT_NAMESPACE.to_string(),
+            wrap_cue: false,
+            untitled: DEFAULT_UNTITLED.to_string(),
+            last_used: false,
+            dim_idle_after: 0,
+            high_contrast: HighContrast::default(),
+            reduced_motion: ReducedMotion::default(),
+            label_scale: 1.0,
+            position_scale: 1.0,
+            tints: Vec::new(),
+            workspace_numbers: false,
+            grid_columns: 0,
+        }
+    }
+}
+
+/// How keys in the switcher are recognised
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+pub struct KeyboardConfig {
+    /// Match the physical keys of a US layout instead of the keysyms
+    /// produced by the active layout
+    pub match_keycodes: bool,
+    pub bindings: KeyBindings,
+}
+
+/// What a bound key does in the switcher
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyAction {
+    Next,
+    Prev,
+    /// Pick the selection, like Enter (Ctrl and Shift work as with Enter)
+    Select,
+    Cancel,
+    /// Pick the selection when the key is released, like Alt
+    Release,
+}
+
+/// The switcher's keys for each action, as GDK key names (e.g. `Tab`,
+/// `j`, `Escape`), each optionally prefixed with `Shift+` or `Ctrl+`
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+pub struct KeyBindings {
+    pub next: Vec<String>,
+    pub prev: Vec<String>,
+    pub select: Vec<String>,
+    pub cancel: Vec<String>,
+    pub release: Vec<String>,
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        let keys = |keys: &[&str]| keys.iter().map(|key| key.to_string()).collect();
+        KeyBindings {
+            next: keys(&["Tab"]),
+            // Shift+Tab often arrives as ISO_Left_Tab
+            prev: keys(&["Shift+Tab", "ISO_Left_Tab"]),
+            select: keys(&["Return", "KP_Enter"]),
+            cancel: keys(&["Escape"]),
+            release: keys(&["Alt_L", "Alt_R"]),
+        }
+    }
+}
+
+impl KeyBindings {
+    /// Every binding with its action, leaving out any that don't parse
+    #[must_use]
+    pub fn chords(&self) -> Vec<(KeyChord, KeyAction)> {
+        self.lists()
+            .into_iter()
+            .flat_map(|(keys, action)| {
+                keys.iter()
+                    .filter_map(move |key| Some((key.parse().ok()?, action)))
+            })
+            .collect()
+    }
+
+    fn lists(&self) -> [(&[String], KeyAction); 5] {
+        [
+            (&self.next, KeyAction::Next),
+            (&self.prev, KeyAction::Prev),
+            (&self.select, KeyAction::Select),
+            (&self.cancel, KeyAction::Cancel),
+            (&self.release, KeyAction::Release),
+        ]
+    }
+}
+
+/// A key with the modifiers that must be held for it, e.g. `Shift+Tab`.
+/// Modifiers not named may be held too.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyChord {
+    pub shift: bool,
+    pub ctrl: bool,
+    /// GDK key name, or `code:N` for a hardware keycode
+    pub key: String,
+}
+
+impl KeyChord {
+    /// The hardware keycode of a `code:N` key
+    #[must_use]
+    pub fn keycode(&self) -> Option<u32> {
+        self.key.strip_prefix("code:")?.parse().ok()
+    }
+}
+
+impl FromStr for KeyChord {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut chord = KeyChord {
+            shift: false,
+            ctrl: false,
+            key: String::new(),
+        };
+        let mut parts: Vec<&str> = s.split('+').collect();
+        let key = parts.pop().unwrap_or_default();
+        for modifier in parts {
+            match modifier.to_ascii_lowercase().as_str() {
+                "shift" => chord.shift = true,
+                "ctrl" | "control" => chord.ctrl = true,
+                _ => anyhow::bail!("Unknown modifier '{}' in key '{}'", modifier, s),
+            }
+        }
+        if key.is_empty() {
+            anyhow::bail!("Key '{}' has no key name", s);
+        }
+        chord.key = key.to_string();
+        if key.starts_with("code:") && chord.keycode().is_none() {
+            anyhow::bail!("Key '{}' has no valid keycode", s);
+        }
+        Ok(chord)
+    }
+}
+
+/// Screen corner the recent-windows dock is placed in
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum DockCorner {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    #[default]
+    BottomRight,
+}
+
+/// A row of the most recently used windows that stays on screen
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+pub struct DockConfig {
+    /// How many windows to show; 0 (the default) turns the dock off
+    pub size: usize,
+    pub corner: DockCorner,
+}
+
+/// A bar of all open windows along the bottom of the screen
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+pub struct TaskbarConfig {
+    pub enabled: bool,
+}
+
+/// Gives matching windows a label shown on their tile.
+/// A rule needs at least one of `app-id` and `title`.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct LabelRule {
+    /// Match windows of this app (app_id or WM_CLASS, case-insensitive)
+    pub app_id: Option<String>,
+    /// Match windows whose title contains this text
+    pub title: Option<String>,
+    pub label: String,
+}
+
+impl LabelRule {
+    #[must_use]
+    pub fn matches(&self, window: &WindowInfo) -> bool {
+        let app_matches = self.app_id.as_ref().is_none_or(|app_id| {
+            window
+                .icon_candidates()
+                .iter()
+                .any(|id| id.eq_ignore_ascii_case(app_id))
+        });
+        let title_matches = self
+            .title
+            .as_ref()
+            .is_none_or(|title| window.title.contains(title.as_str()));
+        app_matches && title_matches
+    }
+}
+
+impl FromStr for LabelRule {
+    type Err = anyhow::Error;
+
+    /// Parse criteria like `app-id=firefox title="Inbox"` (sway's
+    /// `[app_id=... title=...]` form also works) into a rule without a label
+    fn from_str(s: &str) -> Result<Self> {
+        let s = s.trim();
+        let s = s.strip_prefix('[').and_then(|s| s.strip_suffix(']')).unwrap_or(s);
+        let mut rule = LabelRule {
+            app_id: None,
+            title: None,
+            label: String::new(),
+        };
+
+        let mut rest = s.trim_start();
+        while !rest.is_empty() {
+            let (key, after_key) = rest
+                .split_once('=')
+                .with_context(|| format!("Expected key=value in criteria, got '{}'", rest))?;
+            let (value, after_value) = match after_key.strip_prefix('"') {
+                Some(quoted) => quoted
+                    .split_once('"')
+                    .with_context(|| format!("Unclosed quote in criteria: {}", s))?,
+                None => after_key.split_once(' ').unwrap_or((after_key, "")),
+            };
+            match key.trim() {
+                "app-id" | "app_id" => rule.app_id = Some(value.to_string()),
+                "title" => rule.title = Some(value.to_string()),
+                other => anyhow::bail!("Unknown criteria key '{}' (use app-id or title)", other),
+            }
+            rest = after_value.trim_start();
+        }
+
+        if rule.app_id.is_none() && rule.title.is_none() {
+            anyhow::bail!("Criteria need an app-id or title to match");
+        }
+        Ok(rule)
+    }
+}
+
+/// The windows each rule labels, in rule order. Like `apply_labels`, a
+/// window goes to the first rule that matches and windows labelled by a
+/// mark are left out.
+#[must_use]
+pub fn label_captures<'a>(windows: &'a [WindowInfo], rules: &[LabelRule]) -> Vec<Vec<&'a WindowInfo>> {
+    let mut captures = vec![Vec::new(); rules.len()];
+    for window in windows.iter().filter(|w| w.label.is_none()) {
+        if let Some(index) = rules.iter().position(|rule| rule.matches(window)) {
+            captures[index].push(window);
+        }
+    }
+    captures
+}
+
+/// `line N: ` for the first line of `content` containing `needle`, or
+/// nothing if it isn't found
+fn line_prefix(content: &str, needle: &str) -> String {
+    content
+        .lines()
+        .position(|line| line.contains(needle))
+        .map(|index| format!("line {}: ", index + 1))
+        .unwrap_or_default()
+}
+
+/// Label windows from the first matching rule. Labels from marks win.
+pub fn apply_labels(windows: &mut [WindowInfo], rules: &[LabelRule]) {
+    for window in windows.iter_mut().filter(|w| w.label.is_none()) {
+        window.label = rules
+            .iter()
+            .find(|rule| rule.matches(window))
+            .map(|rule| rule.label.clone());
+    }
+}
+
+/// A `[[rules]]` entry: windows matching `app-id` and `title` get its
+/// actions. A rule needs at least one of `app-id` and `title`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+pub struct WindowRule {
+    /// Match windows of this app (app_id or WM_CLASS, case-insensitive);
+    /// `*` matches any run of characters
+    pub app_id: Option<String>,
+    /// Match windows whose title contains this text
+    pub title: Option<String>,
+    /// Rules with a higher priority are tried first; equal ones in file order
+    pub priority: i32,
+    /// Leave matching windows out of the switcher
+    pub hide: bool,
+    /// List matching windows first
+    pub pin: bool,
+    /// Label shown on the tile, as with `[[labels]]`
+    pub alias: Option<String>,
+    /// Title shown instead of the window's; `{title}` is replaced by it
+    pub retitle: Option<String>,
+    /// Icon name or path used instead of the app's icon
+    pub icon: Option<String>,
+    /// Tile background tint, a CSS color
+    pub tint: Option<String>,
+}
+
+impl WindowRule {
+    #[must_use]
+    pub fn matches(&self, window: &WindowInfo) -> bool {
+        let app_matches = self.app_id.as_ref().is_none_or(|app_id| {
+            let pattern = app_id.to_ascii_lowercase();
+            window
+                .icon_candidates()
+                .iter()
+                .any(|id| glob_match(&pattern, &id.to_ascii_lowercase()))
+        });
+        let title_matches = self
+            .title
+            .as_ref()
+            .is_none_or(|title| window.title.contains(title.as_str()));
+        app_matches && title_matches
+    }
+
+    /// Whether the rule does anything to the windows it matches
+    #[must_use]
+    pub fn has_actions(&self) -> bool {
+        self.hide
+            || self.pin
+            || self.alias.is_some()
+            || self.retitle.is_some()
+            || self.icon.is_some()
+            || self.tint.is_some()
+    }
+}
+
+/// Settings read from the config file
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FileConfig {
+    pub actions: ActionMap,
+    pub profiles: BTreeMap<String, Profile>,
+    pub appearance: Appearance,
+    pub keyboard: KeyboardConfig,
+    pub dock: DockConfig,
+    pub taskbar: TaskbarConfig,
+    pub labels: Vec<LabelRule>,
+    pub rules: Vec<WindowRule>,
+    #[serde(rename = "custom-actions")]
+    pub custom_actions: Vec<CustomAction>,
+    pub screenshot: ScreenshotConfig,
+    pub sound: SoundConfig,
+    #[serde(rename = "selection-log")]
+    pub selection_log: SelectionLogConfig,
+    pub preview: PreviewConfig,
+    pub filter: FilterConfig,
+    pub fullscreen: FullscreenConfig,
+    pub mru: MruConfig,
+    /// Named lists of workspaces for `show --set`
+    #[serde(rename = "workspace-sets")]
+    pub workspace_sets: BTreeMap<String, Vec<String>>,
+}
+
+impl FileConfig {
+    /// Default config file location: ~/.config/sway-alttab-gui/config.toml
+    pub fn default_path() -> Option<PathBuf> {
+        dirs::config_dir().map(|d| d.join("sway-alttab-gui").join("config.toml"))
+    }
+
+    /// Parse a config file's contents
+    pub fn from_toml(content: &str) -> Result<Self> {
+        let config: Self = toml::from_str(content)?;
+        let opacity = config.appearance.opacity;
+        if !(0.0..=1.0).contains(&opacity) {
+            anyhow::bail!(
+                "{}appearance.opacity must be between 0.0 and 1.0, got {}",
+                line_prefix(content, "opacity"),
+                opacity
+            );
+        }
+        for (key, scale) in [
+            ("label-scale", config.appearance.label_scale),
+            ("position-scale", config.appearance.position_scale),
+        ] {
+            if !(scale > 0.0 && scale <= MAX_FONT_SCALE) {
+                anyhow::bail!(
+                    "{}appearance.{} must be above 0.0 and at most {}, got {}",
+                    line_prefix(content, key),
+                    key,
+                    MAX_FONT_SCALE,
+                    scale
+                );
+            }
+        }
+        if let Some(tint) = config.appearance.tints.iter().find(|t| !is_css_color(&t.color)) {
+            anyhow::bail!(
+                "{}appearance.tints: '{}' is not a CSS color",
+                line_prefix(content, &tint.color),
+                tint.color
+            );
+        }
+        if config.appearance.namespace.is_empty() {
+            anyhow::bail!(
+                "{}appearance.namespace must not be empty",
+                line_prefix(content, "namespace")
+            );
+        }
+        let scale = config.preview.scale;
+        if !(scale > 0.0 && scale <= 1.0) {
+            anyhow::bail!(
+                "{}preview.scale must be above 0.0 and at most 1.0, got {}",
+                line_prefix(content, "scale"),
+                scale
+            );
+        }
+        if let Some(rule) = config
+            .labels
+            .iter()
+            .find(|rule| rule.app_id.is_none() && rule.title.is_none())
+        {
+            anyhow::bail!(
+                "{}label '{}' needs an app-id or title to match",
+                line_prefix(content, &format!("\"{}\"", rule.label)),
+                rule.label
+            );
+        }
+        for (keys, _) in config.keyboard.bindings.lists() {
+            for key in keys {
+                if let Err(e) = key.parse::<KeyChord>() {
+                    anyhow::bail!("{}keyboard.bindings: {}", line_prefix(content, key), e);
+                }
+            }
+        }
+        for (index, rule) in config.rules.iter().enumerate() {
+            if rule.app_id.is_none() && rule.title.is_none() {
+                anyhow::bail!("rule #{} needs an app-id or title to match", index + 1);
+            }
+            if let Some(tint) = rule.tint.as_ref().filter(|tint| !is_css_color(tint)) {
+                anyhow::bail!(
+                    "{}rules: '{}' is not a CSS color",
+                    line_prefix(content, tint),
+                    tint
+                );
+            }
+        }
+        Ok(config)
+    }
+
+    /// Problems that don't stop the config from loading, checked against
+    /// the workspaces that exist right now. `content` is the file the config
+    /// was parsed from, for line numbers.
+    #[must_use]
+    pub fn warnings(&self, content: &str, workspaces: &[String]) -> Vec<String> {
+        let mut warnings = Vec::new();
+        for (index, rule) in self.rules.iter().enumerate() {
+            if !rule.has_actions() {
+                warnings.push(format!("rule #{} has no actions", index + 1));
+            }
+        }
+        for (set, names) in &self.workspace_sets {
+            if names.is_empty() {
+                warnings.push(format!(
+                    "{}workspace set '{}' is empty",
+                    line_prefix(content, set),
+                    set
+                ));
+            }
+            for name in names.iter().filter(|name| !workspaces.contains(name)) {
+                warnings.push(format!(
+                    "{}workspace '{}' in set '{}' doesn't exist right now",
+                    line_prefix(content, &format!("\"{}\"", name)),
+                    name,
+                    set
+                ));
+            }
+        }
+        warnings
+    }
+
+    /// Load and parse a config file
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read config file {}", path.display()))?;
+        Self::from_toml(&content)
+            .with_context(|| format!("Invalid config file {}", path.display()))
+    }
 }
 
 #[derive(Debug, Clone, Subcommand)]
 pub enum Command {
     /// Run as daemon (default if no command specified)
     Daemon,
+    /// Stop the running daemon and take its place, keeping its window
+    /// order, focus history and profile (e.g. after an upgrade)
+    Replace,
+    /// Show the switcher without a daemon and exit once a window is picked
+    /// (the MRU order is approximated from sway's focus order)
+    Once,
     /// Show the window switcher
-    Show,
+    Show {
+        /// What to switch between
+        #[arg(long, value_enum, default_value_t)]
+        what: SwitchTarget,
+        /// Only list windows on the workspaces of this set from the config file
+        #[arg(long)]
+        set: Option<String>,
+        /// Move backward: open on the least recently used window, or select
+        /// the previous one if the switcher is open (e.g. for Shift+Alt+Tab)
+        #[arg(long)]
+        reverse: bool,
+        /// Move the selection past this many more windows when the switcher
+        /// opens, e.g. 1 to start on the third most recently used window
+        #[arg(long, default_value_t = 0)]
+        skip: usize,
+    },
+    /// Show the window switcher with only the focused application's windows
+    ShowSameApp,
+    /// Move the open switcher's selection to the next window
+    Next,
+    /// Move the open switcher's selection to the previous window
+    Prev,
+    /// Focus the selected window and close the switcher
+    Select,
+    /// Close the switcher without focusing anything
+    Cancel,
+    /// Print the most recently focused windows
+    History {
+        /// Number of focus changes to print
+        #[arg(default_value_t = crate::ipc::DEFAULT_HISTORY_COUNT)]
+        count: usize,
+    },
+    /// Print the most recently used window (for status bars) and exit
+    Current,
+    /// Focus the nth most recently used window without the switcher;
+    /// 1 is the previously used window
+    FocusNth { n: usize },
+    /// Print all windows known to the daemon, most recently used first, as JSON
+    Windows,
+    /// Print the switcher's state changes as JSON lines (for custom frontends)
+    Watch,
+    /// Print tree nodes that look like windows but were left out, and why
+    Skipped,
+    /// Print the window rules in the order they are tried and which of
+    /// them matched each window
+    Rules,
+    /// Print sway rules for `--placement floating`, to add to the sway config
+    SwayRules,
+    /// Check the config file and report problems before (re)starting the daemon
+    CheckConfig,
+    /// Show which windows each `[[labels]]` rule captures, or which windows
+    /// match the given criteria (e.g. `app-id=firefox title="Inbox"`)
+    Match {
+        criteria: Option<String>,
+    },
+    /// Close the switcher and stop the running daemon
+    Shutdown,
+    /// Make the daemon ignore `show` (e.g. while screen sharing) until `resume`
+    Pause,
+    /// Let the daemon handle `show` again after `pause`
+    Resume,
+    /// Make the daemon re-read its config file. Settings the GTK UI uses
+    /// (appearance, keyboard, dock, taskbar, previews) need a restart.
+    Reload,
+    /// Print the compositor resources the daemon's UI holds (surfaces,
+    /// keyboard grab), to check nothing is left over after the switcher closes
+    DebugResources,
+    /// Print the daemon's status as `key=value` lines
+    Status,
+    /// Check the daemon: exits 0 if healthy, 1 if it can't be reached,
+    /// 2 if it answers but is unhealthy
+    Health,
+    /// Print the daemon's MRU order, focus history and profile as JSON
+    ExportState,
+    /// Print the open windows with the commands that likely started them,
+    /// for restoring a session
+    ExportLayout {
+        /// Output format
+        #[arg(long, value_enum, default_value_t)]
+        format: LayoutFormat,
+    },
+    /// Restore state printed by `export-state`, e.g. after restarting the daemon
+    ImportState {
+        /// File to read the state from; reads stdin if omitted
+        file: Option<PathBuf>,
+    },
+    /// Switch the running daemon to a profile from the config file
+    SetProfile {
+        /// Profile name; omit to go back to the command line settings
+        name: Option<String>,
+    },
 }
 
 #[derive(Debug, Clone, Parser)]
@@ -25,6 +1108,82 @@ pub struct Config {
     #[arg(short, long, value_enum, default_value_t)]
     pub mode: WorkspaceMode,
 
+    /// Window ordering (only applies to daemon mode)
+    #[arg(long, value_enum, default_value_t)]
+    pub sort: SortMode,
+
+    /// Switch to the target window's workspace before focusing it, so sway's
+    /// workspace_auto_back_and_forth and workspace history see the switch
+    #[arg(long)]
+    pub focus_workspace_first: bool,
+
+    /// Pressing the show keybinding again while switching selects the
+    /// current window instead of cycling to the next one
+    #[arg(long)]
+    pub select_on_repeat: bool,
+
+    /// Leave the focused window out of the switcher; the selection then
+    /// starts on the first entry
+    #[arg(long)]
+    pub exclude_focused: bool,
+
+    /// Take an Alt release this many milliseconds after the switcher opens
+    /// as a tap, switching to the preselected window straight away
+    #[arg(long, value_name = "MS", default_value_t = 0)]
+    pub min_hold_ms: u64,
+
+    /// Re-read the window list from sway every this many seconds (with some
+    /// jitter), in case an event was missed; 0 disables it
+    #[arg(long, value_name = "SECS", default_value_t = 0)]
+    pub refresh_interval: u64,
+
+    /// After this many minutes without a show, stop optional background
+    /// work (periodic refresh, icon preloading, previews) until the next
+    /// show; 0 disables it
+    #[arg(long, value_name = "MINUTES", default_value_t = 0)]
+    pub rest_after: u64,
+
+    /// Show the switcher straight away from the last known window list and
+    /// swap in the fresh one from sway a moment later
+    #[arg(long)]
+    pub instant_show: bool,
+
+    /// Which tree nodes count as windows; try `lenient` if some windows are
+    /// missing (see the `skipped` command)
+    #[arg(long, value_enum, default_value_t)]
+    pub window_detection: WindowDetection,
+
+    /// How to draw the switcher (only applies to daemon mode)
+    #[arg(long, value_enum, default_value_t)]
+    pub frontend: Frontend,
+
+    /// How to place the GTK switcher window (only applies to daemon mode)
+    #[arg(long, value_enum, default_value_t)]
+    pub placement: Placement,
+
+    /// Profile from the config file to start with (only applies to daemon mode)
+    #[arg(short, long)]
+    pub profile: Option<String>,
+
+    /// Path to the config file (default: ~/.config/sway-alttab-gui/config.toml)
+    #[arg(short, long)]
+    pub config: Option<PathBuf>,
+
+    /// Fork into the background, detached from the terminal, logging to
+    /// `--log-file` (only applies to daemon mode)
+    #[arg(long)]
+    pub daemonize: bool,
+
+    /// Show a tray icon with the daemon's state and quick actions (needs
+    /// the `tray` feature; only applies to daemon mode)
+    #[arg(long)]
+    pub tray: bool,
+
+    /// Where a daemonized daemon writes its log
+    /// (default: $XDG_RUNTIME_DIR/sway-alttab-gui.log)
+    #[arg(long, value_name = "PATH")]
+    pub log_file: Option<PathBuf>,
+
     /// Enable verbose logging
     #[arg(short, long)]
     pub verbose: bool,
@@ -32,6 +1191,10 @@ pub struct Config {
     /// Command to execute
     #[command(subcommand)]
     pub command: Option<Command>,
+
+    /// Settings loaded from the config file
+    #[arg(skip)]
+    pub file: FileConfig,
 }
 
 impl Config {
@@ -39,6 +1202,56 @@ impl Config {
         <Config as Parser>::parse()
     }
 
+    /// Load the config file into `self.file`.
+    ///
+    /// A missing file at the default location is not an error;
+    /// a missing file given with `--config` is.
+    pub fn load_file(&mut self) -> Result<()> {
+        let path = match self.config {
+            Some(ref path) => path.clone(),
+            None => match FileConfig::default_path() {
+                Some(path) if path.exists() => path,
+                _ => return Ok(()),
+            },
+        };
+
+        self.file = FileConfig::load(&path)?;
+        tracing::info!("Loaded config from {}", path.display());
+        Ok(())
+    }
+
+    /// Look up a profile by name
+    pub fn profile(&self, name: &str) -> Result<&Profile> {
+        self.file.profiles.get(name).with_context(|| {
+            let known: Vec<_> = self.file.profiles.keys().map(String::as_str).collect();
+            format!("Unknown profile '{}' (known profiles: {})", name, known.join(", "))
+        })
+    }
+
+    /// Look up a workspace set by name
+    pub fn workspace_set(&self, name: &str) -> Result<&[String]> {
+        self.file
+            .workspace_sets
+            .get(name)
+            .map(Vec::as_slice)
+            .with_context(|| {
+                let known: Vec<_> = self.file.workspace_sets.keys().map(String::as_str).collect();
+                format!("Unknown workspace set '{}' (known sets: {})", name, known.join(", "))
+            })
+    }
+
+    /// Workspace mode with the given profile's override applied
+    #[must_use]
+    pub fn mode_for(&self, profile: Option<&Profile>) -> WorkspaceMode {
+        profile.and_then(|p| p.mode).unwrap_or(self.mode)
+    }
+
+    /// Sort mode with the given profile's override applied
+    #[must_use]
+    pub fn sort_for(&self, profile: Option<&Profile>) -> SortMode {
+        profile.and_then(|p| p.sort).unwrap_or(self.sort)
+    }
+
     /// Get the command, defaulting to Daemon if none specified
     #[must_use]
     pub fn command(&self) -> Command {
@@ -59,8 +1272,25 @@ mod tests {
     fn test_command_default_is_daemon() {
         let config = Config {
             mode: WorkspaceMode::default(),
+            sort: SortMode::default(),
+            focus_workspace_first: false,
+            select_on_repeat: false,
+            exclude_focused: false,
+            min_hold_ms: 0,
+            refresh_interval: 0,
+            rest_after: 0,
+            instant_show: false,
+            window_detection: WindowDetection::default(),
+            frontend: Frontend::Gtk,
+            placement: Placement::default(),
+            profile: None,
+            config: None,
+            daemonize: false,
+            tray: false,
+            log_file: None,
             verbose: false,
             command: None,
+            file: FileConfig::default(),
         };
         assert!(matches!(config.command(), Command::Daemon));
     }
@@ -69,18 +1299,57 @@ mod tests {
     fn test_command_show_when_specified() {
         let config = Config {
             mode: WorkspaceMode::default(),
+            sort: SortMode::default(),
+            focus_workspace_first: false,
+            select_on_repeat: false,
+            exclude_focused: false,
+            min_hold_ms: 0,
+            refresh_interval: 0,
+            rest_after: 0,
+            instant_show: false,
+            window_detection: WindowDetection::default(),
+            frontend: Frontend::Gtk,
+            placement: Placement::default(),
+            profile: None,
+            config: None,
+            daemonize: false,
+            tray: false,
+            log_file: None,
             verbose: false,
-            command: Some(Command::Show),
+            command: Some(Command::Show {
+                what: SwitchTarget::Windows,
+                set: None,
+                reverse: false,
+                skip: 0,
+            }),
+            file: FileConfig::default(),
         };
-        assert!(matches!(config.command(), Command::Show));
+        assert!(matches!(config.command(), Command::Show { .. }));
     }
 
     #[test]
     fn test_command_daemon_when_specified() {
         let config = Config {
             mode: WorkspaceMode::default(),
+            sort: SortMode::default(),
+            focus_workspace_first: false,
+            select_on_repeat: false,
+            exclude_focused: false,
+            min_hold_ms: 0,
+            refresh_interval: 0,
+            rest_after: 0,
+            instant_show: false,
+            window_detection: WindowDetection::default(),
+            frontend: Frontend::Gtk,
+            placement: Placement::default(),
+            profile: None,
+            config: None,
+            daemonize: false,
+            tray: false,
+            log_file: None,
             verbose: false,
             command: Some(Command::Daemon),
+            file: FileConfig::default(),
         };
         assert!(matches!(config.command(), Command::Daemon));
     }
@@ -92,13 +1361,616 @@ mod tests {
         assert_ne!(mode, WorkspaceMode::Current);
     }
 
+    #[test]
+    fn test_sort_mode_default() {
+        assert_eq!(SortMode::default(), SortMode::Mru);
+    }
+
     #[test]
     fn test_config_verbose_flag() {
         let config = Config {
             mode: WorkspaceMode::Current,
+            sort: SortMode::default(),
+            focus_workspace_first: false,
+            select_on_repeat: false,
+            exclude_focused: false,
+            min_hold_ms: 0,
+            refresh_interval: 0,
+            rest_after: 0,
+            instant_show: false,
+            window_detection: WindowDetection::default(),
+            frontend: Frontend::Gtk,
+            placement: Placement::default(),
+            profile: None,
+            config: None,
+            daemonize: false,
+            tray: false,
+            log_file: None,
             verbose: true,
             command: None,
+            file: FileConfig::default(),
         };
         assert!(config.verbose);
     }
+
+    #[test]
+    fn test_file_config_empty_uses_defaults() {
+        let file = FileConfig::from_toml("").unwrap();
+        assert_eq!(file, FileConfig::default());
+    }
+
+    #[test]
+    fn test_action_map_defaults() {
+        let actions = ActionMap::default();
+        assert_eq!(actions.action_for(Trigger::Enter), SwitcherAction::Focus);
+        assert_eq!(actions.action_for(Trigger::Release), SwitcherAction::Focus);
+        assert_eq!(actions.action_for(Trigger::CtrlEnter), SwitcherAction::LaunchNew);
+        assert_eq!(actions.action_for(Trigger::ShiftEnter), SwitcherAction::MoveHere);
+    }
+
+    #[test]
+    fn test_action_map_from_toml() {
+        let file = FileConfig::from_toml(
+            r#"
+            [actions]
+            enter = "move-here"
+            shift-release = "cancel"
+            ctrl-enter = "screenshot"
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(file.actions.action_for(Trigger::Enter), SwitcherAction::MoveHere);
+        assert_eq!(file.actions.action_for(Trigger::ShiftRelease), SwitcherAction::Cancel);
+        assert_eq!(file.actions.action_for(Trigger::CtrlEnter), SwitcherAction::Screenshot);
+        // Unspecified triggers keep their defaults
+        assert_eq!(file.actions.action_for(Trigger::Release), SwitcherAction::Focus);
+    }
+
+    #[test]
+    fn test_file_config_rejects_unknown_action() {
+        assert!(FileConfig::from_toml("[actions]\nenter = \"explode\"").is_err());
+    }
+
+    #[test]
+    fn test_file_config_rejects_unknown_keys() {
+        assert!(FileConfig::from_toml("[actions]\nalt-enter = \"focus\"").is_err());
+        assert!(FileConfig::from_toml("bogus = 1").is_err());
+    }
+
+    #[test]
+    fn test_switcher_action_input_command() {
+        assert_eq!(SwitcherAction::Focus.input_command(), InputCommand::Select);
+        assert_eq!(SwitcherAction::MoveHere.input_command(), InputCommand::MoveHere);
+        assert_eq!(SwitcherAction::LaunchNew.input_command(), InputCommand::LaunchNew);
+        assert_eq!(SwitcherAction::Cancel.input_command(), InputCommand::Cancel);
+    }
+
+    #[test]
+    fn test_profiles_from_toml() {
+        let file = FileConfig::from_toml(
+            r#"
+            [profiles.work]
+            mode = "all"
+            sort = "output"
+
+            [profiles.media]
+            mode = "current"
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(file.profiles.len(), 2);
+        assert_eq!(file.profiles["work"].mode, Some(WorkspaceMode::All));
+        assert_eq!(file.profiles["work"].sort, Some(SortMode::Output));
+        assert_eq!(file.profiles["media"].sort, None);
+    }
+
+    #[test]
+    fn test_profile_overrides_fall_back_to_cli() {
+        let mut config = Config::parse_from(["sway-alttab-gui", "--mode", "all"]);
+        config.file = FileConfig::from_toml("[profiles.sorted]\nsort = \"output\"").unwrap();
+
+        let profile = config.profile("sorted").unwrap();
+        assert_eq!(config.mode_for(Some(profile)), WorkspaceMode::All);
+        assert_eq!(config.sort_for(Some(profile)), SortMode::Output);
+        assert_eq!(config.sort_for(None), SortMode::Mru);
+    }
+
+    #[test]
+    fn test_unknown_profile_is_error() {
+        let config = Config::parse_from(["sway-alttab-gui"]);
+        let err = config.profile("nope").unwrap_err();
+        assert!(err.to_string().contains("Unknown profile 'nope'"));
+    }
+
+    #[test]
+    fn test_show_what() {
+        let config = Config::parse_from(["sway-alttab-gui", "show", "--what", "apps"]);
+        assert!(matches!(
+            config.command(),
+            Command::Show {
+                what: SwitchTarget::Apps,
+                set: None,
+                reverse: false,
+                skip: 0,
+            }
+        ));
+
+        let config = Config::parse_from(["sway-alttab-gui", "show"]);
+        assert!(matches!(
+            config.command(),
+            Command::Show {
+                what: SwitchTarget::Windows,
+                set: None,
+                reverse: false,
+                skip: 0,
+            }
+        ));
+
+        let config = Config::parse_from(["sway-alttab-gui", "show", "--set", "coding"]);
+        assert!(matches!(
+            config.command(),
+            Command::Show { set: Some(ref name), .. } if name == "coding"
+        ));
+
+        let config = Config::parse_from(["sway-alttab-gui", "show", "--reverse"]);
+        assert!(matches!(config.command(), Command::Show { reverse: true, .. }));
+
+        let config = Config::parse_from(["sway-alttab-gui", "show", "--skip", "2"]);
+        assert!(matches!(config.command(), Command::Show { skip: 2, .. }));
+    }
+
+    #[test]
+    fn test_workspace_sets_from_toml() {
+        let mut config = Config::parse_from(["sway-alttab-gui"]);
+        config.file = FileConfig::from_toml(
+            r#"
+            [workspace-sets]
+            coding = ["1", "2", "3"]
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.workspace_set("coding").unwrap(), ["1", "2", "3"]);
+        let err = config.workspace_set("games").unwrap_err();
+        assert!(err.to_string().contains("Unknown workspace set 'games'"));
+    }
+
+    #[test]
+    fn test_appearance_from_toml() {
+        let file = FileConfig::from_toml(
+            r#"
+            [appearance]
+            opacity = 0.8
+            namespace = "switcher"
+            wrap-cue = true
+            last-used = true
+            dim-idle-after = 120
+            high-contrast = "always"
+            reduced-motion = "never"
+            label-scale = 1.5
+            "#,
+        )
+        .unwrap();
+        assert_eq!(file.appearance.opacity, 0.8);
+        assert_eq!(file.appearance.namespace, "switcher");
+        assert!(file.appearance.wrap_cue);
+        assert!(file.appearance.last_used);
+        assert_eq!(file.appearance.dim_idle_after, 120);
+        assert_eq!(file.appearance.high_contrast, HighContrast::Always);
+        assert_eq!(file.appearance.reduced_motion, ReducedMotion::Never);
+        assert_eq!(file.appearance.label_scale, 1.5);
+        assert_eq!(file.appearance.position_scale, 1.0);
+        assert_eq!(file.appearance.untitled, DEFAULT_UNTITLED);
+
+        let file = FileConfig::from_toml("").unwrap();
+        assert_eq!(file.appearance, Appearance::default());
+    }
+
+    #[test]
+    fn test_untitled_from_toml() {
+        let file = FileConfig::from_toml(
+            r#"
+            [appearance]
+            untitled = "{app} (no title)"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(file.appearance.untitled, "{app} (no title)");
+    }
+
+    #[test]
+    fn test_appearance_rejects_invalid_values() {
+        assert!(FileConfig::from_toml("[appearance]\nopacity = 1.5").is_err());
+        assert!(FileConfig::from_toml("[appearance]\nopacity = -0.1").is_err());
+        assert!(FileConfig::from_toml("[appearance]\nnamespace = \"\"").is_err());
+        let error = FileConfig::from_toml("[appearance]\n\nopacity = 2.0").unwrap_err();
+        assert!(error.to_string().starts_with("line 3: appearance.opacity"));
+        assert!(FileConfig::from_toml("[appearance]\nlabel-scale = 0").is_err());
+        let error = FileConfig::from_toml("[appearance]\nposition-scale = 5.0").unwrap_err();
+        assert!(error.to_string().starts_with("line 2: appearance.position-scale"));
+    }
+
+    #[test]
+    fn test_tints_from_toml() {
+        let file = FileConfig::from_toml(
+            r##"
+            [[appearance.tints]]
+            app-id = "*term*"
+            color = "green"
+
+            [[appearance.tints]]
+            app-id = "firefox"
+            color = "#3465a4"
+            "##,
+        )
+        .unwrap();
+        assert_eq!(file.appearance.tints.len(), 2);
+        assert_eq!(file.appearance.tints[1].color, "#3465a4");
+
+        let error = FileConfig::from_toml(
+            "[[appearance.tints]]\napp-id = \"foot\"\ncolor = \"red; }\"",
+        )
+        .unwrap_err();
+        assert!(error.to_string().starts_with("line 3: appearance.tints"));
+    }
+
+    #[test]
+    fn test_tint_rule_matches() {
+        let rule = TintRule {
+            app_id: "*Term*".to_string(),
+            color: "green".to_string(),
+        };
+        let mut window = WindowInfo {
+            app_id: Some("gnome-terminal-server".to_string()),
+            ..Default::default()
+        };
+        assert!(rule.matches(&window));
+        window.app_id = Some("foot".to_string());
+        assert!(!rule.matches(&window));
+        window.window_class = Some("XTerm".to_string());
+        assert!(rule.matches(&window));
+    }
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("foot", "foot"));
+        assert!(!glob_match("foot", "footclient"));
+        assert!(glob_match("foot*", "footclient"));
+        assert!(glob_match("*term*", "xterm"));
+        assert!(glob_match("*.mozilla.*", "org.mozilla.firefox"));
+        assert!(glob_match("a*b*c", "abc"));
+        assert!(!glob_match("a*b*c", "acb"));
+        assert!(!glob_match("ab*ba", "aba"));
+        assert!(glob_match("*", ""));
+    }
+
+    #[test]
+    fn test_keyboard_from_toml() {
+        let file = FileConfig::from_toml("[keyboard]\nmatch-keycodes = true").unwrap();
+        assert!(file.keyboard.match_keycodes);
+        assert!(!FileConfig::default().keyboard.match_keycodes);
+    }
+
+    #[test]
+    fn test_key_bindings_from_toml() {
+        let file = FileConfig::from_toml(
+            "[keyboard.bindings]\nnext = [\"Tab\", \"j\"]\nprev = [\"Shift+Tab\", \"k\"]",
+        )
+        .unwrap();
+        let chords = file.keyboard.bindings.chords();
+        assert_eq!(chords.len(), 9);
+        assert_eq!(chords[1], ("j".parse().unwrap(), KeyAction::Next));
+        assert_eq!(
+            chords[2],
+            (
+                KeyChord {
+                    shift: true,
+                    ctrl: false,
+                    key: "Tab".to_string()
+                },
+                KeyAction::Prev
+            )
+        );
+        // The lists not given keep their defaults
+        assert_eq!(file.keyboard.bindings.cancel, vec!["Escape"]);
+
+        let err = FileConfig::from_toml("[keyboard.bindings]\ncancel = [\"Meta+q\"]").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "line 2: keyboard.bindings: Unknown modifier 'Meta' in key 'Meta+q'"
+        );
+        assert!(FileConfig::from_toml("[keyboard.bindings]\nnext = [\"Ctrl+\"]").is_err());
+    }
+
+    #[test]
+    fn test_keycode_chords() {
+        let chord: KeyChord = "Shift+code:23".parse().unwrap();
+        assert!(chord.shift);
+        assert_eq!(chord.keycode(), Some(23));
+        assert_eq!("Tab".parse::<KeyChord>().unwrap().keycode(), None);
+
+        let err = FileConfig::from_toml("[keyboard.bindings]\nnext = [\"code:tab\"]").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "line 2: keyboard.bindings: Key 'code:tab' has no valid keycode"
+        );
+    }
+
+    #[test]
+    fn test_warnings_for_missing_workspaces() {
+        let content = "[workspace-sets]\ncoding = [\"1\", \"9\"]\nempty = []\n";
+        let file = FileConfig::from_toml(content).unwrap();
+        let warnings = file.warnings(content, &["1".to_string(), "2".to_string()]);
+        assert_eq!(
+            warnings,
+            vec![
+                "line 2: workspace '9' in set 'coding' doesn't exist right now",
+                "line 3: workspace set 'empty' is empty",
+            ]
+        );
+        assert!(FileConfig::default().warnings("", &[]).is_empty());
+    }
+
+    #[test]
+    fn test_custom_action_substitutes_fields() {
+        let file = FileConfig::from_toml(
+            r#"
+            [[custom-actions]]
+            key = "s"
+            command = "notify-send {title} {app_id} && kill {pid}; swaymsg [con_id={con_id}] move workspace {workspace}"
+            "#,
+        )
+        .unwrap();
+        let window = WindowInfo {
+            id: 42,
+            app_id: Some("foot".to_string()),
+            title: "it's $(rm -rf)".to_string(),
+            workspace: "2".to_string(),
+            output: "DP-1".to_string(),
+            window_class: None,
+            window_instance: None,
+            label: None,
+            last_focused: None,
+            ..Default::default()
+        };
+
+        assert_eq!(file.custom_actions[0].key, "s");
+        assert_eq!(
+            file.custom_actions[0].command_for(&window, Some(1234)),
+            "notify-send 'it'\\''s $(rm -rf)' 'foot' && kill 1234; \
+             swaymsg [con_id=42] move workspace '2'"
+        );
+    }
+
+    #[test]
+    fn test_sound_command() {
+        assert_eq!(SoundConfig::default().command(false), None);
+        let sound = FileConfig::from_toml("[sound]\nenabled = true\ncancel = \"dialog-warning\"")
+            .unwrap()
+            .sound;
+        assert_eq!(
+            sound.command(false).as_deref(),
+            Some("canberra-gtk-play -i 'complete' -d sway-alttab-gui")
+        );
+        assert_eq!(
+            sound.command(true).as_deref(),
+            Some("canberra-gtk-play -i 'dialog-warning' -d sway-alttab-gui")
+        );
+    }
+
+    #[test]
+    fn test_screenshot_grim_command() {
+        let screenshot = ScreenshotConfig {
+            path: "/tmp/{app_id}-{time}.png".to_string(),
+            clipboard: false,
+        };
+        assert_eq!(
+            screenshot.grim_command("10,20 300x200", "foot", 1700000000),
+            "grim -g '10,20 300x200' '/tmp/foot-1700000000.png'"
+        );
+
+        let clipboard = ScreenshotConfig {
+            clipboard: true,
+            ..ScreenshotConfig::default()
+        };
+        assert_eq!(
+            clipboard.grim_command("0,0 10x10", "foot", 0),
+            "grim -g '0,0 10x10' - | wl-copy --type image/png"
+        );
+    }
+
+    #[test]
+    fn test_preview_from_toml() {
+        let file = FileConfig::from_toml("[preview]\nenabled = true\nscale = 0.5").unwrap();
+        assert!(file.preview.enabled);
+        assert_eq!(file.preview.scale, 0.5);
+        assert!(!FileConfig::default().preview.enabled);
+        assert!(FileConfig::from_toml("[preview]\nscale = 0.0").is_err());
+        assert!(FileConfig::from_toml("[preview]\nscale = 2.0").is_err());
+    }
+
+    #[test]
+    fn test_filter_from_toml() {
+        let file = FileConfig::from_toml("[filter]\nhide-idle-after = 240").unwrap();
+        assert_eq!(file.filter.hide_idle_after, 240);
+        assert_eq!(FileConfig::default().filter.hide_idle_after, 0);
+    }
+
+    #[test]
+    fn test_mru_from_toml() {
+        let file = FileConfig::from_toml("[mru]\nupdate = \"settled\"\nsettle-ms = 500").unwrap();
+        assert_eq!(file.mru.update, MruUpdate::Settled);
+        assert_eq!(file.mru.settle_ms, 500);
+        assert_eq!(FileConfig::default().mru.update, MruUpdate::Focus);
+        assert!(FileConfig::from_toml("[mru]\nupdate = \"never\"").is_err());
+    }
+
+    #[test]
+    fn test_fullscreen_from_toml() {
+        let file = FileConfig::from_toml("[fullscreen]\naction = \"toggle\"").unwrap();
+        assert_eq!(file.fullscreen.action, FullscreenAction::Toggle);
+        assert_eq!(FileConfig::default().fullscreen.action, FullscreenAction::Show);
+        assert!(FileConfig::from_toml("[fullscreen]\naction = \"hide\"").is_err());
+    }
+
+    #[test]
+    fn test_dock_from_toml() {
+        let file = FileConfig::from_toml("[dock]\nsize = 5\ncorner = \"top-left\"").unwrap();
+        assert_eq!(file.dock.size, 5);
+        assert_eq!(file.dock.corner, DockCorner::TopLeft);
+
+        let default = FileConfig::default().dock;
+        assert_eq!(default.size, 0);
+        assert_eq!(default.corner, DockCorner::BottomRight);
+        assert!(FileConfig::from_toml("[dock]\ncorner = \"middle\"").is_err());
+    }
+
+    #[test]
+    fn test_taskbar_from_toml() {
+        assert!(FileConfig::from_toml("[taskbar]\nenabled = true").unwrap().taskbar.enabled);
+        assert!(!FileConfig::default().taskbar.enabled);
+        assert!(FileConfig::from_toml("[taskbar]\nedge = \"top\"").is_err());
+    }
+
+    #[test]
+    fn test_labels_from_toml() {
+        let file = FileConfig::from_toml(
+            r#"
+            [[labels]]
+            app-id = "firefox"
+            title = "Gmail"
+            label = "Mail"
+
+            [[labels]]
+            app-id = "foot"
+            label = "Shell"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(file.labels.len(), 2);
+        assert_eq!(file.labels[0].title.as_deref(), Some("Gmail"));
+
+        assert!(FileConfig::from_toml("[[labels]]\nlabel = \"Any\"").is_err());
+    }
+
+    #[test]
+    fn test_rules_from_toml() {
+        let content = r#"
+            [[rules]]
+            app-id = "firefox"
+            title = "Picture-in-Picture"
+            priority = 10
+            hide = true
+
+            [[rules]]
+            title = "htop"
+            "#;
+        let file = FileConfig::from_toml(content).unwrap();
+        assert_eq!(file.rules.len(), 2);
+        assert_eq!(file.rules[0].priority, 10);
+        assert!(file.rules[0].hide);
+        assert_eq!(file.warnings(content, &[]), vec!["rule #2 has no actions"]);
+
+        assert!(FileConfig::from_toml("[[rules]]\npin = true").is_err());
+        let err = FileConfig::from_toml("[[rules]]\ntitle = \"a\"\ntint = \"red;\"")
+            .unwrap_err();
+        assert_eq!(err.to_string(), "line 3: rules: 'red;' is not a CSS color");
+        assert!(FileConfig::from_toml("[[rules]]\ntitle = \"a\"\nfloat = true").is_err());
+    }
+
+    #[test]
+    fn test_apply_labels() {
+        let rules = FileConfig::from_toml(
+            r#"
+            [[labels]]
+            app-id = "Firefox"
+            title = "Gmail"
+            label = "Mail"
+
+            [[labels]]
+            title = "htop"
+            label = "Monitor"
+            "#,
+        )
+        .unwrap()
+        .labels;
+
+        let window = |id, app_id: &str, title: &str| WindowInfo {
+            id,
+            app_id: Some(app_id.to_string()),
+            title: title.to_string(),
+            workspace: "1".to_string(),
+            output: "DP-1".to_string(),
+            window_class: None,
+            window_instance: None,
+            label: None,
+            last_focused: None,
+            ..Default::default()
+        };
+        let mut windows = vec![
+            window(1, "firefox", "Inbox - Gmail"),
+            window(2, "firefox", "News"),
+            window(3, "foot", "htop"),
+            WindowInfo {
+                label: Some("from mark".to_string()),
+                ..window(4, "firefox", "Gmail")
+            },
+        ];
+        apply_labels(&mut windows, &rules);
+
+        let labels: Vec<_> = windows.iter().map(|w| w.label.as_deref()).collect();
+        assert_eq!(
+            labels,
+            vec![Some("Mail"), None, Some("Monitor"), Some("from mark")]
+        );
+    }
+
+    #[test]
+    fn test_label_captures_first_rule_wins() {
+        let rule = |criteria: &str| criteria.parse::<LabelRule>().unwrap();
+        let rules = vec![rule("title=Gmail"), rule("app-id=firefox")];
+        let window = |id, app_id: &str, title: &str| WindowInfo {
+            id,
+            app_id: Some(app_id.to_string()),
+            title: title.to_string(),
+            workspace: "1".to_string(),
+            output: "DP-1".to_string(),
+            window_class: None,
+            window_instance: None,
+            label: None,
+            last_focused: None,
+            ..Default::default()
+        };
+        let windows = vec![
+            window(1, "firefox", "Inbox - Gmail"),
+            window(2, "firefox", "News"),
+            WindowInfo {
+                label: Some("from mark".to_string()),
+                ..window(3, "firefox", "Docs")
+            },
+        ];
+
+        let ids: Vec<Vec<i64>> = label_captures(&windows, &rules)
+            .iter()
+            .map(|captured| captured.iter().map(|w| w.id).collect())
+            .collect();
+        assert_eq!(ids, vec![vec![1], vec![2]]);
+    }
+
+    #[test]
+    fn test_label_rule_from_criteria() {
+        let rule: LabelRule = r#"[app_id=firefox title="Inbox - Gmail"]"#.parse().unwrap();
+        assert_eq!(rule.app_id.as_deref(), Some("firefox"));
+        assert_eq!(rule.title.as_deref(), Some("Inbox - Gmail"));
+
+        let rule: LabelRule = "title=htop".parse().unwrap();
+        assert_eq!(rule.app_id, None);
+        assert_eq!(rule.title.as_deref(), Some("htop"));
+
+        assert!("".parse::<LabelRule>().is_err());
+        assert!("class=Firefox".parse::<LabelRule>().is_err());
+        assert!("title=\"unclosed".parse::<LabelRule>().is_err());
+        assert!("firefox".parse::<LabelRule>().is_err());
+    }
 }
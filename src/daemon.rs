@@ -1,23 +1,46 @@
-use crate::config::Config;
-use crate::icon_resolver::WmClassIndex;
-use crate::ipc::InputCommand;
-use crate::ui_commands::UiCommand;
-use crate::window_manager::WindowManager;
+use crate::config::{
+    expand_home, shell_quote, Config, Frontend, FullscreenAction, MruUpdate, Profile,
+    SwitchTarget, WorkspaceMode,
+};
+use crate::event_bus::{DaemonEvent, EventReceiver, EventSender, WindowEvent};
+use crate::focus_history::format_age;
+use crate::icon_resolver::{AppDetailsCache, DesktopFileFinder, WmClassIndex};
+use crate::ipc::{
+    layout_script, DaemonState, Direction, InputCommand, IpcCommand, LayoutEntry, LayoutFormat,
+    SwitcherEvent, TileAction, Trigger, FRONTEND_PROTOCOL_VERSION, STATE_VERSION,
+};
+use crate::key_sequence::{apply_order, KeyOutcome, KeySequence, ListOrder};
+use crate::notify::notify_error;
+use crate::pins::{apply_pins, load_pins, pins_after_move, pins_path, save_pins};
+use crate::rules::{sort_pinned, RuleEngine};
+use crate::selection_log::{self, LoggedWindow, SelectionRecord};
+use crate::session;
+use crate::socket_server::{self, IpcReply, IpcRequest};
+use crate::compositor::{BackgroundReader, Compositor, Snapshot, Subscription};
+use crate::sway_client::{CommandError, RealSwayClient};
+#[cfg(feature = "tray")]
+use crate::tray::{self, TrayState};
+use crate::ui_commands::{
+    preview_dir, preview_path, push_coalesced, SwitcherLayout, UiCommand, UI_RESOURCES,
+};
+use crate::window_manager::{
+    group_sizes, group_windows, use_display_titles, WindowInfo, WindowManager,
+};
 use crate::window_switcher::WindowSwitcher;
-use anyhow::Result;
+use anyhow::{Context, Result};
 use futures_lite::stream::StreamExt;
-use swayipc_async::{Connection, Event, EventType, WindowChange};
+use std::cell::RefCell;
+use std::collections::hash_map::RandomState;
+use std::collections::HashSet;
+use std::fmt::Write;
+use std::hash::{BuildHasher, Hasher};
+use std::time::{Duration, Instant, SystemTime};
 use tokio::signal::unix::{signal, SignalKind};
-use tokio::sync::mpsc;
-use tracing::{debug, error, info};
+use tokio::sync::{broadcast, mpsc, oneshot, watch};
+use tracing::{debug, error, info, warn};
 
 /// Type alias for the optional UI command sender
-type UiSender = Option<mpsc::UnboundedSender<UiCommand>>;
-
-#[derive(Debug, Clone)]
-enum WindowEvent {
-    Focus(i64), // Window ID that received focus
-}
+type UiSender = Option<mpsc::Sender<UiCommand>>;
 
 /// Actions that can be taken by the daemon state machine.
 /// This is a pure representation of what the daemon should do,
@@ -26,6 +49,8 @@ enum WindowEvent {
 pub enum DaemonAction {
     /// Start the window switching UI
     StartSwitching,
+    /// Start switching on the least recently used entry
+    StartSwitchingBackward,
     /// Cycle to the next window
     CycleForward,
     /// Cycle to the previous window
@@ -34,12 +59,113 @@ pub enum DaemonAction {
     FinalizeSelection,
     /// Cancel switching without selecting
     Cancel,
+    /// Launch a new instance of the selected application
+    LaunchNewInstance,
+    /// Move the selected window to the current workspace and focus it
+    MoveSelectionHere,
+    /// Select the window focused this many steps back and focus it
+    JumpBack { steps: usize },
+    /// Select the entry at this index and focus it
+    SelectIndex { index: usize },
+    /// Run a custom action for the selected window
+    RunCustomAction { index: usize },
+    /// Act on the window of the tile at this index, from its context menu
+    TileAction { index: usize, action: TileAction },
+    /// Pin the order left by dragging a tile from one index to another
+    MoveTile { from: usize, to: usize },
+    /// Focus a window picked outside the switcher, closing the switcher
+    FocusWindow { window_id: i64 },
+    /// Focus the selected window and capture it
+    Screenshot,
+    /// Show the idle windows that were hidden, or hide them again
+    ToggleIdle,
+    /// A letter was typed; part of a key sequence or a single-key command
+    Key { key: char },
+    /// An arrow key, which depends on how the switcher is laid out
+    Arrow { direction: Direction },
+    /// Show only the selected application's windows
+    ExpandApp,
+    /// Go back to showing all windows
+    CollapseApp,
+    /// Remember a show that arrived before the UI was up, to cycle later
+    BufferCycle,
+    /// Remember a backward show that arrived before the UI was up
+    BufferCycleBackward,
+    /// The UI is up; replay buffered cycles
+    UiReady,
+    /// The UI's list went stale; send it the current one
+    Resync,
     /// Update MRU order for a window
     UpdateMru { window_id: i64 },
     /// No action needed
     None,
 }
 
+/// The `n`th window of an MRU list, counting from 1 and skipping the
+/// focused window
+#[must_use]
+pub fn nth_recent(windows: &[WindowInfo], focused: Option<i64>, n: usize) -> Option<&WindowInfo> {
+    windows
+        .iter()
+        .filter(|w| Some(w.id) != focused)
+        .nth(n.checked_sub(1)?)
+}
+
+/// Where an arrow key moves the selection from `index` in a grid of `len`
+/// entries, `columns` wide. Left and Right wrap around like cycling; Up
+/// and Down stop at the first and last row, Down landing on the last
+/// entry when the row below is shorter.
+#[must_use]
+pub fn grid_step(index: usize, len: usize, columns: usize, direction: Direction) -> usize {
+    if len == 0 || columns == 0 {
+        return index;
+    }
+    let last = len - 1;
+    match direction {
+        Direction::Left => index.checked_sub(1).unwrap_or(last),
+        Direction::Right => (index + 1) % len,
+        Direction::Up => index.checked_sub(columns).unwrap_or(index),
+        Direction::Down if index / columns < last / columns => (index + columns).min(last),
+        Direction::Down => index,
+    }
+}
+
+/// Where an arrow key moves the selection from `index` in a split layout
+/// of `len` entries, the second section starting at `first` and both
+/// wrapped every `columns` entries (0 for one line each). Left and Right
+/// cycle; Up and Down move a line, across the divider at a section's edge,
+/// keeping the column where it exists. As in a grid, Down doesn't expand
+/// the selected application here.
+#[must_use]
+pub fn split_step(
+    index: usize,
+    len: usize,
+    first: usize,
+    columns: usize,
+    direction: Direction,
+) -> usize {
+    if first == 0 || first >= len {
+        return index;
+    }
+    let columns = if columns == 0 { len } else { columns };
+    let (start, end) = if index < first { (0, first) } else { (first, len) };
+    let (line, column) = ((index - start) / columns, (index - start) % columns);
+    let last_line = (end - start - 1) / columns;
+    match direction {
+        Direction::Left => index.checked_sub(1).unwrap_or(len - 1),
+        Direction::Right => (index + 1) % len,
+        Direction::Up if line > 0 => index - columns,
+        // From the top line of the rest to the current workspace's last line
+        Direction::Up if start == first => {
+            let top_last_line = (first - 1) / columns;
+            (top_last_line * columns + column).min(first - 1)
+        }
+        Direction::Down if line < last_line => (index + columns).min(end - 1),
+        Direction::Down if start == 0 => (first + column).min(len - 1),
+        Direction::Up | Direction::Down => index,
+    }
+}
+
 /// Determine what action to take based on input command and current state.
 /// This is a pure function that encapsulates the state machine logic.
 #[must_use]
@@ -49,22 +175,188 @@ pub fn determine_input_action(cmd: InputCommand, is_switching: bool) -> DaemonAc
         (InputCommand::Prev, true) => DaemonAction::CycleBackward,
         (InputCommand::Select, true) => DaemonAction::FinalizeSelection,
         (InputCommand::Cancel, true) => DaemonAction::Cancel,
+        (InputCommand::LaunchNew, true) => DaemonAction::LaunchNewInstance,
+        (InputCommand::MoveHere, true) => DaemonAction::MoveSelectionHere,
+        // Triggers are resolved through the action map before this point
+        (InputCommand::Trigger(_), true) => DaemonAction::None,
+        (InputCommand::JumpBack(steps), true) => DaemonAction::JumpBack { steps },
+        (InputCommand::SelectIndex(index), true) => DaemonAction::SelectIndex { index },
+        (InputCommand::Custom(index), true) => DaemonAction::RunCustomAction { index },
+        (InputCommand::Screenshot, true) => DaemonAction::Screenshot,
+        (InputCommand::ToggleIdle, true) => DaemonAction::ToggleIdle,
+        (InputCommand::Key(key), true) => DaemonAction::Key { key },
+        (InputCommand::Tile { index, action }, true) => DaemonAction::TileAction { index, action },
+        (InputCommand::Move { from, to }, true) => DaemonAction::MoveTile { from, to },
+        (InputCommand::Focus(window_id), _) => DaemonAction::FocusWindow { window_id },
+        (InputCommand::Arrow(direction), true) => DaemonAction::Arrow { direction },
+        (InputCommand::ExpandApp, true) => DaemonAction::ExpandApp,
+        (InputCommand::CollapseApp, true) => DaemonAction::CollapseApp,
+        (InputCommand::Shown, true) => DaemonAction::UiReady,
+        (InputCommand::Resync, true) => DaemonAction::Resync,
         // When not switching, input commands are ignored
         (_, false) => DaemonAction::None,
     }
 }
 
 /// Determine what action to take when show signal is received.
+///
+/// While switching, show acts like next, or with `select_on_repeat`
+/// selects the current window. Until the UI is up, repeated shows are
+/// quick Tab presses racing the surface; they are held back with other early input.
 #[must_use]
-pub fn determine_show_action(is_switching: bool) -> DaemonAction {
-    if is_switching {
-        // If already switching, show acts like next
-        DaemonAction::CycleForward
-    } else {
-        DaemonAction::StartSwitching
+pub fn determine_show_action(
+    is_switching: bool,
+    select_on_repeat: bool,
+    ui_ready: bool,
+) -> DaemonAction {
+    match (is_switching, ui_ready, select_on_repeat) {
+        (false, _, _) => DaemonAction::StartSwitching,
+        (true, false, _) => DaemonAction::BufferCycle,
+        (true, true, false) => DaemonAction::CycleForward,
+        (true, true, true) => DaemonAction::FinalizeSelection,
+    }
+}
+
+/// Determine what action to take when a backward show is received: the
+/// same as [`determine_show_action`], moving the other way
+#[must_use]
+pub fn determine_show_prev_action(
+    is_switching: bool,
+    select_on_repeat: bool,
+    ui_ready: bool,
+) -> DaemonAction {
+    match determine_show_action(is_switching, select_on_repeat, ui_ready) {
+        DaemonAction::StartSwitching => DaemonAction::StartSwitchingBackward,
+        DaemonAction::BufferCycle => DaemonAction::BufferCycleBackward,
+        DaemonAction::CycleForward => DaemonAction::CycleBackward,
+        action => action,
+    }
+}
+
+/// State changes buffered for each `watch` client before it starts missing some
+const WATCH_BUFFER: usize = 64;
+
+/// Fraction of the refresh interval added or subtracted at random, so the
+/// periodic refresh doesn't line up with other timers
+const REFRESH_JITTER: f64 = 0.1;
+
+/// Seconds grim waits before capturing, so the switcher is gone and the
+/// newly focused window is drawn
+const CAPTURE_DELAY: &str = "0.2";
+
+/// How long a shutdown waits for its reply to be written before the tasks
+/// are stopped
+const SHUTDOWN_GRACE: Duration = Duration::from_secs(1);
+
+/// Focus events closer together than this are one burst (a workspace
+/// switch, a container move) and only the last of them is recorded
+const FOCUS_BURST: Duration = Duration::from_millis(50);
+
+/// How long the focus event of a window the daemon focused is expected,
+/// before that window's focus counts as the user's again
+const OWN_FOCUS_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// How long input is held back for the switcher surface to be mapped.
+/// Input arriving later is handled right away and what was held back is
+/// dropped, so a surface that never maps doesn't swallow the keyboard.
+const MAP_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// A focus change the daemon made itself and already recorded in the MRU
+/// order, so the focus event sway sends for it isn't handled again. Any
+/// other focus event ends the wait and is recorded as usual.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExpectedFocus {
+    pub window_id: i64,
+    pub until: Instant,
+}
+
+impl ExpectedFocus {
+    #[must_use]
+    pub fn new(window_id: i64, now: Instant) -> Self {
+        ExpectedFocus {
+            window_id,
+            until: now + OWN_FOCUS_TIMEOUT,
+        }
+    }
+
+    /// Whether a focus event at `now` still counts as the daemon's
+    #[must_use]
+    pub fn is_pending(&self, now: Instant) -> bool {
+        now < self.until
+    }
+
+    /// Whether the focus event for `window_id` at `now` is the expected one
+    #[must_use]
+    pub fn matches(&self, window_id: i64, now: Instant) -> bool {
+        self.window_id == window_id && self.is_pending(now)
     }
 }
 
+/// Spread `interval` by up to `REFRESH_JITTER` in either direction.
+/// `seed` is any random value. An interval too long to spread is kept as is.
+#[must_use]
+pub fn jittered_interval(interval: Duration, seed: u64) -> Duration {
+    // Map the seed to [-1.0, 1.0]
+    let unit = (seed as f64 / u64::MAX as f64) * 2.0 - 1.0;
+    Duration::try_from_secs_f64(interval.as_secs_f64() * (1.0 + unit * REFRESH_JITTER))
+        .unwrap_or(interval)
+}
+
+/// Next periodic refresh delay, randomised with `jittered_interval`
+fn next_refresh_delay(interval: Duration) -> Duration {
+    let seed = RandomState::new().build_hasher().finish();
+    jittered_interval(interval, seed)
+}
+
+/// The pins saved by a previous daemon, or none if they can't be read
+fn load_saved_pins() -> Vec<String> {
+    let Some(path) = pins_path() else {
+        return Vec::new();
+    };
+    load_pins(&path).unwrap_or_else(|e| {
+        warn!("Ignoring the saved pins: {:#}", e);
+        Vec::new()
+    })
+}
+
+/// A switcher event as a line of JSON
+fn event_line(event: &SwitcherEvent) -> String {
+    // Serializing these plain types can't fail
+    serde_json::to_string(event).expect("switcher event serializes") + "\n"
+}
+
+/// Whether the daemon has gone long enough without a show to stop its
+/// optional background work. A zero `rest_after` never rests.
+#[must_use]
+pub fn should_rest(since_show: Duration, rest_after: Duration) -> bool {
+    !rest_after.is_zero() && since_show >= rest_after
+}
+
+/// Whether an Alt release came so soon after the switcher opened that it
+/// was a tap, to be taken as a quick toggle.
+#[must_use]
+pub fn is_early_release(since_start: Duration, min_hold: Duration) -> bool {
+    since_start < min_hold
+}
+
+/// Whether a window wasn't focused for `idle_after`. Windows not focused
+/// since the daemon started have no focus time and never count as idle.
+#[must_use]
+pub fn is_idle(window: &WindowInfo, now: SystemTime, idle_after: Duration) -> bool {
+    window
+        .last_focused
+        .and_then(|at| now.duration_since(at).ok())
+        .is_some_and(|age| age >= idle_after)
+}
+
+/// Where the windows on `workspace` end in a hybrid list, if there are
+/// windows on both sides to separate.
+#[must_use]
+pub fn separator_index(windows: &[WindowInfo], workspace: &str) -> Option<usize> {
+    let split = windows.iter().take_while(|w| w.workspace == workspace).count();
+    (split > 0 && split < windows.len()).then_some(split)
+}
+
 /// Determine what action to take for a window focus event.
 #[must_use]
 pub fn determine_focus_action(window_id: i64, is_switching: bool) -> DaemonAction {
@@ -76,25 +368,154 @@ pub fn determine_focus_action(window_id: i64, is_switching: bool) -> DaemonActio
     }
 }
 
-pub struct Daemon {
-    window_manager: WindowManager,
+pub struct Daemon<C: Compositor = RealSwayClient> {
+    window_manager: WindowManager<C>,
     config: Config,
     /// Active window switcher session, or None if idle
     switcher: Option<WindowSwitcher>,
+    /// What the active switcher session switches between
+    target: SwitchTarget,
+    /// Whether the UI has reported the switcher surface as mapped
+    ui_ready: bool,
+    /// Navigation received before the UI was ready (repeated shows and
+    /// Tab presses), replayed in order once it is
+    pending_input: Vec<InputCommand>,
+    /// How often the active switcher's selection was moved, for the
+    /// selection log
+    cycles: usize,
+    /// When the active switcher session started
+    started_at: Instant,
     ui_tx: UiSender,
+    /// Commands for the UI queued while handling the current event
+    ui_outbox: RefCell<Vec<UiCommand>>,
+    /// Set once the UI's channel was found closed, so that is reported once
+    ui_lost: bool,
+    /// Switcher state changes for `watch` clients, as JSON lines
+    watchers: broadcast::Sender<String>,
     wmclass_index: WmClassIndex,
+    /// Names and icon files for the `current` command, which bars poll
+    app_details: AppDetailsCache,
+    /// Name of the active config profile, if any
+    profile: Option<String>,
+    /// Set by the `shutdown` command; the event loop stops after the current event
+    shutting_down: bool,
+    /// Fires once the reply to `shutdown` was written, so the daemon
+    /// doesn't stop the socket server under it
+    shutdown_reply: Option<oneshot::Receiver<()>>,
+    /// Set by `pause`: show requests are ignored until `resume`
+    paused: bool,
+    /// Whether logind reports the session as active; while another session
+    /// has the seat, shows and focus changes are ignored
+    session_active: bool,
+    /// When the last show request came in
+    last_show: Instant,
+    /// Set after `rest_after` without a show: optional background work
+    /// stops until the next show. The periodic refresh task follows it.
+    resting: watch::Sender<bool>,
+    /// Windows last sent to the dock, to only send changes
+    dock_ids: Vec<i64>,
+    /// Windows and focus last sent to the taskbar, to only send changes
+    taskbar: (Vec<WindowInfo>, Option<i64>),
+    /// What the tray icon shows
+    #[cfg(feature = "tray")]
+    tray_state: watch::Sender<TrayState>,
+    /// The list the show-all key switches to: all windows while idle ones
+    /// are hidden, and back. None if no windows were hidden.
+    idle_toggle: Option<Vec<WindowInfo>>,
+    /// Leader key typed in the switcher, waiting for the rest of its sequence
+    key_sequence: KeySequence,
+    /// Order the active switcher's list was put in by a key sequence
+    list_order: ListOrder,
+    /// Applications whose windows lead the switcher, in this order,
+    /// from dragging tiles
+    pins: Vec<String>,
+    /// Requests for a background refresh, tagged with the generation they
+    /// were made at. None until the event loop runs.
+    refresher: Option<mpsc::UnboundedSender<u64>>,
+    /// Reads the window list when the daemon waits for it, so the event
+    /// loop isn't blocked while sway answers
+    reader: BackgroundReader<C>,
+    /// Focus changes waiting to settle, so bursts are coalesced. None
+    /// until the event loop runs, and with `update = "selection"`.
+    settler: Option<mpsc::UnboundedSender<i64>>,
+    /// The last focus change sent to the settler and not recorded yet
+    pending_focus: Option<i64>,
+    /// Bumped whenever the window list changes from focus or an in-place
+    /// refresh, so older background snapshots can be told apart
+    generation: u64,
+    /// Set while an instant show waits for the fresh window list
+    reconciling: bool,
+    /// Workspace set the active switcher was limited to
+    switch_set: Option<String>,
+    /// Application (its lowercase app key) the active switcher was limited to
+    switch_app: Option<String>,
+    /// The last focus change the daemon made, while its events may arrive
+    expected_focus: Option<ExpectedFocus>,
 }
 
-impl Daemon {
+impl Daemon<RealSwayClient> {
     pub fn new(config: Config, ui_tx: UiSender, wmclass_index: WmClassIndex) -> Result<Self> {
-        let window_manager = WindowManager::new()?;
+        let window_manager = WindowManager::new(config.window_detection)?;
+        Self::with_window_manager(window_manager, config, ui_tx, wmclass_index)
+    }
+}
+
+impl<C: Compositor> Daemon<C> {
+    /// Create a daemon around a window manager of any compositor (for testing)
+    pub fn with_window_manager(
+        window_manager: WindowManager<C>,
+        config: Config,
+        ui_tx: UiSender,
+        wmclass_index: WmClassIndex,
+    ) -> Result<Self> {
+        let profile = config.profile.clone();
+        if config.file.preview.enabled
+            && let Some(dir) = preview_dir()
+        {
+            std::fs::create_dir_all(&dir)
+                .with_context(|| format!("Failed to create {}", dir.display()))?;
+        }
 
+        let reader = BackgroundReader::new(window_manager.compositor().connect_again()?);
         Ok(Daemon {
             window_manager,
             config,
             switcher: None,
+            target: SwitchTarget::default(),
+            ui_ready: false,
+            pending_input: Vec::new(),
+            cycles: 0,
+            started_at: Instant::now(),
             ui_tx,
+            ui_outbox: RefCell::new(Vec::new()),
+            ui_lost: false,
+            watchers: broadcast::channel(WATCH_BUFFER).0,
+            app_details: AppDetailsCache::new(wmclass_index.clone()),
             wmclass_index,
+            profile,
+            shutting_down: false,
+            shutdown_reply: None,
+            paused: false,
+            session_active: true,
+            last_show: Instant::now(),
+            resting: watch::channel(false).0,
+            dock_ids: Vec::new(),
+            taskbar: (Vec::new(), None),
+            #[cfg(feature = "tray")]
+            tray_state: watch::channel(TrayState::default()).0,
+            idle_toggle: None,
+            key_sequence: KeySequence::default(),
+            list_order: ListOrder::default(),
+            pins: load_saved_pins(),
+            refresher: None,
+            reader,
+            settler: None,
+            pending_focus: None,
+            generation: 0,
+            reconciling: false,
+            switch_set: None,
+            switch_app: None,
+            expected_focus: None,
         })
     }
 
@@ -104,252 +525,1995 @@ impl Daemon {
     }
 
     /// Main event loop
-    pub async fn run(
-        mut self,
-        mut input_rx: mpsc::UnboundedReceiver<InputCommand>,
-    ) -> Result<()> {
+    ///
+    /// `events_tx` is the sending half of `events`, as given to the UI
+    pub async fn run(mut self, events_tx: EventSender, mut events: EventReceiver) -> Result<()> {
         info!("Starting daemon event loop");
 
-        // Set up SIGUSR1 handler for show command
-        let mut sigusr1 = signal(SignalKind::user_defined1())?;
+        // Warm the UI's icon caches for windows that are already open
+        self.preload_icons();
 
-        // Create channel for window events
-        let (window_tx, mut window_rx) = mpsc::unbounded_channel();
+        // SIGUSR1 shows the switcher, SIGUSR2 the focused app's windows
+        let mut sigusr1 = signal(SignalKind::user_defined1())?;
+        let mut sigusr2 = signal(SignalKind::user_defined2())?;
+        let signals_tx = events_tx.clone();
+        let signals = tokio::spawn(async move {
+            loop {
+                let event = tokio::select! {
+                    Some(()) = sigusr1.recv() => {
+                        debug!("Received SIGUSR1, triggering show");
+                        DaemonEvent::Show(SwitchTarget::Windows)
+                    }
+                    Some(()) = sigusr2.recv() => {
+                        debug!("Received SIGUSR2, triggering show for the focused app");
+                        DaemonEvent::ShowSameApp
+                    }
+                    else => break,
+                };
+                if signals_tx.send(event).is_err() {
+                    break;
+                }
+            }
+        });
 
-        // Create Sway IPC connection for event monitoring
+        // Sway window events
+        let window_tx = events_tx.clone();
+        let subscription = self.window_manager.compositor().window_events();
         let sway_events = tokio::spawn(async move {
-            match Self::monitor_sway_events(window_tx).await {
-                Ok(_) => {}
-                Err(e) => error!("Sway event monitoring error: {}", e),
+            let reason = match Self::monitor_sway_events(subscription, window_tx).await {
+                Ok(()) => "sway closed the event stream".to_string(),
+                Err(e) => e.to_string(),
+            };
+            error!("Sway event monitoring stopped: {}", reason);
+            notify_error(
+                "Lost the connection to sway",
+                &format!("The window list will no longer follow focus changes ({}).", reason),
+            );
+        });
+
+        // CLI commands on the Unix socket
+        let ipc_tx = events_tx.clone();
+        let socket_server = tokio::spawn(async move {
+            if let Err(e) = socket_server::serve(ipc_tx).await {
+                error!("Socket server error: {:#}", e);
             }
         });
 
-        // Main event loop
-        loop {
-            tokio::select! {
-                _ = sigusr1.recv() => {
-                    debug!("Received SIGUSR1, triggering show");
-                    self.handle_show()?;
-                }
-                Some(input_cmd) = input_rx.recv() => {
-                    debug!("Received input command: {:?}", input_cmd);
-                    self.handle_input_command(input_cmd)?;
+        // logind session changes (VT switches) and the screen lock
+        session::watch_active(events_tx.clone());
+
+        // Background refreshes, read on the blocking pool
+        let (refresher_tx, mut refresher_rx) = mpsc::unbounded_channel();
+        self.refresher = Some(refresher_tx);
+        let mut reader = BackgroundReader::new(self.window_manager.compositor().connect_again()?);
+        let snapshots_tx = events_tx.clone();
+        let refresher = tokio::spawn(async move {
+            while let Some(generation) = refresher_rx.recv().await {
+                match reader.fetch().await {
+                    Ok(snapshot) => {
+                        let event = DaemonEvent::Snapshot {
+                            generation,
+                            snapshot: Box::new(snapshot),
+                        };
+                        if snapshots_tx.send(event).is_err() {
+                            break;
+                        }
+                    }
+                    Err(e) => warn!("Background refresh failed: {:#}", e),
                 }
-                Some(window_event) = window_rx.recv() => {
-                    debug!("Received window event: {:?}", window_event);
-                    self.handle_window_event(window_event)?;
+            }
+        });
+
+        // Focus changes count once no other follows within FOCUS_BURST,
+        // or settle-ms with `update = "settled"`
+        let settle = match self.config.file.mru.update {
+            MruUpdate::Settled => Duration::from_millis(self.config.file.mru.settle_ms),
+            _ => FOCUS_BURST,
+        };
+        let settler = (self.config.file.mru.update != MruUpdate::Selection).then(|| {
+            let (settler_tx, mut settler_rx) = mpsc::unbounded_channel();
+            self.settler = Some(settler_tx);
+            let settled_tx = events_tx.clone();
+            tokio::spawn(async move {
+                while let Some(mut window_id) = settler_rx.recv().await {
+                    loop {
+                        match tokio::time::timeout(settle, settler_rx.recv()).await {
+                            Ok(Some(next)) => window_id = next,
+                            Ok(None) => return,
+                            Err(_) => break,
+                        }
+                    }
+                    if settled_tx.send(DaemonEvent::FocusSettled(window_id)).is_err() {
+                        break;
+                    }
                 }
-                else => {
-                    error!("All channels closed, shutting down");
-                    break;
+            })
+        });
+
+        // Optional periodic refresh to catch events that were missed
+        let refresh_interval = Duration::from_secs(self.config.refresh_interval);
+        let refresh = (!refresh_interval.is_zero()).then(|| {
+            let refresh_tx = events_tx.clone();
+            let mut resting = self.resting.subscribe();
+            tokio::spawn(async move {
+                loop {
+                    // No wakeups while resting; the next show ends it
+                    while *resting.borrow_and_update() {
+                        if resting.changed().await.is_err() {
+                            return;
+                        }
+                    }
+                    tokio::time::sleep(next_refresh_delay(refresh_interval)).await;
+                    if refresh_tx.send(DaemonEvent::Refresh).is_err() {
+                        break;
+                    }
                 }
+            })
+        });
+        self.update_tray();
+        #[cfg(feature = "tray")]
+        let tray = self
+            .config
+            .tray
+            .then(|| tokio::spawn(tray::run(events_tx.clone(), self.tray_state.subscribe())));
+        #[cfg(not(feature = "tray"))]
+        let tray: Option<tokio::task::JoinHandle<()>> = None;
+        drop(events_tx);
+
+        // Main event loop
+        self.update_dock();
+        self.update_taskbar();
+        self.flush_ui().await;
+        while let Some(event) = events.recv().await {
+            self.handle_event(event).await?;
+            self.update_dock();
+            self.update_taskbar();
+            self.update_tray();
+            self.flush_ui().await;
+            if self.shutting_down {
+                break;
+            }
+        }
+        if self.shutting_down {
+            // Let the connection task write the reply to `shutdown`
+            if let Some(written) = self.shutdown_reply.take()
+                && tokio::time::timeout(SHUTDOWN_GRACE, written).await.is_err()
+            {
+                warn!("The reply to shutdown wasn't written within {:?}", SHUTDOWN_GRACE);
             }
+        } else {
+            error!("All event sources closed, shutting down");
         }
 
         info!("Daemon shutting down gracefully");
-        sway_events.abort();
+        for task in [signals, sway_events, socket_server, refresher]
+            .into_iter()
+            .chain(settler)
+            .chain(refresh)
+            .chain(tray)
+        {
+            task.abort();
+        }
         Ok(())
     }
 
-    /// Handle SIGUSR1 show command
-    fn handle_show(&mut self) -> Result<()> {
-        match determine_show_action(self.is_switching()) {
-            DaemonAction::StartSwitching => self.start_switching(),
-            DaemonAction::CycleForward => self.cycle_windows(true),
-            _ => Ok(()),
+    /// Show the switcher once and return when a window was picked or the
+    /// switcher was cancelled. Only UI input is handled: no socket, signals
+    /// or sway events, so this can run next to a resident daemon.
+    pub async fn run_once(mut self, mut events: EventReceiver) -> Result<()> {
+        self.start_switching(SwitchTarget::Windows, None, true, 0, false).await?;
+        self.flush_ui().await;
+
+        while self.is_switching() {
+            let Some(event) = events.recv().await else {
+                break;
+            };
+            if let DaemonEvent::Input(input_cmd) = event {
+                self.handle_input_command(input_cmd)?;
+                self.flush_ui().await;
+            }
         }
+        Ok(())
     }
 
-    /// Handle keyboard input commands from UI
-    fn handle_input_command(&mut self, cmd: InputCommand) -> Result<()> {
-        debug!("Input command: {:?}, switching: {}", cmd, self.is_switching());
-
-        match determine_input_action(cmd, self.is_switching()) {
-            DaemonAction::CycleForward => self.cycle_windows(true),
-            DaemonAction::CycleBackward => self.cycle_windows(false),
-            DaemonAction::FinalizeSelection => self.finalize_selection(),
-            DaemonAction::Cancel => self.cancel_switching(),
-            DaemonAction::None => Ok(()),
-            _ => Ok(()),
+    /// Queue a command for the UI; it is sent once the current event is handled.
+    /// `watch` clients see the same change right away.
+    fn send_ui(&self, command: UiCommand) {
+        if self.watchers.receiver_count() > 0
+            && let Some(event) = command.switcher_event()
+        {
+            // Only fails when the last watcher just went away
+            let _ = self.watchers.send(event_line(&event));
+        }
+        if self.ui_tx.is_some() {
+            push_coalesced(&mut self.ui_outbox.borrow_mut(), command);
         }
     }
 
-    fn handle_window_event(&mut self, event: WindowEvent) -> Result<()> {
-        match event {
-            WindowEvent::Focus(window_id) => {
-                if let DaemonAction::UpdateMru { window_id } =
-                    determine_focus_action(window_id, self.is_switching())
-                {
-                    debug!("Window {} focused, updating MRU order", window_id);
-                    self.window_manager.on_focus(window_id);
+    /// Send the queued UI commands, waiting while the UI's channel is full
+    async fn flush_ui(&mut self) {
+        let commands = self.ui_outbox.take();
+        if let Some(ref ui_tx) = self.ui_tx {
+            for command in commands {
+                if let Err(e) = ui_tx.send(command).await {
+                    debug!("Failed to send command to UI (channel closed): {}", e);
+                    if !self.ui_lost {
+                        self.ui_lost = true;
+                        error!("The UI has stopped, the switcher can't be shown");
+                        notify_error(
+                            "Window switcher UI stopped",
+                            "The switcher can't be shown until the daemon is restarted.",
+                        );
+                    }
+                    break;
                 }
             }
         }
-        Ok(())
     }
 
-    fn start_switching(&mut self) -> Result<()> {
-        info!("Starting window switching mode");
-
-        // Refresh window list
-        self.window_manager.refresh()?;
-
-        // Get filtered windows
-        let windows = self.window_manager.get_filtered_windows(self.config.mode);
+    /// Send the dock the most recently used windows if they changed
+    fn update_dock(&mut self) {
+        let size = self.config.file.dock.size;
+        if size == 0 {
+            return;
+        }
+        let mut windows: Vec<WindowInfo> = self
+            .window_manager
+            .windows()
+            .iter()
+            .take(size)
+            .cloned()
+            .collect();
+        use_display_titles(&mut windows, &self.config.file.appearance.untitled);
+        let ids: Vec<i64> = windows.iter().map(|w| w.id).collect();
+        if ids == self.dock_ids {
+            return;
+        }
+        self.dock_ids = ids;
+        self.send_ui(UiCommand::UpdateDock {
+            windows,
+            wmclass_index: self.wmclass_index.clone(),
+        });
+    }
 
-        if windows.is_empty() {
-            info!("No windows to switch to");
-            return Ok(());
+    /// Send the taskbar all windows, oldest first with pinned applications
+    /// leading, if they or the focus changed
+    fn update_taskbar(&mut self) {
+        if !self.config.file.taskbar.enabled {
+            return;
+        }
+        // Sway numbers containers in the order they were created
+        let mut windows = self.window_manager.windows().to_vec();
+        // The most recently used window is the focused one
+        let focused = windows.first().map(|w| w.id);
+        windows.sort_by_key(|w| w.id);
+        apply_pins(&mut windows, &self.pins);
+        use_display_titles(&mut windows, &self.config.file.appearance.untitled);
+        if self.taskbar.0 == windows && self.taskbar.1 == focused {
+            return;
         }
+        self.taskbar = (windows.clone(), focused);
+        self.send_ui(UiCommand::UpdateTaskbar {
+            windows,
+            focused,
+            wmclass_index: self.wmclass_index.clone(),
+        });
+    }
 
-        // Create the switcher, starting at the next window
-        let switcher = WindowSwitcher::new(windows, true);
+    /// Let the tray icon know about changes to what it shows
+    #[cfg(feature = "tray")]
+    fn update_tray(&self) {
+        let state = TrayState {
+            paused: self.paused,
+            profile: self.profile.clone(),
+            profiles: self.config.file.profiles.keys().cloned().collect(),
+        };
+        self.tray_state.send_if_modified(|current| {
+            let changed = *current != state;
+            *current = state;
+            changed
+        });
+    }
 
-        // Print to stderr (keep console output)
-        Self::print_switcher_static(&switcher);
+    #[cfg(not(feature = "tray"))]
+    fn update_tray(&self) {}
 
-        // Show UI if available
-        if let Some(ref ui_tx) = self.ui_tx {
-            info!("Sending UiCommand::Show to UI");
-            if let Err(e) = ui_tx.send(UiCommand::Show {
-                windows: switcher.windows().to_vec(),
-                initial_index: switcher.current_index(),
-                wmclass_index: self.wmclass_index.clone(),
-            }) {
-                error!("Failed to send UI command: {:?}", e);
-            } else {
-                info!("UI command sent successfully");
+    /// Dispatch an event from the event bus
+    async fn handle_event(&mut self, event: DaemonEvent) -> Result<()> {
+        if matches!(event, DaemonEvent::Window(_) | DaemonEvent::Refresh) {
+            self.update_rest();
+        }
+        match event {
+            DaemonEvent::Show(target) => self.handle_show(target, None, true, 0, false).await,
+            DaemonEvent::ShowSameApp => {
+                self.handle_show(SwitchTarget::Windows, None, true, 0, true).await
             }
-        } else {
-            info!("No UI channel available");
+            DaemonEvent::Input(input_cmd) => {
+                debug!("Received input command: {:?}", input_cmd);
+                self.handle_input_command(input_cmd)
+            }
+            DaemonEvent::Ipc(request) => self.handle_ipc_request(request).await,
+            DaemonEvent::Window(window_event) => {
+                debug!("Received window event: {:?}", window_event);
+                self.handle_window_event(window_event).await
+            }
+            DaemonEvent::FocusSettled(window_id) => {
+                // Already recorded if a show or the daemon's own focus came first
+                if self.pending_focus == Some(window_id) {
+                    debug!("Window {} stayed focused", window_id);
+                    self.settle_focus();
+                }
+                Ok(())
+            }
+            DaemonEvent::Refresh => self.periodic_refresh().await,
+            DaemonEvent::Snapshot {
+                generation,
+                snapshot,
+            } => self.apply_snapshot(generation, *snapshot).await,
+            DaemonEvent::SessionActive(active) => self.set_session_active(active).await,
         }
-
-        // Enter switching state
-        self.switcher = Some(switcher);
-
-        Ok(())
     }
 
-    fn cycle_windows(&mut self, forward: bool) -> Result<()> {
-        debug!("Cycling windows: forward={}", forward);
-
-        if let Some(ref mut switcher) = self.switcher {
-            if switcher.is_empty() {
-                return Ok(());
-            }
-            switcher.cycle(forward);
-        } else {
+    /// Handle a show command (a signal or IPC). A workspace set, `skip`
+    /// and `same_app` only apply when this starts a new switcher.
+    async fn handle_show(
+        &mut self,
+        target: SwitchTarget,
+        set: Option<&str>,
+        forward: bool,
+        skip: usize,
+        same_app: bool,
+    ) -> Result<()> {
+        self.last_show = Instant::now();
+        if self.is_resting() {
+            info!("Show requested, resuming background work");
+            self.resting.send_replace(false);
+        }
+        if self.paused && !self.is_switching() {
+            debug!("Paused, ignoring show");
+            return Ok(());
+        }
+        if !self.session_active {
+            debug!("Session is inactive, ignoring show");
             return Ok(());
         }
+        let determine = if forward {
+            determine_show_action
+        } else {
+            determine_show_prev_action
+        };
+        match determine(self.is_switching(), self.config.select_on_repeat, self.ui_ready) {
+            DaemonAction::StartSwitching => {
+                self.start_switching(target, set, true, skip, same_app).await
+            }
+            DaemonAction::StartSwitchingBackward => {
+                self.start_switching(target, set, false, skip, same_app).await
+            }
+            DaemonAction::BufferCycle => {
+                if !self.buffer_until_mapped(InputCommand::Next) {
+                    self.cycle_windows(true)?;
+                }
+                Ok(())
+            }
+            DaemonAction::BufferCycleBackward => {
+                if !self.buffer_until_mapped(InputCommand::Prev) {
+                    self.cycle_windows(false)?;
+                }
+                Ok(())
+            }
+            DaemonAction::CycleForward => self.cycle_windows(true),
+            DaemonAction::CycleBackward => self.cycle_windows(false),
+            DaemonAction::FinalizeSelection => self.finalize_selection(),
+            _ => Ok(()),
+        }
+    }
 
-        // Print to stderr (keep console output)
-        if let Some(ref switcher) = self.switcher {
-            Self::print_switcher_static(switcher);
+    /// Handle a command from a CLI client and send back the reply
+    async fn handle_ipc_request(&mut self, request: IpcRequest) -> Result<()> {
+        debug!("IPC command: {}", request.command);
+
+        let reply = match request.command {
+            IpcCommand::Watch => Ok(self.watch()),
+            IpcCommand::Shutdown => self.shutdown().map(|text| {
+                let (written, shutdown_reply) = oneshot::channel();
+                self.shutdown_reply = Some(shutdown_reply);
+                IpcReply::Last { text, written }
+            }),
+            command => self.handle_text_command(command).await.map(IpcReply::Text),
+        };
+
+        if request.reply.send(reply).is_err() {
+            debug!("IPC client went away before the reply was sent");
         }
+        Ok(())
+    }
 
-        // Update UI if available
-        if let Some(ref ui_tx) = self.ui_tx
-            && let Some(ref switcher) = self.switcher {
-                let command = UiCommand::UpdateSelection {
-                    index: switcher.current_index(),
-                };
-                if let Err(e) = ui_tx.send(command) {
-                    debug!("Failed to send selection update to UI (channel closed): {}", e);
+    /// Handle a command whose reply is a single piece of text
+    async fn handle_text_command(&mut self, command: IpcCommand) -> Result<String> {
+        match command {
+            IpcCommand::Show { what, set, skip } => {
+                if let Some(ref name) = set {
+                    self.config.workspace_set(name)?;
+                }
+                self.handle_show(what, set.as_deref(), true, skip, false)
+                    .await
+                    .map(|_| String::new())
+            }
+            IpcCommand::ShowPrev { what, set, skip } => {
+                if let Some(ref name) = set {
+                    self.config.workspace_set(name)?;
                 }
+                self.handle_show(what, set.as_deref(), false, skip, false)
+                    .await
+                    .map(|_| String::new())
             }
+            IpcCommand::ShowSameApp => self
+                .handle_show(SwitchTarget::Windows, None, true, 0, true)
+                .await
+                .map(|_| String::new()),
+            IpcCommand::History { count } => Ok(self.format_history(count)),
+            IpcCommand::Current => Ok(self.format_current()),
+            IpcCommand::FocusNth { n } => self.focus_nth(n).await,
+            IpcCommand::SetProfile { name } => self.set_profile(name),
+            IpcCommand::Windows => self.format_windows(),
+            IpcCommand::Skipped => self.format_skipped().await,
+            IpcCommand::Rules => self.format_rules().await,
+            IpcCommand::ExportState => self.export_state(),
+            IpcCommand::Shutdown => self.shutdown(),
+            IpcCommand::Status => Ok(self.format_status().await),
+            IpcCommand::Pause => Ok(self.set_paused(true)),
+            IpcCommand::Resume => Ok(self.set_paused(false)),
+            IpcCommand::Reload => self.reload_config(),
+            IpcCommand::DebugResources => Ok(self.format_resources()),
+            IpcCommand::ImportState(state) => self.import_state(state).await,
+            IpcCommand::ExportLayout { format } => self.export_layout(format).await,
+            IpcCommand::Input(input) => self.handle_input_command(input).map(|_| String::new()),
+            IpcCommand::Watch => anyhow::bail!("watch streams its reply"),
+        }
+    }
 
-        Ok(())
+    /// All known windows in MRU order as a JSON array
+    fn format_windows(&self) -> Result<String> {
+        let mut windows = self.window_manager.windows().to_vec();
+        use_display_titles(&mut windows, &self.config.file.appearance.untitled);
+        let json = serde_json::to_string(&windows)?;
+        Ok(json + "\n")
     }
 
-    fn print_switcher_static(switcher: &WindowSwitcher) {
-        debug!("=== Window Switcher ===");
-        for (i, window) in switcher.windows().iter().enumerate() {
-            let marker = if i == switcher.current_index() {
-                ">>>"
-            } else {
-                "   "
-            };
-            let app_id = window.app_id.as_deref().unwrap_or("<unknown>");
-            debug!("{} [{}] {} - {}", marker, window.id, app_id, window.title);
+    /// Nodes left out of the window list, one per line with the reason
+    async fn format_skipped(&mut self) -> Result<String> {
+        self.refresh_now().await?;
+        let mut output = String::new();
+        for node in self.window_manager.skipped() {
+            let _ = writeln!(
+                output,
+                "[{}] {} - {}: {}",
+                node.id,
+                node.app_id.as_deref().unwrap_or("<unknown>"),
+                node.name.as_deref().unwrap_or(""),
+                node.reason
+            );
         }
-        debug!("=======================");
+        Ok(output)
     }
 
-    fn finalize_selection(&mut self) -> Result<()> {
-        info!("Finalizing window selection");
+    /// The rules in evaluation order and which of them matched each window
+    async fn format_rules(&mut self) -> Result<String> {
+        self.refresh_now().await?;
+        let engine = RuleEngine::new(&self.config.file);
+        Ok(engine.describe(self.window_manager.windows()))
+    }
 
-        // Take the switcher out, ending switching mode
-        let Some(switcher) = self.switcher.take() else {
-            return Ok(());
+    /// Start streaming switcher state changes, beginning with the current state
+    fn watch(&self) -> IpcReply {
+        let hello = SwitcherEvent::Hello {
+            version: FRONTEND_PROTOCOL_VERSION,
         };
+        let state = match self.switcher {
+            Some(ref switcher) => SwitcherEvent::Show {
+                windows: switcher.windows().to_vec(),
+                selected: switcher.current_index(),
+                target: self.target,
+            },
+            None => SwitcherEvent::Hide,
+        };
+        IpcReply::Stream {
+            initial: event_line(&hello) + &event_line(&state),
+            updates: self.watchers.subscribe(),
+        }
+    }
 
-        // Focus the selected window
-        if let Some(window) = switcher.current() {
-            debug!("Selecting window: {} (ID: {})", window.title, window.id);
-            let window_id = window.id;
-            self.window_manager.focus_window(window_id)?;
-
-            // Update MRU order immediately (don't wait for Sway event)
-            self.window_manager.on_focus(window_id);
+    /// Format the last `count` focus changes, one per line, most recent first
+    fn format_history(&self, count: usize) -> String {
+        let now = SystemTime::now();
+        let mut output = String::new();
+        for (steps, entry) in self.window_manager.history().iter().take(count).enumerate() {
+            let age = now.duration_since(entry.focused_at).unwrap_or_default();
+            let app_id = entry.app_id.as_deref().unwrap_or("<unknown>");
+            let title = entry.display_title(&self.config.file.appearance.untitled);
+            let _ = writeln!(
+                output,
+                "{}\t{}\t[{}] {} - {}",
+                steps,
+                format_age(age),
+                entry.window_id,
+                app_id,
+                title
+            );
         }
+        output
+    }
 
-        // Hide UI if available
-        if let Some(ref ui_tx) = self.ui_tx
-            && let Err(e) = ui_tx.send(UiCommand::Hide) {
-                debug!("Failed to send hide command to UI (channel closed): {}", e);
+    /// Describe the daemon's health as `key=value` lines. The daemon is
+    /// healthy while sway answers and the UI (if any) is still listening.
+    async fn format_status(&mut self) -> String {
+        let sway_ok = match self.refresh_now().await {
+            Ok(()) => true,
+            Err(e) => {
+                warn!("Status check could not reach sway: {:#}", e);
+                false
             }
+        };
+        let ui = match self.ui_tx {
+            None => "none",
+            Some(ref ui_tx) if ui_tx.is_closed() => "closed",
+            Some(_) => "ok",
+        };
+        let state = if self.is_switching() {
+            "switching"
+        } else if !self.session_active {
+            "inactive"
+        } else if self.paused {
+            "paused"
+        } else if self.is_resting() {
+            "resting"
+        } else {
+            "idle"
+        };
+        format!(
+            "healthy={}\nstate={}\nwindows={}\nprofile={}\nsway={}\nui={}\n",
+            sway_ok && ui != "closed",
+            state,
+            self.window_manager.windows().len(),
+            self.profile.as_deref().unwrap_or(""),
+            if sway_ok { "ok" } else { "unreachable" },
+            ui
+        )
+    }
 
-        Ok(())
+    /// What the UI holds from the compositor, to check that nothing is
+    /// left behind that would keep idle or lock tools from acting
+    fn format_resources(&self) -> String {
+        let frontend = format!("frontend={}\n", self.config.frontend.as_str());
+        if self.config.frontend == Frontend::Gtk && self.ui_tx.is_some() {
+            frontend + &UI_RESOURCES.report()
+        } else {
+            frontend
+        }
     }
 
-    /// Cancel switching without selecting a window
-    fn cancel_switching(&mut self) -> Result<()> {
-        info!("Canceling window switching");
+    fn is_resting(&self) -> bool {
+        *self.resting.borrow()
+    }
 
-        self.switcher = None;
+    /// Start resting once `rest_after` has passed without a show
+    fn update_rest(&mut self) {
+        let rest_after = Duration::from_secs(self.config.rest_after * 60);
+        if !self.is_resting() && should_rest(self.last_show.elapsed(), rest_after) {
+            info!("No show for {:?}, pausing background work", rest_after);
+            self.resting.send_replace(true);
+        }
+    }
 
-        // Hide UI if available
-        if let Some(ref ui_tx) = self.ui_tx
-            && let Err(e) = ui_tx.send(UiCommand::Hide) {
-                debug!("Failed to send hide command to UI (channel closed): {}", e);
+    /// Rest while another session has the seat, and catch up on the window
+    /// list when this one comes back
+    async fn set_session_active(&mut self, active: bool) -> Result<()> {
+        if active == self.session_active {
+            return Ok(());
+        }
+        self.session_active = active;
+        if active {
+            info!("Session is active again, refreshing");
+            self.refresh_now().await
+        } else {
+            info!("Session is inactive, resting until it is back");
+            if self.is_switching() {
+                self.cancel_switching()?;
             }
-
-        Ok(())
+            Ok(())
+        }
     }
 
-    /// Monitor Sway events for window changes
-    async fn monitor_sway_events(window_tx: mpsc::UnboundedSender<WindowEvent>) -> Result<()> {
-        let subs = [EventType::Window];
-        let mut events = Connection::new().await?.subscribe(&subs).await?;
+    /// Start or stop ignoring show requests. An open switcher stays open.
+    fn set_paused(&mut self, paused: bool) -> String {
+        let reply = if paused { "Paused" } else { "Resumed" };
+        info!("{}", reply);
+        self.paused = paused;
+        format!("{}\n", reply)
+    }
 
-        info!("Subscribed to Sway window events");
+    /// Re-read the config file. The GTK UI was built from the old settings,
+    /// so the sections it uses are kept until a restart.
+    fn reload_config(&mut self) -> Result<String> {
+        let mut reloaded = self.config.clone();
+        reloaded.load_file()?;
+        let mut file = reloaded.file;
+        let current = &self.config.file;
+        file.appearance = current.appearance.clone();
+        file.keyboard = current.keyboard.clone();
+        file.dock = current.dock.clone();
+        file.taskbar = current.taskbar.clone();
+        file.preview = current.preview.clone();
+        self.config.file = file;
+
+        if let Some(ref name) = self.profile
+            && !self.config.file.profiles.contains_key(name)
+        {
+            warn!("Profile {} is gone from the config, using the defaults", name);
+            self.profile = None;
+        }
+        info!("Reloaded the config file");
+        Ok("Reloaded the config file\n".to_string())
+    }
 
-        while let Some(event) = events.next().await {
-            if let Event::Window(e) = event? {
-                debug!(
-                    "Sway window event: {:?} for container {:?}",
-                    e.change, e.container.id
-                );
-
-                // Track window focus changes for MRU ordering
-                if e.change == WindowChange::Focus
-                    && let Err(e) = window_tx.send(WindowEvent::Focus(e.container.id)) {
-                        error!("Failed to send window focus event: {}", e);
-                    }
-            }
+    /// Close the switcher if it is open and stop the event loop
+    fn shutdown(&mut self) -> Result<String> {
+        info!("Shutdown requested");
+        if self.is_switching() {
+            self.cancel_switching()?;
         }
+        self.shutting_down = true;
+        Ok("Shutting down\n".to_string())
+    }
 
-        Ok(())
+    /// The MRU order, focus history and profile as one line of JSON
+    fn export_state(&self) -> Result<String> {
+        let state = DaemonState {
+            version: STATE_VERSION,
+            mru: self.window_manager.windows().iter().map(|w| w.id).collect(),
+            history: self.window_manager.history().iter().cloned().collect(),
+            profile: self.profile.clone(),
+        };
+        Ok(serde_json::to_string(&state)? + "\n")
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    /// The open windows in MRU order, with where they are and a guess at
+    /// the command that started each from their desktop files
+    async fn export_layout(&mut self, format: LayoutFormat) -> Result<String> {
+        self.refresh_now().await?;
+        let desktop_files = DesktopFileFinder::new(self.wmclass_index.clone());
+        let entries: Vec<LayoutEntry> = self
+            .window_manager
+            .windows()
+            .iter()
+            .map(|window| LayoutEntry {
+                app_id: window.app_key().map(str::to_string),
+                title: window.title.clone(),
+                workspace: window.workspace.clone(),
+                output: window.output.clone(),
+                geometry: Some(window.geometry),
+                exec: window
+                    .icon_candidates()
+                    .into_iter()
+                    .find_map(|id| desktop_files.find_exec(id)),
+            })
+            .collect();
+        match format {
+            LayoutFormat::Json => Ok(serde_json::to_string(&entries)? + "\n"),
+            LayoutFormat::Script => Ok(layout_script(&entries)),
+        }
+    }
 
-    // ==================== determine_input_action tests ====================
+    /// Restore what a previous daemon exported. The profile is checked first
+    /// so a bad import leaves everything as it was.
+    async fn import_state(&mut self, state: DaemonState) -> Result<String> {
+        if state.version != STATE_VERSION {
+            anyhow::bail!(
+                "Unsupported state version {} (expected {})",
+                state.version,
+                STATE_VERSION
+            );
+        }
+        self.set_profile(state.profile)?;
+        self.refresh_now().await?;
+        self.window_manager.restore(&state.mru, state.history);
+        info!("Imported state for {} windows", state.mru.len());
+        Ok(format!("Imported state for {} windows\n", state.mru.len()))
+    }
 
-    #[test]
+    /// Start from the state a replaced daemon exported
+    pub async fn restore(&mut self, state: DaemonState) -> Result<()> {
+        self.import_state(state).await.map(|_| ())
+    }
+
+    /// Switch to a named profile (or back to the command line settings)
+    fn set_profile(&mut self, name: Option<String>) -> Result<String> {
+        if let Some(ref name) = name {
+            self.config.profile(name)?;
+        }
+        info!("Switching to profile {:?}", name);
+        let reply = match name {
+            Some(ref name) => format!("Switched to profile {}\n", name),
+            None => "Switched to default settings\n".to_string(),
+        };
+        self.profile = name;
+        Ok(reply)
+    }
+
+    /// The active profile's settings, if a profile is active
+    fn active_profile(&self) -> Option<&Profile> {
+        self.profile
+            .as_deref()
+            .and_then(|name| self.config.file.profiles.get(name))
+    }
+
+    /// Describe the most recently used window as `key=value` lines,
+    /// for status bars that show the current/last window
+    fn format_current(&mut self) -> String {
+        let Some(window) = self.window_manager.windows().first() else {
+            return String::new();
+        };
+        let details = self.app_details.lookup(window);
+
+        format!(
+            "id={}\napp_id={}\nname={}\ntitle={}\nicon={}\n",
+            window.id,
+            window.app_key().unwrap_or_default(),
+            details.name.unwrap_or_default(),
+            window.display_title(&self.config.file.appearance.untitled),
+            details.icon.map(|path| path.display().to_string()).unwrap_or_default()
+        )
+    }
+
+    /// Handle keyboard input commands from UI
+    fn handle_input_command(&mut self, cmd: InputCommand) -> Result<()> {
+        debug!("Input command: {:?}, switching: {}", cmd, self.is_switching());
+
+        if let InputCommand::Trigger(Trigger::Release | Trigger::ShiftRelease) = cmd {
+            let min_hold = Duration::from_millis(self.config.min_hold_ms);
+            if self.is_switching() && is_early_release(self.started_at.elapsed(), min_hold) {
+                // A tap: switch to the preselected window rather than
+                // leaving the switcher racing its own mapping
+                debug!("Alt released within {:?} of showing, taking it as a tap", min_hold);
+                return self.finalize_selection();
+            }
+        }
+
+        // Resolve configurable triggers (Enter, Alt release, ...) to their action
+        let cmd = match cmd {
+            InputCommand::Trigger(trigger) => {
+                let action = self.config.file.actions.action_for(trigger);
+                debug!("Trigger {:?} mapped to {:?}", trigger, action);
+                action.input_command()
+            }
+            cmd => cmd,
+        };
+
+        // Keys pressed while the surface is still being mapped would move a
+        // selection the UI hasn't drawn yet
+        if matches!(cmd, InputCommand::Next | InputCommand::Prev | InputCommand::Arrow(_))
+            && self.buffer_until_mapped(cmd.clone())
+        {
+            return Ok(());
+        }
+
+        let action = determine_input_action(cmd, self.is_switching());
+        if !matches!(action, DaemonAction::Key { .. }) {
+            self.key_sequence.reset();
+        }
+        match action {
+            DaemonAction::CycleForward => self.cycle_windows(true),
+            DaemonAction::CycleBackward => self.cycle_windows(false),
+            DaemonAction::FinalizeSelection => self.finalize_selection(),
+            DaemonAction::Cancel => {
+                // Only a cancel the user asked for, not the switcher going away
+                self.play_sound(true);
+                self.cancel_switching()
+            }
+            DaemonAction::LaunchNewInstance => self.launch_new_instance(),
+            DaemonAction::MoveSelectionHere => self.move_selection_here(),
+            DaemonAction::JumpBack { steps } => self.jump_back(steps),
+            DaemonAction::SelectIndex { index } => self.select_index(index),
+            DaemonAction::RunCustomAction { index } => self.run_custom_action(index),
+            DaemonAction::Screenshot => self.screenshot_selection(),
+            DaemonAction::ToggleIdle => self.toggle_idle(),
+            DaemonAction::Key { key } => self.handle_key(key),
+            DaemonAction::TileAction { index, action } => self.tile_action(index, action),
+            DaemonAction::MoveTile { from, to } => self.move_tile(from, to),
+            DaemonAction::FocusWindow { window_id } => self.focus_window(window_id),
+            DaemonAction::Arrow { direction } => self.arrow(direction),
+            DaemonAction::ExpandApp => self.expand_app(),
+            DaemonAction::CollapseApp => self.collapse_app(),
+            DaemonAction::UiReady => self.on_ui_ready(),
+            DaemonAction::Resync => {
+                if let Some(ref switcher) = self.switcher {
+                    warn!("UI list out of sync, showing it again");
+                    self.show_ui(switcher);
+                }
+                Ok(())
+            }
+            DaemonAction::None => Ok(()),
+            _ => Ok(()),
+        }
+    }
+
+    async fn handle_window_event(&mut self, event: WindowEvent) -> Result<()> {
+        match event {
+            WindowEvent::Focus(window_id) => {
+                self.generation += 1;
+                if !self.session_active {
+                    debug!("Session is inactive, not recording focus of {}", window_id);
+                    return Ok(());
+                }
+                // Taken either way: another window's focus means the user
+                // (or another client) moved on
+                if let Some(expected) = self.expected_focus.take()
+                    && expected.matches(window_id, Instant::now())
+                {
+                    // The MRU order was updated when the command was sent
+                    debug!("Window {} focused by the daemon, already recorded", window_id);
+                    return Ok(());
+                }
+                if let DaemonAction::UpdateMru { window_id } =
+                    determine_focus_action(window_id, self.is_switching())
+                {
+                    match self.settler {
+                        Some(ref settler) if settler.send(window_id).is_ok() => {
+                            self.pending_focus = Some(window_id);
+                        }
+                        _ if self.config.file.mru.update == MruUpdate::Selection => {
+                            debug!("Window {} focused, MRU only follows selections", window_id);
+                        }
+                        _ => self.record_focus(window_id),
+                    }
+                }
+            }
+            WindowEvent::New(window_id) => {
+                if !self.is_switching() {
+                    debug!("Window {} opened, refreshing", window_id);
+                    self.request_refresh().await?;
+                } else if self.target != SwitchTarget::Windows {
+                    self.update_group_sizes().await?;
+                }
+            }
+            WindowEvent::Close(window_id) => {
+                self.remove_preview(window_id);
+                if self.is_switching() && self.target != SwitchTarget::Windows {
+                    debug!("Window {} closed while switching, recounting groups", window_id);
+                    self.update_group_sizes().await?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Record the focus change waiting in the settler right away, e.g.
+    /// before the switcher lists the windows
+    fn settle_focus(&mut self) {
+        let Some(window_id) = self.pending_focus.take() else {
+            return;
+        };
+        if self.session_active
+            && let DaemonAction::UpdateMru { window_id } =
+                determine_focus_action(window_id, self.is_switching())
+        {
+            self.record_focus(window_id);
+        }
+    }
+
+    /// Move a window the user focused to the front of the MRU order
+    fn record_focus(&mut self, window_id: i64) {
+        debug!("Updating MRU order for window {}", window_id);
+        self.window_manager.on_focus(window_id);
+    }
+
+    /// Record a focus change the daemon made in the MRU order right away,
+    /// and remember it so the focus events it causes aren't handled again
+    fn focused_by_daemon(&mut self, window_id: i64) {
+        self.pending_focus = None;
+        self.window_manager.on_focus(window_id);
+        self.expected_focus = Some(ExpectedFocus::new(window_id, Instant::now()));
+    }
+
+    /// Re-read the window list and send the open grouped switcher's new
+    /// window counts, so its badges follow windows opening and closing
+    async fn update_group_sizes(&mut self) -> Result<()> {
+        self.refresh_now().await?;
+        if let Some(ref switcher) = self.switcher {
+            self.send_ui(UiCommand::UpdateGroupSizes(self.group_sizes(switcher)));
+        }
+        Ok(())
+    }
+
+    /// Capture a thumbnail of a window picked in the switcher for its
+    /// preview, once the switcher is gone and the window is on screen
+    fn capture_preview(&mut self, window: &WindowInfo) {
+        if !self.config.file.preview.enabled || self.is_resting() {
+            return;
+        }
+        let Some(path) = preview_path(window.id) else {
+            return;
+        };
+        let command = format!(
+            "sleep {} && grim -s {} -g {} {}",
+            CAPTURE_DELAY,
+            self.config.file.preview.scale,
+            shell_quote(&window.geometry.to_string()),
+            shell_quote(&path.to_string_lossy())
+        );
+        debug!("Capturing preview: {}", command);
+        if let Err(e) = self.window_manager.exec(&command) {
+            warn!("Failed to capture a preview of window {}: {:#}", window.id, e);
+        }
+    }
+
+    /// Delete the preview of a window that closed
+    fn remove_preview(&self, window_id: i64) {
+        if !self.config.file.preview.enabled {
+            return;
+        }
+        let Some(path) = preview_path(window_id) else {
+            return;
+        };
+        match std::fs::remove_file(&path) {
+            Ok(()) => debug!("Removed the preview of window {}", window_id),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => warn!("Failed to remove {}: {}", path.display(), e),
+        }
+    }
+
+    /// Reconcile the MRU list with the live tree, unless the user is switching
+    async fn periodic_refresh(&mut self) -> Result<()> {
+        if self.is_switching() || self.is_resting() {
+            return Ok(());
+        }
+        debug!("Periodic refresh of the window list");
+        self.request_refresh().await
+    }
+
+    /// Refresh the window list from sway, waiting for the answer on the
+    /// blocking pool. For when the list must be current before going on,
+    /// e.g. to show the switcher.
+    async fn refresh_now(&mut self) -> Result<()> {
+        self.generation += 1;
+        let snapshot = self.reader.fetch().await?;
+        self.window_manager.apply_snapshot(snapshot);
+        Ok(())
+    }
+
+    /// Refresh the window list without waiting on sway: the snapshot is
+    /// read in the background and applied when it arrives. Outside the
+    /// event loop, refresh in place.
+    async fn request_refresh(&mut self) -> Result<()> {
+        match self.refresher {
+            Some(ref refresher) if refresher.send(self.generation).is_ok() => Ok(()),
+            _ => self.refresh_now().await,
+        }
+    }
+
+    /// Apply a snapshot read in the background. While switching it is
+    /// dropped like any refresh, unless an instant show is waiting for it.
+    /// If the list changed since it was requested it may be older than
+    /// what the list knows, so another one is read.
+    async fn apply_snapshot(&mut self, generation: u64, snapshot: Snapshot) -> Result<()> {
+        let reconcile = self.reconciling && self.is_switching();
+        if self.is_switching() && !reconcile {
+            return Ok(());
+        }
+        if generation != self.generation {
+            debug!("Window list changed while reading a snapshot, reading another");
+            return self.request_refresh().await;
+        }
+        let known: HashSet<i64> = self.window_manager.windows().iter().map(|w| w.id).collect();
+        self.window_manager.apply_snapshot(snapshot);
+        if reconcile {
+            return self.reconcile_switcher();
+        }
+        let opened = self.window_manager.windows().iter().any(|w| !known.contains(&w.id));
+        if opened && !self.is_resting() {
+            self.preload_icons();
+        }
+        Ok(())
+    }
+
+    /// Ask the UI to resolve icons for all open windows in the background
+    fn preload_icons(&self) {
+        self.send_ui(UiCommand::PreloadIcons {
+            windows: self.window_manager.windows().to_vec(),
+            wmclass_index: self.wmclass_index.clone(),
+        });
+    }
+
+    async fn start_switching(
+        &mut self,
+        target: SwitchTarget,
+        set: Option<&str>,
+        forward: bool,
+        skip: usize,
+        same_app: bool,
+    ) -> Result<()> {
+        info!("Starting window switching mode ({})", target.as_str());
+
+        // The overlay would show window titles on top of the lock screen
+        if session::screen_locked().await {
+            info!("The screen is locked, not showing the switcher");
+            return Ok(());
+        }
+        // A focus change that hasn't settled yet is where the user is now
+        self.settle_focus();
+
+        // Refresh window list. An instant show goes with the list as it
+        // was and swaps in the fresh one when it arrives.
+        self.reconciling = self.config.instant_show && self.refresher.is_some();
+        if self.reconciling {
+            self.request_refresh().await?;
+        } else {
+            self.refresh_now().await?;
+        }
+        let fullscreen_action = if self.window_manager.focused_is_fullscreen() {
+            self.config.file.fullscreen.action
+        } else {
+            FullscreenAction::Show
+        };
+        if fullscreen_action == FullscreenAction::Pause {
+            info!("The focused window is fullscreen, not showing the switcher");
+            return Ok(());
+        }
+
+        self.switch_app = None;
+        if same_app {
+            let focused = self.window_manager.focused_window_id();
+            let app = self
+                .window_manager
+                .windows()
+                .iter()
+                .find(|w| Some(w.id) == focused)
+                .and_then(WindowInfo::app_key);
+            let Some(app) = app else {
+                info!("No focused application to switch between the windows of");
+                return Ok(());
+            };
+            self.switch_app = Some(app.to_lowercase());
+        }
+        let windows = self.switch_list(target, set)?;
+        self.key_sequence.reset();
+        self.list_order = ListOrder::default();
+        let windows = self.hide_idle(windows);
+        if windows.is_empty() {
+            info!("No windows to switch to");
+            return Ok(());
+        }
+        let mut switcher = self.new_switcher(windows, target);
+        let last = switcher.windows().len() - 1;
+        if !forward {
+            // Backward from the focused window is the end of the list
+            switcher.select_index(last);
+        }
+        if skip > 0 {
+            // Stop at either end rather than wrapping around
+            let index = if forward {
+                (switcher.current_index() + skip).min(last)
+            } else {
+                switcher.current_index().saturating_sub(skip)
+            };
+            switcher.select_index(index);
+        }
+        self.switch_set = set.map(str::to_string);
+
+        // Over a fullscreen window, switch to the next window right away
+        // instead of drawing an overlay and grabbing the keyboard
+        if fullscreen_action == FullscreenAction::Toggle {
+            info!("The focused window is fullscreen, switching without the switcher");
+            self.switcher = Some(switcher);
+            return self.finalize_selection();
+        }
+
+        // Print to stderr (keep console output)
+        Self::print_switcher_static(&switcher);
+
+        // Show UI if available; without one there is nothing to wait for
+        self.target = target;
+        self.ui_ready = self.ui_tx.is_none();
+        self.pending_input.clear();
+        self.cycles = 0;
+        self.started_at = Instant::now();
+        self.show_ui(&switcher);
+
+        // Enter switching state
+        self.switcher = Some(switcher);
+
+        Ok(())
+    }
+
+    /// The windows to switch between, filtered, grouped and labelled as
+    /// configured; a workspace set replaces the mode's filter
+    fn switch_list(&self, target: SwitchTarget, set: Option<&str>) -> Result<Vec<WindowInfo>> {
+        let mut windows = group_windows(self.filtered_windows(set)?, target);
+        if let Some(ref app) = self.switch_app {
+            windows.retain(|w| w.app_key().is_some_and(|key| key.to_lowercase() == *app));
+        }
+        use_display_titles(&mut windows, &self.config.file.appearance.untitled);
+        Ok(windows)
+    }
+
+    /// The windows the mode, or a workspace set, and the window rules let
+    /// into the switcher, before grouping. The focused window is left out
+    /// here with `exclude_focused`, so its app's other windows still get
+    /// an entry when grouped.
+    fn filtered_windows(&self, set: Option<&str>) -> Result<Vec<WindowInfo>> {
+        let profile = self.active_profile();
+        let windows = match set {
+            Some(name) => {
+                let workspaces = self.config.workspace_set(name)?;
+                let mode = match self.config.mode_for(profile) {
+                    mode if mode.current_first() => mode,
+                    _ => WorkspaceMode::All,
+                };
+                let mut windows = self
+                    .window_manager
+                    .get_filtered_windows(mode, self.config.sort_for(profile));
+                windows.retain(|w| workspaces.contains(&w.workspace));
+                windows
+            }
+            None => self.window_manager.get_filtered_windows(
+                self.config.mode_for(profile),
+                self.config.sort_for(profile),
+            ),
+        };
+        let mut windows = RuleEngine::new(&self.config.file).apply(windows);
+        if self.config.exclude_focused
+            && let Some(focused_id) = self.window_manager.focused_window_id()
+        {
+            windows.retain(|w| w.id != focused_id);
+        }
+        Ok(windows)
+    }
+
+    /// How many windows each entry of the active grouped switcher stands
+    /// for; empty while switching between windows
+    fn group_sizes(&self, switcher: &WindowSwitcher) -> Vec<usize> {
+        if self.target == SwitchTarget::Windows {
+            return Vec::new();
+        }
+        match self.filtered_windows(self.switch_set.as_deref()) {
+            Ok(windows) => group_sizes(switcher.windows(), &windows, self.target),
+            Err(e) => {
+                warn!("Failed to count the grouped windows: {:#}", e);
+                Vec::new()
+            }
+        }
+    }
+
+    /// Leave out windows that weren't focused for `hide-idle-after`,
+    /// keeping the full list for the show-all key
+    fn hide_idle(&mut self, mut windows: Vec<WindowInfo>) -> Vec<WindowInfo> {
+        self.idle_toggle = None;
+        let hide_idle_after = self.config.file.filter.hide_idle_after;
+        if hide_idle_after > 0 {
+            let idle_after = Duration::from_secs(hide_idle_after * 60);
+            let now = SystemTime::now();
+            let active: Vec<WindowInfo> = windows
+                .iter()
+                .filter(|w| !is_idle(w, now, idle_after))
+                .cloned()
+                .collect();
+            // Keep the idle windows if hiding them would leave nothing
+            if !active.is_empty() && active.len() < windows.len() {
+                debug!("Hiding {} idle windows", windows.len() - active.len());
+                self.idle_toggle = Some(std::mem::replace(&mut windows, active));
+            }
+        }
+        windows
+    }
+
+    /// Create the switcher, starting at the next window (the first one
+    /// when the focused window isn't listed)
+    fn new_switcher(
+        &mut self,
+        mut windows: Vec<WindowInfo>,
+        target: SwitchTarget,
+    ) -> WindowSwitcher {
+        // Sorting and pins can move the window used before this one away
+        // from the second place, but the selection still starts on it
+        let next = self.next_entry(&windows);
+        let rule_pinned = windows.iter().any(|w| w.pinned);
+        if (!self.pins.is_empty() || rule_pinned) && target == SwitchTarget::Windows {
+            // Windows pinned by a rule lead, then pinned applications
+            apply_pins(&mut windows, &self.pins);
+            sort_pinned(&mut windows);
+            if let Some(ref mut hidden) = self.idle_toggle {
+                apply_pins(hidden, &self.pins);
+                sort_pinned(hidden);
+            }
+        }
+        let mut switcher = WindowSwitcher::new(windows, false);
+        if let Some(id) = next {
+            switcher.select_window(id);
+        }
+        switcher
+    }
+
+    /// The entry that follows the focused window in MRU order, with the
+    /// current workspace's first where the mode lists them first; the
+    /// first entry with `exclude_focused`
+    fn next_entry(&self, windows: &[WindowInfo]) -> Option<i64> {
+        let mru = self.window_manager.windows();
+        let current_first = self.config.mode_for(self.active_profile()).current_first();
+        let current = self.window_manager.current_workspace();
+        let focused = self.window_manager.focused_window_id();
+        let next = windows
+            .iter()
+            .filter(|w| Some(w.id) != focused)
+            .min_by_key(|w| {
+                let elsewhere = current_first && Some(w.workspace.as_str()) != current;
+                let rank = mru.iter().position(|m| m.id == w.id).unwrap_or(usize::MAX);
+                (elsewhere, rank)
+            });
+        next.or(windows.first()).map(|w| w.id)
+    }
+
+    /// Swap the list an instant show drew from the cache for one built
+    /// from the fresh tree, keeping the selection on the same window
+    fn reconcile_switcher(&mut self) -> Result<()> {
+        self.reconciling = false;
+        let Some(ref switcher) = self.switcher else {
+            return Ok(());
+        };
+        // The user already rearranged the list; keep what they see
+        if switcher.is_expanded() || self.list_order != ListOrder::default() {
+            return Ok(());
+        }
+        let selected = switcher.current().map(|w| w.id);
+        let set = self.switch_set.clone();
+        let windows = self.switch_list(self.target, set.as_deref())?;
+        let windows = self.hide_idle(windows);
+        if windows.is_empty() {
+            info!("No windows left to switch to");
+            return self.cancel_switching();
+        }
+        let mut switcher = self.new_switcher(windows, self.target);
+        if let Some(id) = selected {
+            switcher.select_window(id);
+        }
+        debug!("Reconciled the switcher with {} windows", switcher.windows().len());
+        self.show_ui(&switcher);
+        self.switcher = Some(switcher);
+        Ok(())
+    }
+
+    /// Send the switcher's current window list and selection to the UI
+    fn show_ui(&self, switcher: &WindowSwitcher) {
+        if self.ui_tx.is_some() {
+            info!("Sending UiCommand::Show to UI");
+            let layout = self.switcher_layout(switcher);
+            let hybrid = self.config.mode_for(self.active_profile()) == WorkspaceMode::Hybrid;
+            let separator = match self.window_manager.current_workspace() {
+                Some(workspace)
+                    if hybrid && layout == SwitcherLayout::Row && self.target == SwitchTarget::Windows =>
+                {
+                    separator_index(switcher.windows(), workspace)
+                }
+                _ => None,
+            };
+            self.send_ui(UiCommand::Show {
+                windows: switcher.windows().to_vec(),
+                initial_index: switcher.current_index(),
+                wmclass_index: self.wmclass_index.clone(),
+                layout,
+                target: self.target,
+                separator,
+                output_size: self.window_manager.current_output_size(),
+                refreshing: self.reconciling,
+                group_sizes: self.group_sizes(switcher),
+            });
+        } else {
+            info!("No UI channel available");
+        }
+    }
+
+    /// How the UI lays out `switcher`: an expanded application as a list,
+    /// the split mode's windows in two rows, otherwise a row, wrapped into
+    /// a grid when `grid-columns` is exceeded
+    fn switcher_layout(&self, switcher: &WindowSwitcher) -> SwitcherLayout {
+        let columns = self.config.file.appearance.grid_columns;
+        let split = self.config.mode_for(self.active_profile()) == WorkspaceMode::Split
+            && self.target == SwitchTarget::Windows;
+        let first = self
+            .window_manager
+            .current_workspace()
+            .filter(|_| split)
+            .and_then(|workspace| separator_index(switcher.windows(), workspace));
+        if switcher.is_expanded() {
+            SwitcherLayout::AppList
+        } else if let Some(first) = first {
+            SwitcherLayout::Split { first, columns }
+        } else if columns > 0 && switcher.windows().len() > columns {
+            SwitcherLayout::Grid { columns }
+        } else {
+            SwitcherLayout::Row
+        }
+    }
+
+    /// Move the selection across a grid or between the rows of a split
+    /// layout, or cycle, expand and collapse as the keys do in a row
+    fn arrow(&mut self, direction: Direction) -> Result<()> {
+        let Some(ref switcher) = self.switcher else {
+            return Ok(());
+        };
+        let (current, len) = (switcher.current_index(), switcher.windows().len());
+        let index = match self.switcher_layout(switcher) {
+            SwitcherLayout::Grid { columns } => grid_step(current, len, columns, direction),
+            SwitcherLayout::Split { first, columns } => {
+                split_step(current, len, first, columns, direction)
+            }
+            _ => {
+                return match direction {
+                    Direction::Left => self.cycle_windows(false),
+                    Direction::Right => self.cycle_windows(true),
+                    Direction::Up => self.collapse_app(),
+                    Direction::Down => self.expand_app(),
+                };
+            }
+        };
+        let Some(ref mut switcher) = self.switcher else {
+            return Ok(());
+        };
+        if index == switcher.current_index() || !switcher.select_index(index) {
+            return Ok(());
+        }
+        self.cycles += 1;
+        self.send_ui(UiCommand::UpdateSelection { index, len });
+        Ok(())
+    }
+
+    /// Hold `cmd` back until the switcher surface is mapped, unless it
+    /// already is or [`MAP_TIMEOUT`] has passed. Returns whether it was held.
+    fn buffer_until_mapped(&mut self, cmd: InputCommand) -> bool {
+        if self.ui_ready || !self.is_switching() {
+            return false;
+        }
+        if self.started_at.elapsed() >= MAP_TIMEOUT {
+            warn!(
+                "Switcher not mapped after {:?}, dropping {} buffered input(s)",
+                MAP_TIMEOUT,
+                self.pending_input.len()
+            );
+            self.pending_input.clear();
+            self.ui_ready = true;
+            return false;
+        }
+        self.pending_input.push(cmd);
+        debug!("UI not up yet, buffered {} input(s)", self.pending_input.len());
+        true
+    }
+
+    /// Replay the input that arrived while the UI was being mapped
+    fn on_ui_ready(&mut self) -> Result<()> {
+        self.ui_ready = true;
+        let pending = std::mem::take(&mut self.pending_input);
+        if !pending.is_empty() {
+            debug!("UI is up, replaying {} buffered input(s)", pending.len());
+        }
+        for cmd in pending {
+            self.handle_input_command(cmd)?;
+        }
+        Ok(())
+    }
+
+    /// Narrow the switcher to the selected application's windows
+    fn expand_app(&mut self) -> Result<()> {
+        // Grouped lists only hold one window per app or workspace
+        if self.target != SwitchTarget::Windows {
+            return Ok(());
+        }
+        if let Some(ref mut switcher) = self.switcher
+            && switcher.expand_app()
+        {
+            debug!("Expanded to {} windows of the selected app", switcher.windows().len());
+            if let Some(ref switcher) = self.switcher {
+                self.show_ui(switcher);
+            }
+        }
+        Ok(())
+    }
+
+    /// Return from the application list to all windows
+    fn collapse_app(&mut self) -> Result<()> {
+        if let Some(ref mut switcher) = self.switcher
+            && switcher.collapse()
+        {
+            debug!("Collapsed back to {} windows", switcher.windows().len());
+            if let Some(ref switcher) = self.switcher {
+                self.show_ui(switcher);
+            }
+        }
+        Ok(())
+    }
+
+    /// Swap between the list without idle windows and all windows,
+    /// keeping the selected window selected
+    fn toggle_idle(&mut self) -> Result<()> {
+        let Some(ref mut switcher) = self.switcher else {
+            return Ok(());
+        };
+        if switcher.is_expanded() {
+            return Ok(());
+        }
+        let Some(mut other) = self.idle_toggle.take() else {
+            debug!("No idle windows were hidden");
+            return Ok(());
+        };
+
+        // Keep an order picked with a key sequence
+        let mru: Vec<i64> = self.window_manager.windows().iter().map(|w| w.id).collect();
+        if self.list_order != ListOrder::Mru {
+            apply_order(&mut other, self.list_order, &mru);
+        }
+        let selected = switcher.current().map(|w| w.id);
+        let previous = std::mem::replace(switcher, WindowSwitcher::new(other, false));
+        if let Some(id) = selected {
+            switcher.select_window(id);
+        }
+        debug!("Switched to {} windows", switcher.windows().len());
+        self.idle_toggle = Some(previous.windows().to_vec());
+        if let Some(ref switcher) = self.switcher {
+            self.show_ui(switcher);
+        }
+        Ok(())
+    }
+
+    /// A letter typed in the switcher: a key sequence changes the list's
+    /// order, and `a` on its own shows or hides idle windows
+    fn handle_key(&mut self, key: char) -> Result<()> {
+        match self.key_sequence.press(key) {
+            KeyOutcome::Pending => {
+                debug!("Waiting for the key after {}", key);
+                Ok(())
+            }
+            KeyOutcome::Order(order) => self.reorder_switcher(order),
+            KeyOutcome::Unknown => {
+                debug!("No key sequence ends with {}", key);
+                Ok(())
+            }
+            KeyOutcome::Single('a') => self.toggle_idle(),
+            KeyOutcome::Single(_) => Ok(()),
+        }
+    }
+
+    /// Pin the order left by dragging the tile at `from` to `to`, and
+    /// show the list in it
+    fn move_tile(&mut self, from: usize, to: usize) -> Result<()> {
+        // Grouped lists and the application list aren't reordered
+        if self.target != SwitchTarget::Windows {
+            return Ok(());
+        }
+        let Some(ref switcher) = self.switcher else {
+            return Ok(());
+        };
+        if switcher.is_expanded() {
+            return Ok(());
+        }
+        let pins = pins_after_move(switcher.windows(), &self.pins, from, to);
+        self.set_pins(pins)
+    }
+
+    /// Take the selected tile's application out of the pins
+    fn unpin_selection(&mut self) -> Result<()> {
+        let Some(app) = self
+            .switcher
+            .as_ref()
+            .and_then(|switcher| switcher.current())
+            .and_then(|window| window.app_key())
+        else {
+            return Ok(());
+        };
+        let pins = self.pins.iter().filter(|pin| *pin != app).cloned().collect();
+        self.set_pins(pins)
+    }
+
+    /// Replace the pins, saving them and reordering the open switcher
+    fn set_pins(&mut self, pins: Vec<String>) -> Result<()> {
+        if pins == self.pins {
+            return Ok(());
+        }
+        info!("Pinned applications: {}", pins.join(", "));
+        if let Some(path) = pins_path()
+            && let Err(e) = save_pins(&path, &pins)
+        {
+            warn!("Failed to save the pins: {:#}", e);
+        }
+        self.pins = pins;
+
+        let Some(ref mut switcher) = self.switcher else {
+            return Ok(());
+        };
+        let mru: Vec<i64> = self.window_manager.windows().iter().map(|w| w.id).collect();
+        let mut windows = switcher.windows().to_vec();
+        apply_order(&mut windows, self.list_order, &mru);
+        apply_pins(&mut windows, &self.pins);
+        let selected = switcher.current().map(|w| w.id);
+        *switcher = WindowSwitcher::new(windows, false);
+        if let Some(id) = selected {
+            switcher.select_window(id);
+        }
+        if let Some(ref switcher) = self.switcher {
+            self.show_ui(switcher);
+        }
+        Ok(())
+    }
+
+    /// Put the switcher's list in a new order, keeping the selection
+    fn reorder_switcher(&mut self, order: ListOrder) -> Result<()> {
+        let Some(ref mut switcher) = self.switcher else {
+            return Ok(());
+        };
+        if switcher.is_expanded() {
+            return Ok(());
+        }
+
+        let mru: Vec<i64> = self.window_manager.windows().iter().map(|w| w.id).collect();
+        let mut windows = switcher.windows().to_vec();
+        apply_order(&mut windows, order, &mru);
+        let selected = switcher.current().map(|w| w.id);
+        *switcher = WindowSwitcher::new(windows, false);
+        if let Some(id) = selected {
+            switcher.select_window(id);
+        }
+        info!("Ordering the switcher by {:?}", order);
+        self.list_order = order;
+        if let Some(ref switcher) = self.switcher {
+            self.show_ui(switcher);
+        }
+        Ok(())
+    }
+
+    fn cycle_windows(&mut self, forward: bool) -> Result<()> {
+        debug!("Cycling windows: forward={}", forward);
+
+        let wrapped = if let Some(ref mut switcher) = self.switcher {
+            if switcher.is_empty() {
+                return Ok(());
+            }
+            let wrapped = switcher.would_wrap(forward);
+            switcher.cycle(forward);
+            self.cycles += 1;
+            wrapped
+        } else {
+            return Ok(());
+        };
+
+        // Print to stderr (keep console output)
+        if let Some(ref switcher) = self.switcher {
+            Self::print_switcher_static(switcher);
+        }
+
+        // Update UI if available
+        if let Some(ref switcher) = self.switcher {
+            self.send_ui(UiCommand::UpdateSelection {
+                index: switcher.current_index(),
+                len: switcher.windows().len(),
+            });
+            if wrapped && self.config.file.appearance.wrap_cue {
+                self.send_ui(UiCommand::WrapCue);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn print_switcher_static(switcher: &WindowSwitcher) {
+        debug!("=== Window Switcher ===");
+        for (i, window) in switcher.windows().iter().enumerate() {
+            let marker = if i == switcher.current_index() {
+                ">>>"
+            } else {
+                "   "
+            };
+            let app_id = window.app_id.as_deref().unwrap_or("<unknown>");
+            debug!("{} [{}] {} - {}", marker, window.id, app_id, window.title);
+        }
+        debug!("=======================");
+    }
+
+    fn finalize_selection(&mut self) -> Result<()> {
+        info!("Finalizing window selection");
+
+        // Take the switcher out, ending switching mode
+        let Some(switcher) = self.switcher.take() else {
+            return Ok(());
+        };
+
+        if let Some(selected) = switcher.current() {
+            debug!("Selected window: {} (ID: {})", selected.title, selected.id);
+        }
+
+        // The MRU order isn't updated while switching, so this is the
+        // window focused when the switcher opened
+        let origin = self.window_manager.windows().first().map(LoggedWindow::from);
+
+        // Focus the selected window, falling back to the next MRU window
+        // if the selection was closed while the switcher was open
+        for window in switcher.selection_candidates() {
+            match self
+                .window_manager
+                .focus_window(window.id, self.config.focus_workspace_first)
+            {
+                Ok(()) => {
+                    // Update MRU order immediately (don't wait for Sway event)
+                    self.focused_by_daemon(window.id);
+                    self.log_selection(origin, window);
+                    self.capture_preview(window);
+                    self.play_sound(false);
+                    break;
+                }
+                Err(e) => {
+                    self.expected_focus = None;
+                    // Only a rejected command means the window is gone; a
+                    // command sway couldn't parse says nothing about it
+                    if let Some(CommandError::Failed { .. }) = e.downcast_ref::<CommandError>() {
+                        warn!(
+                            "Window closed: '{}' (ID: {}) could not be focused ({}), trying next window",
+                            window.title, window.id, e
+                        );
+                        self.window_manager.remove_window(window.id);
+                        self.send_ui(UiCommand::WindowClosed {
+                            title: window.title.clone(),
+                        });
+                    } else {
+                        error!(
+                            "Failed to focus '{}' (ID: {}): {:#}",
+                            window.title, window.id, e
+                        );
+                        break;
+                    }
+                }
+            }
+        }
+
+        self.hide_ui();
+
+        Ok(())
+    }
+
+    /// Record a finished switch in the selection log, if one is configured
+    fn log_selection(&self, origin: Option<LoggedWindow>, selected: &WindowInfo) {
+        let Some(ref path) = self.config.file.selection_log.path else {
+            return;
+        };
+        let record = SelectionRecord {
+            time: SystemTime::now()
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .map_or(0, |since| since.as_secs()),
+            origin,
+            selected: LoggedWindow::from(selected),
+            cycles: self.cycles,
+        };
+        if let Err(e) = selection_log::write_record(&expand_home(path), &record) {
+            debug!("Selection not logged: {:#}", e);
+        }
+    }
+
+    /// Play the theme sound for a pick or a cancel, if sounds are on
+    fn play_sound(&mut self, cancelled: bool) {
+        let Some(command) = self.config.file.sound.command(cancelled) else {
+            return;
+        };
+        debug!("Playing a sound: {}", command);
+        if let Err(e) = self.window_manager.exec(&command) {
+            warn!("Failed to play a sound: {:#}", e);
+        }
+    }
+
+    /// Cancel switching without selecting a window
+    fn cancel_switching(&mut self) -> Result<()> {
+        info!("Canceling window switching");
+
+        self.switcher = None;
+        self.hide_ui();
+
+        Ok(())
+    }
+
+    /// Select the window focused `steps` focus changes ago and focus it
+    fn jump_back(&mut self, steps: usize) -> Result<()> {
+        let Some(entry) = self.window_manager.history().steps_back(steps) else {
+            debug!("Focus history has no entry {} steps back", steps);
+            return Ok(());
+        };
+        let window_id = entry.window_id;
+
+        let selected = self
+            .switcher
+            .as_mut()
+            .is_some_and(|switcher| switcher.select_window(window_id));
+        if !selected {
+            debug!("Window {} from history is not in the switcher", window_id);
+            return Ok(());
+        }
+
+        info!("Jumping back {} steps to window {}", steps, window_id);
+        self.finalize_selection()
+    }
+
+    /// End switching and launch a new instance of the selected window's
+    /// application using the Exec line from its desktop entry
+    fn launch_new_instance(&mut self) -> Result<()> {
+        let Some(switcher) = self.switcher.take() else {
+            return Ok(());
+        };
+        self.hide_ui();
+
+        let Some(window) = switcher.current() else {
+            return Ok(());
+        };
+
+        let desktop_files = DesktopFileFinder::new(self.wmclass_index.clone());
+        let Some(command) = window
+            .icon_candidates()
+            .into_iter()
+            .find_map(|id| desktop_files.find_exec(id))
+        else {
+            warn!(
+                "No desktop entry with an Exec line found for '{}' (app_id: {:?}, class: {:?})",
+                window.title, window.app_id, window.window_class
+            );
+            return Ok(());
+        };
+
+        info!("Launching new instance: {}", command);
+        if let Err(e) = self.window_manager.exec(&command) {
+            error!("Failed to launch '{}': {:#}", command, e);
+        }
+
+        Ok(())
+    }
+
+    /// End switching and run a custom action's command for the selected window
+    fn run_custom_action(&mut self, index: usize) -> Result<()> {
+        let Some(action) = self.config.file.custom_actions.get(index).cloned() else {
+            warn!("No custom action {}", index);
+            return Ok(());
+        };
+        let Some(switcher) = self.switcher.take() else {
+            return Ok(());
+        };
+        self.hide_ui();
+
+        let Some(window) = switcher.current() else {
+            return Ok(());
+        };
+        let command = action.command_for(window, window.pid);
+        info!("Running custom action '{}': {}", action.key, command);
+        if let Err(e) = self.window_manager.exec(&command) {
+            error!("Failed to run '{}': {:#}", command, e);
+        }
+        Ok(())
+    }
+
+    /// End switching, focus the selected window so it is on screen and
+    /// capture its area with grim
+    fn screenshot_selection(&mut self) -> Result<()> {
+        let Some(switcher) = self.switcher.take() else {
+            return Ok(());
+        };
+        self.hide_ui();
+
+        let Some(window) = switcher.current() else {
+            return Ok(());
+        };
+        if let Err(e) = self
+            .window_manager
+            .focus_window(window.id, self.config.focus_workspace_first)
+        {
+            self.expected_focus = None;
+            return Err(e);
+        }
+        self.focused_by_daemon(window.id);
+
+        let time = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let grim = self.config.file.screenshot.grim_command(
+            &window.geometry.to_string(),
+            window.app_key().unwrap_or("window"),
+            time,
+        );
+        // Give sway a moment to unmap the switcher and draw the window
+        let command = format!("sleep {} && {}", CAPTURE_DELAY, grim);
+        info!("Taking a screenshot: {}", command);
+        if let Err(e) = self.window_manager.exec(&command) {
+            error!("Failed to take a screenshot: {:#}", e);
+        }
+        Ok(())
+    }
+
+    /// End switching and bring the selected window to the current workspace
+    fn move_selection_here(&mut self) -> Result<()> {
+        let Some(switcher) = self.switcher.take() else {
+            return Ok(());
+        };
+        self.hide_ui();
+
+        if let Some(window) = switcher.current() {
+            info!("Moving window '{}' (ID: {}) here", window.title, window.id);
+            match self.window_manager.move_window_here(window.id) {
+                Ok(()) => self.focused_by_daemon(window.id),
+                Err(e) => {
+                    self.expected_focus = None;
+                    error!("Failed to move '{}' here: {:#}", window.title, e);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Focus a window picked in the taskbar, closing the switcher if open
+    fn focus_window(&mut self, window_id: i64) -> Result<()> {
+        if self.is_switching() {
+            self.cancel_switching()?;
+        }
+        info!("Focusing window {}", window_id);
+        match self
+            .window_manager
+            .focus_window(window_id, self.config.focus_workspace_first)
+        {
+            Ok(()) => self.focused_by_daemon(window_id),
+            Err(e) => {
+                self.expected_focus = None;
+                warn!("Failed to focus window {}: {:#}", window_id, e);
+            }
+        }
+        Ok(())
+    }
+
+    /// Focus the `n`th most recently used window the switcher would list,
+    /// not counting the focused one, without showing the switcher
+    async fn focus_nth(&mut self, n: usize) -> Result<String> {
+        self.settle_focus();
+        self.refresh_now().await?;
+        let windows = self.switch_list(SwitchTarget::Windows, None)?;
+        let focused = self.window_manager.focused_window_id();
+        let Some(window_id) = nth_recent(&windows, focused, n).map(|w| w.id) else {
+            anyhow::bail!("There is no window {} in the MRU order", n);
+        };
+        self.focus_window(window_id)?;
+        Ok(String::new())
+    }
+
+    /// Select the entry at `index` and focus its window
+    fn select_index(&mut self, index: usize) -> Result<()> {
+        let Some(ref mut switcher) = self.switcher else {
+            return Ok(());
+        };
+        if !switcher.select_index(index) {
+            debug!("No entry {} to select", index);
+            return Ok(());
+        }
+        self.finalize_selection()
+    }
+
+    /// Run an action from a tile's context menu: select the tile, then act
+    /// on it the way the matching key would
+    fn tile_action(&mut self, index: usize, action: TileAction) -> Result<()> {
+        let Some(ref mut switcher) = self.switcher else {
+            return Ok(());
+        };
+        if !switcher.select_index(index) {
+            debug!("No tile {} to act on", index);
+            return Ok(());
+        }
+        debug!("Tile {} context menu: {}", index, action.as_str());
+        match action {
+            TileAction::Focus => self.finalize_selection(),
+            TileAction::MoveHere => self.move_selection_here(),
+            TileAction::Float => self.float_selection(),
+            TileAction::Close => self.close_selection(),
+            TileAction::Unpin => self.unpin_selection(),
+        }
+    }
+
+    /// End switching and toggle the selected window between tiling and
+    /// floating, focusing it
+    fn float_selection(&mut self) -> Result<()> {
+        let Some(switcher) = self.switcher.take() else {
+            return Ok(());
+        };
+        self.hide_ui();
+
+        if let Some(window) = switcher.current() {
+            info!("Toggling floating for '{}' (ID: {})", window.title, window.id);
+            match self.window_manager.toggle_floating(window.id) {
+                Ok(()) => self.focused_by_daemon(window.id),
+                Err(e) => {
+                    self.expected_focus = None;
+                    error!("Failed to float '{}': {:#}", window.title, e);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Close the selected window and drop it from the list, keeping the
+    /// switcher open on the rest
+    fn close_selection(&mut self) -> Result<()> {
+        let Some(ref mut switcher) = self.switcher else {
+            return Ok(());
+        };
+        let Some(window) = switcher.remove_current() else {
+            return Ok(());
+        };
+        info!("Closing '{}' (ID: {})", window.title, window.id);
+        if let Err(e) = self.window_manager.close_window(window.id) {
+            error!("Failed to close '{}': {:#}", window.title, e);
+        }
+
+        if switcher.is_empty() {
+            return self.cancel_switching();
+        }
+        if let Some(ref switcher) = self.switcher {
+            self.show_ui(switcher);
+        }
+        Ok(())
+    }
+
+    /// Hide the UI if available
+    fn hide_ui(&mut self) {
+        self.pending_input.clear();
+        self.send_ui(UiCommand::Hide);
+    }
+
+    /// Forward the compositor's window events to the event bus
+    async fn monitor_sway_events(subscription: Subscription, events_tx: EventSender) -> Result<()> {
+        let mut events = subscription.await?;
+        info!("Subscribed to Sway window events");
+
+        while let Some(window_event) = events.next().await {
+            if let Err(e) = events_tx.send(DaemonEvent::Window(window_event?)) {
+                error!("Failed to send window event: {}", e);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compositor::mock::{MockCompositor, MockWindow};
+    use crate::ipc::Trigger;
+    use clap::Parser;
+
+    /// A daemon without a UI on `windows`, with `args` on the command line
+    fn mock_daemon(
+        args: &[&str],
+        windows: Vec<MockWindow>,
+    ) -> (Daemon<MockCompositor>, MockCompositor) {
+        let compositor = MockCompositor::new(windows);
+        let args = std::iter::once("sway-alttab-gui").chain(args.iter().copied());
+        let config = Config::parse_from(args);
+        let window_manager =
+            WindowManager::with_client(compositor.clone(), config.window_detection).unwrap();
+        let daemon =
+            Daemon::with_window_manager(window_manager, config, None, WmClassIndex::default())
+                .unwrap();
+        (daemon, compositor)
+    }
+
+    /// Windows 1 to 3 on workspace 1, most recently used first
+    fn three_windows() -> Vec<MockWindow> {
+        vec![
+            MockWindow::new(1, "firefox", "1"),
+            MockWindow::new(2, "foot", "1"),
+            MockWindow::new(3, "code", "1"),
+        ]
+    }
+
+    // ==================== determine_input_action tests ====================
+
+    #[test]
     fn test_input_next_while_switching() {
         let action = determine_input_action(InputCommand::Next, true);
         assert_eq!(action, DaemonAction::CycleForward);
@@ -373,6 +2537,154 @@ mod tests {
         assert_eq!(action, DaemonAction::Cancel);
     }
 
+    #[test]
+    fn test_input_launch_new_while_switching() {
+        let action = determine_input_action(InputCommand::LaunchNew, true);
+        assert_eq!(action, DaemonAction::LaunchNewInstance);
+    }
+
+    #[test]
+    fn test_input_launch_new_while_not_switching() {
+        let action = determine_input_action(InputCommand::LaunchNew, false);
+        assert_eq!(action, DaemonAction::None);
+    }
+
+    #[test]
+    fn test_input_jump_back_while_switching() {
+        let action = determine_input_action(InputCommand::JumpBack(3), true);
+        assert_eq!(action, DaemonAction::JumpBack { steps: 3 });
+    }
+
+    #[test]
+    fn test_nth_recent() {
+        let windows: Vec<WindowInfo> =
+            (1..=4).map(|id| make_window_in_workspace(id, "1")).collect();
+        let ids = |focused, n| nth_recent(&windows, focused, n).map(|w| w.id);
+        assert_eq!(ids(Some(1), 1), Some(2));
+        assert_eq!(ids(Some(1), 3), Some(4));
+        assert_eq!(ids(Some(1), 4), None);
+        assert_eq!(ids(Some(1), 0), None);
+        // The focused window isn't listed, e.g. with --exclude-focused
+        assert_eq!(ids(Some(9), 1), Some(1));
+        assert_eq!(ids(None, 2), Some(2));
+    }
+
+    #[test]
+    fn test_grid_step() {
+        // 0 1 2
+        // 3 4 5
+        // 6 7
+        let step = |index, direction| grid_step(index, 8, 3, direction);
+        assert_eq!(step(1, Direction::Down), 4);
+        assert_eq!(step(5, Direction::Down), 7);
+        assert_eq!(step(7, Direction::Down), 7);
+        assert_eq!(step(4, Direction::Up), 1);
+        assert_eq!(step(1, Direction::Up), 1);
+        assert_eq!(step(0, Direction::Left), 7);
+        assert_eq!(step(7, Direction::Right), 0);
+        assert_eq!(step(2, Direction::Right), 3);
+        assert_eq!(grid_step(0, 0, 3, Direction::Down), 0);
+    }
+
+    #[test]
+    fn test_split_step() {
+        // 0 1
+        // 2 3 4
+        let step = |index, direction| split_step(index, 5, 2, 0, direction);
+        assert_eq!(step(1, Direction::Down), 3);
+        assert_eq!(step(4, Direction::Up), 1);
+        assert_eq!(step(2, Direction::Up), 0);
+        assert_eq!(step(0, Direction::Up), 0);
+        assert_eq!(step(3, Direction::Down), 3);
+        assert_eq!(step(1, Direction::Right), 2);
+        assert_eq!(step(0, Direction::Left), 4);
+        assert_eq!(split_step(0, 3, 0, 0, Direction::Down), 0);
+    }
+
+    #[test]
+    fn test_split_step_wraps_by_columns() {
+        // 0 1 2
+        // 3
+        // -----
+        // 4 5 6
+        // 7 8
+        let step = |index, direction| split_step(index, 9, 4, 3, direction);
+        assert_eq!(step(1, Direction::Down), 3);
+        assert_eq!(step(3, Direction::Down), 4);
+        assert_eq!(step(2, Direction::Down), 3);
+        assert_eq!(step(6, Direction::Down), 8);
+        assert_eq!(step(8, Direction::Down), 8);
+        assert_eq!(step(8, Direction::Up), 5);
+        assert_eq!(step(5, Direction::Up), 3);
+        assert_eq!(step(4, Direction::Up), 3);
+        assert_eq!(step(1, Direction::Up), 1);
+        assert_eq!(step(3, Direction::Right), 4);
+    }
+
+    #[test]
+    fn test_input_arrow() {
+        let action = determine_input_action(InputCommand::Arrow(Direction::Up), true);
+        assert_eq!(action, DaemonAction::Arrow { direction: Direction::Up });
+        let action = determine_input_action(InputCommand::Arrow(Direction::Up), false);
+        assert_eq!(action, DaemonAction::None);
+    }
+
+    #[test]
+    fn test_input_select_index() {
+        let action = determine_input_action(InputCommand::SelectIndex(2), true);
+        assert_eq!(action, DaemonAction::SelectIndex { index: 2 });
+        let action = determine_input_action(InputCommand::SelectIndex(2), false);
+        assert_eq!(action, DaemonAction::None);
+    }
+
+    #[test]
+    fn test_input_custom_action_while_switching() {
+        let action = determine_input_action(InputCommand::Custom(1), true);
+        assert_eq!(action, DaemonAction::RunCustomAction { index: 1 });
+        let action = determine_input_action(InputCommand::Custom(1), false);
+        assert_eq!(action, DaemonAction::None);
+    }
+
+    #[test]
+    fn test_input_jump_back_while_not_switching() {
+        let action = determine_input_action(InputCommand::JumpBack(3), false);
+        assert_eq!(action, DaemonAction::None);
+    }
+
+    #[test]
+    fn test_input_move_here_while_switching() {
+        let action = determine_input_action(InputCommand::MoveHere, true);
+        assert_eq!(action, DaemonAction::MoveSelectionHere);
+    }
+
+    #[test]
+    fn test_input_focus_works_while_not_switching() {
+        let action = determine_input_action(InputCommand::Focus(7), false);
+        assert_eq!(action, DaemonAction::FocusWindow { window_id: 7 });
+    }
+
+    #[test]
+    fn test_input_tile_action_while_switching() {
+        let cmd = InputCommand::Tile {
+            index: 2,
+            action: TileAction::Close,
+        };
+        assert_eq!(
+            determine_input_action(cmd.clone(), true),
+            DaemonAction::TileAction {
+                index: 2,
+                action: TileAction::Close
+            }
+        );
+        assert_eq!(determine_input_action(cmd, false), DaemonAction::None);
+    }
+
+    #[test]
+    fn test_input_unresolved_trigger_is_ignored() {
+        let action = determine_input_action(InputCommand::Trigger(Trigger::Enter), true);
+        assert_eq!(action, DaemonAction::None);
+    }
+
     #[test]
     fn test_input_next_while_not_switching() {
         let action = determine_input_action(InputCommand::Next, false);
@@ -401,16 +2713,398 @@ mod tests {
 
     #[test]
     fn test_show_while_not_switching_starts_switching() {
-        let action = determine_show_action(false);
+        let action = determine_show_action(false, false, false);
+        assert_eq!(action, DaemonAction::StartSwitching);
+        let action = determine_show_action(false, true, true);
         assert_eq!(action, DaemonAction::StartSwitching);
     }
 
     #[test]
     fn test_show_while_switching_cycles_forward() {
-        let action = determine_show_action(true);
+        let action = determine_show_action(true, false, true);
         assert_eq!(action, DaemonAction::CycleForward);
     }
 
+    #[test]
+    fn test_show_while_switching_selects_with_select_on_repeat() {
+        let action = determine_show_action(true, true, true);
+        assert_eq!(action, DaemonAction::FinalizeSelection);
+    }
+
+    #[test]
+    fn test_show_before_ui_ready_is_buffered() {
+        let action = determine_show_action(true, false, false);
+        assert_eq!(action, DaemonAction::BufferCycle);
+        // Early presses never select, even with select_on_repeat
+        let action = determine_show_action(true, true, false);
+        assert_eq!(action, DaemonAction::BufferCycle);
+    }
+
+    #[test]
+    fn test_show_prev_moves_backward() {
+        let action = determine_show_prev_action(false, false, false);
+        assert_eq!(action, DaemonAction::StartSwitchingBackward);
+        let action = determine_show_prev_action(true, false, true);
+        assert_eq!(action, DaemonAction::CycleBackward);
+        let action = determine_show_prev_action(true, false, false);
+        assert_eq!(action, DaemonAction::BufferCycleBackward);
+        let action = determine_show_prev_action(true, true, true);
+        assert_eq!(action, DaemonAction::FinalizeSelection);
+    }
+
+    #[test]
+    fn test_shown_while_switching_marks_ui_ready() {
+        let action = determine_input_action(InputCommand::Shown, true);
+        assert_eq!(action, DaemonAction::UiReady);
+        let action = determine_input_action(InputCommand::Shown, false);
+        assert_eq!(action, DaemonAction::None);
+    }
+
+    // ==================== is_early_release tests ====================
+
+    #[test]
+    fn test_release_before_min_hold_is_early() {
+        let min_hold = Duration::from_millis(150);
+        assert!(is_early_release(Duration::from_millis(40), min_hold));
+        assert!(!is_early_release(Duration::from_millis(150), min_hold));
+        assert!(!is_early_release(Duration::from_millis(400), min_hold));
+    }
+
+    #[test]
+    fn test_no_release_is_early_without_min_hold() {
+        assert!(!is_early_release(Duration::ZERO, Duration::ZERO));
+    }
+
+    #[tokio::test]
+    async fn test_tap_switches_to_the_preselected_window() {
+        let (mut daemon, compositor) = mock_daemon(&["--min-hold-ms", "60000"], three_windows());
+        daemon.start_switching(SwitchTarget::Windows, None, true, 0, false).await.unwrap();
+        assert!(daemon.is_switching());
+
+        daemon
+            .handle_input_command(InputCommand::Trigger(Trigger::Release))
+            .unwrap();
+        assert!(!daemon.is_switching());
+        assert_eq!(compositor.focused(), Some(2));
+        assert_eq!(daemon.window_manager.windows()[0].id, 2);
+    }
+
+    #[tokio::test]
+    async fn test_preview_is_captured_when_a_window_is_picked() {
+        let (mut daemon, compositor) = mock_daemon(&[], three_windows());
+        daemon.config.file.preview.enabled = true;
+        // Focus alone doesn't capture
+        compositor.state.lock().unwrap().windows.swap(0, 2);
+        daemon.handle_window_event(WindowEvent::Focus(3)).await.unwrap();
+        assert!(compositor.commands().is_empty());
+
+        daemon.start_switching(SwitchTarget::Windows, None, true, 0, false).await.unwrap();
+        daemon.finalize_selection().unwrap();
+        let path = preview_path(1).unwrap();
+        let capture = format!(
+            "exec sleep {} && grim -s 0.25 -g '0,0 0x0' {}",
+            CAPTURE_DELAY,
+            shell_quote(&path.to_string_lossy())
+        );
+        assert_eq!(compositor.commands(), ["[con_id=1] focus".to_string(), capture]);
+    }
+
+    /// Three windows with the focused one fullscreen, and `[fullscreen]`
+    /// set to `action`
+    fn fullscreen_daemon(action: FullscreenAction) -> (Daemon<MockCompositor>, MockCompositor) {
+        let mut windows = three_windows();
+        windows[0].fullscreen = true;
+        let (mut daemon, compositor) = mock_daemon(&[], windows);
+        daemon.config.file.fullscreen.action = action;
+        // Catch what would go to a UI
+        daemon.ui_tx = Some(mpsc::channel(8).0);
+        (daemon, compositor)
+    }
+
+    #[tokio::test]
+    async fn test_fullscreen_toggle_switches_without_the_switcher() {
+        let (mut daemon, compositor) = fullscreen_daemon(FullscreenAction::Toggle);
+        daemon.start_switching(SwitchTarget::Windows, None, true, 0, false).await.unwrap();
+
+        assert!(!daemon.is_switching());
+        assert_eq!(compositor.focused(), Some(2));
+        assert_eq!(compositor.commands(), ["[con_id=2] focus"]);
+        assert_eq!(daemon.window_manager.windows()[0].id, 2);
+        let outbox = daemon.ui_outbox.borrow();
+        assert!(!outbox.iter().any(|command| matches!(command, UiCommand::Show { .. })));
+    }
+
+    #[tokio::test]
+    async fn test_fullscreen_pause_leaves_the_focus_alone() {
+        let (mut daemon, compositor) = fullscreen_daemon(FullscreenAction::Pause);
+        daemon.start_switching(SwitchTarget::Windows, None, true, 0, false).await.unwrap();
+
+        assert!(!daemon.is_switching());
+        assert_eq!(compositor.focused(), Some(1));
+        assert!(compositor.commands().is_empty());
+        assert!(daemon.ui_outbox.borrow().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_fullscreen_show_opens_the_switcher() {
+        let (mut daemon, compositor) = fullscreen_daemon(FullscreenAction::Show);
+        daemon.start_switching(SwitchTarget::Windows, None, true, 0, false).await.unwrap();
+
+        assert!(daemon.is_switching());
+        assert!(compositor.commands().is_empty());
+        let outbox = daemon.ui_outbox.borrow();
+        assert!(outbox.iter().any(|command| matches!(command, UiCommand::Show { .. })));
+    }
+
+    /// The last list sent to the UI, with its selected window and whether
+    /// it was marked as refreshing
+    fn last_show(daemon: &Daemon<MockCompositor>) -> (Vec<i64>, i64, bool) {
+        let outbox = daemon.ui_outbox.borrow();
+        outbox
+            .iter()
+            .rev()
+            .find_map(|command| match command {
+                UiCommand::Show {
+                    windows,
+                    initial_index,
+                    refreshing,
+                    ..
+                } => Some((
+                    windows.iter().map(|w| w.id).collect(),
+                    windows[*initial_index].id,
+                    *refreshing,
+                )),
+                _ => None,
+            })
+            .expect("a list was shown")
+    }
+
+    #[tokio::test]
+    async fn test_instant_show_reconciles_the_stale_list() {
+        let (mut daemon, compositor) = mock_daemon(&["--instant-show"], three_windows());
+        let (refresher, mut requests) = mpsc::unbounded_channel();
+        daemon.refresher = Some(refresher);
+        daemon.ui_tx = Some(mpsc::channel(8).0);
+        // Opened since the list was last read
+        compositor.state.lock().unwrap().windows.push(MockWindow::new(4, "gimp", "1"));
+
+        daemon.start_switching(SwitchTarget::Windows, None, true, 0, false).await.unwrap();
+        let generation = requests.try_recv().expect("a snapshot was requested");
+        assert_eq!(last_show(&daemon), (vec![1, 2, 3], 2, true));
+        daemon.handle_input_command(InputCommand::Shown).unwrap();
+        daemon.handle_input_command(InputCommand::Next).unwrap();
+
+        let snapshot = crate::compositor::fetch_snapshot(&mut compositor.clone()).unwrap();
+        daemon.apply_snapshot(generation, snapshot).await.unwrap();
+        // The fresh list, still on the window the user moved to
+        assert_eq!(last_show(&daemon), (vec![1, 2, 3, 4], 3, false));
+        assert!(daemon.is_switching());
+    }
+
+    /// Ids of the windows the daemon knows, in MRU order
+    fn known_ids(daemon: &Daemon<MockCompositor>) -> Vec<i64> {
+        daemon.window_manager.windows().iter().map(|w| w.id).collect()
+    }
+
+    #[tokio::test]
+    async fn test_stale_snapshot_is_read_again() {
+        let (mut daemon, compositor) = mock_daemon(&[], three_windows());
+        let (refresher, mut requests) = mpsc::unbounded_channel();
+        daemon.refresher = Some(refresher);
+        compositor.state.lock().unwrap().windows.push(MockWindow::new(4, "gimp", "1"));
+
+        daemon.request_refresh().await.unwrap();
+        let stale = requests.try_recv().expect("a snapshot was requested");
+        let snapshot = crate::compositor::fetch_snapshot(&mut compositor.clone()).unwrap();
+        // Focus moves on before the snapshot arrives
+        compositor.state.lock().unwrap().windows.swap(0, 1);
+        daemon.handle_window_event(WindowEvent::Focus(2)).await.unwrap();
+
+        daemon.apply_snapshot(stale, snapshot).await.unwrap();
+        assert_eq!(known_ids(&daemon), [2, 1, 3]);
+        let fresh = requests.try_recv().expect("another snapshot was requested");
+        assert!(fresh > stale);
+
+        let snapshot = crate::compositor::fetch_snapshot(&mut compositor.clone()).unwrap();
+        daemon.apply_snapshot(fresh, snapshot).await.unwrap();
+        assert_eq!(known_ids(&daemon), [2, 1, 3, 4]);
+    }
+
+    #[tokio::test]
+    async fn test_snapshot_is_dropped_while_switching() {
+        let (mut daemon, compositor) = mock_daemon(&[], three_windows());
+        let (refresher, mut requests) = mpsc::unbounded_channel();
+        daemon.refresher = Some(refresher);
+        daemon.start_switching(SwitchTarget::Windows, None, true, 0, false).await.unwrap();
+        compositor.state.lock().unwrap().windows.push(MockWindow::new(4, "gimp", "1"));
+
+        let snapshot = crate::compositor::fetch_snapshot(&mut compositor.clone()).unwrap();
+        daemon.apply_snapshot(daemon.generation, snapshot).await.unwrap();
+        assert_eq!(known_ids(&daemon), [1, 2, 3]);
+        assert!(requests.try_recv().is_err());
+        assert_eq!(daemon.switcher.as_ref().unwrap().windows().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_run_once_exits_after_the_selection() {
+        let (daemon, compositor) = mock_daemon(&[], three_windows());
+        let (events_tx, events) = crate::event_bus::channel();
+        // Only input counts; sway events are left to a resident daemon
+        events_tx.send(DaemonEvent::Window(WindowEvent::Close(3))).unwrap();
+        events_tx.send_input(InputCommand::Next).unwrap();
+        events_tx.send_input(InputCommand::Select).unwrap();
+        // Too late to matter, the switcher is gone
+        events_tx.send_input(InputCommand::Next).unwrap();
+
+        // The sender stays open, so only the selection can end it
+        tokio::time::timeout(Duration::from_secs(5), daemon.run_once(events))
+            .await
+            .expect("run_once should return after the selection")
+            .unwrap();
+        assert_eq!(compositor.focused(), Some(3));
+        assert_eq!(compositor.commands(), ["[con_id=3] focus"]);
+        drop(events_tx);
+    }
+
+    #[tokio::test]
+    async fn test_input_before_the_switcher_maps_is_replayed() {
+        let (mut daemon, _compositor) = mock_daemon(&[], three_windows());
+        daemon.start_switching(SwitchTarget::Windows, None, true, 0, false).await.unwrap();
+        // As if a UI were still mapping the surface
+        daemon.ui_ready = false;
+        let selected = |daemon: &Daemon<MockCompositor>| {
+            daemon.switcher.as_ref().unwrap().current_index()
+        };
+        assert_eq!(selected(&daemon), 1);
+
+        daemon.handle_input_command(InputCommand::Next).unwrap();
+        daemon.handle_input_command(InputCommand::Next).unwrap();
+        daemon.handle_input_command(InputCommand::Prev).unwrap();
+        assert_eq!(daemon.pending_input.len(), 3);
+        assert_eq!(selected(&daemon), 1);
+
+        daemon.handle_input_command(InputCommand::Shown).unwrap();
+        assert!(daemon.pending_input.is_empty());
+        assert_eq!(selected(&daemon), 2);
+    }
+
+    #[tokio::test]
+    async fn test_buffered_input_is_dropped_when_the_switcher_closes() {
+        let (mut daemon, _compositor) = mock_daemon(&[], three_windows());
+        daemon.start_switching(SwitchTarget::Windows, None, true, 0, false).await.unwrap();
+        daemon.ui_ready = false;
+        daemon.handle_input_command(InputCommand::Next).unwrap();
+        assert_eq!(daemon.pending_input.len(), 1);
+
+        daemon.handle_input_command(InputCommand::Cancel).unwrap();
+        assert!(!daemon.is_switching());
+        assert!(daemon.pending_input.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_input_after_the_map_timeout_is_handled() {
+        let (mut daemon, _compositor) = mock_daemon(&[], three_windows());
+        daemon.start_switching(SwitchTarget::Windows, None, true, 0, false).await.unwrap();
+        daemon.ui_ready = false;
+        daemon.handle_input_command(InputCommand::Next).unwrap();
+
+        // The surface never mapped: stale input is dropped, new input moves
+        daemon.started_at -= MAP_TIMEOUT;
+        daemon.handle_input_command(InputCommand::Next).unwrap();
+        assert!(daemon.pending_input.is_empty());
+        assert!(daemon.ui_ready);
+        assert_eq!(daemon.switcher.as_ref().unwrap().current_index(), 2);
+    }
+
+    // ==================== is_idle tests ====================
+
+    #[test]
+    fn test_should_rest() {
+        let hour = Duration::from_secs(3600);
+        assert!(!should_rest(Duration::from_secs(60), hour));
+        assert!(should_rest(hour, hour));
+        // Zero turns resting off
+        assert!(!should_rest(hour * 100, Duration::ZERO));
+    }
+
+    #[test]
+    fn test_is_idle() {
+        let now = SystemTime::UNIX_EPOCH + Duration::from_secs(10_000);
+        let idle_after = Duration::from_secs(3600);
+        let focused = |secs_ago| WindowInfo {
+            last_focused: Some(now - Duration::from_secs(secs_ago)),
+            ..make_window_in_workspace(1, "1")
+        };
+
+        assert!(!is_idle(&focused(60), now, idle_after));
+        assert!(is_idle(&focused(3600), now, idle_after));
+        // Never focused since the daemon started: unknown, so kept
+        assert!(!is_idle(&make_window_in_workspace(2, "1"), now, idle_after));
+    }
+
+    // ==================== separator_index tests ====================
+
+    fn make_window_in_workspace(id: i64, workspace: &str) -> WindowInfo {
+        WindowInfo {
+            id,
+            app_id: None,
+            title: format!("Window {}", id),
+            workspace: workspace.to_string(),
+            output: "DP-1".to_string(),
+            window_class: None,
+            window_instance: None,
+            label: None,
+            last_focused: None,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_separator_index_after_current_workspace() {
+        let windows = vec![
+            make_window_in_workspace(1, "1"),
+            make_window_in_workspace(2, "1"),
+            make_window_in_workspace(3, "2"),
+        ];
+        assert_eq!(separator_index(&windows, "1"), Some(2));
+    }
+
+    #[test]
+    fn test_no_separator_with_one_side_empty() {
+        let windows = vec![make_window_in_workspace(1, "1"), make_window_in_workspace(2, "1")];
+        assert_eq!(separator_index(&windows, "1"), None);
+        assert_eq!(separator_index(&windows, "2"), None);
+        assert_eq!(separator_index(&[], "1"), None);
+    }
+
+    // ==================== jittered_interval tests ====================
+
+    #[test]
+    fn test_jittered_interval_stays_within_bounds() {
+        let interval = Duration::from_secs(30);
+        for seed in [0, 1, u64::MAX / 3, u64::MAX / 2, u64::MAX - 1, u64::MAX] {
+            let delay = jittered_interval(interval, seed);
+            assert!(delay >= Duration::from_secs(27), "{:?}", delay);
+            assert!(delay <= Duration::from_secs(33), "{:?}", delay);
+        }
+    }
+
+    #[test]
+    fn test_jittered_interval_extremes() {
+        let interval = Duration::from_secs(30);
+        assert_eq!(jittered_interval(interval, 0), Duration::from_secs(27));
+        assert_eq!(jittered_interval(interval, u64::MAX), Duration::from_secs(33));
+    }
+
+    #[test]
+    fn test_jittered_interval_does_not_overflow() {
+        // --refresh-interval takes any u64 number of seconds
+        let interval = Duration::from_secs(u64::MAX);
+        assert_eq!(jittered_interval(interval, u64::MAX), interval);
+        assert!(jittered_interval(interval, 0) < interval);
+        assert_eq!(jittered_interval(Duration::MAX, u64::MAX), Duration::MAX);
+    }
+
     // ==================== determine_focus_action tests ====================
 
     #[test]
@@ -431,6 +3125,153 @@ mod tests {
         assert_eq!(action, DaemonAction::UpdateMru { window_id: 12345 });
     }
 
+    #[test]
+    fn test_expected_focus_times_out() {
+        let now = Instant::now();
+        let expected = ExpectedFocus::new(42, now);
+        assert!(expected.is_pending(now));
+        assert!(expected.is_pending(now + OWN_FOCUS_TIMEOUT / 2));
+        assert!(!expected.is_pending(now + OWN_FOCUS_TIMEOUT));
+    }
+
+    #[test]
+    fn test_expected_focus_matches_only_its_window() {
+        let now = Instant::now();
+        let expected = ExpectedFocus::new(42, now);
+        assert!(expected.matches(42, now));
+        assert!(!expected.matches(7, now));
+        assert!(!expected.matches(42, now + OWN_FOCUS_TIMEOUT));
+    }
+
+    #[tokio::test]
+    async fn test_own_focus_event_is_not_recorded_again() {
+        let (mut daemon, _compositor) = mock_daemon(&[], three_windows());
+        daemon.focus_window(3).unwrap();
+        // The user went back to window 1 before sway's event came in
+        daemon.window_manager.on_focus(1);
+
+        daemon.handle_window_event(WindowEvent::Focus(3)).await.unwrap();
+        assert_eq!(daemon.window_manager.windows()[0].id, 1);
+        assert_eq!(daemon.expected_focus, None);
+    }
+
+    #[tokio::test]
+    async fn test_other_focus_event_is_recorded() {
+        let (mut daemon, _compositor) = mock_daemon(&[], three_windows());
+        daemon.focus_window(3).unwrap();
+
+        daemon.handle_window_event(WindowEvent::Focus(2)).await.unwrap();
+        assert_eq!(daemon.window_manager.windows()[0].id, 2);
+        assert_eq!(daemon.expected_focus, None);
+
+        // The expected window's late event is a focus change like any other
+        daemon.handle_window_event(WindowEvent::Focus(3)).await.unwrap();
+        assert_eq!(daemon.window_manager.windows()[0].id, 3);
+    }
+
+    #[tokio::test]
+    async fn test_sorted_switcher_starts_on_the_previous_window() {
+        let mut windows = three_windows();
+        windows[0].workspace = "2".to_string();
+        let args = ["--mode", "all", "--sort", "workspace"];
+        let (mut daemon, _compositor) = mock_daemon(&args, windows);
+        daemon.start_switching(SwitchTarget::Windows, None, true, 0, false).await.unwrap();
+
+        // Workspace 1's windows lead, the focused window comes last
+        let switcher = daemon.switcher.as_ref().unwrap();
+        let ids: Vec<i64> = switcher.windows().iter().map(|w| w.id).collect();
+        assert_eq!(ids, [2, 3, 1]);
+        assert_eq!(switcher.current().unwrap().id, 2);
+    }
+
+    #[test]
+    fn test_exclude_focused_keeps_the_rest_of_its_group() {
+        let windows = vec![
+            MockWindow::new(1, "foot", "1"),
+            MockWindow::new(2, "firefox", "1"),
+            MockWindow::new(3, "foot", "1"),
+        ];
+        let (daemon, _compositor) = mock_daemon(&["--exclude-focused"], windows);
+        let apps = daemon.switch_list(SwitchTarget::Apps, None).unwrap();
+        // foot's entry is its other window, not dropped with the focused one
+        let ids: Vec<i64> = apps.iter().map(|w| w.id).collect();
+        assert_eq!(ids, [2, 3]);
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_waits_for_its_reply() {
+        let (mut daemon, _compositor) = mock_daemon(&[], three_windows());
+        let (reply, mut replies) = oneshot::channel();
+        let request = IpcRequest {
+            command: IpcCommand::Shutdown,
+            reply,
+        };
+        daemon.handle_ipc_request(request).await.unwrap();
+        assert!(daemon.shutting_down);
+
+        let Ok(Ok(IpcReply::Last { text, written })) = replies.try_recv() else {
+            panic!("shutdown should get a last reply");
+        };
+        assert_eq!(text, "Shutting down\n");
+        let mut shutdown_reply = daemon.shutdown_reply.take().unwrap();
+        assert!(shutdown_reply.try_recv().is_err());
+        written.send(()).unwrap();
+        assert_eq!(shutdown_reply.try_recv(), Ok(()));
+    }
+
+    #[tokio::test]
+    async fn test_selection_falls_back_when_the_window_is_gone() {
+        let (mut daemon, compositor) = mock_daemon(&[], three_windows());
+        daemon.start_switching(SwitchTarget::Windows, None, true, 0, false).await.unwrap();
+        compositor.fail_next(CommandError::Failed {
+            command: String::new(),
+            reason: "No matching node.".to_string(),
+        });
+
+        // Catch what would go to a UI
+        daemon.ui_tx = Some(mpsc::channel(8).0);
+
+        daemon.finalize_selection().unwrap();
+        // Window 2 was dropped and the next one in the MRU order focused
+        assert_eq!(compositor.focused(), Some(3));
+        let ids: Vec<i64> = daemon.window_manager.windows().iter().map(|w| w.id).collect();
+        assert_eq!(ids, [3, 1]);
+        assert!(daemon.ui_outbox.borrow().iter().any(|command| matches!(
+            command,
+            UiCommand::WindowClosed { title } if title == "foot window"
+        )));
+    }
+
+    #[tokio::test]
+    async fn test_selection_keeps_the_window_on_a_parse_error() {
+        let (mut daemon, compositor) = mock_daemon(&[], three_windows());
+        daemon.start_switching(SwitchTarget::Windows, None, true, 0, false).await.unwrap();
+        compositor.fail_next(CommandError::Parse {
+            command: String::new(),
+            reason: "Unknown command".to_string(),
+        });
+
+        daemon.finalize_selection().unwrap();
+        assert_eq!(compositor.focused(), Some(1));
+        assert_eq!(daemon.window_manager.windows().len(), 3);
+        assert!(!daemon.is_switching());
+    }
+
+    #[test]
+    fn test_failed_focus_forgets_the_expected_focus() {
+        let (mut daemon, compositor) = mock_daemon(&[], three_windows());
+        daemon.focus_window(3).unwrap();
+        assert!(daemon.expected_focus.is_some());
+
+        compositor.fail_next(CommandError::Failed {
+            command: String::new(),
+            reason: "No matching node.".to_string(),
+        });
+        daemon.focus_window(2).unwrap();
+        assert_eq!(daemon.expected_focus, None);
+        assert_eq!(compositor.focused(), Some(3));
+    }
+
     // ==================== DaemonAction enum tests ====================
 
     #[test]
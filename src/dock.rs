@@ -0,0 +1,142 @@
+//! A small row of the most recently used windows that stays in a screen
+//! corner while not switching, fed by the daemon's MRU list.
+
+use crate::config::{Appearance, DockConfig, DockCorner};
+use crate::icon_resolver::{IconResolver, WmClassIndex};
+use crate::ui::follow_high_contrast;
+use crate::ui_commands::UI_RESOURCES;
+use crate::window_manager::WindowInfo;
+use gtk4::prelude::*;
+use gtk4::{Application, ApplicationWindow, Box as GtkBox, Image, Label, Orientation};
+use gtk4_layer_shell::{Edge, KeyboardMode, Layer, LayerShell};
+use tracing::{debug, warn};
+
+const DOCK_ICON_SIZE: i32 = 32;
+const DOCK_MARGIN: i32 = 8;
+const DOCK_SPACING: i32 = 6;
+
+pub struct DockWindow {
+    window: ApplicationWindow,
+    container: GtkBox,
+    /// Created on the first update, when the WMClass index is available
+    icon_resolver: Option<IconResolver>,
+}
+
+impl DockWindow {
+    /// Create the dock, or None if it is turned off or the compositor has
+    /// no layer shell to keep it in a corner
+    pub fn new(app: &Application, dock: &DockConfig, appearance: &Appearance) -> Option<Self> {
+        if dock.size == 0 {
+            return None;
+        }
+        if !gtk4_layer_shell::is_supported() {
+            warn!("Layer shell is not supported here, the dock is disabled");
+            return None;
+        }
+
+        let window = ApplicationWindow::builder()
+            .application(app)
+            .title("Recent Windows")
+            .decorated(false)
+            .resizable(false)
+            .build();
+        window.add_css_class("dock");
+        follow_high_contrast(&window, appearance.high_contrast);
+
+        window.init_layer_shell();
+        window.set_namespace(&format!("{}-dock", appearance.namespace));
+        window.set_layer(Layer::Top);
+        window.set_keyboard_mode(KeyboardMode::None);
+        let (vertical, horizontal) = corner_edges(dock.corner);
+        for edge in [vertical, horizontal] {
+            window.set_anchor(edge, true);
+            window.set_margin(edge, DOCK_MARGIN);
+        }
+
+        let container = GtkBox::new(Orientation::Horizontal, DOCK_SPACING);
+        container.set_margin_top(DOCK_SPACING);
+        container.set_margin_bottom(DOCK_SPACING);
+        container.set_margin_start(DOCK_SPACING);
+        container.set_margin_end(DOCK_SPACING);
+        window.set_child(Some(&container));
+
+        Some(DockWindow {
+            window,
+            container,
+            icon_resolver: None,
+        })
+    }
+
+    /// Replace the dock's icons with `windows`, most recently used first
+    pub fn update(&mut self, windows: &[WindowInfo], wmclass_index: WmClassIndex) {
+        let mut icon_resolver = match self.icon_resolver.take() {
+            Some(mut resolver) => {
+                resolver.set_wmclass_index(wmclass_index);
+                resolver
+            }
+            None => IconResolver::with_wmclass_index(DOCK_ICON_SIZE, wmclass_index),
+        };
+        icon_resolver.set_scale(self.window.scale_factor());
+
+        while let Some(child) = self.container.first_child() {
+            self.container.remove(&child);
+        }
+        for (steps, window) in windows.iter().enumerate() {
+            let entry = GtkBox::new(Orientation::Vertical, 2);
+            let pixbuf = window
+                .icon_candidates()
+                .into_iter()
+                .find_map(|id| icon_resolver.resolve_icon(Some(id)))
+                .or_else(|| icon_resolver.get_fallback_icon());
+            let icon = match pixbuf {
+                Some(pb) => Image::from_pixbuf(Some(&pb)),
+                None => Image::from_icon_name("application-x-executable"),
+            };
+            icon.set_pixel_size(DOCK_ICON_SIZE);
+            entry.append(&icon);
+
+            let number = Label::new(Some(&step_label(steps)));
+            number.add_css_class("dock-number");
+            entry.append(&number);
+
+            entry.set_tooltip_text(Some(&window.title));
+            self.container.append(&entry);
+        }
+        self.icon_resolver = Some(icon_resolver);
+
+        self.window.set_visible(!windows.is_empty());
+        UI_RESOURCES.set_dock_mapped(!windows.is_empty());
+        debug!("Dock shows {} windows", windows.len());
+    }
+}
+
+/// The layer-shell edges a corner is anchored to
+fn corner_edges(corner: DockCorner) -> (Edge, Edge) {
+    match corner {
+        DockCorner::TopLeft => (Edge::Top, Edge::Left),
+        DockCorner::TopRight => (Edge::Top, Edge::Right),
+        DockCorner::BottomLeft => (Edge::Bottom, Edge::Left),
+        DockCorner::BottomRight => (Edge::Bottom, Edge::Right),
+    }
+}
+
+/// Caption under a dock icon: the digit that jumps back to it while
+/// switching. The focused window needs none.
+fn step_label(steps: usize) -> String {
+    match steps {
+        0 => String::new(),
+        steps => steps.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_step_label() {
+        assert_eq!(step_label(0), "");
+        assert_eq!(step_label(1), "1");
+        assert_eq!(step_label(4), "4");
+    }
+}
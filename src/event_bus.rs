@@ -0,0 +1,195 @@
+use crate::compositor::Snapshot;
+use crate::config::SwitchTarget;
+use crate::ipc::InputCommand;
+use crate::socket_server::IpcRequest;
+use tokio::sync::mpsc::{self, error::SendError};
+
+/// Sway window events the daemon cares about
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WindowEvent {
+    Focus(i64), // Window ID that received focus
+    New(i64),   // Window ID that was opened
+    Close(i64), // Window ID that was closed
+}
+
+/// Everything the daemon reacts to, from every event source
+/// (signals, the socket, the UI, sway, timers).
+#[derive(Debug)]
+pub enum DaemonEvent {
+    /// Show the switcher (SIGUSR1)
+    Show(SwitchTarget),
+    /// Show the switcher with only the focused application's windows (SIGUSR2)
+    ShowSameApp,
+    /// Keyboard input from the UI
+    Input(InputCommand),
+    /// A command from a CLI client
+    Ipc(IpcRequest),
+    /// A sway window event
+    Window(WindowEvent),
+    /// A window stayed focused long enough to count: the end of a burst of
+    /// focus events, or `settle-ms` with `update = "settled"`
+    FocusSettled(i64),
+    /// Time to reconcile the window list with sway
+    Refresh,
+    /// A snapshot of sway's state read in the background, for the refresh
+    /// requested at `generation`
+    Snapshot {
+        generation: u64,
+        snapshot: Box<Snapshot>,
+    },
+    /// logind reported the session became active or inactive (e.g. a VT switch)
+    SessionActive(bool),
+}
+
+/// Which queue an event goes through
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Priority {
+    /// Direct user interaction; handled before anything else that is queued
+    High,
+    /// Everything else
+    Normal,
+}
+
+impl DaemonEvent {
+    /// User-facing events jump ahead of background work, so a burst of
+    /// sway events can't delay a key press
+    #[must_use]
+    pub fn priority(&self) -> Priority {
+        match self {
+            DaemonEvent::Show(_) | DaemonEvent::ShowSameApp | DaemonEvent::Input(_) => {
+                Priority::High
+            }
+            // `sway-alttab-gui show` and frontends' keys come over the socket
+            DaemonEvent::Ipc(request) if request.command.is_interactive() => Priority::High,
+            DaemonEvent::Ipc(_)
+            | DaemonEvent::Window(_)
+            | DaemonEvent::FocusSettled(_)
+            | DaemonEvent::Refresh
+            | DaemonEvent::Snapshot { .. }
+            | DaemonEvent::SessionActive(_) => Priority::Normal,
+        }
+    }
+}
+
+/// Sending half of the event bus; cheap to clone for each event source
+#[derive(Debug, Clone)]
+pub struct EventSender {
+    high: mpsc::UnboundedSender<DaemonEvent>,
+    normal: mpsc::UnboundedSender<DaemonEvent>,
+}
+
+impl EventSender {
+    /// Queue an event according to its priority
+    pub fn send(&self, event: DaemonEvent) -> Result<(), SendError<DaemonEvent>> {
+        match event.priority() {
+            Priority::High => self.high.send(event),
+            Priority::Normal => self.normal.send(event),
+        }
+    }
+
+    /// Queue input from a frontend
+    pub fn send_input(&self, input: InputCommand) -> Result<(), SendError<DaemonEvent>> {
+        self.send(DaemonEvent::Input(input))
+    }
+}
+
+/// Receiving half of the event bus, owned by the daemon
+#[derive(Debug)]
+pub struct EventReceiver {
+    high: mpsc::UnboundedReceiver<DaemonEvent>,
+    normal: mpsc::UnboundedReceiver<DaemonEvent>,
+}
+
+impl EventReceiver {
+    /// Wait for the next event, preferring high priority ones.
+    /// Returns `None` once every sender is gone.
+    pub async fn recv(&mut self) -> Option<DaemonEvent> {
+        tokio::select! {
+            biased;
+            Some(event) = self.high.recv() => Some(event),
+            Some(event) = self.normal.recv() => Some(event),
+            else => None,
+        }
+    }
+}
+
+/// Create a new event bus
+#[must_use]
+pub fn channel() -> (EventSender, EventReceiver) {
+    let (high_tx, high_rx) = mpsc::unbounded_channel();
+    let (normal_tx, normal_rx) = mpsc::unbounded_channel();
+    (
+        EventSender {
+            high: high_tx,
+            normal: normal_tx,
+        },
+        EventReceiver {
+            high: high_rx,
+            normal: normal_rx,
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ipc::IpcCommand;
+
+    #[test]
+    fn test_priority() {
+        assert_eq!(DaemonEvent::Input(InputCommand::Next).priority(), Priority::High);
+        assert_eq!(
+            DaemonEvent::Show(SwitchTarget::Windows).priority(),
+            Priority::High
+        );
+        assert_eq!(
+            DaemonEvent::Window(WindowEvent::Focus(1)).priority(),
+            Priority::Normal
+        );
+        assert_eq!(DaemonEvent::Refresh.priority(), Priority::Normal);
+    }
+
+    #[test]
+    fn test_ipc_priority() {
+        let ipc = |command| {
+            let (reply, _) = tokio::sync::oneshot::channel();
+            DaemonEvent::Ipc(IpcRequest { command, reply })
+        };
+        let show = IpcCommand::Show {
+            what: SwitchTarget::Windows,
+            set: None,
+            skip: 0,
+        };
+        assert_eq!(ipc(show).priority(), Priority::High);
+        assert_eq!(ipc(IpcCommand::Input(InputCommand::Next)).priority(), Priority::High);
+        assert_eq!(ipc(IpcCommand::Windows).priority(), Priority::Normal);
+    }
+
+    #[tokio::test]
+    async fn test_high_priority_events_first() {
+        let (tx, mut rx) = channel();
+        tx.send(DaemonEvent::Window(WindowEvent::Focus(1))).unwrap();
+        tx.send(DaemonEvent::Refresh).unwrap();
+        tx.send(DaemonEvent::Input(InputCommand::Next)).unwrap();
+
+        assert!(matches!(
+            rx.recv().await,
+            Some(DaemonEvent::Input(InputCommand::Next))
+        ));
+        assert!(matches!(
+            rx.recv().await,
+            Some(DaemonEvent::Window(WindowEvent::Focus(1)))
+        ));
+        assert!(matches!(rx.recv().await, Some(DaemonEvent::Refresh)));
+    }
+
+    #[tokio::test]
+    async fn test_recv_ends_when_senders_dropped() {
+        let (tx, mut rx) = channel();
+        tx.send(DaemonEvent::Refresh).unwrap();
+        drop(tx);
+
+        assert!(matches!(rx.recv().await, Some(DaemonEvent::Refresh)));
+        assert!(rx.recv().await.is_none());
+    }
+}
@@ -0,0 +1,233 @@
+//! Focus history tracking.
+//!
+//! Unlike the MRU list, which keeps each window once, the history records
+//! every focus change (up to a fixed capacity) with a timestamp, so users
+//! can see and jump back through exactly what they did.
+
+use crate::window_manager::{display_title, WindowInfo};
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::collections::VecDeque;
+use std::time::{Duration, SystemTime};
+
+/// Number of focus changes kept in the history ring
+pub const FOCUS_HISTORY_CAPACITY: usize = 64;
+
+/// A single focus change
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FocusEntry {
+    pub window_id: i64,
+    /// The window's app key: its app_id, or WM_CLASS for X11 windows
+    pub app_id: Option<String>,
+    pub title: String,
+    pub focused_at: SystemTime,
+}
+
+impl FocusEntry {
+    /// The title, or `untitled` filled in for the app if it had none
+    pub fn display_title(&self, untitled: &str) -> Cow<'_, str> {
+        display_title(&self.title, self.app_id.as_deref(), untitled)
+    }
+}
+
+/// Bounded ring of focus changes, most recent first.
+#[derive(Debug, Clone)]
+pub struct FocusHistory {
+    entries: VecDeque<FocusEntry>,
+    capacity: usize,
+}
+
+impl Default for FocusHistory {
+    fn default() -> Self {
+        Self::new(FOCUS_HISTORY_CAPACITY)
+    }
+}
+
+impl FocusHistory {
+    /// Create an empty history holding at most `capacity` entries
+    pub fn new(capacity: usize) -> Self {
+        FocusHistory {
+            entries: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    /// Record that a window received focus.
+    ///
+    /// Repeated focus events for the window that is already most recent
+    /// are ignored, so the history only contains actual changes.
+    pub fn record(&mut self, window: &WindowInfo) {
+        self.record_at(window, SystemTime::now());
+    }
+
+    fn record_at(&mut self, window: &WindowInfo, focused_at: SystemTime) {
+        if self.entries.front().is_some_and(|e| e.window_id == window.id) {
+            return;
+        }
+
+        self.entries.push_front(FocusEntry {
+            window_id: window.id,
+            app_id: window.app_key().map(str::to_string),
+            title: window.title.clone(),
+            focused_at,
+        });
+        self.entries.truncate(self.capacity);
+    }
+
+    /// Iterate over the history, most recent first
+    pub fn iter(&self) -> impl Iterator<Item = &FocusEntry> {
+        self.entries.iter()
+    }
+
+    /// Replace the history with `entries` (most recent first), e.g. from a
+    /// previous daemon's exported state
+    pub fn restore(&mut self, entries: Vec<FocusEntry>) {
+        self.entries = entries.into_iter().take(self.capacity).collect();
+    }
+
+    /// Get the entry `steps` focus changes ago (0 is the current focus)
+    #[must_use]
+    pub fn steps_back(&self, steps: usize) -> Option<&FocusEntry> {
+        self.entries.get(steps)
+    }
+}
+
+/// Format a duration as a short human-readable age, e.g. "42s ago" or "3h ago"
+#[must_use]
+pub fn format_age(age: Duration) -> String {
+    let secs = age.as_secs();
+    match secs {
+        0..60 => format!("{}s ago", secs),
+        60..3600 => format!("{}m ago", secs / 60),
+        3600..86400 => format!("{}h ago", secs / 3600),
+        _ => format!("{}d ago", secs / 86400),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_window(id: i64, title: &str) -> WindowInfo {
+        WindowInfo {
+            id,
+            app_id: Some(format!("app-{}", id)),
+            title: title.to_string(),
+            workspace: "1".to_string(),
+            output: "DP-1".to_string(),
+            window_class: None,
+            window_instance: None,
+            label: None,
+            last_focused: None,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_record_most_recent_first() {
+        let mut history = FocusHistory::default();
+        history.record(&make_window(1, "A"));
+        history.record(&make_window(2, "B"));
+
+        let ids: Vec<i64> = history.iter().map(|e| e.window_id).collect();
+        assert_eq!(ids, vec![2, 1]);
+    }
+
+    #[test]
+    fn test_record_ignores_repeated_focus() {
+        let mut history = FocusHistory::default();
+        history.record(&make_window(1, "A"));
+        history.record(&make_window(1, "A"));
+
+        assert_eq!(history.iter().count(), 1);
+    }
+
+    #[test]
+    fn test_record_keeps_revisits() {
+        // Unlike MRU, going back to a window adds a new entry
+        let mut history = FocusHistory::default();
+        history.record(&make_window(1, "A"));
+        history.record(&make_window(2, "B"));
+        history.record(&make_window(1, "A"));
+
+        let ids: Vec<i64> = history.iter().map(|e| e.window_id).collect();
+        assert_eq!(ids, vec![1, 2, 1]);
+    }
+
+    #[test]
+    fn test_record_bounded_by_capacity() {
+        let mut history = FocusHistory::new(3);
+        for id in 1..=5 {
+            history.record(&make_window(id, "W"));
+        }
+
+        let ids: Vec<i64> = history.iter().map(|e| e.window_id).collect();
+        assert_eq!(ids, vec![5, 4, 3]);
+    }
+
+    #[test]
+    fn test_steps_back() {
+        let mut history = FocusHistory::default();
+        history.record(&make_window(1, "A"));
+        history.record(&make_window(2, "B"));
+        history.record(&make_window(3, "C"));
+
+        assert_eq!(history.steps_back(0).unwrap().window_id, 3);
+        assert_eq!(history.steps_back(2).unwrap().window_id, 1);
+        assert!(history.steps_back(3).is_none());
+    }
+
+    #[test]
+    fn test_record_stores_timestamp_and_title() {
+        let mut history = FocusHistory::default();
+        let at = SystemTime::UNIX_EPOCH + Duration::from_secs(1000);
+        history.record_at(&make_window(7, "Editor"), at);
+
+        let entry = history.steps_back(0).unwrap();
+        assert_eq!(entry.title, "Editor");
+        assert_eq!(entry.app_id.as_deref(), Some("app-7"));
+        assert_eq!(entry.focused_at, at);
+    }
+
+    #[test]
+    fn test_record_keeps_the_app_key_of_x11_windows() {
+        let mut history = FocusHistory::default();
+        let mut window = make_window(7, "");
+        window.app_id = None;
+        window.window_class = Some("XTerm".to_string());
+        history.record(&window);
+
+        let entry = history.steps_back(0).unwrap();
+        assert_eq!(entry.app_id.as_deref(), Some("XTerm"));
+        assert_eq!(entry.display_title("Untitled — {app}"), "Untitled — XTerm");
+    }
+
+    #[test]
+    fn test_restore_keeps_order_within_capacity() {
+        let mut source = FocusHistory::new(3);
+        for id in 1..=3 {
+            source.record(&make_window(id, "W"));
+        }
+
+        let mut history = FocusHistory::new(2);
+        history.restore(source.iter().cloned().collect());
+        let ids: Vec<i64> = history.iter().map(|e| e.window_id).collect();
+        assert_eq!(ids, vec![3, 2]);
+    }
+
+    #[test]
+    fn test_empty_history() {
+        let history = FocusHistory::default();
+        assert_eq!(history.iter().count(), 0);
+        assert!(history.steps_back(0).is_none());
+    }
+
+    #[test]
+    fn test_format_age() {
+        assert_eq!(format_age(Duration::from_secs(0)), "0s ago");
+        assert_eq!(format_age(Duration::from_secs(59)), "59s ago");
+        assert_eq!(format_age(Duration::from_secs(60)), "1m ago");
+        assert_eq!(format_age(Duration::from_secs(7200)), "2h ago");
+        assert_eq!(format_age(Duration::from_secs(3 * 86400)), "3d ago");
+    }
+}
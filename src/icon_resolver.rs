@@ -4,16 +4,27 @@ use gtk4::gio::prelude::FileExt;
 use gtk4::IconLookupFlags;
 use gtk4::IconTheme;
 use lru::LruCache;
+use crate::config::shell_quote;
+use crate::notify::notify_error;
+use crate::window_manager::WindowInfo;
 use std::collections::HashMap;
 use std::num::NonZeroUsize;
 use std::path::{Path, PathBuf};
 use std::sync::{Arc, LazyLock};
+use std::time::{Duration, Instant};
 use tracing::{debug, info, warn};
 
 /// Maximum number of entries in the desktop file cache.
 /// This prevents unbounded memory growth if many different apps are used.
 const DESKTOP_FILE_CACHE_SIZE: usize = 256;
 
+/// Maximum number of decoded icons kept in memory.
+const PIXBUF_CACHE_SIZE: usize = 128;
+
+/// How long a failed desktop file lookup is cached before being retried.
+/// Lets icons appear for apps whose desktop file is installed while the daemon runs.
+const NEGATIVE_CACHE_TTL: Duration = Duration::from_secs(60);
+
 /// Cached XDG application directories plus flatpak locations.
 /// Computed once at first access.
 static APPLICATION_DIRS: LazyLock<Vec<PathBuf>> = LazyLock::new(|| {
@@ -29,18 +40,96 @@ static APPLICATION_DIRS: LazyLock<Vec<PathBuf>> = LazyLock::new(|| {
     .collect()
 });
 
+/// Icon directories searched by [`find_icon_file`], in XDG order
+static ICON_DIRS: LazyLock<Vec<PathBuf>> = LazyLock::new(|| {
+    let data_dirs = std::env::var("XDG_DATA_DIRS")
+        .ok()
+        .filter(|dirs| !dirs.is_empty())
+        .unwrap_or_else(|| "/usr/local/share:/usr/share".to_string());
+    dirs::home_dir()
+        .map(|d| d.join(".icons"))
+        .into_iter()
+        .chain(dirs::data_local_dir().map(|d| d.join("icons")))
+        .chain(data_dirs.split(':').map(|dir| Path::new(dir).join("icons")))
+        .collect()
+});
+
+/// Sizes of the hicolor theme searched by [`find_icon_file`], largest first
+const ICON_FILE_SIZES: [&str; 7] =
+    ["scalable", "512x512", "256x256", "128x128", "64x64", "48x48", "32x32"];
+
 /// A pre-built index mapping StartupWMClass values to desktop file paths.
 /// This allows resolving icons for apps like Signal where app_id ("signal")
 /// doesn't match the desktop file name ("signal-desktop.desktop").
 pub type WmClassIndex = Arc<HashMap<String, PathBuf>>;
 
+/// Identifies a decoded icon: (icon name, size, scale)
+type PixbufKey = (String, i32, i32);
+
+/// Decoded icons, so repeated shows don't read and decode the same files
+/// again. Generic over the icon so it can be tested without GTK.
+struct IconCache<T> {
+    icons: LruCache<PixbufKey, T>,
+}
+
+impl<T: Clone> IconCache<T> {
+    fn new() -> Self {
+        let size = NonZeroUsize::new(PIXBUF_CACHE_SIZE).expect("cache size must be non-zero");
+        IconCache {
+            icons: LruCache::new(size),
+        }
+    }
+
+    /// The icon cached under `key`, or else `decode`'s, kept if it loaded
+    fn get_or_decode(&mut self, key: PixbufKey, decode: impl FnOnce() -> Option<T>) -> Option<T> {
+        if let Some(icon) = self.icons.get(&key) {
+            return Some(icon.clone());
+        }
+        let icon = decode()?;
+        self.icons.put(key, icon.clone());
+        Some(icon)
+    }
+
+    fn clear(&mut self) {
+        self.icons.clear();
+    }
+}
+
+/// A cached desktop file lookup result
+#[derive(Debug, Clone)]
+struct CachedLookup {
+    icon_name: Option<String>,
+    resolved_at: Instant,
+}
+
+impl CachedLookup {
+    fn new(icon_name: Option<String>) -> Self {
+        CachedLookup {
+            icon_name,
+            resolved_at: Instant::now(),
+        }
+    }
+
+    /// Successful lookups never expire; failed ones expire after `NEGATIVE_CACHE_TTL`
+    fn is_expired(&self, now: Instant) -> bool {
+        self.icon_name.is_none() && now.duration_since(self.resolved_at) >= NEGATIVE_CACHE_TTL
+    }
+}
+
 pub struct IconResolver {
     icon_theme: IconTheme,
     /// LRU cache for desktop file lookups: app_id -> icon_name
     /// Bounded to prevent unbounded memory growth
-    desktop_file_cache: LruCache<String, Option<String>>,
-    wmclass_index: WmClassIndex, // StartupWMClass -> desktop file path
+    desktop_file_cache: LruCache<String, CachedLookup>,
+    /// Decoded icons by name, size and scale
+    pixbuf_cache: IconCache<Pixbuf>,
+    desktop_files: DesktopFileFinder,
     icon_size: i32,
+    /// Scale factor of the surface the icons are drawn on; icons are
+    /// decoded at `icon_size * scale` pixels so they stay sharp
+    scale: i32,
+    /// The generic application icon, resolved once per icon theme
+    fallback_icon: Option<Pixbuf>,
 }
 
 impl IconResolver {
@@ -50,14 +139,53 @@ impl IconResolver {
         let cache_size =
             NonZeroUsize::new(DESKTOP_FILE_CACHE_SIZE).expect("cache size must be non-zero");
 
-        IconResolver {
+        let mut resolver = IconResolver {
             icon_theme,
             desktop_file_cache: LruCache::new(cache_size),
-            wmclass_index,
+            pixbuf_cache: IconCache::new(),
+            desktop_files: DesktopFileFinder::new(wmclass_index),
             icon_size,
+            scale: 1,
+            fallback_icon: None,
+        };
+        resolver.fallback_icon = resolver.resolve_fallback_icon();
+        resolver
+    }
+
+    /// Replace the WMClass index, invalidating caches if it actually changed
+    pub fn set_wmclass_index(&mut self, wmclass_index: WmClassIndex) {
+        if !Arc::ptr_eq(&self.desktop_files.wmclass_index, &wmclass_index) {
+            info!("WMClass index changed, invalidating icon caches");
+            self.desktop_files = DesktopFileFinder::new(wmclass_index);
+            self.invalidate();
+        }
+    }
+
+    /// Load icons for a surface with this scale factor. Decoded icons are
+    /// cached per scale, so moving between outputs doesn't drop them.
+    pub fn set_scale(&mut self, scale: i32) {
+        let scale = scale.max(1);
+        if scale != self.scale {
+            debug!("Loading icons at scale {}", scale);
+            self.scale = scale;
+            self.fallback_icon = self.resolve_fallback_icon();
         }
     }
 
+    /// The scale factor icons are loaded at
+    pub fn scale(&self) -> i32 {
+        self.scale
+    }
+
+    /// Drop all cached lookups and reload the icon theme.
+    /// Call when the icon theme or installed desktop files change.
+    pub fn invalidate(&mut self) {
+        self.desktop_file_cache.clear();
+        self.pixbuf_cache.clear();
+        self.icon_theme = IconTheme::new();
+        self.fallback_icon = self.resolve_fallback_icon();
+    }
+
     /// Build an index mapping StartupWMClass values to desktop file paths.
     /// This scans all standard XDG application directories at startup.
     pub fn build_wmclass_index() -> WmClassIndex {
@@ -70,7 +198,18 @@ impl IconResolver {
 
             let entries = match std::fs::read_dir(dir) {
                 Ok(entries) => entries,
-                Err(_) => continue,
+                Err(e) => {
+                    warn!("Failed to read {}: {}", dir.display(), e);
+                    notify_error(
+                        "Couldn't index application icons",
+                        &format!(
+                            "{} is unreadable ({}), some icons may be missing.",
+                            dir.display(),
+                            e
+                        ),
+                    );
+                    continue;
+                }
             };
 
             for entry in entries.flatten() {
@@ -105,37 +244,215 @@ impl IconResolver {
         // Check LRU cache first (also promotes to most-recently-used)
         // Clone the cached value to release the mutable borrow before calling load_icon_by_name
         if let Some(cached) = self.desktop_file_cache.get(app_id).cloned() {
-            return cached.and_then(|name| self.load_icon_by_name(&name));
+            if !cached.is_expired(Instant::now()) {
+                return cached.icon_name.and_then(|name| self.load_icon_by_name(&name));
+            }
+            debug!("Retrying expired failed icon lookup for app_id: {}", app_id);
         }
 
         // Try to find desktop file
-        let icon_name = self.find_icon_from_desktop_file(app_id);
+        let icon_name = self.desktop_files.find(app_id, "Icon");
 
         // Cache the result in LRU cache (evicts oldest if at capacity)
         self.desktop_file_cache
-            .put(app_id.to_string(), icon_name.clone());
+            .put(app_id.to_string(), CachedLookup::new(icon_name.clone()));
 
         // Load icon if found
         icon_name.and_then(|name| self.load_icon_by_name(&name))
     }
 
-    /// Find desktop file and extract icon name using multiple search strategies
-    fn find_icon_from_desktop_file(&self, app_id: &str) -> Option<String> {
+    /// Resolve the icon for a window: the one a rule picked, then by app_id,
+    /// then by WM_CLASS class and instance
+    pub fn resolve_window_icon(&mut self, window: &WindowInfo) -> Option<Pixbuf> {
+        window
+            .icon
+            .as_deref()
+            .and_then(|name| self.load_icon_by_name(name))
+            .or_else(|| {
+                window
+                    .icon_candidates()
+                    .into_iter()
+                    .find_map(|id| self.resolve_icon(Some(id)))
+            })
+    }
+
+    /// Load icon by name, reusing a previously decoded copy if cached
+    fn load_icon_by_name(&mut self, icon_name: &str) -> Option<Pixbuf> {
+        let key = (icon_name.to_string(), self.icon_size, self.scale);
+        let (icon_theme, size, scale) = (&self.icon_theme, self.icon_size, self.scale);
+        self.pixbuf_cache
+            .get_or_decode(key, || decode_icon(icon_theme, icon_name, size, scale))
+    }
+
+    /// Get a fallback icon (generic application icon)
+    pub fn get_fallback_icon(&self) -> Option<Pixbuf> {
+        self.fallback_icon.clone()
+    }
+
+    /// Look up the first generic application icon the theme has. Decoded
+    /// directly, so it stays out of the LRU cache and can't be evicted.
+    fn resolve_fallback_icon(&self) -> Option<Pixbuf> {
+        let icon = ["application-x-executable", "application-default-icon", "gtk-missing-image"]
+            .into_iter()
+            .find_map(|name| decode_icon(&self.icon_theme, name, self.icon_size, self.scale));
+        if icon.is_none() {
+            warn!("No fallback icon in the icon theme, icon-less windows show none");
+        }
+        icon
+    }
+}
+
+/// Look up and decode an icon using GTK IconTheme, at `size` logical
+/// pixels on a surface with scale factor `scale`
+fn decode_icon(icon_theme: &IconTheme, icon_name: &str, size: i32, scale: i32) -> Option<Pixbuf> {
+    let pixels = size * scale;
+
+    // Try to load from icon theme
+    let paintable = icon_theme.lookup_icon(
+        icon_name,
+        &[], // No fallbacks
+        size,
+        scale,
+        gtk4::TextDirection::None,
+        IconLookupFlags::empty(),
+    );
+
+    // Try to get the file and load as pixbuf
+    if let Some(file) = paintable.file() {
+        // In GTK4, get path from URI
+        if let Some(path_str) = file.path()
+            && let Ok(pixbuf) = Pixbuf::from_file_at_scale(&path_str, pixels, pixels, true)
+        {
+            return Some(pixbuf);
+        }
+    }
+
+    // Try loading directly as a file path (absolute icon paths)
+    if let Ok(pixbuf) = Pixbuf::from_file_at_scale(icon_name, pixels, pixels, true) {
+        return Some(pixbuf);
+    }
+
+    // Try /usr/share/pixmaps as fallback (many apps install icons here)
+    let pixmaps_path = format!("/usr/share/pixmaps/{}.png", icon_name);
+    if let Ok(pixbuf) = Pixbuf::from_file_at_scale(&pixmaps_path, pixels, pixels, true) {
+        debug!("Found icon in pixmaps: {}", pixmaps_path);
+        return Some(pixbuf);
+    }
+
+    warn!("Failed to load icon: {}", icon_name);
+    None
+}
+
+/// Find an icon's file without GTK, for output that names the icon rather
+/// than drawing it: an absolute path as is, then the hicolor theme that
+/// applications install their icons into, then `/usr/share/pixmaps`
+pub fn find_icon_file(icon_name: &str) -> Option<PathBuf> {
+    let path = Path::new(icon_name);
+    if path.is_absolute() {
+        return path.is_file().then(|| path.to_path_buf());
+    }
+    ICON_DIRS
+        .iter()
+        .flat_map(|dir| {
+            ICON_FILE_SIZES
+                .iter()
+                .map(move |size| dir.join("hicolor").join(size).join("apps"))
+        })
+        .chain(std::iter::once(PathBuf::from("/usr/share/pixmaps")))
+        .flat_map(|dir| ["png", "svg", "xpm"].map(|ext| dir.join(format!("{}.{}", icon_name, ext))))
+        .find(|file| file.is_file())
+}
+
+/// An application's name and icon file, from its desktop entry
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AppDetails {
+    pub name: Option<String>,
+    pub icon: Option<PathBuf>,
+}
+
+/// [`AppDetails`] by window, cached like the icon resolver's lookups:
+/// complete ones never expire, others are retried after `NEGATIVE_CACHE_TTL`
+pub struct AppDetailsCache {
+    desktop_files: DesktopFileFinder,
+    /// Keyed by the window's rule icon and its icon candidates
+    cache: LruCache<String, (AppDetails, Instant)>,
+}
+
+impl AppDetailsCache {
+    pub fn new(wmclass_index: WmClassIndex) -> Self {
+        let cache_size =
+            NonZeroUsize::new(DESKTOP_FILE_CACHE_SIZE).expect("cache size must be non-zero");
+        AppDetailsCache {
+            desktop_files: DesktopFileFinder::new(wmclass_index),
+            cache: LruCache::new(cache_size),
+        }
+    }
+
+    /// The name and icon file of a window's application. The icon a rule
+    /// picked wins over the desktop entry's.
+    pub fn lookup(&mut self, window: &WindowInfo) -> AppDetails {
+        let candidates = window.icon_candidates();
+        let rule_icon = window.icon.as_deref().unwrap_or_default();
+        let key = format!("{}\n{}", rule_icon, candidates.join("\n"));
+        if let Some((details, resolved_at)) = self.cache.get(&key) {
+            let complete = details.name.is_some() && details.icon.is_some();
+            if complete || resolved_at.elapsed() < NEGATIVE_CACHE_TTL {
+                return details.clone();
+            }
+        }
+
+        let find = |key: &str| candidates.iter().find_map(|id| self.desktop_files.find(id, key));
+        let icon_name = window.icon.clone().or_else(|| find("Icon"));
+        let details = AppDetails {
+            name: find("Name"),
+            icon: icon_name.as_deref().and_then(find_icon_file),
+        };
+        self.cache.put(key, (details.clone(), Instant::now()));
+        details
+    }
+}
+
+/// Locates an application's desktop file from its app_id or WM_CLASS.
+///
+/// Shared by icon resolution (UI thread) and app launching (daemon), which
+/// only differ in the desktop entry key they read.
+#[derive(Clone)]
+pub struct DesktopFileFinder {
+    wmclass_index: WmClassIndex, // StartupWMClass -> desktop file path
+}
+
+impl DesktopFileFinder {
+    pub fn new(wmclass_index: WmClassIndex) -> Self {
+        DesktopFileFinder { wmclass_index }
+    }
+
+    /// Find the desktop file for an application ID using multiple search
+    /// strategies, and return the value of the given `[Desktop Entry]` key
+    /// (e.g. "Icon" or "Exec"). Files without the key are skipped.
+    pub fn find(&self, app_id: &str, key: &str) -> Option<String> {
         // Try strategies in order of likelihood
-        self.try_wmclass_index_lookup(app_id)
-            .or_else(|| self.try_exact_desktop_match(app_id))
-            .or_else(|| self.try_case_insensitive_match(app_id))
-            .or_else(|| self.try_reverse_domain_match(app_id))
-            .or_else(|| self.try_common_variations(app_id))
+        self.try_wmclass_index_lookup(app_id, key)
+            .or_else(|| self.try_exact_desktop_match(app_id, key))
+            .or_else(|| self.try_case_insensitive_match(app_id, key))
+            .or_else(|| self.try_reverse_domain_match(app_id, key))
+            .or_else(|| self.try_common_variations(app_id, key))
             .or_else(|| {
-                debug!("No desktop file found for app_id: {}", app_id);
+                debug!("No desktop file with {} found for app_id: {}", key, app_id);
                 None
             })
     }
 
+    /// Find the command line to launch a new instance of an application,
+    /// with desktop entry field codes removed
+    pub fn find_exec(&self, app_id: &str) -> Option<String> {
+        let exec = self.find(app_id, "Exec")?;
+        let command = strip_field_codes(&exec);
+        (!command.is_empty()).then_some(command)
+    }
+
     /// Try to match reverse-domain app_ids like "org.speedcrunch.speedcrunch"
     /// by extracting the last segment and looking for that desktop file
-    fn try_reverse_domain_match(&self, app_id: &str) -> Option<String> {
+    fn try_reverse_domain_match(&self, app_id: &str, key: &str) -> Option<String> {
         // Only try if app_id contains dots (reverse-domain style)
         if !app_id.contains('.') {
             return None;
@@ -146,12 +463,12 @@ impl IconResolver {
             let last_segment_lower = last_segment.to_lowercase();
             for dir in APPLICATION_DIRS.iter() {
                 let desktop_file = dir.join(format!("{}.desktop", last_segment_lower));
-                if let Some(icon) = self.parse_desktop_file(&desktop_file) {
+                if let Some(value) = self.parse_desktop_file(&desktop_file, key) {
                     debug!(
-                        "Found icon '{}' for app_id '{}' via reverse-domain last segment '{}' in {:?}",
-                        icon, app_id, last_segment_lower, desktop_file
+                        "Found {} '{}' for app_id '{}' via reverse-domain last segment '{}' in {:?}",
+                        key, value, app_id, last_segment_lower, desktop_file
                     );
-                    return Some(icon);
+                    return Some(value);
                 }
             }
         }
@@ -159,32 +476,32 @@ impl IconResolver {
         None
     }
 
-    /// Try to find icon via the pre-built WMClass index
-    fn try_wmclass_index_lookup(&self, app_id: &str) -> Option<String> {
+    /// Try to find the desktop file via the pre-built WMClass index
+    fn try_wmclass_index_lookup(&self, app_id: &str, key: &str) -> Option<String> {
         let app_id_lower = app_id.to_lowercase();
         let desktop_path = self.wmclass_index.get(&app_id_lower)?;
-        let icon = self.parse_desktop_file(desktop_path)?;
+        let value = self.parse_desktop_file(desktop_path, key)?;
         debug!(
-            "Found icon '{}' for app_id '{}' via StartupWMClass in {:?}",
-            icon, app_id, desktop_path
+            "Found {} '{}' for app_id '{}' via StartupWMClass in {:?}",
+            key, value, app_id, desktop_path
         );
-        Some(icon)
+        Some(value)
     }
 
     /// Try exact match: app_id.desktop
-    fn try_exact_desktop_match(&self, app_id: &str) -> Option<String> {
+    fn try_exact_desktop_match(&self, app_id: &str, key: &str) -> Option<String> {
         for dir in APPLICATION_DIRS.iter() {
             let desktop_file = dir.join(format!("{}.desktop", app_id));
-            if let Some(icon) = self.parse_desktop_file(&desktop_file) {
-                debug!("Found icon '{}' for app_id '{}' in {:?}", icon, app_id, desktop_file);
-                return Some(icon);
+            if let Some(value) = self.parse_desktop_file(&desktop_file, key) {
+                debug!("Found {} '{}' for app_id '{}' in {:?}", key, value, app_id, desktop_file);
+                return Some(value);
             }
         }
         None
     }
 
     /// Try case-insensitive match by scanning directories
-    fn try_case_insensitive_match(&self, app_id: &str) -> Option<String> {
+    fn try_case_insensitive_match(&self, app_id: &str, key: &str) -> Option<String> {
         let target = format!("{}.desktop", app_id.to_lowercase());
         for dir in APPLICATION_DIRS.iter() {
             let entries = match std::fs::read_dir(dir) {
@@ -195,12 +512,12 @@ impl IconResolver {
                 let path = entry.path();
                 if let Some(filename) = path.file_name()
                     && filename.to_string_lossy().to_lowercase() == target
-                        && let Some(icon) = self.parse_desktop_file(&path) {
+                        && let Some(value) = self.parse_desktop_file(&path, key) {
                             debug!(
-                                "Found icon '{}' for app_id '{}' (case-insensitive) in {:?}",
-                                icon, app_id, path
+                                "Found {} '{}' for app_id '{}' (case-insensitive) in {:?}",
+                                key, value, app_id, path
                             );
-                            return Some(icon);
+                            return Some(value);
                         }
             }
         }
@@ -208,7 +525,7 @@ impl IconResolver {
     }
 
     /// Try common variations: remove spaces, replace with dashes, first word only
-    fn try_common_variations(&self, app_id: &str) -> Option<String> {
+    fn try_common_variations(&self, app_id: &str, key: &str) -> Option<String> {
         let variations = [
             app_id.replace(' ', "").to_lowercase(),
             app_id.replace(' ', "-").to_lowercase(),
@@ -221,85 +538,77 @@ impl IconResolver {
             }
             for dir in APPLICATION_DIRS.iter() {
                 let desktop_file = dir.join(format!("{}.desktop", variation));
-                if let Some(icon) = self.parse_desktop_file(&desktop_file) {
+                if let Some(value) = self.parse_desktop_file(&desktop_file, key) {
                     debug!(
-                        "Found icon '{}' for app_id '{}' using variation '{}' in {:?}",
-                        icon, app_id, variation, desktop_file
+                        "Found {} '{}' for app_id '{}' using variation '{}' in {:?}",
+                        key, value, app_id, variation, desktop_file
                     );
-                    return Some(icon);
+                    return Some(value);
                 }
             }
         }
         None
     }
 
-    /// Parse desktop file and extract Icon field
-    fn parse_desktop_file(&self, path: &Path) -> Option<String> {
+    /// Parse desktop file and extract the given `[Desktop Entry]` field
+    fn parse_desktop_file(&self, path: &Path, key: &str) -> Option<String> {
         let bytes = std::fs::read(path).ok()?;
         let content = String::from_utf8(bytes).ok()?;
         let entry = DesktopEntry::decode(path, &content).ok()?;
 
-        entry.icon().map(|s| s.to_string())
+        entry.desktop_entry(key).map(|s| s.to_string())
     }
+}
 
-    /// Load icon by name using GTK IconTheme
-    fn load_icon_by_name(&self, icon_name: &str) -> Option<Pixbuf> {
-        // Try to load from icon theme
-        let paintable = self.icon_theme.lookup_icon(
-            icon_name,
-            &[], // No fallbacks
-            self.icon_size,
-            1, // scale
-            gtk4::TextDirection::None,
-            IconLookupFlags::empty(),
-        );
+/// Remove field codes (`%f`, `%U`, `%i`, ...) from a desktop entry Exec value.
+/// There are no files or URLs to pass when launching from the switcher.
+/// `%%` is unescaped to a literal `%`, and the arguments are quoted again
+/// for the shell where they need it.
+fn strip_field_codes(exec: &str) -> String {
+    exec_args(exec)
+        .into_iter()
+        .filter(|arg| arg.len() != 2 || !arg.starts_with('%') || arg == "%%")
+        .map(|arg| {
+            let arg = arg.replace("%%", "%");
+            let plain = |c: char| c.is_ascii_alphanumeric() || "-_./=:,+@%".contains(c);
+            if !arg.is_empty() && arg.chars().all(plain) {
+                arg
+            } else {
+                shell_quote(&arg)
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
 
-        // Try to get the file and load as pixbuf
-        if let Some(file) = paintable.file() {
-            // In GTK4, get path from URI
-            if let Some(path_str) = file.path()
-                && let Ok(pixbuf) = Pixbuf::from_file_at_scale(
-                    &path_str,
-                    self.icon_size,
-                    self.icon_size,
-                    true,
-                ) {
-                    return Some(pixbuf);
+/// Split a desktop entry Exec value into its arguments. As the spec has it,
+/// an argument holding spaces is double-quoted, and inside the quotes a
+/// backslash escapes `"`, `` ` ``, `$` and `\`.
+fn exec_args(exec: &str) -> Vec<String> {
+    let mut args = Vec::new();
+    // None between arguments, so `""` still makes an empty one
+    let mut arg: Option<String> = None;
+    let mut quoted = false;
+    let mut chars = exec.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => {
+                quoted = !quoted;
+                arg.get_or_insert_default();
+            }
+            '\\' if quoted => {
+                let arg = arg.get_or_insert_default();
+                match chars.next_if(|next| matches!(next, '"' | '`' | '$' | '\\')) {
+                    Some(escaped) => arg.push(escaped),
+                    None => arg.push(c),
                 }
+            }
+            c if c.is_whitespace() && !quoted => args.extend(arg.take()),
+            c => arg.get_or_insert_default().push(c),
         }
-
-        // Try loading directly as a file path (absolute icon paths)
-        if let Ok(pixbuf) = Pixbuf::from_file_at_scale(
-            icon_name,
-            self.icon_size,
-            self.icon_size,
-            true,
-        ) {
-            return Some(pixbuf);
-        }
-
-        // Try /usr/share/pixmaps as fallback (many apps install icons here)
-        let pixmaps_path = format!("/usr/share/pixmaps/{}.png", icon_name);
-        if let Ok(pixbuf) = Pixbuf::from_file_at_scale(
-            &pixmaps_path,
-            self.icon_size,
-            self.icon_size,
-            true,
-        ) {
-            debug!("Found icon in pixmaps: {}", pixmaps_path);
-            return Some(pixbuf);
-        }
-
-        warn!("Failed to load icon: {}", icon_name);
-        None
-    }
-
-    /// Get a fallback icon (generic application icon)
-    pub fn get_fallback_icon(&self) -> Option<Pixbuf> {
-        self.load_icon_by_name("application-x-executable")
-            .or_else(|| self.load_icon_by_name("application-default-icon"))
-            .or_else(|| self.load_icon_by_name("gtk-missing-image"))
     }
+    args.extend(arg);
+    args
 }
 
 #[cfg(test)]
@@ -358,6 +667,7 @@ Exec=signal-desktop
     }
 
     #[test]
+    #[allow(clippy::unnecessary_get_then_check)]
     fn test_empty_index_returns_none() {
         let index: HashMap<String, PathBuf> = HashMap::new();
 
@@ -462,7 +772,7 @@ Exec=signal-desktop
             println!("Testing pixmaps fallback with icon: {}", icon_name);
 
             let wmclass_index = IconResolver::build_wmclass_index();
-            let resolver = IconResolver::with_wmclass_index(48, wmclass_index);
+            let mut resolver = IconResolver::with_wmclass_index(48, wmclass_index);
 
             // The icon should be loadable via the pixmaps fallback
             let pixbuf = resolver.load_icon_by_name(&icon_name);
@@ -478,6 +788,7 @@ Exec=signal-desktop
     }
 
     #[test]
+    #[allow(clippy::assertions_on_constants)]
     fn test_lru_cache_size_constant() {
         // Verify the cache size constant is reasonable
         assert!(DESKTOP_FILE_CACHE_SIZE > 0);
@@ -485,6 +796,80 @@ Exec=signal-desktop
         assert!(DESKTOP_FILE_CACHE_SIZE <= 1024); // But not excessively large
     }
 
+    #[test]
+    fn test_find_icon_file_takes_absolute_paths() {
+        let path = std::env::temp_dir().join(format!("icon-file-{}.png", std::process::id()));
+        std::fs::write(&path, b"").unwrap();
+        assert_eq!(find_icon_file(path.to_str().unwrap()), Some(path.clone()));
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(find_icon_file(path.to_str().unwrap()), None);
+    }
+
+    #[test]
+    fn test_app_details_are_cached() {
+        let path = std::env::temp_dir().join(format!("app-details-{}.svg", std::process::id()));
+        std::fs::write(&path, b"").unwrap();
+        let window = WindowInfo {
+            app_id: Some("no-such-app-for-sure".to_string()),
+            icon: Some(path.to_string_lossy().into_owned()),
+            ..Default::default()
+        };
+        let mut details = AppDetailsCache::new(Arc::new(HashMap::new()));
+        assert_eq!(details.lookup(&window).icon, Some(path.clone()));
+
+        // Served from the cache while the failed name lookup is fresh
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(details.lookup(&window).icon, Some(path.clone()));
+        assert_eq!(details.lookup(&window).name, None);
+        assert_eq!(details.cache.len(), 1);
+    }
+
+    #[test]
+    fn test_strip_field_codes() {
+        assert_eq!(strip_field_codes("firefox %u"), "firefox");
+        assert_eq!(strip_field_codes("code --new-window %F"), "code --new-window");
+        assert_eq!(strip_field_codes("app %i %c %k"), "app");
+    }
+
+    #[test]
+    fn test_strip_field_codes_keeps_literal_percent() {
+        assert_eq!(strip_field_codes("printf 100%% %f"), "printf 100%");
+    }
+
+    #[test]
+    fn test_strip_field_codes_no_codes() {
+        assert_eq!(strip_field_codes("alacritty"), "alacritty");
+        assert_eq!(strip_field_codes(""), "");
+    }
+
+    #[test]
+    fn test_strip_field_codes_keeps_quoted_arguments() {
+        assert_eq!(
+            strip_field_codes(r#""/opt/My App/app" --name "a b" %U"#),
+            "'/opt/My App/app' --name 'a b'"
+        );
+        assert_eq!(
+            strip_field_codes(r#"sh -c "echo \"it's\" \$HOME \\n""#),
+            r#"sh -c 'echo "it'\''s" $HOME \n'"#
+        );
+        assert_eq!(strip_field_codes(r#"app "" "%%f""#), "app '' %f");
+    }
+
+    #[test]
+    fn test_cached_lookup_positive_never_expires() {
+        let lookup = CachedLookup::new(Some("firefox".to_string()));
+        let much_later = lookup.resolved_at + NEGATIVE_CACHE_TTL * 100;
+        assert!(!lookup.is_expired(much_later));
+    }
+
+    #[test]
+    fn test_cached_lookup_negative_expires_after_ttl() {
+        let lookup = CachedLookup::new(None);
+        assert!(!lookup.is_expired(lookup.resolved_at));
+        assert!(!lookup.is_expired(lookup.resolved_at + NEGATIVE_CACHE_TTL / 2));
+        assert!(lookup.is_expired(lookup.resolved_at + NEGATIVE_CACHE_TTL));
+    }
+
     #[test]
     fn test_lru_cache_eviction() {
         use std::num::NonZeroUsize;
@@ -531,4 +916,43 @@ Exec=signal-desktop
         assert!(cache.get("app2").is_none());
         assert!(cache.get("app3").is_some());
     }
+
+    #[test]
+    fn test_icon_cache_decodes_once_per_scale() {
+        let mut cache = IconCache::new();
+        let decoded = std::cell::Cell::new(0);
+        let load = |cache: &mut IconCache<i32>, scale| {
+            cache.get_or_decode(("firefox".to_string(), 48, scale), || {
+                decoded.set(decoded.get() + 1);
+                Some(48 * scale)
+            })
+        };
+
+        assert_eq!(load(&mut cache, 1), Some(48));
+        // The second load is a cache hit
+        assert_eq!(load(&mut cache, 1), Some(48));
+        // A surface with another scale gets its own copy
+        assert_eq!(load(&mut cache, 2), Some(96));
+        assert_eq!(decoded.get(), 2);
+    }
+
+    #[test]
+    fn test_icon_cache_clear_decodes_again() {
+        let mut cache = IconCache::new();
+        let key = || ("firefox".to_string(), 48, 1);
+        assert_eq!(cache.get_or_decode(key(), || Some(1)), Some(1));
+        assert_eq!(cache.get_or_decode(key(), || Some(2)), Some(1));
+
+        // What `IconResolver::invalidate` does to decoded icons
+        cache.clear();
+        assert_eq!(cache.get_or_decode(key(), || Some(2)), Some(2));
+    }
+
+    #[test]
+    fn test_icon_cache_skips_failed_decodes() {
+        let mut cache = IconCache::new();
+        let key = || ("missing".to_string(), 48, 1);
+        assert_eq!(cache.get_or_decode(key(), || None), None);
+        assert_eq!(cache.get_or_decode(key(), || Some(1)), Some(1));
+    }
 }
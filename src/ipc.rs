@@ -1,3 +1,87 @@
+use crate::config::{shell_quote, SwitchTarget};
+use crate::focus_history::FocusEntry;
+use crate::window_manager::{Geometry, WindowInfo};
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+/// Default number of entries printed by the `history` command
+pub const DEFAULT_HISTORY_COUNT: usize = 10;
+
+/// Version of the frontend protocol spoken over `watch` connections.
+/// Bumped on changes that existing frontends can't ignore.
+pub const FRONTEND_PROTOCOL_VERSION: u32 = 1;
+
+/// Ways the user can activate the selection in the switcher.
+/// The daemon maps each to an action from the config's action map.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Trigger {
+    /// Enter
+    Enter,
+    /// Shift+Enter
+    ShiftEnter,
+    /// Ctrl+Enter
+    CtrlEnter,
+    /// Alt released
+    Release,
+    /// Alt released while Shift is held
+    ShiftRelease,
+}
+
+/// What a tile's context menu can do to its window
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum TileAction {
+    /// Focus the window
+    Focus,
+    /// Move the window to the current workspace and focus it
+    MoveHere,
+    /// Toggle the window between tiling and floating and focus it
+    Float,
+    /// Close the window, leaving the switcher open
+    Close,
+    /// Take the window's application out of the pinned order
+    Unpin,
+}
+
+impl TileAction {
+    /// Name used in input commands
+    #[must_use]
+    pub fn as_str(self) -> &'static str {
+        match self {
+            TileAction::Focus => "focus",
+            TileAction::MoveHere => "move-here",
+            TileAction::Float => "float",
+            TileAction::Close => "close",
+            TileAction::Unpin => "unpin",
+        }
+    }
+}
+
+/// An arrow key pressed in the switcher
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Direction {
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+impl Direction {
+    /// Name used in input commands
+    #[must_use]
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Direction::Up => "up",
+            Direction::Down => "down",
+            Direction::Left => "left",
+            Direction::Right => "right",
+        }
+    }
+}
+
 /// Commands sent from UI to daemon (keyboard input)
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum InputCommand {
@@ -9,4 +93,849 @@ pub enum InputCommand {
     Select,
     /// Cancel switching without selecting
     Cancel,
+    /// Launch a new instance of the selected window's application
+    LaunchNew,
+    /// Move the selected window to the current workspace and focus it
+    MoveHere,
+    /// The user activated the selection; the action depends on configuration
+    Trigger(Trigger),
+    /// Select the window focused this many steps back in the focus history
+    JumpBack(usize),
+    /// Select the entry at this index and focus it, e.g. a clicked tile
+    SelectIndex(usize),
+    /// An arrow key: moves the selection across a grid, otherwise Left
+    /// and Right cycle and Down and Up expand and collapse the application
+    Arrow(Direction),
+    /// Expand the selected application's windows into a list
+    ExpandApp,
+    /// Return from the application list to all windows
+    CollapseApp,
+    /// The switcher surface is mapped and can receive keyboard input
+    Shown,
+    /// The UI's list no longer matches the daemon's; show it again
+    Resync,
+    /// Run the custom action with this index for the selected window
+    Custom(usize),
+    /// Focus the selected window and take a screenshot of it
+    Screenshot,
+    /// Switch between the list without idle windows and all windows
+    ToggleIdle,
+    /// A letter typed in the switcher, for key sequences like `g w`
+    Key(char),
+    /// An action picked from the context menu of the tile at this index
+    Tile { index: usize, action: TileAction },
+    /// The tile at `from` was dragged to `to`, pinning the new order
+    Move { from: usize, to: usize },
+    /// Focus the window with this ID, e.g. clicked in the taskbar; also
+    /// works while not switching
+    Focus(i64),
+}
+
+/// Commands sent from CLI clients to the daemon over the Unix socket.
+///
+/// The wire format is a single line of text, e.g. `history 5`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IpcCommand {
+    /// Show the window switcher (same as SIGUSR1 for `SwitchTarget::Windows`),
+    /// optionally limited to the workspaces of a named set. `skip` moves
+    /// the selection past that many more entries when the switcher opens.
+    ///
+    /// Wire format: `show [what] [--set <name>] [--skip <n>]`
+    Show {
+        what: SwitchTarget,
+        set: Option<String>,
+        skip: usize,
+    },
+    /// Like `Show`, but moving backward: the switcher opens on the least
+    /// recently used entry, and while open it selects the previous one.
+    ///
+    /// Wire format: `show-prev [what] [--set <name>] [--skip <n>]`
+    ShowPrev {
+        what: SwitchTarget,
+        set: Option<String>,
+        skip: usize,
+    },
+    /// Show the window switcher with only the windows of the focused
+    /// window's application (same as SIGUSR2)
+    ShowSameApp,
+    /// Print the most recent focus changes
+    History { count: usize },
+    /// Print details of the most recently used window
+    Current,
+    /// Focus the `n`th most recently used window, not counting the focused
+    /// one, without showing the switcher.
+    /// Wire format: `focus-nth N`, N counting from 1
+    FocusNth { n: usize },
+    /// Switch to a named profile, or back to the defaults if `None`
+    SetProfile { name: Option<String> },
+    /// Print all known windows in MRU order as JSON
+    Windows,
+    /// Print the tree nodes left out of the window list and why
+    Skipped,
+    /// Print the window rules in evaluation order and which of them
+    /// matched each window
+    Rules,
+    /// Stream the switcher's state as JSON lines until the client disconnects.
+    /// The client may send input commands (one per line) on the same connection.
+    Watch,
+    /// Act on the switcher as if a key was pressed in the UI
+    Input(InputCommand),
+    /// Print the daemon's state as JSON, for `import-state`
+    ExportState,
+    /// Close the switcher and stop the daemon
+    Shutdown,
+    /// Report the daemon's health as `key=value` lines
+    Status,
+    /// Ignore show requests until `resume`
+    Pause,
+    /// Handle show requests again after `pause`
+    Resume,
+    /// Re-read the config file
+    Reload,
+    /// Report the compositor resources the UI holds as `key=value` lines
+    DebugResources,
+    /// Restore state exported by a previous daemon.
+    /// Wire format: `import-state <json>`, the JSON on the same line.
+    ImportState(DaemonState),
+    /// Print the open windows with a guess at the command that started
+    /// each, for a rudimentary session restore.
+    /// Wire format: `export-layout [json|script]`
+    ExportLayout { format: LayoutFormat },
+}
+
+/// How `export-layout` prints the windows
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum LayoutFormat {
+    /// A JSON array of windows
+    #[default]
+    Json,
+    /// A shell script that starts each application on its workspace
+    Script,
+}
+
+impl LayoutFormat {
+    /// Name used on the command line and in IPC commands
+    #[must_use]
+    pub fn as_str(self) -> &'static str {
+        match self {
+            LayoutFormat::Json => "json",
+            LayoutFormat::Script => "script",
+        }
+    }
+}
+
+/// A window as `export-layout` describes it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LayoutEntry {
+    pub app_id: Option<String>,
+    pub title: String,
+    pub workspace: String,
+    pub output: String,
+    pub geometry: Option<Geometry>,
+    /// The Exec line of the application's desktop file, if one was found
+    pub exec: Option<String>,
+}
+
+/// A shell script that starts every window's application on its
+/// workspace, in order. Windows without a known command are listed as
+/// comments.
+#[must_use]
+pub fn layout_script(entries: &[LayoutEntry]) -> String {
+    let mut script = String::from("#!/bin/sh\n# Generated by sway-alttab-gui export-layout\n");
+    for entry in entries {
+        match entry.exec {
+            Some(ref exec) => {
+                let sway_command = format!(
+                    "workspace \"{}\"; exec {}",
+                    entry.workspace.replace('"', "\\\""),
+                    exec
+                );
+                script.push_str(&format!("swaymsg {}\n", shell_quote(&sway_command)));
+            }
+            None => script.push_str(&format!(
+                "# {} on workspace {}: no desktop file found\n",
+                entry.app_id.as_deref().unwrap_or("unknown"),
+                entry.workspace
+            )),
+        }
+    }
+    script
+}
+
+/// Whether a `status` reply says the daemon is healthy
+#[must_use]
+pub fn status_is_healthy(reply: &str) -> bool {
+    reply.lines().any(|line| line.trim() == "healthy=true")
+}
+
+/// Version of the `export-state` format
+pub const STATE_VERSION: u32 = 1;
+
+/// What a daemon carries over to its replacement through
+/// `export-state`/`import-state`, so an upgrade keeps the window order
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DaemonState {
+    pub version: u32,
+    /// Window IDs, most recently used first
+    pub mru: Vec<i64>,
+    /// Focus history, most recent first
+    pub history: Vec<FocusEntry>,
+    /// Active profile
+    pub profile: Option<String>,
+}
+
+/// The frontend protocol: messages streamed to `watch` clients, one JSON
+/// object per line, tagged by `event`. A frontend draws the switcher from
+/// these and sends input commands back; the built-in UI gets the same
+/// changes over its channel.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "kebab-case")]
+pub enum SwitcherEvent {
+    /// First message on every connection
+    Hello { version: u32 },
+    /// The switcher opened (or its list changed)
+    Show {
+        windows: Vec<WindowInfo>,
+        selected: usize,
+        target: SwitchTarget,
+    },
+    /// The selection moved
+    Update { selected: usize },
+    /// The switcher closed
+    Hide,
+}
+
+impl FromStr for InputCommand {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut parts = s.split_whitespace();
+        let name = parts.next().context("Empty input command")?;
+
+        let command = match name {
+            "next" => InputCommand::Next,
+            "prev" => InputCommand::Prev,
+            "select" => InputCommand::Select,
+            "cancel" => InputCommand::Cancel,
+            "launch-new" => InputCommand::LaunchNew,
+            "move-here" => InputCommand::MoveHere,
+            "expand-app" => InputCommand::ExpandApp,
+            "collapse-app" => InputCommand::CollapseApp,
+            "shown" => InputCommand::Shown,
+            "resync" => InputCommand::Resync,
+            "screenshot" => InputCommand::Screenshot,
+            "toggle-idle" => InputCommand::ToggleIdle,
+            "custom" => {
+                let index = parts.next().context("custom needs an action index")?;
+                InputCommand::Custom(
+                    index
+                        .parse()
+                        .with_context(|| format!("Invalid action index: {}", index))?,
+                )
+            }
+            "key" => {
+                let key = parts.next().context("key needs a character")?;
+                let mut chars = key.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(c), None) => InputCommand::Key(c),
+                    _ => anyhow::bail!("Invalid key: {}", key),
+                }
+            }
+            "tile" => {
+                let index = parts.next().context("tile needs a tile index")?;
+                let index = index
+                    .parse()
+                    .with_context(|| format!("Invalid tile index: {}", index))?;
+                let action = parts.next().context("tile needs an action")?;
+                let action = TileAction::from_str(action, false)
+                    .map_err(|_| anyhow::anyhow!("Unknown tile action: {}", action))?;
+                InputCommand::Tile { index, action }
+            }
+            "focus" => {
+                let id = parts.next().context("focus needs a window ID")?;
+                InputCommand::Focus(
+                    id.parse()
+                        .with_context(|| format!("Invalid window ID: {}", id))?,
+                )
+            }
+            "move" => {
+                let mut index = || -> Result<usize> {
+                    let index = parts.next().context("move needs two tile indexes")?;
+                    index
+                        .parse()
+                        .with_context(|| format!("Invalid tile index: {}", index))
+                };
+                let from = index()?;
+                let to = index()?;
+                InputCommand::Move { from, to }
+            }
+            "arrow" => {
+                let direction = parts.next().context("arrow needs a direction")?;
+                let direction = Direction::from_str(direction, false)
+                    .map_err(|_| anyhow::anyhow!("Unknown direction: {}", direction))?;
+                InputCommand::Arrow(direction)
+            }
+            "select-index" => {
+                let index = parts.next().context("select-index needs an index")?;
+                InputCommand::SelectIndex(
+                    index
+                        .parse()
+                        .with_context(|| format!("Invalid tile index: {}", index))?,
+                )
+            }
+            "jump-back" => {
+                let steps = parts.next().context("jump-back needs a step count")?;
+                InputCommand::JumpBack(
+                    steps
+                        .parse()
+                        .with_context(|| format!("Invalid step count: {}", steps))?,
+                )
+            }
+            _ => anyhow::bail!("Unknown input command: {}", name),
+        };
+
+        if let Some(extra) = parts.next() {
+            anyhow::bail!("Unexpected argument for {}: {}", name, extra);
+        }
+
+        Ok(command)
+    }
+}
+
+impl fmt::Display for InputCommand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InputCommand::Next => write!(f, "next"),
+            InputCommand::Prev => write!(f, "prev"),
+            InputCommand::Select => write!(f, "select"),
+            InputCommand::Cancel => write!(f, "cancel"),
+            InputCommand::LaunchNew => write!(f, "launch-new"),
+            InputCommand::MoveHere => write!(f, "move-here"),
+            InputCommand::ExpandApp => write!(f, "expand-app"),
+            InputCommand::CollapseApp => write!(f, "collapse-app"),
+            InputCommand::Shown => write!(f, "shown"),
+            InputCommand::Resync => write!(f, "resync"),
+            InputCommand::Screenshot => write!(f, "screenshot"),
+            InputCommand::ToggleIdle => write!(f, "toggle-idle"),
+            InputCommand::JumpBack(steps) => write!(f, "jump-back {}", steps),
+            InputCommand::SelectIndex(index) => write!(f, "select-index {}", index),
+            InputCommand::Arrow(direction) => write!(f, "arrow {}", direction.as_str()),
+            InputCommand::Custom(index) => write!(f, "custom {}", index),
+            InputCommand::Key(key) => write!(f, "key {}", key),
+            InputCommand::Tile { index, action } => {
+                write!(f, "tile {} {}", index, action.as_str())
+            }
+            InputCommand::Move { from, to } => write!(f, "move {} {}", from, to),
+            InputCommand::Focus(id) => write!(f, "focus {}", id),
+            // Triggers only come from the built-in UI's key handling
+            InputCommand::Trigger(trigger) => write!(f, "{:?}", trigger),
+        }
+    }
+}
+
+impl FromStr for IpcCommand {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut parts = s.split_whitespace();
+        let name = parts.next().context("Empty command")?;
+
+        if name == "input" {
+            let rest: Vec<&str> = parts.collect();
+            return Ok(IpcCommand::Input(rest.join(" ").parse()?));
+        }
+        if name == "import-state" {
+            // The JSON may contain spaces, so take the rest of the line as is
+            let json = s.trim_start()[name.len()..].trim();
+            let state = serde_json::from_str(json).context("Invalid state JSON")?;
+            return Ok(IpcCommand::ImportState(state));
+        }
+
+        let command = match name {
+            "show" | "show-prev" => {
+                let mut what = None;
+                let mut set = None;
+                let mut skip = 0;
+                while let Some(arg) = parts.next() {
+                    if arg == "--set" {
+                        let name = parts.next().context("--set needs a workspace set name")?;
+                        set = Some(name.to_string());
+                    } else if arg == "--skip" {
+                        let count = parts.next().context("--skip needs a count")?;
+                        skip = count.parse().context("Invalid skip count")?;
+                    } else if what.is_none() {
+                        what = Some(
+                            SwitchTarget::from_str(arg, false)
+                                .map_err(|_| anyhow::anyhow!("Unknown switch target '{}'", arg))?,
+                        );
+                    } else {
+                        anyhow::bail!("Unexpected argument for {}: {}", name, arg);
+                    }
+                }
+                let what = what.unwrap_or_default();
+                if name == "show" {
+                    IpcCommand::Show { what, set, skip }
+                } else {
+                    IpcCommand::ShowPrev { what, set, skip }
+                }
+            }
+            "current" => IpcCommand::Current,
+            "windows" => IpcCommand::Windows,
+            "skipped" => IpcCommand::Skipped,
+            "rules" => IpcCommand::Rules,
+            "show-same-app" => IpcCommand::ShowSameApp,
+            "watch" => IpcCommand::Watch,
+            "export-state" => IpcCommand::ExportState,
+            "shutdown" => IpcCommand::Shutdown,
+            "status" => IpcCommand::Status,
+            "pause" => IpcCommand::Pause,
+            "resume" => IpcCommand::Resume,
+            "reload" => IpcCommand::Reload,
+            "debug-resources" => IpcCommand::DebugResources,
+            "export-layout" => {
+                let format = match parts.next() {
+                    Some(arg) => LayoutFormat::from_str(arg, false)
+                        .map_err(|_| anyhow::anyhow!("Unknown layout format '{}'", arg))?,
+                    None => LayoutFormat::default(),
+                };
+                IpcCommand::ExportLayout { format }
+            }
+            "set-profile" => IpcCommand::SetProfile {
+                name: parts.next().map(str::to_string),
+            },
+            "focus-nth" => {
+                let n = parts.next().context("focus-nth needs a number")?;
+                let n: usize = n
+                    .parse()
+                    .with_context(|| format!("Invalid window number: {}", n))?;
+                if n == 0 {
+                    anyhow::bail!("focus-nth counts from 1, the previously used window");
+                }
+                IpcCommand::FocusNth { n }
+            }
+            "history" => {
+                let count = match parts.next() {
+                    Some(n) => n
+                        .parse()
+                        .with_context(|| format!("Invalid history count: {}", n))?,
+                    None => DEFAULT_HISTORY_COUNT,
+                };
+                IpcCommand::History { count }
+            }
+            _ => anyhow::bail!("Unknown command: {}", name),
+        };
+
+        if let Some(extra) = parts.next() {
+            anyhow::bail!("Unexpected argument for {}: {}", name, extra);
+        }
+
+        Ok(command)
+    }
+}
+
+impl IpcCommand {
+    /// Whether the command comes from a key the user pressed (a show
+    /// binding, or a frontend's input), so it goes ahead of background work
+    #[must_use]
+    pub fn is_interactive(&self) -> bool {
+        matches!(
+            self,
+            IpcCommand::Show { .. }
+                | IpcCommand::ShowPrev { .. }
+                | IpcCommand::ShowSameApp
+                | IpcCommand::Input(_)
+        )
+    }
+}
+
+/// Write a `show` or `show-prev` command, leaving out default options
+fn write_show(
+    f: &mut fmt::Formatter<'_>,
+    name: &str,
+    what: SwitchTarget,
+    set: &Option<String>,
+    skip: usize,
+) -> fmt::Result {
+    write!(f, "{} {}", name, what.as_str())?;
+    if let Some(set) = set {
+        write!(f, " --set {}", set)?;
+    }
+    if skip > 0 {
+        write!(f, " --skip {}", skip)?;
+    }
+    Ok(())
+}
+
+impl fmt::Display for IpcCommand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IpcCommand::Show { what, set, skip } => write_show(f, "show", *what, set, *skip),
+            IpcCommand::ShowPrev { what, set, skip } => {
+                write_show(f, "show-prev", *what, set, *skip)
+            }
+            IpcCommand::History { count } => write!(f, "history {}", count),
+            IpcCommand::Current => write!(f, "current"),
+            IpcCommand::FocusNth { n } => write!(f, "focus-nth {}", n),
+            IpcCommand::Windows => write!(f, "windows"),
+            IpcCommand::Skipped => write!(f, "skipped"),
+            IpcCommand::Rules => write!(f, "rules"),
+            IpcCommand::ShowSameApp => write!(f, "show-same-app"),
+            IpcCommand::Watch => write!(f, "watch"),
+            IpcCommand::Input(input) => write!(f, "input {}", input),
+            IpcCommand::ExportState => write!(f, "export-state"),
+            IpcCommand::Shutdown => write!(f, "shutdown"),
+            IpcCommand::Status => write!(f, "status"),
+            IpcCommand::Pause => write!(f, "pause"),
+            IpcCommand::Resume => write!(f, "resume"),
+            IpcCommand::Reload => write!(f, "reload"),
+            IpcCommand::DebugResources => write!(f, "debug-resources"),
+            IpcCommand::ExportLayout { format } => write!(f, "export-layout {}", format.as_str()),
+            IpcCommand::ImportState(state) => {
+                let json = serde_json::to_string(state).map_err(|_| fmt::Error)?;
+                write!(f, "import-state {}", json)
+            }
+            IpcCommand::SetProfile { name: None } => write!(f, "set-profile"),
+            IpcCommand::SetProfile { name: Some(name) } => write!(f, "set-profile {}", name),
+        }
+    }
+}
+
+/// Get the path to the daemon's Unix socket
+pub fn get_socket_path() -> Result<PathBuf> {
+    // Try to use XDG_RUNTIME_DIR, fall back to ~/.cache
+    let runtime_dir = dirs::runtime_dir()
+        .or_else(dirs::cache_dir)
+        .context("Could not determine runtime directory")?;
+
+    Ok(runtime_dir.join("sway-alttab-gui.sock"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_show() {
+        assert_eq!(
+            "show".parse::<IpcCommand>().unwrap(),
+            IpcCommand::Show {
+                what: SwitchTarget::Windows,
+                set: None,
+                skip: 0,
+            }
+        );
+        assert_eq!(
+            "show workspaces".parse::<IpcCommand>().unwrap(),
+            IpcCommand::Show {
+                what: SwitchTarget::Workspaces,
+                set: None,
+                skip: 0,
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_show_set() {
+        assert_eq!(
+            "show --set coding".parse::<IpcCommand>().unwrap(),
+            IpcCommand::Show {
+                what: SwitchTarget::Windows,
+                set: Some("coding".to_string()),
+                skip: 0,
+            }
+        );
+        assert_eq!(
+            "show apps --set coding".parse::<IpcCommand>().unwrap(),
+            IpcCommand::Show {
+                what: SwitchTarget::Apps,
+                set: Some("coding".to_string()),
+                skip: 0,
+            }
+        );
+        assert!("show --set".parse::<IpcCommand>().is_err());
+        assert!("show apps windows".parse::<IpcCommand>().is_err());
+    }
+
+    #[test]
+    fn test_parse_show_skip() {
+        assert_eq!(
+            "show --skip 2".parse::<IpcCommand>().unwrap(),
+            IpcCommand::Show {
+                what: SwitchTarget::Windows,
+                set: None,
+                skip: 2,
+            }
+        );
+        assert!("show --skip".parse::<IpcCommand>().is_err());
+        assert!("show --skip -1".parse::<IpcCommand>().is_err());
+    }
+
+    #[test]
+    fn test_parse_show_prev() {
+        assert_eq!(
+            "show-prev".parse::<IpcCommand>().unwrap(),
+            IpcCommand::ShowPrev {
+                what: SwitchTarget::Windows,
+                set: None,
+                skip: 0,
+            }
+        );
+        assert_eq!(
+            "show-prev apps --set coding".parse::<IpcCommand>().unwrap(),
+            IpcCommand::ShowPrev {
+                what: SwitchTarget::Apps,
+                set: Some("coding".to_string()),
+                skip: 0,
+            }
+        );
+        assert!("show-prev apps windows".parse::<IpcCommand>().is_err());
+    }
+
+    #[test]
+    fn test_parse_show_same_app() {
+        assert_eq!(
+            "show-same-app".parse::<IpcCommand>().unwrap(),
+            IpcCommand::ShowSameApp
+        );
+        assert!("show-same-app apps".parse::<IpcCommand>().is_err());
+    }
+
+    #[test]
+    fn test_parse_current() {
+        assert_eq!("current".parse::<IpcCommand>().unwrap(), IpcCommand::Current);
+    }
+
+    #[test]
+    fn test_parse_history_default_count() {
+        assert_eq!(
+            "history".parse::<IpcCommand>().unwrap(),
+            IpcCommand::History {
+                count: DEFAULT_HISTORY_COUNT
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_history_with_count() {
+        assert_eq!(
+            "history 3\n".parse::<IpcCommand>().unwrap(),
+            IpcCommand::History { count: 3 }
+        );
+    }
+
+    #[test]
+    fn test_parse_focus_nth() {
+        assert_eq!(
+            "focus-nth 2".parse::<IpcCommand>().unwrap(),
+            IpcCommand::FocusNth { n: 2 }
+        );
+        assert!("focus-nth".parse::<IpcCommand>().is_err());
+        assert!("focus-nth 0".parse::<IpcCommand>().is_err());
+        assert!("focus-nth x".parse::<IpcCommand>().is_err());
+    }
+
+    #[test]
+    fn test_parse_set_profile() {
+        assert_eq!(
+            "set-profile work".parse::<IpcCommand>().unwrap(),
+            IpcCommand::SetProfile {
+                name: Some("work".to_string())
+            }
+        );
+        assert_eq!(
+            "set-profile".parse::<IpcCommand>().unwrap(),
+            IpcCommand::SetProfile { name: None }
+        );
+        assert!("set-profile a b".parse::<IpcCommand>().is_err());
+    }
+
+    #[test]
+    fn test_parse_input_commands() {
+        assert_eq!(
+            "input next".parse::<IpcCommand>().unwrap(),
+            IpcCommand::Input(InputCommand::Next)
+        );
+        assert_eq!(
+            "jump-back 3".parse::<InputCommand>().unwrap(),
+            InputCommand::JumpBack(3)
+        );
+        assert_eq!("shown".parse::<InputCommand>().unwrap(), InputCommand::Shown);
+        assert!("input".parse::<IpcCommand>().is_err());
+        assert!("input bogus".parse::<IpcCommand>().is_err());
+        assert!("jump-back".parse::<InputCommand>().is_err());
+        assert!("custom x".parse::<InputCommand>().is_err());
+        assert!("next now".parse::<InputCommand>().is_err());
+        assert!("tile 1".parse::<InputCommand>().is_err());
+        assert!("tile 1 minimize".parse::<InputCommand>().is_err());
+        assert!("move 1".parse::<InputCommand>().is_err());
+        assert!("select-index".parse::<InputCommand>().is_err());
+        assert!("select-index -1".parse::<InputCommand>().is_err());
+        assert_eq!(
+            "arrow down".parse::<InputCommand>().unwrap(),
+            InputCommand::Arrow(Direction::Down)
+        );
+        assert!("arrow".parse::<InputCommand>().is_err());
+        assert!("arrow sideways".parse::<InputCommand>().is_err());
+    }
+
+    #[test]
+    fn test_parse_rejects_invalid_input() {
+        assert!("".parse::<IpcCommand>().is_err());
+        assert!("bogus".parse::<IpcCommand>().is_err());
+        assert!("history abc".parse::<IpcCommand>().is_err());
+        assert!("history 1 2".parse::<IpcCommand>().is_err());
+        assert!("show now".parse::<IpcCommand>().is_err());
+        assert!("show apps now".parse::<IpcCommand>().is_err());
+    }
+
+    #[test]
+    fn test_display_round_trip() {
+        for command in [
+            IpcCommand::Show {
+                what: SwitchTarget::Apps,
+                set: None,
+                skip: 0,
+            },
+            IpcCommand::Show {
+                what: SwitchTarget::Windows,
+                set: Some("coding".to_string()),
+                skip: 0,
+            },
+            IpcCommand::ShowPrev {
+                what: SwitchTarget::Workspaces,
+                set: None,
+                skip: 0,
+            },
+            IpcCommand::ShowPrev {
+                what: SwitchTarget::Windows,
+                set: Some("coding".to_string()),
+                skip: 0,
+            },
+            IpcCommand::Show {
+                what: SwitchTarget::Windows,
+                set: Some("coding".to_string()),
+                skip: 2,
+            },
+            IpcCommand::History { count: 7 },
+            IpcCommand::Current,
+            IpcCommand::FocusNth { n: 3 },
+            IpcCommand::Windows,
+            IpcCommand::Skipped,
+            IpcCommand::Rules,
+            IpcCommand::ShowSameApp,
+            IpcCommand::Watch,
+            IpcCommand::Input(InputCommand::Next),
+            IpcCommand::Input(InputCommand::JumpBack(2)),
+            IpcCommand::Input(InputCommand::SelectIndex(4)),
+            IpcCommand::Input(InputCommand::Arrow(Direction::Left)),
+            IpcCommand::Input(InputCommand::Custom(1)),
+            IpcCommand::Input(InputCommand::Screenshot),
+            IpcCommand::Input(InputCommand::ToggleIdle),
+            IpcCommand::Input(InputCommand::Key('g')),
+            IpcCommand::Input(InputCommand::Tile {
+                index: 2,
+                action: TileAction::MoveHere,
+            }),
+            IpcCommand::Input(InputCommand::Move { from: 3, to: 0 }),
+            IpcCommand::Input(InputCommand::Focus(94)),
+            IpcCommand::Input(InputCommand::Resync),
+            IpcCommand::SetProfile { name: None },
+            IpcCommand::SetProfile {
+                name: Some("work".to_string()),
+            },
+            IpcCommand::ExportState,
+            IpcCommand::Shutdown,
+            IpcCommand::Status,
+            IpcCommand::Pause,
+            IpcCommand::Resume,
+            IpcCommand::Reload,
+            IpcCommand::DebugResources,
+            IpcCommand::ExportLayout {
+                format: LayoutFormat::Script,
+            },
+            IpcCommand::ImportState(DaemonState {
+                version: STATE_VERSION,
+                mru: vec![3, 1, 2],
+                history: vec![FocusEntry {
+                    window_id: 3,
+                    app_id: Some("foot".to_string()),
+                    title: "a title with spaces".to_string(),
+                    focused_at: std::time::UNIX_EPOCH + std::time::Duration::from_millis(1500),
+                }],
+                profile: Some("work".to_string()),
+            }),
+        ] {
+            assert_eq!(command.to_string().parse::<IpcCommand>().unwrap(), command);
+        }
+    }
+
+    #[test]
+    fn test_layout_script() {
+        let entry = |app_id: &str, workspace: &str, exec: Option<&str>| LayoutEntry {
+            app_id: Some(app_id.to_string()),
+            title: String::new(),
+            workspace: workspace.to_string(),
+            output: "DP-1".to_string(),
+            geometry: None,
+            exec: exec.map(str::to_string),
+        };
+        let script = layout_script(&[
+            entry("foot", "1", Some("foot")),
+            entry("firefox", "web's", Some("firefox --new-window")),
+            entry("mystery", "3", None),
+        ]);
+        let lines: Vec<&str> = script.lines().collect();
+        assert_eq!(lines[0], "#!/bin/sh");
+        assert_eq!(lines[2], r#"swaymsg 'workspace "1"; exec foot'"#);
+        assert_eq!(
+            lines[3],
+            r#"swaymsg 'workspace "web'\''s"; exec firefox --new-window'"#
+        );
+        assert_eq!(lines[4], "# mystery on workspace 3: no desktop file found");
+    }
+
+    #[test]
+    fn test_status_is_healthy() {
+        assert!(status_is_healthy("healthy=true\nstate=idle\n"));
+        assert!(!status_is_healthy("healthy=false\nsway=unreachable\n"));
+        assert!(!status_is_healthy(""));
+    }
+
+    #[test]
+    fn test_switcher_event_json() {
+        let window = WindowInfo {
+            id: 7,
+            app_id: Some("foot".to_string()),
+            title: "~".to_string(),
+            workspace: "1".to_string(),
+            output: "DP-1".to_string(),
+            window_class: None,
+            window_instance: None,
+            label: None,
+            last_focused: None,
+            ..Default::default()
+        };
+        let event = SwitcherEvent::Show {
+            windows: vec![window],
+            selected: 0,
+            target: SwitchTarget::Apps,
+        };
+        let json = serde_json::to_string(&event).unwrap();
+        assert!(json.starts_with(r#"{"event":"show","windows":[{"id":7,"app_id":"foot""#));
+        assert!(json.ends_with(r#""selected":0,"target":"apps"}"#));
+        assert_eq!(serde_json::from_str::<SwitcherEvent>(&json).unwrap(), event);
+
+        assert_eq!(
+            serde_json::to_string(&SwitcherEvent::Hello { version: 1 }).unwrap(),
+            r#"{"event":"hello","version":1}"#
+        );
+        assert_eq!(
+            serde_json::to_string(&SwitcherEvent::Update { selected: 2 }).unwrap(),
+            r#"{"event":"update","selected":2}"#
+        );
+        assert_eq!(
+            serde_json::to_string(&SwitcherEvent::Hide).unwrap(),
+            r#"{"event":"hide"}"#
+        );
+    }
 }
@@ -0,0 +1,178 @@
+//! Two-key sequences typed in the switcher (e.g. `g` then `w`) that change
+//! how the open list is ordered without closing it.
+
+use crate::window_manager::WindowInfo;
+use std::collections::HashMap;
+
+/// Keys that start a sequence instead of acting on their own
+const LEADER_KEYS: [char; 2] = ['g', 's'];
+
+/// How the switcher's list is ordered
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ListOrder {
+    /// Most recently used first
+    #[default]
+    Mru,
+    /// Windows of a workspace together, the most recently used workspace first
+    Workspace,
+    /// Windows of an output together, the most recently used output first
+    Output,
+    /// Windows of an application together, the most recently used
+    /// application first
+    App,
+    /// By application, then title, ignoring case
+    Alphabetical,
+}
+
+/// The order a leader key followed by `key` selects, if any
+#[must_use]
+pub fn sequence_order(leader: char, key: char) -> Option<ListOrder> {
+    match (leader, key) {
+        ('g', 'w') => Some(ListOrder::Workspace),
+        ('g', 'o') => Some(ListOrder::Output),
+        ('g', 'a') => Some(ListOrder::App),
+        ('s', 'a') => Some(ListOrder::Alphabetical),
+        ('s', 'm') => Some(ListOrder::Mru),
+        _ => None,
+    }
+}
+
+/// What a key press means to the sequence state machine
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyOutcome {
+    /// A leader key; waiting for the second key
+    Pending,
+    /// A sequence was completed
+    Order(ListOrder),
+    /// A leader followed by a key that completes no sequence
+    Unknown,
+    /// Not part of a sequence; the key acts on its own
+    Single(char),
+}
+
+/// Remembers a typed leader key until the next key press
+#[derive(Debug, Default)]
+pub struct KeySequence {
+    leader: Option<char>,
+}
+
+impl KeySequence {
+    /// Feed a key press through the state machine
+    pub fn press(&mut self, key: char) -> KeyOutcome {
+        match self.leader.take() {
+            Some(leader) => match sequence_order(leader, key) {
+                Some(order) => KeyOutcome::Order(order),
+                None => KeyOutcome::Unknown,
+            },
+            None if LEADER_KEYS.contains(&key) => {
+                self.leader = Some(key);
+                KeyOutcome::Pending
+            }
+            None => KeyOutcome::Single(key),
+        }
+    }
+
+    /// Forget a typed leader key, e.g. when another command comes in
+    pub fn reset(&mut self) {
+        self.leader = None;
+    }
+}
+
+/// Stable-sort `windows` into `order`. `mru` is every window ID, most
+/// recently used first; windows missing from it go last.
+pub fn apply_order(windows: &mut [WindowInfo], order: ListOrder, mru: &[i64]) {
+    let rank: HashMap<i64, usize> = mru.iter().enumerate().map(|(i, &id)| (id, i)).collect();
+    let rank_of = |w: &WindowInfo| rank.get(&w.id).copied().unwrap_or(usize::MAX);
+    windows.sort_by_key(rank_of);
+
+    // Groups are ranked by their most recently used window
+    let group_by = |windows: &mut [WindowInfo], key: fn(&WindowInfo) -> String| {
+        let mut group_rank: HashMap<String, usize> = HashMap::new();
+        for (i, window) in windows.iter().enumerate() {
+            group_rank.entry(key(window)).or_insert(i);
+        }
+        windows.sort_by_key(|w| group_rank[&key(w)]);
+    };
+    match order {
+        ListOrder::Mru => {}
+        ListOrder::Workspace => group_by(windows, |w| w.workspace.clone()),
+        ListOrder::Output => group_by(windows, |w| w.output.clone()),
+        ListOrder::App => group_by(windows, |w| w.app_key().unwrap_or_default().to_string()),
+        ListOrder::Alphabetical => windows.sort_by_key(|w| {
+            (
+                w.app_key().unwrap_or_default().to_lowercase(),
+                w.title.to_lowercase(),
+            )
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_window(id: i64, app_id: &str, workspace: &str) -> WindowInfo {
+        WindowInfo {
+            id,
+            app_id: Some(app_id.to_string()),
+            title: format!("Window {}", id),
+            workspace: workspace.to_string(),
+            output: "DP-1".to_string(),
+            window_class: None,
+            window_instance: None,
+            label: None,
+            last_focused: None,
+            ..Default::default()
+        }
+    }
+
+    fn ids(windows: &[WindowInfo]) -> Vec<i64> {
+        windows.iter().map(|w| w.id).collect()
+    }
+
+    #[test]
+    fn test_key_sequence_completes_after_leader() {
+        let mut keys = KeySequence::default();
+        assert_eq!(keys.press('g'), KeyOutcome::Pending);
+        assert_eq!(keys.press('w'), KeyOutcome::Order(ListOrder::Workspace));
+        assert_eq!(keys.press('a'), KeyOutcome::Single('a'));
+    }
+
+    #[test]
+    fn test_key_sequence_unknown_and_reset() {
+        let mut keys = KeySequence::default();
+        keys.press('s');
+        assert_eq!(keys.press('x'), KeyOutcome::Unknown);
+        assert_eq!(keys.press('x'), KeyOutcome::Single('x'));
+
+        keys.press('g');
+        keys.reset();
+        assert_eq!(keys.press('w'), KeyOutcome::Single('w'));
+    }
+
+    #[test]
+    fn test_apply_order_groups_by_most_recent_workspace() {
+        let mut windows = vec![
+            make_window(1, "foot", "2"),
+            make_window(2, "firefox", "1"),
+            make_window(3, "foot", "2"),
+            make_window(4, "code", "1"),
+        ];
+        apply_order(&mut windows, ListOrder::Workspace, &[1, 2, 3, 4]);
+        assert_eq!(ids(&windows), vec![1, 3, 2, 4]);
+
+        apply_order(&mut windows, ListOrder::Mru, &[1, 2, 3, 4]);
+        assert_eq!(ids(&windows), vec![1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn test_apply_order_alphabetical() {
+        let mut windows = vec![
+            make_window(1, "foot", "1"),
+            make_window(2, "Firefox", "1"),
+            make_window(3, "code", "1"),
+        ];
+        apply_order(&mut windows, ListOrder::Alphabetical, &[1, 2, 3]);
+        assert_eq!(ids(&windows), vec![3, 2, 1]);
+    }
+}
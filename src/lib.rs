@@ -0,0 +1,32 @@
+//! Library half of sway-alttab-gui: everything but the command line entry
+//! point, so the fuzz targets can reach the parsers.
+
+pub mod capabilities;
+pub mod compositor;
+pub mod config;
+pub mod daemon;
+pub mod dock;
+pub mod event_bus;
+pub mod focus_history;
+pub mod icon_resolver;
+pub mod ipc;
+pub mod key_sequence;
+pub mod notify;
+pub mod pins;
+pub mod rules;
+pub mod selection_log;
+pub mod session;
+pub mod setup_check;
+pub mod socket_client;
+pub mod socket_server;
+pub mod sway_client;
+pub mod taskbar;
+#[cfg(feature = "tray")]
+pub mod tray;
+#[cfg(feature = "tui")]
+pub mod tui;
+pub mod ui;
+pub mod ui_commands;
+pub mod ui_handler;
+pub mod window_manager;
+pub mod window_switcher;
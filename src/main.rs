@@ -1,26 +1,36 @@
-mod config;
-mod daemon;
-mod icon_resolver;
-mod ipc;
-mod sway_client;
-mod ui;
-mod ui_commands;
-mod ui_handler;
-mod window_manager;
-mod window_switcher;
-
 use anyhow::{Context, Result};
-use config::{Command, Config};
+use config::{label_captures, Command, Config, FileConfig, Frontend, LabelRule, SwitchTarget};
 use daemon::Daemon;
+use dock::DockWindow;
+use event_bus::{EventReceiver, EventSender};
 use gtk4::prelude::*;
 use icon_resolver::{IconResolver, WmClassIndex};
+use ipc::{DaemonState, InputCommand, IpcCommand};
+use nix::sys::signal::Signal;
+use rules::RuleEngine;
 use std::cell::RefCell;
 use std::fs;
-use std::path::PathBuf;
+use std::ops::ControlFlow;
+use std::path::{Path, PathBuf};
 use std::rc::Rc;
+use std::time::{Duration, Instant};
+#[cfg(feature = "tui")]
+use sway_alttab_gui::tui;
+use sway_alttab_gui::{
+    capabilities, config, daemon, dock, event_bus, icon_resolver, ipc, rules, setup_check,
+    socket_client, taskbar, ui, ui_commands, ui_handler, window_manager,
+};
+use taskbar::TaskbarWindow;
 use tokio::sync::mpsc;
-use tracing::{error, info};
+use tracing::{error, info, warn};
 use ui::SwitcherWindow;
+use window_manager::{WindowInfo, WindowManager};
+
+/// How long `replace` waits for the running daemon to exit
+const REPLACE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How often `replace` checks whether the running daemon has exited
+const REPLACE_POLL_INTERVAL: Duration = Duration::from_millis(50);
 
 /// Get the path to the pidfile
 fn get_pidfile_path() -> Result<PathBuf> {
@@ -106,9 +116,62 @@ impl Drop for PidfileGuard {
     }
 }
 
+/// Get the default log file path for `--daemonize`
+fn get_log_path() -> Result<PathBuf> {
+    let runtime_dir = dirs::runtime_dir()
+        .or_else(dirs::cache_dir)
+        .context("Could not determine runtime directory")?;
+
+    Ok(runtime_dir.join("sway-alttab-gui.log"))
+}
+
+/// Fork into the background and detach from the terminal.
+///
+/// The parent exits; the child starts a new session and sends stdout and
+/// stderr (and so the log) to `log_file`. Must run before any threads are
+/// started, so before GTK and the Tokio runtime.
+fn daemonize(log_file: &Path) -> Result<()> {
+    use nix::unistd::{dup2, fork, setsid, ForkResult};
+    use std::os::fd::AsRawFd;
+
+    // Fail in the terminal rather than in the log
+    check_pidfile()?;
+    let log = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(log_file)
+        .with_context(|| format!("Failed to open log file {}", log_file.display()))?;
+    let null = fs::File::open("/dev/null").context("Failed to open /dev/null")?;
+
+    // SAFETY: no other threads exist yet, so the child can't inherit a lock
+    // held by a thread that doesn't exist in it
+    match unsafe { fork() }.context("Failed to fork")? {
+        ForkResult::Parent { child } => {
+            println!("Daemon started (PID {}), logging to {}", child, log_file.display());
+            std::process::exit(0);
+        }
+        ForkResult::Child => {}
+    }
+
+    setsid().context("Failed to start a new session")?;
+    dup2(null.as_raw_fd(), 0).context("Failed to redirect stdin")?;
+    dup2(log.as_raw_fd(), 1).context("Failed to redirect stdout")?;
+    dup2(log.as_raw_fd(), 2).context("Failed to redirect stderr")?;
+    info!("Daemonized with PID {}", std::process::id());
+
+    Ok(())
+}
+
 fn main() -> Result<()> {
     // Parse CLI arguments
-    let config = Config::parse();
+    let mut config = Config::parse();
+
+    // Commands for a running daemon only write to its socket or signal it;
+    // they exit before any logging, config, GTK, icon or Tokio setup
+    let command = match run_client(config.command()) {
+        ControlFlow::Break(result) => return result,
+        ControlFlow::Continue(command) => command,
+    };
 
     // Initialize logging
     let log_level = if config.verbose {
@@ -117,21 +180,295 @@ fn main() -> Result<()> {
         tracing::Level::INFO
     };
 
-    tracing_subscriber::fmt()
+    // `replace` starts a daemon like `daemon` does, once the old one is gone
+    let starts_daemon = matches!(command, LocalCommand::Daemon { .. });
+
+    let logging = tracing_subscriber::fmt()
         .with_max_level(log_level)
         .with_target(false)
-        .init();
+        // A daemonized daemon logs to a file
+        .with_ansi(!config.daemonize);
+    if starts_daemon && config.frontend == Frontend::Tui {
+        // Log lines would draw over the terminal frontend
+        logging.with_writer(std::io::sink).init();
+    } else {
+        logging.init();
+    }
+
+    // Only the daemon (and `once`, which works like it) and `match` read the config file
+    if starts_daemon || matches!(command, LocalCommand::Once | LocalCommand::Match { .. }) {
+        config.load_file()?;
+        if let Some(ref name) = config.profile {
+            config.profile(name)?;
+        }
+    }
+
+    // Help a first setup along; after daemonizing so the hints reach the log
+    let first_start = starts_daemon
+        && config.config.is_none()
+        && FileConfig::default_path().is_some_and(|path| !path.exists());
+
+    if starts_daemon && config.tray && !cfg!(feature = "tray") {
+        anyhow::bail!("--tray needs sway-alttab-gui to be built with the `tray` feature");
+    }
+
+    if starts_daemon && config.daemonize && config.frontend == Frontend::Tui {
+        anyhow::bail!("--daemonize can't be used with --frontend tui");
+    }
+
+    // Take the running daemon's state before it stops; it has to be gone
+    // before the pidfile check
+    let state = match command {
+        LocalCommand::Daemon { replace: true } => take_over()?,
+        _ => None,
+    };
+
+    if starts_daemon && config.daemonize {
+        let log_file = match config.log_file {
+            Some(ref path) => path.clone(),
+            None => get_log_path()?,
+        };
+        daemonize(&log_file)?;
+    }
+
+    if first_start {
+        setup_check::log_first_start_hints();
+    }
 
     // Dispatch based on command
-    match config.command() {
-        Command::Daemon => run_daemon_mode(config),
-        Command::Show => send_show_signal(),
+    match command {
+        LocalCommand::Daemon { .. } => match config.frontend {
+            Frontend::Gtk => run_daemon_mode(config, state),
+            Frontend::Tui => run_tui_mode(config, state),
+            Frontend::None => run_headless_mode(config, state),
+        },
+        LocalCommand::Once => run_once_mode(config),
+        LocalCommand::CheckConfig => check_config(&config),
+        LocalCommand::Match { criteria } => print_matches(&config, criteria.as_deref()),
     }
 }
 
-/// Send SIGUSR1 to the running daemon to trigger the window switcher
-fn send_show_signal() -> Result<()> {
-    use nix::sys::signal::{kill, Signal};
+/// A command that needs more than the socket and the pidfile
+enum LocalCommand {
+    /// `daemon`, or `replace` taking over from a running one
+    Daemon { replace: bool },
+    Once,
+    CheckConfig,
+    Match { criteria: Option<String> },
+}
+
+/// Run a command that only talks to a running daemon, or hand back the
+/// commands that need more than the socket and the pidfile
+fn run_client(command: Command) -> ControlFlow<Result<()>, LocalCommand> {
+    let result = match command {
+        Command::Show {
+            what: SwitchTarget::Windows,
+            set: None,
+            reverse: false,
+            skip: 0,
+        } => send_show_signal(Signal::SIGUSR1),
+        Command::Show {
+            what,
+            set,
+            reverse: false,
+            skip,
+        } => send_ipc_command(IpcCommand::Show { what, set, skip }),
+        Command::Show {
+            what, set, skip, ..
+        } => send_ipc_command(IpcCommand::ShowPrev { what, set, skip }),
+        Command::ShowSameApp => send_show_signal(Signal::SIGUSR2),
+        Command::Next => send_input(InputCommand::Next),
+        Command::Prev => send_input(InputCommand::Prev),
+        Command::Select => send_input(InputCommand::Select),
+        Command::Cancel => send_input(InputCommand::Cancel),
+        Command::History { count } => send_ipc_command(IpcCommand::History { count }),
+        Command::Current => send_ipc_command(IpcCommand::Current),
+        Command::FocusNth { n } => send_ipc_command(IpcCommand::FocusNth { n }),
+        Command::SetProfile { name } => send_ipc_command(IpcCommand::SetProfile { name }),
+        Command::Windows => send_ipc_command(IpcCommand::Windows),
+        Command::Skipped => send_ipc_command(IpcCommand::Skipped),
+        Command::Rules => send_ipc_command(IpcCommand::Rules),
+        Command::Shutdown => send_ipc_command(IpcCommand::Shutdown),
+        Command::Pause => send_ipc_command(IpcCommand::Pause),
+        Command::Resume => send_ipc_command(IpcCommand::Resume),
+        Command::Reload => send_ipc_command(IpcCommand::Reload),
+        Command::DebugResources => send_ipc_command(IpcCommand::DebugResources),
+        Command::Status => send_ipc_command(IpcCommand::Status),
+        Command::Health => check_health(),
+        Command::ExportState => send_ipc_command(IpcCommand::ExportState),
+        Command::ExportLayout { format } => send_ipc_command(IpcCommand::ExportLayout { format }),
+        Command::ImportState { file } => import_state(file.as_deref()),
+        Command::SwayRules => {
+            print!("{}", ui::sway_rules());
+            Ok(())
+        }
+        Command::Watch => socket_client::stream_command(&IpcCommand::Watch, |line| {
+            println!("{}", line);
+        }),
+        Command::Daemon => return ControlFlow::Continue(LocalCommand::Daemon { replace: false }),
+        Command::Replace => return ControlFlow::Continue(LocalCommand::Daemon { replace: true }),
+        Command::Once => return ControlFlow::Continue(LocalCommand::Once),
+        Command::CheckConfig => return ControlFlow::Continue(LocalCommand::CheckConfig),
+        Command::Match { criteria } => {
+            return ControlFlow::Continue(LocalCommand::Match { criteria });
+        }
+    };
+    ControlFlow::Break(result)
+}
+
+/// Send a state export, read from `file` or stdin, back to the daemon
+fn import_state(file: Option<&Path>) -> Result<()> {
+    let json = match file {
+        Some(path) => std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?,
+        None => std::io::read_to_string(std::io::stdin())?,
+    };
+    let state = serde_json::from_str(&json).context("Invalid state JSON")?;
+    send_ipc_command(IpcCommand::ImportState(state))
+}
+
+/// Send a command to the daemon over its socket and print the reply
+fn send_ipc_command(command: IpcCommand) -> Result<()> {
+    let reply = socket_client::send_command(&command)?;
+    print!("{}", reply);
+    Ok(())
+}
+
+/// Act on the daemon's switcher as if a key was pressed in it
+fn send_input(input: InputCommand) -> Result<()> {
+    send_ipc_command(IpcCommand::Input(input))
+}
+
+/// Print the daemon's status and exit 0 if it is healthy, 1 if it can't be
+/// reached and 2 if it reports a problem
+fn check_health() -> Result<()> {
+    let reply = match socket_client::send_command(&IpcCommand::Status) {
+        Ok(reply) => reply,
+        Err(e) => {
+            eprintln!("Daemon unreachable: {:#}", e);
+            std::process::exit(1);
+        }
+    };
+    print!("{}", reply);
+    if !ipc::status_is_healthy(&reply) {
+        std::process::exit(2);
+    }
+    Ok(())
+}
+
+/// Parse the config file and print errors and warnings with line numbers.
+/// Fails if the daemon would refuse to start with it.
+fn check_config(config: &Config) -> Result<()> {
+    let Some(path) = config.config.clone().or_else(FileConfig::default_path) else {
+        anyhow::bail!("Can't find the config directory");
+    };
+    if !path.exists() {
+        println!("{}: not found, the defaults are used", path.display());
+        return Ok(());
+    }
+
+    let content = fs::read_to_string(&path)
+        .with_context(|| format!("Failed to read config file {}", path.display()))?;
+    let file = FileConfig::from_toml(&content)
+        .map_err(|e| anyhow::anyhow!("{}: {:#}", path.display(), e))?;
+    if let Some(ref name) = config.profile
+        && !file.profiles.contains_key(name)
+    {
+        anyhow::bail!("{}: --profile '{}' is not defined", path.display(), name);
+    }
+
+    match swayipc::Connection::new().and_then(|mut sway| sway.get_workspaces()) {
+        Ok(workspaces) => {
+            let names: Vec<String> = workspaces.into_iter().map(|ws| ws.name).collect();
+            for warning in file.warnings(&content, &names) {
+                println!("{}: warning: {}", path.display(), warning);
+            }
+        }
+        Err(e) => println!("Skipping checks against sway: {}", e),
+    }
+    println!("{}: OK", path.display());
+    Ok(())
+}
+
+/// Print the live windows captured by each label rule, or by `criteria`
+fn print_matches(config: &Config, criteria: Option<&str>) -> Result<()> {
+    let mut window_manager = WindowManager::new(config.window_detection)?;
+    window_manager.refresh()?;
+    let windows = window_manager.windows();
+
+    let rules = match criteria {
+        Some(criteria) => vec![criteria.parse::<LabelRule>()?],
+        None => config.file.labels.clone(),
+    };
+    if rules.is_empty() {
+        println!("No [[labels]] rules in the config file");
+    }
+    for (rule, captured) in rules.iter().zip(label_captures(windows, &rules)) {
+        if criteria.is_none() {
+            println!("label \"{}\"", rule.label);
+        }
+        if captured.is_empty() {
+            println!("  (no windows)");
+        }
+        for window in captured {
+            println!("  {}", describe_window(window));
+        }
+    }
+
+    let marked: Vec<_> = windows.iter().filter(|w| w.label.is_some()).collect();
+    if !marked.is_empty() {
+        println!("labelled by a mark (rules don't apply):");
+        for window in marked {
+            println!("  {}", describe_window(window));
+        }
+    }
+    Ok(())
+}
+
+/// One window as `[id] app - title`
+fn describe_window(window: &WindowInfo) -> String {
+    format!(
+        "[{}] {} - {}",
+        window.id,
+        window.app_key().unwrap_or("<unknown>"),
+        window.title
+    )
+}
+
+/// For `replace`: export the running daemon's state, shut it down and
+/// wait for it to exit. None if no daemon answers.
+fn take_over() -> Result<Option<DaemonState>> {
+    let reply = match socket_client::send_command(&IpcCommand::ExportState) {
+        Ok(reply) => reply,
+        Err(e) => {
+            info!("No daemon to replace ({:#}), starting a new one", e);
+            return Ok(None);
+        }
+    };
+    let state = serde_json::from_str(&reply).context("Invalid state from the running daemon")?;
+    socket_client::send_command(&IpcCommand::Shutdown)?;
+
+    let deadline = Instant::now() + REPLACE_TIMEOUT;
+    while let Some(pid) = read_pidfile()?
+        && process_exists(pid)
+    {
+        if Instant::now() >= deadline {
+            anyhow::bail!(
+                "The running daemon (PID {}) didn't stop within {:?}",
+                pid,
+                REPLACE_TIMEOUT
+            );
+        }
+        std::thread::sleep(REPLACE_POLL_INTERVAL);
+    }
+    info!("Replaced the running daemon");
+    Ok(Some(state))
+}
+
+/// Send SIGUSR1 (or SIGUSR2, for the focused application's windows) to the
+/// running daemon to trigger the window switcher
+fn send_show_signal(signal: Signal) -> Result<()> {
+    use nix::sys::signal::kill;
     use nix::unistd::Pid;
 
     let Some(pid) = read_pidfile()? else {
@@ -142,14 +479,123 @@ fn send_show_signal() -> Result<()> {
         );
     };
 
-    // Send SIGUSR1 to the daemon process using nix crate
-    kill(Pid::from_raw(pid), Signal::SIGUSR1)
+    // Send the signal to the daemon process using nix crate
+    kill(Pid::from_raw(pid), signal)
         .with_context(|| format!("Failed to send signal to daemon (PID {})", pid))
 }
 
-fn run_daemon_mode(config: Config) -> Result<()> {
+/// Run the daemon without a built-in UI, for frontends using `watch`
+fn run_headless_mode(config: Config, state: Option<DaemonState>) -> Result<()> {
+    check_pidfile()?;
+    let _pidfile_guard = create_pidfile()?;
+
+    // Still needed for launching apps and the `current` command
+    let wmclass_index = IconResolver::build_wmclass_index();
+
+    // Input only arrives over the socket
+    let (events_tx, events) = event_bus::channel();
+
+    let rt = tokio::runtime::Runtime::new().context("Failed to create Tokio runtime")?;
+    rt.block_on(async move {
+        let mut daemon = Daemon::new(config, None, wmclass_index)?;
+        restore(&mut daemon, state).await;
+        daemon.run(events_tx, events).await
+    })
+}
+
+/// Run the daemon with the terminal frontend instead of GTK
+#[cfg(feature = "tui")]
+fn run_tui_mode(config: Config, state: Option<DaemonState>) -> Result<()> {
+    check_pidfile()?;
+    let _pidfile_guard = create_pidfile()?;
+
+    // Still needed for launching apps and the `current` command
+    let wmclass_index = IconResolver::build_wmclass_index();
+
+    let (ui_cmd_tx, ui_cmd_rx) = mpsc::channel(ui_commands::UI_CHANNEL_CAPACITY);
+    let (input_cmd_tx, events) = event_bus::channel();
+
+    let events_tx = input_cmd_tx.clone();
+    std::thread::spawn(move || {
+        let rt = tokio::runtime::Runtime::new().expect("Failed to create Tokio runtime");
+        rt.block_on(async move {
+            let result =
+                run_daemon_async(config, state, ui_cmd_tx, events_tx, events, wmclass_index).await;
+            if let Err(e) = result {
+                error!("Daemon error: {}", e);
+            }
+        });
+    });
+
+    tui::run(ui_cmd_rx, input_cmd_tx)
+}
+
+#[cfg(not(feature = "tui"))]
+fn run_tui_mode(_config: Config, _state: Option<DaemonState>) -> Result<()> {
+    anyhow::bail!("--frontend tui needs sway-alttab-gui to be built with the `tui` feature")
+}
+
+/// Show the GTK switcher once, without a pidfile or socket, and exit after
+/// the selection
+fn run_once_mode(mut config: Config) -> Result<()> {
+    let wmclass_index = IconResolver::build_wmclass_index();
+    gtk4::init()?;
+    capabilities::probe_and_degrade(&mut config);
+
+    // Not unique, so a running daemon's GTK instance doesn't take the activation
+    let app = gtk4::Application::builder()
+        .application_id(ui::APPLICATION_ID)
+        .flags(gtk4::gio::ApplicationFlags::NON_UNIQUE)
+        .build();
+
+    app.connect_activate(move |app| {
+        let tint_colors = RuleEngine::new(&config.file).tint_colors();
+        ui::setup_css(&config.file.appearance, &tint_colors);
+
+        let (ui_cmd_tx, ui_cmd_rx) = mpsc::channel(ui_commands::UI_CHANNEL_CAPACITY);
+        let (input_cmd_tx, events) = event_bus::channel();
+        let switcher = Rc::new(RefCell::new(SwitcherWindow::new(
+            app,
+            input_cmd_tx,
+            &config.file.appearance,
+            &config.file.keyboard,
+            &config.file.custom_actions,
+            &config.file.preview,
+            config.placement,
+        )));
+        ui_handler::handle_ui_commands(switcher, None, None, ui_cmd_rx);
+
+        let (done_tx, done_rx) = tokio::sync::oneshot::channel();
+        let config = config.clone();
+        let wmclass_index = wmclass_index.clone();
+        std::thread::spawn(move || {
+            let rt = tokio::runtime::Runtime::new().expect("Failed to create Tokio runtime");
+            let result = rt.block_on(async move {
+                Daemon::new(config, Some(ui_cmd_tx), wmclass_index)?
+                    .run_once(events)
+                    .await
+            });
+            if let Err(e) = result {
+                error!("Switcher error: {}", e);
+            }
+            let _ = done_tx.send(());
+        });
+
+        let app = app.clone();
+        glib::spawn_future_local(async move {
+            let _ = done_rx.await;
+            app.quit();
+        });
+    });
+
+    app.run_with_args::<&str>(&[]);
+
+    Ok(())
+}
+
+fn run_daemon_mode(mut config: Config, state: Option<DaemonState>) -> Result<()> {
     info!("Starting sway-alttab-gui daemon with GTK UI");
-    info!("Workspace mode: {:?}", config.mode);
+    info!("Workspace mode: {:?}, sort: {:?}", config.mode, config.sort);
 
     // Check if another instance is already running
     check_pidfile()?;
@@ -164,6 +610,9 @@ fn run_daemon_mode(config: Config) -> Result<()> {
     // Initialize GTK
     gtk4::init()?;
 
+    // Turn off what the compositor can't support before anything is shown
+    capabilities::probe_and_degrade(&mut config);
+
     // Pre-warm GTK IconTheme cache to avoid slow first alt-tab
     // The first lookup_icon() call triggers GTK to parse and index all icon theme directories
     {
@@ -180,37 +629,65 @@ fn run_daemon_mode(config: Config) -> Result<()> {
 
     // Create GTK Application
     let app = gtk4::Application::builder()
-        .application_id("com.github.itsjfx.sway-alttab-gui-gui")
+        .application_id(ui::APPLICATION_ID)
         .build();
 
     let wmclass_index_clone = wmclass_index.clone();
     app.connect_activate(move |app| {
         // Setup CSS
-        ui::setup_css();
+        let tint_colors = RuleEngine::new(&config.file).tint_colors();
+        ui::setup_css(&config.file.appearance, &tint_colors);
 
         // Create channels for daemon communication
-        let (ui_cmd_tx, ui_cmd_rx) = mpsc::unbounded_channel();
-        let (input_cmd_tx, input_cmd_rx) = mpsc::unbounded_channel();
+        let (ui_cmd_tx, ui_cmd_rx) = mpsc::channel(ui_commands::UI_CHANNEL_CAPACITY);
+        let (input_cmd_tx, events) = event_bus::channel();
 
         // Create SwitcherWindow with input channel
-        let switcher = Rc::new(RefCell::new(SwitcherWindow::new(app, input_cmd_tx)));
+        let switcher = Rc::new(RefCell::new(SwitcherWindow::new(
+            app,
+            input_cmd_tx.clone(),
+            &config.file.appearance,
+            &config.file.keyboard,
+            &config.file.custom_actions,
+            &config.file.preview,
+            config.placement,
+        )));
 
         // Pre-realize window to avoid slow first show
-        switcher.borrow().warm_up();
+        switcher.borrow_mut().warm_up(wmclass_index_clone.clone());
+
+        let dock = DockWindow::new(app, &config.file.dock, &config.file.appearance);
+        let taskbar = TaskbarWindow::new(
+            app,
+            &config.file.taskbar,
+            &config.file.appearance,
+            input_cmd_tx.clone(),
+        );
 
         // Setup UI command handler
-        ui_handler::handle_ui_commands(switcher.clone(), ui_cmd_rx);
+        ui_handler::handle_ui_commands(switcher.clone(), dock, taskbar, ui_cmd_rx);
 
         // Spawn Tokio runtime in a background thread
         let config_clone = config.clone();
+        let state = state.clone();
         let wmclass_index_for_daemon = wmclass_index_clone.clone();
+        let (done_tx, done_rx) = tokio::sync::oneshot::channel();
         std::thread::spawn(move || {
             // Create Tokio runtime
             let rt = tokio::runtime::Runtime::new().expect("Failed to create Tokio runtime");
 
             // Run daemon in Tokio runtime
             rt.block_on(async move {
-                match run_daemon_async(config_clone, ui_cmd_tx, input_cmd_rx, wmclass_index_for_daemon).await {
+                let result = run_daemon_async(
+                    config_clone,
+                    state,
+                    ui_cmd_tx,
+                    input_cmd_tx,
+                    events,
+                    wmclass_index_for_daemon,
+                )
+                .await;
+                match result {
                     Ok(_) => {
                         info!("Daemon exited normally");
                     }
@@ -220,7 +697,16 @@ fn run_daemon_mode(config: Config) -> Result<()> {
                 }
             });
 
-            error!("Daemon thread exiting");
+            info!("Daemon thread exiting");
+            let _ = done_tx.send(());
+        });
+
+        // Quit GTK when the daemon stops (e.g. `shutdown`), so the pidfile
+        // guard is dropped and the process exits
+        let app = app.clone();
+        glib::spawn_future_local(async move {
+            let _ = done_rx.await;
+            app.quit();
         });
     });
 
@@ -233,14 +719,27 @@ fn run_daemon_mode(config: Config) -> Result<()> {
 /// Run the async daemon logic
 async fn run_daemon_async(
     config: Config,
-    ui_cmd_tx: mpsc::UnboundedSender<ui_commands::UiCommand>,
-    input_cmd_rx: mpsc::UnboundedReceiver<ipc::InputCommand>,
+    state: Option<DaemonState>,
+    ui_cmd_tx: mpsc::Sender<ui_commands::UiCommand>,
+    events_tx: EventSender,
+    events: EventReceiver,
     wmclass_index: WmClassIndex,
 ) -> Result<()> {
     // Create and run daemon
-    let daemon = Daemon::new(config, Some(ui_cmd_tx), wmclass_index)?;
+    let mut daemon = Daemon::new(config, Some(ui_cmd_tx), wmclass_index)?;
+    restore(&mut daemon, state).await;
     info!("Starting daemon event loop");
-    daemon.run(input_cmd_rx).await?;
+    daemon.run(events_tx, events).await?;
 
     Ok(())
 }
+
+/// Carry over the state of the daemon `replace` stopped, if any. A failure
+/// only costs the old window order, so the daemon starts anyway.
+async fn restore(daemon: &mut Daemon, state: Option<DaemonState>) {
+    if let Some(state) = state
+        && let Err(e) = daemon.restore(state).await
+    {
+        warn!("Failed to restore the replaced daemon's state: {:#}", e);
+    }
+}
@@ -0,0 +1,82 @@
+//! Desktop notifications for failures the user would otherwise only find
+//! in the log, sent through org.freedesktop.Notifications.
+
+use anyhow::Result;
+use gtk4::gio;
+use gtk4::glib::{ToVariant, Variant};
+use std::collections::HashMap;
+use tracing::debug;
+
+/// How long to wait for the notification daemon
+const NOTIFY_TIMEOUT_MS: i32 = 1000;
+
+/// How long a notice stays up; errors use the server's default
+const NOTICE_EXPIRE_MS: i32 = 3000;
+
+/// Let the user know something went wrong. Sent from a thread of its own,
+/// so a slow or missing notification daemon never holds up the caller.
+pub fn notify_error(summary: &str, body: &str) {
+    notify(summary, body, Kind::Error);
+}
+
+/// Briefly tell the user about something that didn't go as asked, like
+/// [`notify_error`] but at normal urgency
+pub fn notify_notice(summary: &str, body: &str) {
+    notify(summary, body, Kind::Notice);
+}
+
+#[derive(Clone, Copy)]
+enum Kind {
+    Error,
+    Notice,
+}
+
+fn notify(summary: &str, body: &str, kind: Kind) {
+    let summary = summary.to_string();
+    let body = body.to_string();
+    let spawned = std::thread::Builder::new()
+        .name("notify".to_string())
+        .spawn(move || {
+            if let Err(e) = send_notification(&summary, &body, kind) {
+                debug!("Could not send a desktop notification: {:#}", e);
+            }
+        });
+    if let Err(e) = spawned {
+        debug!("Failed to start the notification thread: {}", e);
+    }
+}
+
+fn send_notification(summary: &str, body: &str, kind: Kind) -> Result<()> {
+    let (icon, urgency, expire) = match kind {
+        Kind::Error => ("dialog-error", 2u8, -1i32),
+        Kind::Notice => ("dialog-information", 1u8, NOTICE_EXPIRE_MS),
+    };
+    let bus = gio::bus_get_sync(gio::BusType::Session, gio::Cancellable::NONE)?;
+    let actions: Vec<String> = Vec::new();
+    let hints: HashMap<String, Variant> =
+        HashMap::from([("urgency".to_string(), urgency.to_variant())]);
+    bus.call_sync(
+        Some("org.freedesktop.Notifications"),
+        "/org/freedesktop/Notifications",
+        "org.freedesktop.Notifications",
+        "Notify",
+        Some(
+            &(
+                "sway-alttab-gui",
+                0u32,
+                icon,
+                summary,
+                body,
+                actions,
+                hints,
+                expire,
+            )
+                .to_variant(),
+        ),
+        None,
+        gio::DBusCallFlags::NONE,
+        NOTIFY_TIMEOUT_MS,
+        gio::Cancellable::NONE,
+    )?;
+    Ok(())
+}
@@ -0,0 +1,152 @@
+//! A fixed order for the switcher, set by dragging tiles. Applications
+//! are pinned rather than windows, as window IDs don't outlive a session;
+//! pinned applications' windows come first, in pin order, and the rest
+//! follow in MRU order.
+
+use crate::window_manager::WindowInfo;
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// Where the pins are kept: $XDG_STATE_HOME/sway-alttab-gui/pins.json
+pub fn pins_path() -> Option<PathBuf> {
+    dirs::state_dir()
+        .or_else(dirs::data_local_dir)
+        .map(|dir| dir.join("sway-alttab-gui").join("pins.json"))
+}
+
+/// Read the pinned applications; a missing file means none
+pub fn load_pins(path: &Path) -> Result<Vec<String>> {
+    match std::fs::read_to_string(path) {
+        Ok(content) => serde_json::from_str(&content)
+            .with_context(|| format!("Invalid pins in {}", path.display())),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(e) => Err(e).with_context(|| format!("Failed to read {}", path.display())),
+    }
+}
+
+/// Write the pinned applications, creating the directory if needed
+pub fn save_pins(path: &Path, pins: &[String]) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)
+            .with_context(|| format!("Failed to create {}", dir.display()))?;
+    }
+    std::fs::write(path, serde_json::to_string(pins)?)
+        .with_context(|| format!("Failed to write {}", path.display()))
+}
+
+/// Stable-sort `windows` so pinned applications' windows come first, in
+/// pin order
+pub fn apply_pins(windows: &mut [WindowInfo], pins: &[String]) {
+    if pins.is_empty() {
+        return;
+    }
+    let rank: HashMap<&str, usize> = pins
+        .iter()
+        .enumerate()
+        .map(|(i, app)| (app.as_str(), i))
+        .collect();
+    windows.sort_by_key(|w| {
+        w.app_key()
+            .and_then(|app| rank.get(app).copied())
+            .unwrap_or(usize::MAX)
+    });
+}
+
+/// The pins after the tile at `from` was dragged to `to`: every
+/// application up to the drop position (or the last pinned one, if
+/// further) is pinned in the order the list now shows
+#[must_use]
+pub fn pins_after_move(
+    windows: &[WindowInfo],
+    pins: &[String],
+    from: usize,
+    to: usize,
+) -> Vec<String> {
+    if from >= windows.len() || to >= windows.len() {
+        return pins.to_vec();
+    }
+    let mut apps: Vec<Option<&str>> = windows.iter().map(WindowInfo::app_key).collect();
+    let moved = apps.remove(from);
+    apps.insert(to, moved);
+
+    let last_pinned = apps
+        .iter()
+        .rposition(|app| app.is_some_and(|app| pins.iter().any(|p| p == app)))
+        .unwrap_or(0);
+    let mut new_pins: Vec<String> = Vec::new();
+    for app in apps[..=to.max(last_pinned)].iter().flatten() {
+        if !new_pins.iter().any(|p| p == app) {
+            new_pins.push(app.to_string());
+        }
+    }
+    new_pins
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_window(id: i64, app_id: &str) -> WindowInfo {
+        WindowInfo {
+            id,
+            app_id: Some(app_id.to_string()),
+            title: format!("Window {}", id),
+            workspace: "1".to_string(),
+            output: "DP-1".to_string(),
+            window_class: None,
+            window_instance: None,
+            label: None,
+            last_focused: None,
+            ..Default::default()
+        }
+    }
+
+    fn ids(windows: &[WindowInfo]) -> Vec<i64> {
+        windows.iter().map(|w| w.id).collect()
+    }
+
+    fn pins(apps: &[&str]) -> Vec<String> {
+        apps.iter().map(|app| app.to_string()).collect()
+    }
+
+    #[test]
+    fn test_apply_pins_puts_pinned_first() {
+        let mut windows = vec![
+            make_window(1, "foot"),
+            make_window(2, "firefox"),
+            make_window(3, "code"),
+            make_window(4, "firefox"),
+        ];
+        apply_pins(&mut windows, &pins(&["code", "firefox"]));
+        assert_eq!(ids(&windows), vec![3, 2, 4, 1]);
+    }
+
+    #[test]
+    fn test_pins_after_move_pins_up_to_drop() {
+        let windows = vec![
+            make_window(1, "foot"),
+            make_window(2, "firefox"),
+            make_window(3, "code"),
+        ];
+        // Dragging code to the front pins just code
+        assert_eq!(pins_after_move(&windows, &[], 2, 0), pins(&["code"]));
+        // Dragging foot to the middle pins firefox then foot
+        assert_eq!(pins_after_move(&windows, &[], 0, 1), pins(&["firefox", "foot"]));
+    }
+
+    #[test]
+    fn test_pins_after_move_keeps_later_pins() {
+        let windows = vec![
+            make_window(1, "foot"),
+            make_window(2, "firefox"),
+            make_window(3, "code"),
+        ];
+        let current = pins(&["foot", "firefox", "code"]);
+        assert_eq!(
+            pins_after_move(&windows, &current, 2, 0),
+            pins(&["code", "foot", "firefox"])
+        );
+        assert_eq!(pins_after_move(&windows, &current, 5, 0), current);
+    }
+}
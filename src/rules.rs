@@ -0,0 +1,426 @@
+//! The rule engine: `[[rules]]`, `[[labels]]` and `appearance.tints` as one
+//! ordered list, evaluated per window while the switch list is built.
+//!
+//! Rules are tried by priority, highest first, and in file order within a
+//! priority, with `[[rules]]` ahead of labels and tints. Each action comes
+//! from the first matching rule that sets it.
+
+use crate::config::{FileConfig, WindowRule};
+use crate::window_manager::WindowInfo;
+use std::fmt::{self, Display, Write};
+
+/// Where in the config file a rule comes from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RuleSource {
+    Rules,
+    Labels,
+    Tints,
+}
+
+impl Display for RuleSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RuleSource::Rules => write!(f, "rules"),
+            RuleSource::Labels => write!(f, "labels"),
+            RuleSource::Tints => write!(f, "appearance.tints"),
+        }
+    }
+}
+
+/// A rule as the engine evaluates it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EffectiveRule {
+    pub source: RuleSource,
+    /// Position among the rules of its source, from 1
+    pub number: usize,
+    pub rule: WindowRule,
+}
+
+/// What the rules do to one window
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Outcome {
+    /// Indices of the matching rules, in evaluation order
+    pub matched: Vec<usize>,
+    /// The actions taken, without criteria
+    pub actions: WindowRule,
+}
+
+/// The config's rules in evaluation order
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RuleEngine {
+    rules: Vec<EffectiveRule>,
+}
+
+impl RuleEngine {
+    #[must_use]
+    pub fn new(config: &FileConfig) -> Self {
+        let rules = config
+            .rules
+            .iter()
+            .cloned()
+            .map(|rule| (RuleSource::Rules, rule));
+        let labels = config.labels.iter().map(|label| {
+            let rule = WindowRule {
+                app_id: label.app_id.clone(),
+                title: label.title.clone(),
+                alias: Some(label.label.clone()),
+                ..Default::default()
+            };
+            (RuleSource::Labels, rule)
+        });
+        let tints = config.appearance.tints.iter().map(|tint| {
+            let rule = WindowRule {
+                app_id: Some(tint.app_id.clone()),
+                tint: Some(tint.color.clone()),
+                ..Default::default()
+            };
+            (RuleSource::Tints, rule)
+        });
+
+        let mut numbers = [0; 3];
+        let mut rules: Vec<EffectiveRule> = rules
+            .chain(labels)
+            .chain(tints)
+            .map(|(source, rule)| {
+                let number = &mut numbers[source as usize];
+                *number += 1;
+                EffectiveRule {
+                    source,
+                    number: *number,
+                    rule,
+                }
+            })
+            .collect();
+        // Stable, so equal priorities keep their file order
+        rules.sort_by_key(|effective| std::cmp::Reverse(effective.rule.priority));
+        RuleEngine { rules }
+    }
+
+    #[must_use]
+    pub fn rules(&self) -> &[EffectiveRule] {
+        &self.rules
+    }
+
+    /// The rules matching `window` and the actions they add up to
+    #[must_use]
+    pub fn evaluate(&self, window: &WindowInfo) -> Outcome {
+        let mut outcome = Outcome::default();
+        for (index, effective) in self.rules.iter().enumerate() {
+            let rule = &effective.rule;
+            if !rule.matches(window) {
+                continue;
+            }
+            outcome.matched.push(index);
+            let actions = &mut outcome.actions;
+            actions.hide |= rule.hide;
+            actions.pin |= rule.pin;
+            for (taken, offered) in [
+                (&mut actions.alias, &rule.alias),
+                (&mut actions.retitle, &rule.retitle),
+                (&mut actions.icon, &rule.icon),
+                (&mut actions.tint, &rule.tint),
+            ] {
+                if taken.is_none() {
+                    taken.clone_from(offered);
+                }
+            }
+        }
+        outcome
+    }
+
+    /// Drop hidden windows and give the rest their labels, titles, icons
+    /// and tints. A label from a mark wins over an alias. Pinned windows
+    /// are only marked; see [`sort_pinned`].
+    #[must_use]
+    pub fn apply(&self, windows: Vec<WindowInfo>) -> Vec<WindowInfo> {
+        if self.rules.is_empty() {
+            return windows;
+        }
+        windows
+            .into_iter()
+            .filter_map(|mut window| {
+                let actions = self.evaluate(&window).actions;
+                if actions.hide {
+                    return None;
+                }
+                if window.label.is_none() {
+                    window.label = actions.alias;
+                }
+                if let Some(retitle) = actions.retitle {
+                    window.title = retitle.replace("{title}", &window.title);
+                }
+                window.icon = actions.icon;
+                window.tint = actions.tint;
+                window.pinned = actions.pin;
+                Some(window)
+            })
+            .collect()
+    }
+
+    /// Every distinct tint color, for the stylesheet
+    #[must_use]
+    pub fn tint_colors(&self) -> Vec<String> {
+        let mut colors: Vec<String> = Vec::new();
+        for color in self
+            .rules
+            .iter()
+            .filter_map(|effective| effective.rule.tint.as_ref())
+        {
+            if !colors.contains(color) {
+                colors.push(color.clone());
+            }
+        }
+        colors
+    }
+
+    /// The rules in evaluation order, then which of them matched each of
+    /// `windows` and what they did, for `rules`
+    #[must_use]
+    pub fn describe(&self, windows: &[WindowInfo]) -> String {
+        let mut output = String::from("Rules, first match wins for each action:\n");
+        if self.rules.is_empty() {
+            output.push_str("  none\n");
+        }
+        for (index, effective) in self.rules.iter().enumerate() {
+            let rule = &effective.rule;
+            let _ = write!(
+                output,
+                "  {}. {} #{}",
+                index + 1,
+                effective.source,
+                effective.number
+            );
+            if rule.priority != 0 {
+                let _ = write!(output, " (priority {})", rule.priority);
+            }
+            let _ = writeln!(output, ": {} -> {}", criteria(rule), actions(rule));
+        }
+        output.push_str("Windows:\n");
+        for window in windows {
+            let outcome = self.evaluate(window);
+            let _ = write!(
+                output,
+                "  [{}] {} - {}: ",
+                window.id,
+                window.app_key().unwrap_or("<unknown>"),
+                window.title
+            );
+            if outcome.matched.is_empty() {
+                output.push_str("no rules\n");
+            } else {
+                let numbers: Vec<String> = outcome
+                    .matched
+                    .iter()
+                    .map(|index| (index + 1).to_string())
+                    .collect();
+                let _ = writeln!(
+                    output,
+                    "rules {} -> {}",
+                    numbers.join(", "),
+                    actions(&outcome.actions)
+                );
+            }
+        }
+        output
+    }
+}
+
+/// Stable-sort `windows` so those pinned by a rule come first
+pub fn sort_pinned(windows: &mut [WindowInfo]) {
+    windows.sort_by_key(|window| !window.pinned);
+}
+
+/// A rule's criteria in the form `match` accepts
+fn criteria(rule: &WindowRule) -> String {
+    let mut parts = Vec::new();
+    if let Some(ref app_id) = rule.app_id {
+        parts.push(format!("app-id={}", app_id));
+    }
+    if let Some(ref title) = rule.title {
+        parts.push(format!("title=\"{}\"", title));
+    }
+    parts.join(" ")
+}
+
+/// A rule's actions, comma separated
+fn actions(rule: &WindowRule) -> String {
+    let mut parts = Vec::new();
+    if rule.hide {
+        parts.push("hide".to_string());
+    }
+    if rule.pin {
+        parts.push("pin".to_string());
+    }
+    for (name, value) in [
+        ("alias", &rule.alias),
+        ("retitle", &rule.retitle),
+        ("icon", &rule.icon),
+        ("tint", &rule.tint),
+    ] {
+        if let Some(value) = value {
+            parts.push(format!("{} \"{}\"", name, value));
+        }
+    }
+    if parts.is_empty() {
+        "nothing".to_string()
+    } else {
+        parts.join(", ")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_window(id: i64, app_id: &str, title: &str) -> WindowInfo {
+        WindowInfo {
+            id,
+            app_id: Some(app_id.to_string()),
+            title: title.to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn engine(content: &str) -> RuleEngine {
+        RuleEngine::new(&FileConfig::from_toml(content).unwrap())
+    }
+
+    #[test]
+    fn test_order_by_priority_then_source() {
+        let engine = engine(
+            r#"
+            [[labels]]
+            app-id = "foot"
+            label = "term"
+
+            [[rules]]
+            app-id = "firefox"
+            pin = true
+
+            [[rules]]
+            title = "Inbox"
+            priority = 5
+            hide = true
+
+            [appearance]
+            tints = [{ app-id = "*term*", color = "green" }]
+            "#,
+        );
+        let order: Vec<(RuleSource, usize)> = engine
+            .rules()
+            .iter()
+            .map(|effective| (effective.source, effective.number))
+            .collect();
+        assert_eq!(
+            order,
+            vec![
+                (RuleSource::Rules, 2),
+                (RuleSource::Rules, 1),
+                (RuleSource::Labels, 1),
+                (RuleSource::Tints, 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_first_rule_wins_each_action() {
+        let engine = engine(
+            r#"
+            [[rules]]
+            app-id = "fire*"
+            alias = "web"
+
+            [[rules]]
+            app-id = "firefox"
+            alias = "browser"
+            tint = "blue"
+            retitle = "[{title}]"
+            "#,
+        );
+        let outcome = engine.evaluate(&make_window(1, "Firefox", "Inbox"));
+        assert_eq!(outcome.matched, vec![0, 1]);
+        assert_eq!(outcome.actions.alias.as_deref(), Some("web"));
+        assert_eq!(outcome.actions.tint.as_deref(), Some("blue"));
+
+        let windows = engine.apply(vec![make_window(1, "firefox", "Inbox")]);
+        assert_eq!(windows[0].title, "[Inbox]");
+        assert_eq!(windows[0].label.as_deref(), Some("web"));
+    }
+
+    #[test]
+    fn test_apply_hides_and_pins() {
+        let engine = engine(
+            r#"
+            [[rules]]
+            title = "Picture-in-Picture"
+            hide = true
+
+            [[rules]]
+            app-id = "foot"
+            pin = true
+            icon = "utilities-terminal"
+            "#,
+        );
+        let mut windows = engine.apply(vec![
+            make_window(1, "firefox", "Inbox"),
+            make_window(2, "firefox", "Picture-in-Picture"),
+            make_window(3, "foot", "~"),
+        ]);
+        sort_pinned(&mut windows);
+        let ids: Vec<i64> = windows.iter().map(|w| w.id).collect();
+        assert_eq!(ids, vec![3, 1]);
+        assert_eq!(windows[0].icon.as_deref(), Some("utilities-terminal"));
+        assert!(windows[0].pinned);
+    }
+
+    #[test]
+    fn test_mark_label_wins_over_alias() {
+        let engine = engine("[[labels]]\napp-id = \"foot\"\nlabel = \"term\"\n");
+        let mut marked = make_window(1, "foot", "~");
+        marked.label = Some("mine".to_string());
+        let windows = engine.apply(vec![marked, make_window(2, "foot", "~")]);
+        assert_eq!(windows[0].label.as_deref(), Some("mine"));
+        assert_eq!(windows[1].label.as_deref(), Some("term"));
+    }
+
+    #[test]
+    fn test_describe() {
+        let engine = engine(
+            r#"
+            [[rules]]
+            app-id = "firefox"
+            title = "Inbox"
+            priority = 2
+            alias = "Mail"
+            pin = true
+            "#,
+        );
+        let windows = [
+            make_window(94, "firefox", "Inbox"),
+            make_window(95, "foot", "~"),
+        ];
+        assert_eq!(
+            engine.describe(&windows),
+            "Rules, first match wins for each action:\n\
+             \x20 1. rules #1 (priority 2): app-id=firefox title=\"Inbox\" -> pin, alias \"Mail\"\n\
+             Windows:\n\
+             \x20 [94] firefox - Inbox: rules 1 -> pin, alias \"Mail\"\n\
+             \x20 [95] foot - ~: no rules\n"
+        );
+    }
+
+    #[test]
+    fn test_tint_colors_are_distinct() {
+        let engine = engine(
+            r#"
+            [[rules]]
+            app-id = "foot"
+            tint = "green"
+
+            [appearance]
+            tints = [{ app-id = "kitty", color = "green" }, { app-id = "vim", color = "blue" }]
+            "#,
+        );
+        assert_eq!(engine.tint_colors(), vec!["green", "blue"]);
+    }
+}
@@ -0,0 +1,121 @@
+//! A JSON line per finished switch, appended to a file or written to a
+//! FIFO configured with `[selection-log]`, for scripts and statistics.
+
+use crate::window_manager::WindowInfo;
+use anyhow::{Context, Result};
+use nix::fcntl::OFlag;
+use serde::Serialize;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::Path;
+
+/// A window as the log describes it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct LoggedWindow {
+    pub id: i64,
+    pub app_id: Option<String>,
+    pub title: String,
+    pub workspace: String,
+}
+
+impl From<&WindowInfo> for LoggedWindow {
+    fn from(window: &WindowInfo) -> Self {
+        LoggedWindow {
+            id: window.id,
+            app_id: window.app_key().map(str::to_string),
+            title: window.title.clone(),
+            workspace: window.workspace.clone(),
+        }
+    }
+}
+
+/// One finished switch
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SelectionRecord {
+    /// Unix time of the switch, in seconds
+    pub time: u64,
+    /// The window focused when the switcher opened, if any
+    pub origin: Option<LoggedWindow>,
+    pub selected: LoggedWindow,
+    /// How often the selection was moved before it was picked
+    pub cycles: usize,
+}
+
+impl SelectionRecord {
+    /// The record as a single line of JSON, newline included
+    #[must_use]
+    pub fn to_line(&self) -> String {
+        let mut line = serde_json::to_string(self).expect("a record always serializes");
+        line.push('\n');
+        line
+    }
+}
+
+/// Append `record` to the file at `path`, creating it and its directory if
+/// needed. A FIFO is opened without blocking, so with no reader the record
+/// is dropped with an error instead of stalling the daemon.
+pub fn write_record(path: &Path, record: &SelectionRecord) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)
+            .with_context(|| format!("Failed to create {}", dir.display()))?;
+    }
+    let mut file = OpenOptions::new()
+        .append(true)
+        .create(true)
+        .custom_flags(OFlag::O_NONBLOCK.bits())
+        .open(path)
+        .with_context(|| format!("Failed to open {}", path.display()))?;
+    // A single write, so readers of a FIFO never see half a record
+    file.write_all(record.to_line().as_bytes())
+        .with_context(|| format!("Failed to write to {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_window(id: i64, app_id: &str, title: &str) -> WindowInfo {
+        WindowInfo {
+            id,
+            app_id: Some(app_id.to_string()),
+            title: title.to_string(),
+            workspace: "2".to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_record_line() {
+        let record = SelectionRecord {
+            time: 1_700_000_000,
+            origin: Some(LoggedWindow::from(&make_window(1, "foot", "~"))),
+            selected: LoggedWindow::from(&make_window(2, "firefox", "Inbox")),
+            cycles: 3,
+        };
+        assert_eq!(
+            record.to_line(),
+            "{\"time\":1700000000,\
+             \"origin\":{\"id\":1,\"app_id\":\"foot\",\"title\":\"~\",\"workspace\":\"2\"},\
+             \"selected\":{\"id\":2,\"app_id\":\"firefox\",\"title\":\"Inbox\",\"workspace\":\"2\"},\
+             \"cycles\":3}\n"
+        );
+    }
+
+    #[test]
+    fn test_write_record_appends() {
+        let path = std::env::temp_dir().join(format!("selection-log-{}", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let record = SelectionRecord {
+            time: 1,
+            origin: None,
+            selected: LoggedWindow::from(&make_window(2, "firefox", "Inbox")),
+            cycles: 0,
+        };
+        write_record(&path, &record).unwrap();
+        write_record(&path, &record).unwrap();
+        let content = std::fs::read_to_string(&path).unwrap();
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(content, record.to_line().repeat(2));
+    }
+}
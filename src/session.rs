@@ -0,0 +1,173 @@
+//! The login session's state, so the switcher stays off the lock screen
+//! and the daemon rests while another session has the seat.
+
+use crate::event_bus::{DaemonEvent, EventSender};
+use anyhow::{Context, Result};
+use gtk4::gio;
+use gtk4::glib::{self, ToVariant, Variant, VariantTy};
+use std::sync::atomic::{AtomicBool, Ordering};
+use tracing::{debug, info, warn};
+
+/// Screen lockers whose running process means the screen is locked
+const LOCKERS: [&str; 4] = ["swaylock", "gtklock", "waylock", "hyprlock"];
+
+/// How long to wait for logind before giving up on a call
+const LOGIND_TIMEOUT_MS: i32 = 500;
+
+/// The session's `LockedHint` as logind last reported it, kept up to date
+/// by the logind watcher; there is one session per process
+static LOCKED_HINT: AtomicBool = AtomicBool::new(false);
+
+/// Whether the screen is locked: logind's `LockedHint` is set (by lockers
+/// that report to it), or a known locker is running. Lockers are only
+/// looked for here, on the blocking pool, so nothing scans `/proc` while
+/// the switcher isn't used.
+///
+/// Lockers speaking ext-session-lock also make sway hide every other
+/// surface, but the switcher would still take the keyboard and show up
+/// on unlock, so they are looked for all the same.
+pub async fn screen_locked() -> bool {
+    if LOCKED_HINT.load(Ordering::Relaxed) {
+        return true;
+    }
+    tokio::task::spawn_blocking(locker_running).await.unwrap_or(false)
+}
+
+/// Watch logind for the session becoming active or inactive (VT switches)
+/// and send each change to the daemon, and keep [`LOCKED_HINT`] up to
+/// date. Runs on a thread of its own with its own GLib main context, so it
+/// works with every frontend.
+pub fn watch_active(events_tx: EventSender) {
+    let spawned = std::thread::Builder::new()
+        .name("logind".to_string())
+        .spawn(move || {
+            if let Err(e) = run_active_watch(events_tx) {
+                warn!("Not tracking the login session: {:#}", e);
+            }
+        });
+    if let Err(e) = spawned {
+        warn!("Failed to start the logind watcher: {}", e);
+    }
+}
+
+fn run_active_watch(events_tx: EventSender) -> Result<()> {
+    let context = glib::MainContext::new();
+    let main_loop = glib::MainLoop::new(Some(&context), false);
+    context
+        .with_thread_default(|| -> Result<()> {
+            let bus = gio::bus_get_sync(gio::BusType::System, gio::Cancellable::NONE)?;
+            let path = own_session_path(&bus)?;
+            info!("Tracking login session {}", path);
+            match logind_locked_hint(&bus, &path) {
+                Ok(locked) => LOCKED_HINT.store(locked, Ordering::Relaxed),
+                Err(e) => debug!("Could not ask logind whether the session is locked: {:#}", e),
+            }
+
+            let quit = main_loop.clone();
+            let _subscription = bus.signal_subscribe(
+                Some("org.freedesktop.login1"),
+                Some("org.freedesktop.DBus.Properties"),
+                Some("PropertiesChanged"),
+                Some(&path),
+                None,
+                gio::DBusSignalFlags::NONE,
+                move |_, _, _, _, _, parameters| {
+                    if let Some(locked) = bool_from_changed(parameters, "LockedHint") {
+                        debug!("Session locked: {}", locked);
+                        LOCKED_HINT.store(locked, Ordering::Relaxed);
+                    }
+                    let Some(active) = bool_from_changed(parameters, "Active") else {
+                        return;
+                    };
+                    debug!("Session active: {}", active);
+                    if events_tx.send(DaemonEvent::SessionActive(active)).is_err() {
+                        quit.quit();
+                    }
+                },
+            );
+            main_loop.run();
+            Ok(())
+        })
+        .map_err(|e| anyhow::anyhow!("{}", e))?
+}
+
+/// Object path of the session this process belongs to. Signals carry the
+/// real path, so logind's `session/auto` alias can't be subscribed to.
+fn own_session_path(bus: &gio::DBusConnection) -> Result<String> {
+    let reply = bus.call_sync(
+        Some("org.freedesktop.login1"),
+        "/org/freedesktop/login1",
+        "org.freedesktop.login1.Manager",
+        "GetSessionByPID",
+        Some(&(std::process::id(),).to_variant()),
+        Some(VariantTy::new("(o)")?),
+        gio::DBusCallFlags::NONE,
+        LOGIND_TIMEOUT_MS,
+        gio::Cancellable::NONE,
+    )?;
+    reply
+        .child_value(0)
+        .str()
+        .map(str::to_string)
+        .context("logind returned no session path")
+}
+
+/// The new value of a boolean property (`Active`, `LockedHint`) from a
+/// session's PropertiesChanged signal, if it is among the changes
+#[must_use]
+fn bool_from_changed(parameters: &Variant, property: &str) -> Option<bool> {
+    if !parameters.is_type(VariantTy::new("(sa{sv}as)").ok()?) {
+        return None;
+    }
+    glib::VariantDict::new(Some(&parameters.child_value(1)))
+        .lookup::<bool>(property)
+        .ok()
+        .flatten()
+}
+
+/// Whether a process name is a known screen locker
+#[must_use]
+fn is_locker(comm: &str) -> bool {
+    LOCKERS.contains(&comm.trim())
+}
+
+fn locker_running() -> bool {
+    let Ok(entries) = std::fs::read_dir("/proc") else {
+        return false;
+    };
+    entries.flatten().any(|entry| {
+        std::fs::read_to_string(entry.path().join("comm")).is_ok_and(|comm| is_locker(&comm))
+    })
+}
+
+fn logind_locked_hint(bus: &gio::DBusConnection, path: &str) -> Result<bool> {
+    let reply = bus.call_sync(
+        Some("org.freedesktop.login1"),
+        path,
+        "org.freedesktop.DBus.Properties",
+        "Get",
+        Some(&("org.freedesktop.login1.Session", "LockedHint").to_variant()),
+        Some(VariantTy::new("(v)")?),
+        gio::DBusCallFlags::NONE,
+        LOGIND_TIMEOUT_MS,
+        gio::Cancellable::NONE,
+    )?;
+    reply
+        .child_value(0)
+        .as_variant()
+        .and_then(|value| value.get::<bool>())
+        .context("LockedHint is not a boolean")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_locker() {
+        assert!(is_locker("swaylock\n"));
+        assert!(is_locker("gtklock"));
+        assert!(!is_locker("sway"));
+        assert!(!is_locker("swaylock-wrapper"));
+    }
+}
@@ -0,0 +1,105 @@
+//! First-start check: when the daemon starts without a config file, look
+//! for the pieces a new setup usually lacks and log what to add.
+
+use std::path::PathBuf;
+use tracing::{info, warn};
+
+/// The command a sway binding needs to run to open the switcher
+const SHOW_COMMAND: &str = "sway-alttab-gui show";
+
+/// Setup hints for what is missing, each with the snippet that fixes it.
+/// `sway_config` is None when sway's config couldn't be read.
+#[must_use]
+pub fn setup_hints(sway_config: Option<&str>, icon_theme_found: bool) -> Vec<String> {
+    let mut hints = Vec::new();
+    match sway_config {
+        Some(config) if config.contains(SHOW_COMMAND) => {}
+        Some(_) => hints.push(format!(
+            "No sway binding runs `{}`. Add to your sway config:\n    bindsym Mod1+Tab exec {}",
+            SHOW_COMMAND, SHOW_COMMAND
+        )),
+        None => hints.push(format!(
+            "Couldn't read the sway config; make sure a binding runs `{}`",
+            SHOW_COMMAND
+        )),
+    }
+    if !icon_theme_found {
+        hints.push(
+            "No icon theme found, so windows will get placeholder icons. \
+             Install one, e.g. hicolor-icon-theme and adwaita-icon-theme"
+                .to_string(),
+        );
+    }
+    hints
+}
+
+/// Gather what the hints need from sway and the filesystem and log them
+pub fn log_first_start_hints() {
+    let sway_config = swayipc::Connection::new()
+        .and_then(|mut connection| connection.get_config())
+        .map(|config| config.config)
+        .ok();
+    let hints = setup_hints(sway_config.as_deref(), icon_theme_installed());
+    if hints.is_empty() {
+        return;
+    }
+
+    info!("No config file found, checking the setup:");
+    for hint in hints {
+        warn!("{}", hint);
+    }
+}
+
+/// Whether any icon theme (a directory with an index.theme) is installed
+fn icon_theme_installed() -> bool {
+    icon_dirs().into_iter().any(|dir| {
+        std::fs::read_dir(dir)
+            .map(|entries| {
+                entries
+                    .flatten()
+                    .any(|entry| entry.path().join("index.theme").is_file())
+            })
+            .unwrap_or(false)
+    })
+}
+
+/// Where icon themes are installed, as the icon theme spec lists them
+fn icon_dirs() -> Vec<PathBuf> {
+    let data_dirs = std::env::var("XDG_DATA_DIRS")
+        .ok()
+        .filter(|dirs| !dirs.is_empty())
+        .unwrap_or_else(|| "/usr/local/share:/usr/share".to_string());
+    [
+        dirs::home_dir().map(|d| d.join(".icons")),
+        dirs::data_local_dir().map(|d| d.join("icons")),
+    ]
+    .into_iter()
+    .flatten()
+    .chain(data_dirs.split(':').map(|dir| PathBuf::from(dir).join("icons")))
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_setup_hints_complete_setup() {
+        let config = "bindsym Mod1+Tab exec sway-alttab-gui show\n";
+        assert!(setup_hints(Some(config), true).is_empty());
+    }
+
+    #[test]
+    fn test_setup_hints_missing_binding_includes_snippet() {
+        let hints = setup_hints(Some("bindsym Mod4+Return exec foot\n"), true);
+        assert_eq!(hints.len(), 1);
+        assert!(hints[0].contains("bindsym Mod1+Tab exec sway-alttab-gui show"));
+    }
+
+    #[test]
+    fn test_setup_hints_unreadable_config_and_no_icons() {
+        let hints = setup_hints(None, false);
+        assert_eq!(hints.len(), 2);
+        assert!(hints[1].contains("icon theme"));
+    }
+}
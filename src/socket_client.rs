@@ -0,0 +1,54 @@
+//! Client side of the daemon's Unix socket.
+
+use crate::ipc::{get_socket_path, IpcCommand};
+use crate::socket_server::ERROR_PREFIX;
+use anyhow::{Context, Result};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::Shutdown;
+use std::os::unix::net::UnixStream;
+
+/// Send a command to the running daemon and return its reply.
+///
+/// Error replies from the daemon are returned as errors.
+pub fn send_command(command: &IpcCommand) -> Result<String> {
+    let mut stream = connect(command)?;
+
+    let mut reply = String::new();
+    stream
+        .read_to_string(&mut reply)
+        .context("Failed to read reply")?;
+
+    match reply.strip_prefix(ERROR_PREFIX) {
+        Some(message) => anyhow::bail!("{}", message.trim_end()),
+        None => Ok(reply),
+    }
+}
+
+/// Send a command whose reply is streamed, calling `on_line` for each line
+/// until the daemon closes the connection.
+pub fn stream_command(command: &IpcCommand, mut on_line: impl FnMut(&str)) -> Result<()> {
+    let stream = connect(command)?;
+    for line in BufReader::new(stream).lines() {
+        let line = line.context("Failed to read reply")?;
+        if let Some(message) = line.strip_prefix(ERROR_PREFIX) {
+            anyhow::bail!("{}", message);
+        }
+        on_line(&line);
+    }
+    Ok(())
+}
+
+/// Connect to the daemon and send a command
+fn connect(command: &IpcCommand) -> Result<UnixStream> {
+    let path = get_socket_path()?;
+    let mut stream = UnixStream::connect(&path).with_context(|| {
+        format!(
+            "Failed to connect to daemon socket at {} (is the daemon running?)",
+            path.display()
+        )
+    })?;
+
+    writeln!(stream, "{}", command).context("Failed to send command")?;
+    stream.shutdown(Shutdown::Write)?;
+    Ok(stream)
+}
@@ -0,0 +1,257 @@
+//! Unix socket server for CLI → daemon commands.
+//!
+//! Each connection carries a single command line; the daemon's reply is
+//! written back and the connection is closed. A streamed reply keeps the
+//! connection open and writes lines until the client goes away; meanwhile
+//! each line the client sends is handled as an input command.
+
+use crate::event_bus::{DaemonEvent, EventSender};
+use crate::ipc::{get_socket_path, InputCommand, IpcCommand};
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::PathBuf;
+use std::time::Duration;
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{UnixListener, UnixStream};
+use tokio::sync::{broadcast, oneshot};
+use tracing::{debug, error, info, warn};
+
+/// Prefix marking an error reply, so clients can exit non-zero
+pub const ERROR_PREFIX: &str = "error: ";
+
+/// Longest line a client may send, newline included
+pub const MAX_LINE: usize = 64 * 1024;
+
+/// How long a client has to send its command after connecting
+const COMMAND_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Pause after a failed `accept`, e.g. while out of file descriptors
+const ACCEPT_BACKOFF: Duration = Duration::from_millis(100);
+
+/// The daemon's answer to a command
+#[derive(Debug)]
+pub enum IpcReply {
+    /// A complete reply; the connection is closed after it is written
+    Text(String),
+    /// A complete reply the daemon waits on before stopping: `written`
+    /// fires once it is written and the connection closed
+    Last {
+        text: String,
+        written: oneshot::Sender<()>,
+    },
+    /// A first line, then each line from `updates` as it comes
+    Stream {
+        initial: String,
+        updates: broadcast::Receiver<String>,
+    },
+}
+
+/// A command received over the socket, with a channel for the daemon's reply
+#[derive(Debug)]
+pub struct IpcRequest {
+    pub command: IpcCommand,
+    pub reply: oneshot::Sender<Result<IpcReply>>,
+}
+
+/// Guard that removes the socket file when dropped
+struct SocketGuard {
+    path: PathBuf,
+}
+
+impl Drop for SocketGuard {
+    fn drop(&mut self) {
+        if let Err(e) = fs::remove_file(&self.path) {
+            error!("Failed to remove socket: {}", e);
+        } else {
+            info!("Removed socket at {}", self.path.display());
+        }
+    }
+}
+
+/// Listen on the daemon socket and pass commands to the daemon's event bus.
+///
+/// Runs until the task is aborted.
+pub async fn serve(events: EventSender) -> Result<()> {
+    let path = get_socket_path()?;
+
+    // The pidfile guarantees a single daemon, so any existing socket is stale
+    if path.exists() {
+        debug!("Removing stale socket at {}", path.display());
+        fs::remove_file(&path).context("Failed to remove stale socket")?;
+    }
+
+    let listener = UnixListener::bind(&path)
+        .with_context(|| format!("Failed to bind socket at {}", path.display()))?;
+    let _guard = SocketGuard { path: path.clone() };
+    info!("Listening for commands on {}", path.display());
+
+    loop {
+        let stream = match listener.accept().await {
+            Ok((stream, _)) => stream,
+            Err(e) => {
+                warn!("Failed to accept a connection: {}", e);
+                tokio::time::sleep(ACCEPT_BACKOFF).await;
+                continue;
+            }
+        };
+        let events = events.clone();
+        tokio::spawn(async move {
+            if let Err(e) = handle_connection(stream, events).await {
+                warn!("IPC connection error: {:#}", e);
+            }
+        });
+    }
+}
+
+/// Read one command, pass it to the daemon and write back the reply
+async fn handle_connection(stream: UnixStream, events: EventSender) -> Result<()> {
+    let (reader, mut writer) = stream.into_split();
+    let mut reader = BufReader::new(reader);
+    let mut buf = Vec::new();
+    let first = read_line(&mut reader, &mut buf);
+    let line = match tokio::time::timeout(COMMAND_TIMEOUT, first).await {
+        Ok(line) => line.map(Option::unwrap_or_default),
+        Err(_) => Err(anyhow::anyhow!("No command within {:?}", COMMAND_TIMEOUT)),
+    };
+
+    let reply = match line.and_then(|line| line.parse::<IpcCommand>()) {
+        Ok(command) => {
+            debug!("Received IPC command: {}", command);
+            request(&events, command).await
+        }
+        Err(e) => Err(e),
+    };
+
+    let mut written = None;
+    match reply {
+        Ok(IpcReply::Text(text)) => writer.write_all(text.as_bytes()).await?,
+        Ok(IpcReply::Last {
+            text,
+            written: done,
+        }) => {
+            writer.write_all(text.as_bytes()).await?;
+            written = Some(done);
+        }
+        Ok(IpcReply::Stream {
+            initial,
+            mut updates,
+        }) => {
+            writer.write_all(initial.as_bytes()).await?;
+            let mut reading = true;
+            loop {
+                tokio::select! {
+                    update = updates.recv() => match update {
+                        Ok(line) => writer.write_all(line.as_bytes()).await?,
+                        Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                            warn!("Stream client fell behind, skipped {} lines", skipped);
+                        }
+                        Err(broadcast::error::RecvError::Closed) => break,
+                    },
+                    // Partial lines stay in `buf` if another branch wins
+                    line = read_line(&mut reader, &mut buf), if reading => match line {
+                        Ok(Some(line)) if line.trim().is_empty() => {}
+                        Ok(Some(line)) => {
+                            let result = match line.parse::<InputCommand>() {
+                                Ok(input) => request(&events, IpcCommand::Input(input)).await,
+                                Err(e) => Err(e),
+                            };
+                            if let Err(e) = result {
+                                let response = format!("{}{:#}\n", ERROR_PREFIX, e);
+                                writer.write_all(response.as_bytes()).await?;
+                            }
+                        }
+                        // The client is done sending but still listening
+                        Ok(None) => reading = false,
+                        Err(e) => {
+                            let response = format!("{}{:#}\n", ERROR_PREFIX, e);
+                            writer.write_all(response.as_bytes()).await?;
+                            reading = false;
+                        }
+                    },
+                }
+            }
+        }
+        Err(e) => {
+            let response = format!("{}{:#}\n", ERROR_PREFIX, e);
+            writer.write_all(response.as_bytes()).await?;
+        }
+    }
+    writer.shutdown().await?;
+    if let Some(written) = written {
+        // The daemon may have stopped waiting already
+        let _ = written.send(());
+    }
+
+    Ok(())
+}
+
+/// Read a line of at most [`MAX_LINE`] bytes, without its line ending.
+/// Bytes of an unfinished line are kept in `buf` for the next call.
+/// Returns None at the end of the input.
+async fn read_line<R: AsyncBufRead + Unpin>(
+    reader: &mut R,
+    buf: &mut Vec<u8>,
+) -> Result<Option<String>> {
+    let limit = MAX_LINE.saturating_sub(buf.len()) as u64;
+    if reader.take(limit).read_until(b'\n', buf).await? == 0 && buf.is_empty() {
+        return Ok(None);
+    }
+    decode_line(std::mem::take(buf)).map(Some)
+}
+
+/// Check a line read from a client and strip its line ending. A line
+/// filling [`MAX_LINE`] without ending is too long.
+pub fn decode_line(mut buf: Vec<u8>) -> Result<String> {
+    if buf.last() == Some(&b'\n') {
+        buf.pop();
+        if buf.last() == Some(&b'\r') {
+            buf.pop();
+        }
+    } else if buf.len() >= MAX_LINE {
+        anyhow::bail!("Line longer than {} bytes", MAX_LINE);
+    }
+    String::from_utf8(buf).context("Line is not UTF-8")
+}
+
+/// Pass a command to the daemon and wait for its reply
+async fn request(events: &EventSender, command: IpcCommand) -> Result<IpcReply> {
+    let (reply_tx, reply_rx) = oneshot::channel();
+    events
+        .send(DaemonEvent::Ipc(IpcRequest {
+            command,
+            reply: reply_tx,
+        }))
+        .ok()
+        .context("Daemon is shutting down")?;
+    reply_rx.await.context("Daemon dropped the request")?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_line() {
+        assert_eq!(decode_line(b"show apps\r\n".to_vec()).unwrap(), "show apps");
+        // The last line may come without a newline
+        assert_eq!(decode_line(b"history".to_vec()).unwrap(), "history");
+        assert!(decode_line(vec![b'a'; MAX_LINE]).is_err());
+        assert!(decode_line(vec![0xff, b'\n']).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_read_line_is_capped() {
+        let mut input = vec![b'a'; MAX_LINE * 2];
+        input.push(b'\n');
+        let mut reader = &input[..];
+        let mut buf = Vec::new();
+        assert!(read_line(&mut reader, &mut buf).await.is_err());
+
+        let mut reader = &b"next\nprev\n"[..];
+        let line = read_line(&mut reader, &mut buf).await.unwrap();
+        assert_eq!(line.as_deref(), Some("next"));
+        let line = read_line(&mut reader, &mut buf).await.unwrap();
+        assert_eq!(line.as_deref(), Some("prev"));
+        assert_eq!(read_line(&mut reader, &mut buf).await.unwrap(), None);
+    }
+}
@@ -1,23 +1,71 @@
-//! Sway IPC abstraction for testability.
-//!
-//! This module provides a trait that abstracts Sway IPC operations,
-//! allowing for mock implementations in tests.
+//! The sway backend of the [`Compositor`] trait.
 
+use crate::compositor::{Compositor, Subscription, WindowEvents};
+use crate::event_bus::WindowEvent;
 use anyhow::Result;
-use swayipc::{Connection, Node, Workspace};
+use futures_lite::StreamExt;
+use std::fmt;
+use swayipc::{Connection, Node, Output, Workspace};
+use swayipc_async::{Event, EventType, WindowChange};
+use tokio::runtime::{Handle, RuntimeFlavor};
+use tracing::debug;
 
-/// Trait for Sway IPC operations.
+/// A sway command that was delivered but rejected by the compositor.
 ///
-/// This abstraction allows for mock implementations in tests.
-pub trait SwayClient {
-    /// Get the full window tree from Sway
-    fn get_tree(&mut self) -> Result<Node>;
+/// Distinguishes "sway said no" (e.g. the target window no longer exists)
+/// from transport errors on the IPC connection.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CommandError {
+    /// Sway ran the command but reported failure
+    Failed { command: String, reason: String },
+    /// Sway could not parse the command
+    Parse { command: String, reason: String },
+}
+
+impl fmt::Display for CommandError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CommandError::Failed { command, reason } => {
+                write!(f, "sway command '{}' failed: {}", command, reason)
+            }
+            CommandError::Parse { command, reason } => {
+                write!(f, "sway could not parse command '{}': {}", command, reason)
+            }
+        }
+    }
+}
 
-    /// Get the list of workspaces
-    fn get_workspaces(&mut self) -> Result<Vec<Workspace>>;
+impl std::error::Error for CommandError {}
 
-    /// Focus a window by its container ID
-    fn focus_window(&mut self, window_id: i64) -> Result<()>;
+/// Convert the per-command replies from `run_command` into a single result.
+///
+/// The first failing reply is turned into a [`CommandError`]; other errors
+/// are passed through unchanged.
+fn check_command_outcomes(
+    command: &str,
+    outcomes: Vec<Result<(), swayipc::Error>>,
+) -> Result<()> {
+    for outcome in outcomes {
+        match outcome {
+            Ok(()) => {}
+            Err(swayipc::Error::CommandFailed(reason)) => {
+                return Err(CommandError::Failed {
+                    command: command.to_string(),
+                    reason,
+                }
+                .into());
+            }
+            Err(swayipc::Error::CommandParse(reason)) => {
+                return Err(CommandError::Parse {
+                    command: command.to_string(),
+                    reason,
+                }
+                .into());
+            }
+            Err(e) => return Err(e.into()),
+        }
+    }
+    Ok(())
 }
 
 /// Real implementation using swayipc
@@ -33,26 +81,193 @@ impl RealSwayClient {
     }
 }
 
-impl SwayClient for RealSwayClient {
+impl RealSwayClient {
+    /// Run a command list and check every reply
+    fn run(&mut self, command: &str) -> Result<()> {
+        let outcomes = blocking(|| self.connection.run_command(command))?;
+        check_command_outcomes(command, outcomes)
+    }
+}
+
+impl Compositor for RealSwayClient {
     fn get_tree(&mut self) -> Result<Node> {
-        Ok(self.connection.get_tree()?)
+        Ok(blocking(|| self.connection.get_tree())?)
     }
 
     fn get_workspaces(&mut self) -> Result<Vec<Workspace>> {
-        Ok(self.connection.get_workspaces()?)
+        Ok(blocking(|| self.connection.get_workspaces())?)
+    }
+
+    fn get_outputs(&mut self) -> Result<Vec<Output>> {
+        Ok(blocking(|| self.connection.get_outputs())?)
     }
 
     fn focus_window(&mut self, window_id: i64) -> Result<()> {
-        self.connection
-            .run_command(format!("[con_id={}] focus", window_id))?;
-        Ok(())
+        let command = format!("[con_id={}] focus", window_id);
+        self.run(&command)
+    }
+
+    fn focus_window_on_workspace(&mut self, window_id: i64, workspace: &str) -> Result<()> {
+        let command = format!(
+            "workspace {}; [con_id={}] focus",
+            quote_argument(workspace),
+            window_id
+        );
+        self.run(&command)
+    }
+
+    fn move_window_to_workspace(&mut self, window_id: i64, workspace: &str) -> Result<()> {
+        let command = format!(
+            "[con_id={id}] move container to workspace {ws}; [con_id={id}] focus",
+            id = window_id,
+            ws = quote_argument(workspace)
+        );
+        self.run(&command)
+    }
+
+    fn toggle_floating(&mut self, window_id: i64) -> Result<()> {
+        let command = format!(
+            "[con_id={id}] floating toggle; [con_id={id}] focus",
+            id = window_id
+        );
+        self.run(&command)
+    }
+
+    fn close_window(&mut self, window_id: i64) -> Result<()> {
+        let command = format!("[con_id={}] kill", window_id);
+        self.run(&command)
+    }
+
+    fn exec(&mut self, command: &str) -> Result<()> {
+        let command = format!("exec {}", quote_argument(command));
+        self.run(&command)
+    }
+
+    fn connect_again(&self) -> Result<Self> {
+        RealSwayClient::new()
+    }
+
+    fn window_events(&self) -> Subscription {
+        Box::pin(async {
+            let events = swayipc_async::Connection::new()
+                .await?
+                .subscribe([EventType::Window])
+                .await?;
+            let events = events.filter_map(|event| match event {
+                Ok(Event::Window(e)) => {
+                    debug!("Sway window event: {:?} for container {:?}", e.change, e.container.id);
+                    // Focus changes for MRU ordering, new and closed windows
+                    // for refreshes
+                    match e.change {
+                        WindowChange::Focus => Some(Ok(WindowEvent::Focus(e.container.id))),
+                        WindowChange::New => Some(Ok(WindowEvent::New(e.container.id))),
+                        WindowChange::Close => Some(Ok(WindowEvent::Close(e.container.id))),
+                        _ => None,
+                    }
+                }
+                Ok(_) => None,
+                Err(e) => Some(Err(e.into())),
+            });
+            Ok(Box::pin(events) as WindowEvents)
+        })
     }
 }
 
-// Note: A full mock implementation would require creating swayipc Node structs,
-// but these are non-exhaustive and complex. For unit testing, consider:
-// 1. Testing the pure functions (preserve_mru_order, collect_windows, etc.) directly
-// 2. Using integration tests that connect to a real Sway instance
-//
-// The SwayClient trait is provided for future extensibility and to document
-// the interface, but the tests for window_manager use the extracted pure functions.
+/// Run a blocking sway request. On a multi-threaded runtime the worker
+/// thread hands its other tasks (socket clients, sway events) to the rest
+/// of the pool first, so they keep running while sway answers.
+fn blocking<T>(request: impl FnOnce() -> T) -> T {
+    match Handle::try_current() {
+        Ok(handle) if handle.runtime_flavor() == RuntimeFlavor::MultiThread => {
+            tokio::task::block_in_place(request)
+        }
+        _ => request(),
+    }
+}
+
+/// Quote a string so it is passed to sway as a single command argument.
+fn quote_argument(arg: &str) -> String {
+    format!("\"{}\"", arg.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+// Note: The tests for window_manager use the extracted pure functions
+// (preserve_mru_order, collect_windows, etc.). The daemon's tests run against
+// `compositor::mock::MockCompositor`, which builds swayipc's non-exhaustive
+// Node structs from sway's JSON.
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_command_outcomes_all_ok() {
+        assert!(check_command_outcomes("focus", vec![Ok(()), Ok(())]).is_ok());
+    }
+
+    #[test]
+    fn test_check_command_outcomes_empty() {
+        assert!(check_command_outcomes("focus", vec![]).is_ok());
+    }
+
+    #[test]
+    fn test_check_command_outcomes_failed_is_typed() {
+        let err = check_command_outcomes(
+            "[con_id=1] focus",
+            vec![Err(swayipc::Error::CommandFailed("No matching node.".to_string()))],
+        )
+        .unwrap_err();
+
+        assert_eq!(
+            err.downcast_ref::<CommandError>(),
+            Some(&CommandError::Failed {
+                command: "[con_id=1] focus".to_string(),
+                reason: "No matching node.".to_string(),
+            })
+        );
+    }
+
+    #[test]
+    fn test_check_command_outcomes_parse_error_is_typed() {
+        let err = check_command_outcomes(
+            "bogus",
+            vec![Ok(()), Err(swayipc::Error::CommandParse("Unknown command".to_string()))],
+        )
+        .unwrap_err();
+
+        assert!(matches!(
+            err.downcast_ref::<CommandError>(),
+            Some(CommandError::Parse { .. })
+        ));
+    }
+
+    #[test]
+    fn test_check_command_outcomes_other_errors_pass_through() {
+        let err = check_command_outcomes("focus", vec![Err(swayipc::Error::SocketNotFound)])
+            .unwrap_err();
+        assert!(err.downcast_ref::<CommandError>().is_none());
+    }
+
+    #[test]
+    fn test_quote_argument_plain() {
+        assert_eq!(quote_argument("1"), "\"1\"");
+        assert_eq!(quote_argument("2: web"), "\"2: web\"");
+    }
+
+    #[test]
+    fn test_quote_argument_escapes_quotes_and_backslashes() {
+        assert_eq!(quote_argument(r#"a"b"#), r#""a\"b""#);
+        assert_eq!(quote_argument(r"a\b"), r#""a\\b""#);
+    }
+
+    #[test]
+    fn test_command_error_display() {
+        let err = CommandError::Failed {
+            command: "[con_id=7] focus".to_string(),
+            reason: "No matching node.".to_string(),
+        };
+        assert_eq!(
+            err.to_string(),
+            "sway command '[con_id=7] focus' failed: No matching node."
+        );
+    }
+}
@@ -0,0 +1,141 @@
+//! A slim bar of every open window along the bottom of the screen, fed
+//! by the daemon's window list. Clicking a window focuses it through the
+//! same input channel the switcher uses.
+
+use crate::config::{Appearance, TaskbarConfig};
+use crate::icon_resolver::{IconResolver, WmClassIndex};
+use crate::ipc::InputCommand;
+use crate::ui::{follow_high_contrast, InputSender};
+use crate::ui_commands::UI_RESOURCES;
+use crate::window_manager::WindowInfo;
+use gtk4::prelude::*;
+use gtk4::{Application, ApplicationWindow, Box as GtkBox, Button, Image, Label, Orientation};
+use gtk4_layer_shell::{Edge, KeyboardMode, Layer, LayerShell};
+use tracing::{debug, warn};
+
+const TASKBAR_ICON_SIZE: i32 = 20;
+const TASKBAR_SPACING: i32 = 4;
+const MAX_TASKBAR_TITLE_LENGTH: i32 = 24;
+
+pub struct TaskbarWindow {
+    window: ApplicationWindow,
+    container: GtkBox,
+    input_tx: InputSender,
+    /// Created on the first update, when the WMClass index is available
+    icon_resolver: Option<IconResolver>,
+}
+
+impl TaskbarWindow {
+    /// Create the taskbar, or None if it is turned off or the compositor
+    /// has no layer shell to keep it at the screen's edge
+    pub fn new(
+        app: &Application,
+        taskbar: &TaskbarConfig,
+        appearance: &Appearance,
+        input_tx: InputSender,
+    ) -> Option<Self> {
+        if !taskbar.enabled {
+            return None;
+        }
+        if !gtk4_layer_shell::is_supported() {
+            warn!("Layer shell is not supported here, the taskbar is disabled");
+            return None;
+        }
+
+        let window = ApplicationWindow::builder()
+            .application(app)
+            .title("Taskbar")
+            .decorated(false)
+            .resizable(false)
+            .build();
+        window.add_css_class("taskbar");
+        follow_high_contrast(&window, appearance.high_contrast);
+
+        window.init_layer_shell();
+        window.set_namespace(&format!("{}-taskbar", appearance.namespace));
+        window.set_layer(Layer::Top);
+        window.set_keyboard_mode(KeyboardMode::None);
+        for edge in [Edge::Bottom, Edge::Left, Edge::Right] {
+            window.set_anchor(edge, true);
+        }
+        // Keep tiled windows from going under the bar
+        window.auto_exclusive_zone_enable();
+
+        let container = GtkBox::new(Orientation::Horizontal, TASKBAR_SPACING);
+        container.set_margin_top(TASKBAR_SPACING);
+        container.set_margin_bottom(TASKBAR_SPACING);
+        container.set_margin_start(TASKBAR_SPACING);
+        container.set_margin_end(TASKBAR_SPACING);
+        window.set_child(Some(&container));
+
+        Some(TaskbarWindow {
+            window,
+            container,
+            input_tx,
+            icon_resolver: None,
+        })
+    }
+
+    /// Replace the taskbar's entries with `windows`, marking the focused one
+    pub fn update(
+        &mut self,
+        windows: &[WindowInfo],
+        focused: Option<i64>,
+        wmclass_index: WmClassIndex,
+    ) {
+        let mut icon_resolver = match self.icon_resolver.take() {
+            Some(mut resolver) => {
+                resolver.set_wmclass_index(wmclass_index);
+                resolver
+            }
+            None => IconResolver::with_wmclass_index(TASKBAR_ICON_SIZE, wmclass_index),
+        };
+        icon_resolver.set_scale(self.window.scale_factor());
+
+        while let Some(child) = self.container.first_child() {
+            self.container.remove(&child);
+        }
+        for window in windows {
+            let entry = GtkBox::new(Orientation::Horizontal, TASKBAR_SPACING);
+            let pixbuf = window
+                .icon_candidates()
+                .into_iter()
+                .find_map(|id| icon_resolver.resolve_icon(Some(id)))
+                .or_else(|| icon_resolver.get_fallback_icon());
+            let icon = match pixbuf {
+                Some(pb) => Image::from_pixbuf(Some(&pb)),
+                None => Image::from_icon_name("application-x-executable"),
+            };
+            icon.set_pixel_size(TASKBAR_ICON_SIZE);
+            entry.append(&icon);
+
+            let title = Label::new(Some(&window.title));
+            title.set_ellipsize(gtk4::pango::EllipsizeMode::End);
+            title.set_max_width_chars(MAX_TASKBAR_TITLE_LENGTH);
+            entry.append(&title);
+
+            let button = Button::builder().child(&entry).build();
+            button.add_css_class("flat");
+            if focused == Some(window.id) {
+                button.add_css_class("selected");
+            }
+            button.set_tooltip_text(Some(&format!(
+                "{}\nWorkspace {} on {}",
+                window.title, window.workspace, window.output
+            )));
+            let input_tx = self.input_tx.clone();
+            let window_id = window.id;
+            button.connect_clicked(move |_| {
+                if let Err(e) = input_tx.send_input(InputCommand::Focus(window_id)) {
+                    warn!("Failed to send input command: {}", e);
+                }
+            });
+            self.container.append(&button);
+        }
+        self.icon_resolver = Some(icon_resolver);
+
+        self.window.set_visible(!windows.is_empty());
+        UI_RESOURCES.set_taskbar_mapped(!windows.is_empty());
+        debug!("Taskbar shows {} windows", windows.len());
+    }
+}
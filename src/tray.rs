@@ -0,0 +1,160 @@
+//! A StatusNotifierItem tray icon (e.g. for waybar's tray) showing the
+//! daemon's state, with a menu of quick actions. Each action is sent to
+//! the daemon as the IPC command the command line would send.
+
+use crate::config::SwitchTarget;
+use crate::event_bus::{DaemonEvent, EventSender};
+use crate::ipc::IpcCommand;
+use crate::socket_server::IpcRequest;
+use ksni::menu::{CheckmarkItem, RadioGroup, RadioItem, StandardItem};
+use ksni::{MenuItem, TrayMethods};
+use tokio::sync::{oneshot, watch};
+use tracing::{debug, info, warn};
+
+/// What the tray shows about the daemon
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TrayState {
+    pub paused: bool,
+    /// The active profile, None for the defaults
+    pub profile: Option<String>,
+    /// All profiles in the config file
+    pub profiles: Vec<String>,
+}
+
+struct Tray {
+    state: TrayState,
+    events_tx: EventSender,
+}
+
+impl Tray {
+    /// Run an IPC command; its reply only matters to the log
+    fn send(&self, command: IpcCommand) {
+        let (reply, _) = oneshot::channel();
+        let request = IpcRequest { command, reply };
+        if self.events_tx.send(DaemonEvent::Ipc(request)).is_err() {
+            debug!("The daemon is gone, dropping a tray action");
+        }
+    }
+}
+
+impl ksni::Tray for Tray {
+    fn id(&self) -> String {
+        env!("CARGO_PKG_NAME").into()
+    }
+
+    fn title(&self) -> String {
+        "Window switcher".into()
+    }
+
+    fn icon_name(&self) -> String {
+        "preferences-system-windows".into()
+    }
+
+    fn tool_tip(&self) -> ksni::ToolTip {
+        let profile = self.state.profile.as_deref().unwrap_or("default");
+        let description = if self.state.paused {
+            format!("Paused, profile {}", profile)
+        } else {
+            format!("Running, profile {}", profile)
+        };
+        ksni::ToolTip {
+            title: "sway-alttab-gui".into(),
+            description,
+            ..Default::default()
+        }
+    }
+
+    /// A left click opens the switcher
+    fn activate(&mut self, _x: i32, _y: i32) {
+        self.send(IpcCommand::Show {
+            what: SwitchTarget::Windows,
+            set: None,
+            skip: 0,
+        });
+    }
+
+    fn menu(&self) -> Vec<MenuItem<Self>> {
+        // The defaults come first, then each profile
+        let selected = self
+            .state
+            .profile
+            .as_ref()
+            .and_then(|name| self.state.profiles.iter().position(|p| p == name))
+            .map_or(0, |i| i + 1);
+        let options = std::iter::once("Default".to_string())
+            .chain(self.state.profiles.iter().cloned())
+            .map(|label| RadioItem {
+                label,
+                ..Default::default()
+            })
+            .collect();
+
+        vec![
+            CheckmarkItem {
+                label: "Paused".into(),
+                checked: self.state.paused,
+                activate: Box::new(|tray: &mut Self| {
+                    tray.send(if tray.state.paused {
+                        IpcCommand::Resume
+                    } else {
+                        IpcCommand::Pause
+                    });
+                }),
+                ..Default::default()
+            }
+            .into(),
+            MenuItem::Separator,
+            RadioGroup {
+                selected,
+                select: Box::new(|tray: &mut Self, index| {
+                    let name = index
+                        .checked_sub(1)
+                        .and_then(|i| tray.state.profiles.get(i).cloned());
+                    tray.send(IpcCommand::SetProfile { name });
+                }),
+                options,
+            }
+            .into(),
+            MenuItem::Separator,
+            StandardItem {
+                label: "Reload config".into(),
+                icon_name: "view-refresh".into(),
+                activate: Box::new(|tray: &mut Self| tray.send(IpcCommand::Reload)),
+                ..Default::default()
+            }
+            .into(),
+            StandardItem {
+                label: "Quit".into(),
+                icon_name: "application-exit".into(),
+                activate: Box::new(|tray: &mut Self| tray.send(IpcCommand::Shutdown)),
+                ..Default::default()
+            }
+            .into(),
+        ]
+    }
+}
+
+/// Register the tray icon and keep it in step with the daemon's state
+/// until the daemon stops
+pub async fn run(events_tx: EventSender, mut state: watch::Receiver<TrayState>) {
+    let tray = Tray {
+        state: state.borrow_and_update().clone(),
+        events_tx,
+    };
+    let handle = match tray.spawn().await {
+        Ok(handle) => handle,
+        Err(e) => {
+            warn!("Failed to register the tray icon: {}", e);
+            return;
+        }
+    };
+    info!("Tray icon registered");
+
+    while state.changed().await.is_ok() {
+        let new_state = state.borrow_and_update().clone();
+        if handle.update(|tray| tray.state = new_state).await.is_none() {
+            break;
+        }
+    }
+    handle.shutdown().await;
+}
@@ -0,0 +1,325 @@
+//! Terminal frontend: draws the switcher with ratatui in the terminal the
+//! daemon was started from and reads keys from it. Driven by the same
+//! `UiCommand`/`InputCommand` channels as the GTK window.
+
+use crate::config::SwitchTarget;
+use crate::event_bus::EventSender;
+use crate::ipc::{InputCommand, Trigger};
+use crate::ui_commands::UiCommand;
+use crate::window_manager::WindowInfo;
+use anyhow::Result;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use ratatui::style::{Modifier, Style};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, List, ListItem, ListState, Paragraph};
+use ratatui::{DefaultTerminal, Frame};
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tracing::warn;
+
+/// How long to wait for a key before checking for daemon commands again
+const POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+/// What the terminal currently shows
+#[derive(Debug, Default)]
+struct TuiState {
+    windows: Vec<WindowInfo>,
+    selected: usize,
+    target: SwitchTarget,
+    /// Index of the first window drawn after a separator line
+    separator: Option<usize>,
+    visible: bool,
+}
+
+impl TuiState {
+    /// Update the state from a daemon command.
+    /// Returns true if the switcher was just shown.
+    fn apply(&mut self, command: UiCommand) -> bool {
+        match command {
+            UiCommand::Show {
+                windows,
+                initial_index,
+                target,
+                separator,
+                ..
+            } => {
+                let shown = !self.visible;
+                self.selected = initial_index.min(windows.len().saturating_sub(1));
+                self.windows = windows;
+                self.target = target;
+                self.separator = separator;
+                self.visible = true;
+                shown
+            }
+            UiCommand::UpdateSelection { index, .. } => {
+                if index < self.windows.len() {
+                    self.selected = index;
+                }
+                false
+            }
+            UiCommand::Hide => {
+                self.visible = false;
+                false
+            }
+            // There are no icons to load, tiles to flash, badges, bars or
+            // notifications in a terminal
+            UiCommand::WrapCue
+            | UiCommand::WindowClosed { .. }
+            | UiCommand::UpdateGroupSizes(_)
+            | UiCommand::PreloadIcons { .. }
+            | UiCommand::UpdateDock { .. }
+            | UiCommand::UpdateTaskbar { .. } => false,
+        }
+    }
+}
+
+/// Run the terminal frontend until the user quits or the daemon goes away
+pub fn run(
+    mut ui_rx: mpsc::Receiver<UiCommand>,
+    input_tx: EventSender,
+) -> Result<()> {
+    let mut terminal = ratatui::try_init()?;
+    let result = event_loop(&mut terminal, &mut ui_rx, &input_tx);
+    ratatui::restore();
+    result
+}
+
+fn event_loop(
+    terminal: &mut DefaultTerminal,
+    ui_rx: &mut mpsc::Receiver<UiCommand>,
+    input_tx: &EventSender,
+) -> Result<()> {
+    let mut state = TuiState::default();
+    terminal.draw(|frame| draw(frame, &state))?;
+
+    loop {
+        let mut changed = false;
+        loop {
+            match ui_rx.try_recv() {
+                Ok(command) => {
+                    changed = true;
+                    if state.apply(command) {
+                        // Nothing to map in a terminal; keys work right away
+                        send_input_command(input_tx, InputCommand::Shown);
+                    }
+                }
+                Err(mpsc::error::TryRecvError::Empty) => break,
+                Err(mpsc::error::TryRecvError::Disconnected) => return Ok(()),
+            }
+        }
+
+        if event::poll(POLL_INTERVAL)? {
+            match event::read()? {
+                Event::Key(key) if key.kind != KeyEventKind::Release => {
+                    if is_quit(key, state.visible) {
+                        return Ok(());
+                    }
+                    if state.visible
+                        && let Some(command) = input_for_key(key)
+                    {
+                        send_input_command(input_tx, command);
+                    }
+                }
+                Event::Resize(..) => changed = true,
+                _ => {}
+            }
+        }
+
+        if changed {
+            terminal.draw(|frame| draw(frame, &state))?;
+        }
+    }
+}
+
+/// Ctrl+C always quits; q quits while the switcher is closed
+fn is_quit(key: KeyEvent, visible: bool) -> bool {
+    (key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL))
+        || (!visible && key.code == KeyCode::Char('q'))
+}
+
+/// Map a key to the same input commands as the GTK window.
+/// Terminals don't report Alt releases, so Enter selects.
+fn input_for_key(key: KeyEvent) -> Option<InputCommand> {
+    let command = match key.code {
+        KeyCode::Tab if key.modifiers.contains(KeyModifiers::SHIFT) => InputCommand::Prev,
+        KeyCode::Tab => InputCommand::Next,
+        KeyCode::BackTab => InputCommand::Prev,
+        KeyCode::Down => InputCommand::ExpandApp,
+        KeyCode::Up => InputCommand::CollapseApp,
+        KeyCode::Esc => InputCommand::Cancel,
+        KeyCode::Enter if key.modifiers.contains(KeyModifiers::CONTROL) => {
+            InputCommand::Trigger(Trigger::CtrlEnter)
+        }
+        KeyCode::Enter if key.modifiers.contains(KeyModifiers::SHIFT) => {
+            InputCommand::Trigger(Trigger::ShiftEnter)
+        }
+        KeyCode::Enter => InputCommand::Trigger(Trigger::Enter),
+        KeyCode::Char(c) if c.is_ascii_lowercase() => InputCommand::Key(c),
+        KeyCode::Char(c) => match c.to_digit(10) {
+            Some(steps) if steps > 0 => InputCommand::JumpBack(steps as usize),
+            _ => return None,
+        },
+        _ => return None,
+    };
+    Some(command)
+}
+
+fn draw(frame: &mut Frame, state: &TuiState) {
+    let area = frame.area();
+    if !state.visible {
+        let idle = Paragraph::new("Waiting for Alt+Tab (q to quit)")
+            .block(Block::bordered().title("sway-alttab-gui"));
+        frame.render_widget(idle, area);
+        return;
+    }
+
+    let mut items: Vec<ListItem> = state
+        .windows
+        .iter()
+        .map(|w| ListItem::new(row_text(w, state.target)))
+        .collect();
+    // The separator is a row of its own, so rows after it shift down one
+    let mut selected_row = state.selected;
+    if let Some(separator) = state.separator.filter(|&i| i < items.len()) {
+        items.insert(separator, ListItem::new("─".repeat(area.width as usize)));
+        if selected_row >= separator {
+            selected_row += 1;
+        }
+    }
+    let position = format!(" {} / {} ", state.selected + 1, state.windows.len());
+    let list = List::new(items)
+        .block(
+            Block::bordered()
+                .title("sway-alttab-gui")
+                .title_bottom(Line::from(position).right_aligned()),
+        )
+        .highlight_style(Style::default().add_modifier(Modifier::REVERSED))
+        .highlight_symbol("> ");
+    let mut list_state = ListState::default().with_selected(Some(selected_row));
+    frame.render_stateful_widget(list, area, &mut list_state);
+}
+
+/// One line per entry: what it stands for, then where it lives
+fn row_text(window: &WindowInfo, target: SwitchTarget) -> String {
+    let app = window.app_key().unwrap_or("?");
+    match target {
+        SwitchTarget::Windows => match window.label {
+            Some(ref label) => format!(
+                "{}: {}  [{}] ws {}",
+                label, window.title, app, window.workspace
+            ),
+            None => format!("{}  [{}] ws {}", window.title, app, window.workspace),
+        },
+        SwitchTarget::Apps => format!("{}  ({})", app, window.title),
+        SwitchTarget::Workspaces => format!("Workspace {}  ({})", window.workspace, window.title),
+    }
+}
+
+/// Send an input command to the daemon without blocking the terminal
+fn send_input_command(tx: &EventSender, cmd: InputCommand) {
+    if let Err(e) = tx.send_input(cmd) {
+        warn!("Failed to send input command: {}", e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use std::sync::Arc;
+
+    fn make_window(id: i64, title: &str) -> WindowInfo {
+        WindowInfo {
+            id,
+            app_id: Some(format!("app-{}", id)),
+            title: title.to_string(),
+            workspace: "1".to_string(),
+            output: "DP-1".to_string(),
+            window_class: None,
+            window_instance: None,
+            label: None,
+            last_focused: None,
+            ..Default::default()
+        }
+    }
+
+    fn show(windows: Vec<WindowInfo>, initial_index: usize) -> UiCommand {
+        UiCommand::Show {
+            windows,
+            initial_index,
+            wmclass_index: Arc::new(HashMap::new()),
+            layout: Default::default(),
+            target: SwitchTarget::Windows,
+            separator: None,
+            output_size: None,
+            refreshing: false,
+            group_sizes: Vec::new(),
+        }
+    }
+
+    fn key(code: KeyCode, modifiers: KeyModifiers) -> KeyEvent {
+        KeyEvent::new(code, modifiers)
+    }
+
+    // ==================== TuiState tests ====================
+
+    #[test]
+    fn test_apply_show_and_hide() {
+        let mut state = TuiState::default();
+        assert!(state.apply(show(vec![make_window(1, "a"), make_window(2, "b")], 1)));
+        assert!(state.visible);
+        assert_eq!(state.selected, 1);
+
+        // Showing again while visible (e.g. expanding an app) is not a new show
+        assert!(!state.apply(show(vec![make_window(1, "a")], 0)));
+
+        state.apply(UiCommand::Hide);
+        assert!(!state.visible);
+    }
+
+    #[test]
+    fn test_apply_selection_out_of_range_is_ignored() {
+        let mut state = TuiState::default();
+        state.apply(show(vec![make_window(1, "a"), make_window(2, "b")], 0));
+
+        state.apply(UiCommand::UpdateSelection { index: 1, len: 2 });
+        assert_eq!(state.selected, 1);
+        state.apply(UiCommand::UpdateSelection { index: 5, len: 2 });
+        assert_eq!(state.selected, 1);
+    }
+
+    // ==================== key mapping tests ====================
+
+    #[test]
+    fn test_input_for_key() {
+        assert_eq!(
+            input_for_key(key(KeyCode::Tab, KeyModifiers::NONE)),
+            Some(InputCommand::Next)
+        );
+        assert_eq!(
+            input_for_key(key(KeyCode::BackTab, KeyModifiers::SHIFT)),
+            Some(InputCommand::Prev)
+        );
+        assert_eq!(
+            input_for_key(key(KeyCode::Enter, KeyModifiers::CONTROL)),
+            Some(InputCommand::Trigger(Trigger::CtrlEnter))
+        );
+        assert_eq!(
+            input_for_key(key(KeyCode::Char('3'), KeyModifiers::NONE)),
+            Some(InputCommand::JumpBack(3))
+        );
+        assert_eq!(input_for_key(key(KeyCode::Char('0'), KeyModifiers::NONE)), None);
+        assert_eq!(
+            input_for_key(key(KeyCode::Char('g'), KeyModifiers::NONE)),
+            Some(InputCommand::Key('g'))
+        );
+        assert_eq!(input_for_key(key(KeyCode::Char('!'), KeyModifiers::NONE)), None);
+    }
+
+    #[test]
+    fn test_is_quit() {
+        assert!(is_quit(key(KeyCode::Char('c'), KeyModifiers::CONTROL), true));
+        assert!(is_quit(key(KeyCode::Char('q'), KeyModifiers::NONE), false));
+        assert!(!is_quit(key(KeyCode::Char('q'), KeyModifiers::NONE), true));
+    }
+}
@@ -1,34 +1,121 @@
+use crate::config::{
+    Appearance, CustomAction, HighContrast, KeyAction, KeyBindings, KeyboardConfig, Placement,
+    PreviewConfig, ReducedMotion,
+    SwitchTarget,
+};
+use crate::event_bus::EventSender;
+use crate::focus_history::format_age;
 use crate::icon_resolver::{IconResolver, WmClassIndex};
-use crate::ipc::InputCommand;
-use crate::window_manager::WindowInfo;
-use gtk4::gdk::Key;
+use crate::ipc::{Direction, InputCommand, TileAction, Trigger};
+use crate::ui_commands::{preview_path, SwitcherLayout, UI_RESOURCES};
+use crate::window_manager::{workspace_number, WindowInfo};
+use gtk4::gdk::{Key, ModifierType};
 use gtk4::prelude::*;
 use gtk4::{
-    Application, ApplicationWindow, Box as GtkBox, EventControllerKey, Image, Label, Orientation,
-    Widget,
+    Align, Application, ApplicationWindow, Box as GtkBox, Button, DragSource, DropTarget,
+    EventControllerKey, EventControllerScroll, EventControllerScrollFlags, GestureClick, Image,
+    Label, Orientation, Overlay, Picture, PolicyType, Popover, ScrolledWindow, Widget,
 };
 use gtk4_layer_shell::{Edge, KeyboardMode, Layer, LayerShell};
-use tokio::sync::mpsc;
+use std::cell::Cell;
+use std::fmt::Write;
+use std::rc::Rc;
+use std::time::{Duration, SystemTime};
 use tracing::{debug, info, warn};
 
 const ICON_SIZE: i32 = 64;
 const WINDOW_PADDING: i32 = 25;
 const TILE_PADDING: i32 = 10;
 const MAX_TITLE_LENGTH: usize = 20;
+const LIST_ICON_SIZE: i32 = 32;
+const MAX_LIST_TITLE_LENGTH: usize = 60;
+
+/// GTK application ID, which is also the switcher window's app_id
+pub const APPLICATION_ID: &str = "com.github.itsjfx.sway-alttab-gui-gui";
+const PREVIEW_WIDTH: i32 = 320;
+const PREVIEW_HEIGHT: i32 = 180;
+
+/// Share of the output's logical size the tiles may take up before they scroll
+const MAX_OUTPUT_FRACTION: f64 = 0.9;
+
+/// Entries of a tile's context menu; `None` copies the title, which the UI
+/// does itself
+const TILE_MENU: [(&str, Option<TileAction>); 6] = [
+    ("Focus", Some(TileAction::Focus)),
+    ("Move here", Some(TileAction::MoveHere)),
+    ("Float", Some(TileAction::Float)),
+    ("Close", Some(TileAction::Close)),
+    ("Unpin", Some(TileAction::Unpin)),
+    ("Copy title", None),
+];
+
+/// How long the selected tile stays flashed after cycling wraps
+const WRAP_CUE_DURATION: Duration = Duration::from_millis(150);
+
+/// Font size of the position indicator at `position-scale = 1`, in em;
+/// what CSS's `smaller` gives
+const POSITION_FONT_EM: f64 = 0.83;
+
+/// Opacity of a tile's tint, faint enough to keep labels readable
+const TINT_OPACITY: f64 = 0.3;
+
+/// How much larger the high-contrast style makes the tile labels
+const HIGH_CONTRAST_LABEL_SCALE: f64 = 1.2;
 
 pub struct SwitcherWindow {
     window: ApplicationWindow,
     container: GtkBox,
+    /// Scrolls the tiles when they don't fit on the output
+    scroller: ScrolledWindow,
+    /// Whether the window is a layer surface, which grabs the keyboard itself
+    layer_shell: bool,
+    /// Shows the selected position and the total, e.g. "3 / 12"
+    position: Label,
+    /// Whether the list shown is the last known one, waiting for a fresh one
+    refreshing: bool,
+    /// Last captured thumbnail of the selected window, if previews are on
+    preview: Option<Picture>,
     windows: Vec<WindowInfo>,
     current_index: usize,
     tiles: Vec<Widget>,
+    /// How many windows each tile stands for in a grouped list, shown as
+    /// a badge on its icon; empty when not grouped
+    group_sizes: Vec<usize>,
+    /// The counts for the next show, set just before it
+    next_group_sizes: Vec<usize>,
+    /// How the tiles shown were arranged, so a show of the same
+    /// arrangement while visible can update them in place
+    arrangement: Option<(SwitcherLayout, SwitchTarget, Option<usize>)>,
+    /// Shared across shows so its caches persist for the daemon lifetime.
+    /// Created on the first show, when the WMClass index is available.
+    icon_resolver: Option<IconResolver>,
+    /// Set when the user's icon theme changes, so caches are dropped on next show
+    icon_theme_changed: Rc<Cell<bool>>,
+    /// Caption tiles with when the window was last focused
+    last_used: bool,
+    /// Dim windows that weren't focused for this long
+    dim_idle_after: Option<Duration>,
+    /// Show each window's workspace number on its tile
+    workspace_numbers: bool,
+    /// For the tiles' context menus
+    input_tx: InputSender,
 }
 
-/// Sender type for input commands to daemon
-pub type InputSender = mpsc::UnboundedSender<InputCommand>;
+/// Sender type for input commands to daemon: its event bus, which is
+/// unbounded, so a key press or a lifecycle event is never dropped while
+/// the daemon is busy
+pub type InputSender = EventSender;
 
 impl SwitcherWindow {
-    pub fn new(app: &Application, input_tx: InputSender) -> Self {
+    pub fn new(
+        app: &Application,
+        input_tx: InputSender,
+        appearance: &Appearance,
+        keyboard: &KeyboardConfig,
+        custom_actions: &[CustomAction],
+        preview: &PreviewConfig,
+        placement: Placement,
+    ) -> Self {
         let window = ApplicationWindow::builder()
             .application(app)
             .title("Window Switcher")
@@ -38,71 +125,164 @@ impl SwitcherWindow {
             .resizable(false)
             .build();
 
-        // Initialize layer shell
-        window.init_layer_shell();
-        window.set_layer(Layer::Overlay);
-        window.set_keyboard_mode(KeyboardMode::Exclusive);
+        let placement = if placement == Placement::LayerShell && !gtk4_layer_shell::is_supported() {
+            warn!("Layer shell is not supported here, using a floating window");
+            Placement::Floating
+        } else {
+            placement
+        };
 
-        // Center the window
-        window.set_anchor(Edge::Top, false);
-        window.set_anchor(Edge::Bottom, false);
-        window.set_anchor(Edge::Left, false);
-        window.set_anchor(Edge::Right, false);
+        match placement {
+            Placement::LayerShell => {
+                // Initialize layer shell
+                window.init_layer_shell();
+                window.set_namespace(&appearance.namespace);
+                window.set_layer(Layer::Overlay);
+                // The keyboard is only grabbed while shown
+                window.set_keyboard_mode(KeyboardMode::None);
+
+                // Center the window
+                window.set_anchor(Edge::Top, false);
+                window.set_anchor(Edge::Bottom, false);
+                window.set_anchor(Edge::Left, false);
+                window.set_anchor(Edge::Right, false);
+            }
+            // Sway places it by its app_id (see `sway_rules`)
+            Placement::Floating => info!("Using a floating window"),
+        }
+
+        let custom_keys = custom_action_keys(custom_actions);
+        let bindings = Rc::new(key_bindings(&keyboard.bindings));
 
         // Setup keyboard event controller
         let key_controller = EventControllerKey::new();
         let tx_pressed = input_tx.clone();
-        key_controller.connect_key_pressed(move |_controller, keyval, _keycode, state| {
-            debug!("Key pressed: {:?}, state: {:?}", keyval, state);
+        let match_keycodes = keyboard.match_keycodes;
+        let pressed_bindings = bindings.clone();
+        key_controller.connect_key_pressed(move |_controller, keyval, keycode, state| {
+            debug!("Key pressed: {:?} ({}), state: {:?}", keyval, keycode, state);
+            let keyval = resolve_key(keyval, keycode, match_keycodes);
 
-            match keyval {
-                Key::Tab => {
-                    // Check if Shift is held
-                    if state.contains(gtk4::gdk::ModifierType::SHIFT_MASK) {
-                        debug!("Shift+Tab pressed, sending prev");
-                        send_input_command(&tx_pressed, InputCommand::Prev);
-                    } else {
-                        debug!("Tab pressed, sending next");
-                        send_input_command(&tx_pressed, InputCommand::Next);
-                    }
-                    gtk4::glib::Propagation::Stop
-                }
-                Key::ISO_Left_Tab => {
-                    // Shift+Tab often generates ISO_Left_Tab
-                    debug!("ISO_Left_Tab (Shift+Tab) pressed, sending prev");
-                    send_input_command(&tx_pressed, InputCommand::Prev);
-                    gtk4::glib::Propagation::Stop
+            // Bound keys come first, so they can take over any other key
+            match bound_action(&pressed_bindings, keyval.to_lower(), keycode, state) {
+                Some(KeyAction::Release) => return gtk4::glib::Propagation::Stop,
+                Some(action) => {
+                    let command = match action {
+                        KeyAction::Next => InputCommand::Next,
+                        KeyAction::Prev => InputCommand::Prev,
+                        KeyAction::Cancel => InputCommand::Cancel,
+                        KeyAction::Select | KeyAction::Release => {
+                            let trigger = if state.contains(ModifierType::CONTROL_MASK) {
+                                Trigger::CtrlEnter
+                            } else if state.contains(ModifierType::SHIFT_MASK) {
+                                Trigger::ShiftEnter
+                            } else {
+                                Trigger::Enter
+                            };
+                            InputCommand::Trigger(trigger)
+                        }
+                    };
+                    debug!("{:?} pressed, sending {}", keyval, command);
+                    send_input_command(&tx_pressed, command);
+                    return gtk4::glib::Propagation::Stop;
                 }
-                Key::Escape => {
-                    debug!("Escape pressed, sending cancel");
-                    send_input_command(&tx_pressed, InputCommand::Cancel);
+                None => {}
+            }
+
+            match keyval {
+                // What arrows do depends on the layout, which the daemon decides
+                Key::Up | Key::Down | Key::Left | Key::Right => {
+                    let direction = match keyval {
+                        Key::Up => Direction::Up,
+                        Key::Down => Direction::Down,
+                        Key::Left => Direction::Left,
+                        _ => Direction::Right,
+                    };
+                    debug!("{:?} pressed, sending arrow", keyval);
+                    send_input_command(&tx_pressed, InputCommand::Arrow(direction));
                     gtk4::glib::Propagation::Stop
                 }
-                Key::Return | Key::KP_Enter => {
-                    debug!("Enter pressed, sending select");
-                    send_input_command(&tx_pressed, InputCommand::Select);
-                    gtk4::glib::Propagation::Stop
+                _ => {
+                    if let Some(&(_, index)) =
+                        custom_keys.iter().find(|(key, _)| *key == keyval.to_lower())
+                    {
+                        debug!("{:?} pressed, sending custom action {}", keyval, index);
+                        send_input_command(&tx_pressed, InputCommand::Custom(index));
+                        return gtk4::glib::Propagation::Stop;
+                    }
+                    // Digits jump back exactly that many steps in the focus history
+                    if let Some(steps) = keyval.to_unicode().and_then(|c| c.to_digit(10))
+                        && steps > 0
+                    {
+                        debug!("{} pressed, sending jump back", steps);
+                        send_input_command(&tx_pressed, InputCommand::JumpBack(steps as usize));
+                        return gtk4::glib::Propagation::Stop;
+                    }
+                    // Other letters go to the daemon's key sequences
+                    if let Some(letter) = keyval.to_unicode().filter(char::is_ascii_lowercase) {
+                        debug!("{} pressed, sending key", letter);
+                        send_input_command(&tx_pressed, InputCommand::Key(letter));
+                        return gtk4::glib::Propagation::Stop;
+                    }
+                    gtk4::glib::Propagation::Proceed
                 }
-                _ => gtk4::glib::Propagation::Proceed,
             }
         });
 
-        // Detect Alt release
-        let tx_released = input_tx;
-        key_controller.connect_key_released(move |_controller, keyval, _keycode, _state| {
-            debug!("Key released: {:?}", keyval);
+        // Detect Alt release, or whichever keys are bound to release
+        let tx_released = input_tx.clone();
+        key_controller.connect_key_released(move |_controller, keyval, keycode, state| {
+            debug!("Key released: {:?} ({})", keyval, keycode);
+            let keyval = resolve_key(keyval, keycode, match_keycodes);
 
-            match keyval {
-                Key::Alt_L | Key::Alt_R => {
-                    debug!("Alt released, sending select");
-                    send_input_command(&tx_released, InputCommand::Select);
-                }
-                _ => {}
+            let action = bound_action(&bindings, keyval.to_lower(), keycode, state);
+            if action == Some(KeyAction::Release) {
+                let trigger = if state.contains(ModifierType::SHIFT_MASK) {
+                    Trigger::ShiftRelease
+                } else {
+                    Trigger::Release
+                };
+                debug!("{:?} released ({:?}), sending trigger", keyval, trigger);
+                send_input_command(&tx_released, InputCommand::Trigger(trigger));
             }
         });
 
         window.add_controller(key_controller);
 
+        // The mouse wheel cycles like Tab and Shift+Tab. Captured before the
+        // scroller, which follows the selection anyway.
+        let scroll_controller = EventControllerScroll::new(
+            EventControllerScrollFlags::BOTH_AXES | EventControllerScrollFlags::DISCRETE,
+        );
+        scroll_controller.set_propagation_phase(gtk4::PropagationPhase::Capture);
+        let tx_scroll = input_tx.clone();
+        scroll_controller.connect_scroll(move |_, dx, dy| {
+            let Some(forward) = scroll_direction(dx, dy) else {
+                return gtk4::glib::Propagation::Proceed;
+            };
+            debug!("Scrolled, sending {}", if forward { "next" } else { "prev" });
+            let command = if forward {
+                InputCommand::Next
+            } else {
+                InputCommand::Prev
+            };
+            send_input_command(&tx_scroll, command);
+            gtk4::glib::Propagation::Stop
+        });
+        window.add_controller(scroll_controller);
+
+        // Let the daemon know once the surface is up and taking keys
+        let tx_mapped = input_tx.clone();
+        window.connect_map(move |_| {
+            debug!("Switcher mapped");
+            UI_RESOURCES.set_switcher_mapped(true);
+            send_input_command(&tx_mapped, InputCommand::Shown);
+        });
+        window.connect_unmap(|_| {
+            debug!("Switcher unmapped");
+            UI_RESOURCES.set_switcher_mapped(false);
+        });
+
         // Create horizontal container for window tiles
         let container = GtkBox::new(Orientation::Horizontal, TILE_PADDING);
         container.set_margin_start(WINDOW_PADDING);
@@ -112,23 +292,115 @@ impl SwitcherWindow {
         container.set_halign(gtk4::Align::Center);
         container.set_valign(gtk4::Align::Center);
 
-        window.set_child(Some(&container));
+        let position = Label::new(None);
+        position.add_css_class("position");
+        position.set_margin_bottom(TILE_PADDING);
+
+        let chrome = GtkBox::new(Orientation::Vertical, 0);
+        let preview = preview.enabled.then(|| {
+            let picture = Picture::new();
+            picture.set_content_fit(gtk4::ContentFit::Contain);
+            picture.set_size_request(PREVIEW_WIDTH, PREVIEW_HEIGHT);
+            picture.set_margin_top(WINDOW_PADDING);
+            picture.set_visible(false);
+            chrome.append(&picture);
+            picture
+        });
+        let scroller = ScrolledWindow::builder()
+            .hscrollbar_policy(PolicyType::Automatic)
+            .vscrollbar_policy(PolicyType::Automatic)
+            .propagate_natural_width(true)
+            .propagate_natural_height(true)
+            .child(&container)
+            .build();
+        chrome.append(&scroller);
+        chrome.append(&position);
+        window.set_child(Some(&chrome));
+
+        // Invalidate cached icons when the icon theme changes
+        let icon_theme_changed = Rc::new(Cell::new(false));
+        if let Some(settings) = gtk4::Settings::default() {
+            let icon_theme_changed = icon_theme_changed.clone();
+            settings.connect_gtk_icon_theme_name_notify(move |_| {
+                info!("Icon theme changed");
+                icon_theme_changed.set(true);
+            });
+
+            // GTK follows the desktop's reduced motion preference (via the
+            // settings portal) through gtk-enable-animations
+            let mode = appearance.reduced_motion;
+            let enabled = settings.is_gtk_enable_animations();
+            set_reduced_motion(&window, wants_reduced_motion(mode, enabled));
+            let window = window.clone();
+            settings.connect_gtk_enable_animations_notify(move |settings| {
+                let enabled = settings.is_gtk_enable_animations();
+                info!("Animations {}", if enabled { "enabled" } else { "disabled" });
+                set_reduced_motion(&window, wants_reduced_motion(mode, enabled));
+            });
+        }
+        follow_high_contrast(&window, appearance.high_contrast);
 
         SwitcherWindow {
             window,
             container,
+            scroller,
+            layer_shell: placement == Placement::LayerShell,
+            position,
+            refreshing: false,
+            preview,
             windows: Vec::new(),
             current_index: 0,
             tiles: Vec::new(),
+            group_sizes: Vec::new(),
+            next_group_sizes: Vec::new(),
+            arrangement: None,
+            icon_resolver: None,
+            icon_theme_changed,
+            last_used: appearance.last_used,
+            dim_idle_after: (appearance.dim_idle_after > 0)
+                .then(|| Duration::from_secs(appearance.dim_idle_after * 60)),
+            workspace_numbers: appearance.workspace_numbers,
+            input_tx,
         }
     }
 
     /// Pre-realize the window to avoid slow first show.
     /// This creates the Wayland surface and layer shell setup without displaying anything.
-    pub fn warm_up(&self) {
+    /// Also lays out a sample tile in the unshown window, so the tile
+    /// styles, fonts and fallback icon are ready too; the first show
+    /// clears it away with the rest of the container.
+    pub fn warm_up(&mut self, wmclass_index: WmClassIndex) {
+        let mut icon_resolver = self.take_icon_resolver(wmclass_index);
+        let sample = WindowInfo {
+            title: "Window".to_string(),
+            ..Default::default()
+        };
+        let tile = self.create_window_tile(&sample, SwitchTarget::Windows, 1, &mut icon_resolver);
+        self.icon_resolver = Some(icon_resolver);
+        self.container.append(&tile);
+
         // Realize creates the underlying GDK surface without showing
         gtk4::prelude::WidgetExt::realize(&self.window);
-        info!("Window pre-realized for faster first show");
+        // Measuring resolves the tile's CSS and lays out its label
+        let _ = tile.measure(Orientation::Horizontal, -1);
+        let _ = tile.measure(Orientation::Vertical, -1);
+        info!("Window and a sample tile pre-realized for faster first show");
+    }
+
+    /// Limit the tiles to the output they appear on, given its size in
+    /// logical pixels (rotated and scaled, as surfaces are laid out).
+    /// Without a size the tiles are never limited.
+    pub fn fit_to_output(&self, output_size: Option<(i32, i32)>) {
+        let preview_height = if self.preview.is_some() { PREVIEW_HEIGHT } else { 0 };
+        let (width, height) = match output_size {
+            Some((width, height)) => (
+                max_content_size(width),
+                max_content_size(height) - preview_height,
+            ),
+            None => (-1, -1),
+        };
+        self.scroller.set_max_content_width(width);
+        self.scroller.set_max_content_height(height);
     }
 
     /// Show the window switcher with a list of windows
@@ -137,10 +409,41 @@ impl SwitcherWindow {
         windows: Vec<WindowInfo>,
         initial_index: usize,
         wmclass_index: WmClassIndex,
+        layout: SwitcherLayout,
+        target: SwitchTarget,
+        separator: Option<usize>,
     ) {
-        self.windows = windows;
+        let old_windows = std::mem::replace(&mut self.windows, windows);
+        let next_group_sizes = std::mem::take(&mut self.next_group_sizes);
+        let old_group_sizes = std::mem::replace(&mut self.group_sizes, next_group_sizes);
         self.current_index = initial_index.min(self.windows.len().saturating_sub(1));
 
+        // Rebuilding a visible switcher (e.g. a show right after a cancel, or
+        // a fresh list replacing the cached one) would flicker, so keep the
+        // window up and swap only the tiles that changed
+        let arrangement = (layout, target, separator);
+        let in_place = self.window.is_visible()
+            && self.arrangement == Some(arrangement)
+            && separator.is_none_or(|i| i < self.windows.len())
+            && (!matches!(layout, SwitcherLayout::Grid { .. } | SwitcherLayout::Split { .. })
+                || old_windows.len() == self.windows.len());
+        self.arrangement = Some(arrangement);
+
+        // Reuse the icon resolver (and its caches) from previous shows
+        let mut icon_resolver = self.take_icon_resolver(wmclass_index);
+
+        if in_place {
+            info!("Updating the {} shown windows in place", self.windows.len());
+            self.update_tiles(&old_windows, &old_group_sizes, layout, target, &mut icon_resolver);
+            self.icon_resolver = Some(icon_resolver);
+            self.update_position();
+            self.update_preview();
+            self.scroll_to_selection();
+            // Nothing gets mapped, but the daemon may be waiting for it
+            send_input_command(&self.input_tx, InputCommand::Shown);
+            return;
+        }
+
         info!("Building UI for {} windows", self.windows.len());
 
         // Clear existing tiles
@@ -149,43 +452,222 @@ impl SwitcherWindow {
         }
         self.tiles.clear();
 
-        // Create icon resolver with the pre-built WMClass index
-        let mut icon_resolver = IconResolver::with_wmclass_index(ICON_SIZE, wmclass_index);
+        self.container.set_orientation(match layout {
+            SwitcherLayout::Row => Orientation::Horizontal,
+            SwitcherLayout::AppList
+            | SwitcherLayout::Grid { .. }
+            | SwitcherLayout::Split { .. } => Orientation::Vertical,
+        });
+        let grid = match layout {
+            SwitcherLayout::Grid { columns } => {
+                let grid = gtk4::Grid::new();
+                grid.set_row_spacing(TILE_PADDING as u32);
+                grid.set_column_spacing(TILE_PADDING as u32);
+                grid.set_column_homogeneous(true);
+                self.container.append(&grid);
+                Some((grid, columns))
+            }
+            _ => None,
+        };
+        let rows = match layout {
+            SwitcherLayout::Split { first, columns } => {
+                let (top, rest) = (split_section(), split_section());
+                self.container.append(&top);
+                self.container
+                    .append(&gtk4::Separator::new(Orientation::Horizontal));
+                self.container.append(&rest);
+                Some((top, rest, first, columns))
+            }
+            _ => None,
+        };
 
         // Create tiles for each window
         for (i, window) in self.windows.iter().enumerate() {
-            let tile = self.create_window_tile(window, &mut icon_resolver);
+            let tile = self.build_tile(i, window, layout, target, &mut icon_resolver);
+
+            if separator == Some(i) {
+                self.container
+                    .append(&gtk4::Separator::new(Orientation::Vertical));
+            }
 
             // Highlight the selected tile
             if i == self.current_index {
                 self.highlight_tile(&tile);
             }
 
-            self.container.append(&tile);
+            if let Some((ref grid, columns)) = grid {
+                let (column, row) = (i % columns, i / columns);
+                grid.attach(&tile, column as i32, row as i32, 1, 1);
+            } else if let Some((ref top, ref rest, first, columns)) = rows {
+                let (section, i) = if i < first { (top, i) } else { (rest, i - first) };
+                let (column, row) = if columns == 0 {
+                    (i, 0)
+                } else {
+                    (i % columns, i / columns)
+                };
+                section.attach(&tile, column as i32, row as i32, 1, 1);
+            } else {
+                self.container.append(&tile);
+            }
             self.tiles.push(tile);
         }
+        self.icon_resolver = Some(icon_resolver);
+        self.update_position();
+        self.update_preview();
 
         info!("Presenting window...");
+        if self.layer_shell {
+            self.window.set_keyboard_mode(KeyboardMode::Exclusive);
+            UI_RESOURCES.set_keyboard_grab(true);
+        }
         self.window.set_visible(true);
         self.window.present();
         info!("Window presented, is_visible={}", self.window.is_visible());
     }
 
-    fn create_window_tile(&self, window: &WindowInfo, icon_resolver: &mut IconResolver) -> Widget {
+    /// Replace the tiles of windows that differ from `old_windows` at the
+    /// same position, keeping the others and the window as they are
+    fn update_tiles(
+        &mut self,
+        old_windows: &[WindowInfo],
+        old_group_sizes: &[usize],
+        layout: SwitcherLayout,
+        target: SwitchTarget,
+        icon_resolver: &mut IconResolver,
+    ) {
+        let old_tiles = std::mem::take(&mut self.tiles);
+        let mut reusable = reusable_tiles(old_windows, &self.windows);
+        for (i, reused) in reusable.iter_mut().enumerate() {
+            *reused &= old_group_sizes.get(i) == self.group_sizes.get(i);
+        }
+        let mut tiles = Vec::with_capacity(self.windows.len());
+        for (i, window) in self.windows.iter().enumerate() {
+            let tile = match old_tiles.get(i) {
+                Some(old) if reusable[i] => {
+                    self.unhighlight_tile(old);
+                    old.clone()
+                }
+                Some(old) => {
+                    let tile = self.build_tile(i, window, layout, target, icon_resolver);
+                    replace_tile(old, &tile);
+                    tile
+                }
+                None => {
+                    let tile = self.build_tile(i, window, layout, target, icon_resolver);
+                    self.container.append(&tile);
+                    tile
+                }
+            };
+            if i == self.current_index {
+                self.highlight_tile(&tile);
+            }
+            tiles.push(tile);
+        }
+        for stale in old_tiles.iter().skip(tiles.len()) {
+            self.container.remove(stale);
+        }
+        let rebuilt = reusable.iter().filter(|reused| !**reused).count();
+        debug!("Rebuilt {} of {} tiles", rebuilt, tiles.len());
+        self.tiles = tiles;
+    }
+
+    /// Create the tile for the window at `index`, with its context menu
+    /// and drag and drop
+    fn build_tile(
+        &self,
+        index: usize,
+        window: &WindowInfo,
+        layout: SwitcherLayout,
+        target: SwitchTarget,
+        icon_resolver: &mut IconResolver,
+    ) -> Widget {
+        let tile = match layout {
+            SwitcherLayout::Row | SwitcherLayout::Grid { .. } | SwitcherLayout::Split { .. } => {
+                let group_size = self.group_sizes.get(index).copied().unwrap_or(1);
+                self.create_window_tile(window, target, group_size, icon_resolver)
+            }
+            SwitcherLayout::AppList => self.create_list_row(window, icon_resolver),
+        };
+        self.add_click_to_select(&tile, index);
+        self.add_context_menu(&tile, index, &window.title);
+        if layout != SwitcherLayout::AppList && target == SwitchTarget::Windows {
+            self.add_drag_and_drop(&tile, index);
+        }
+        tile
+    }
+
+    /// Take the shared icon resolver, creating it on first use and
+    /// invalidating its caches if the index or icon theme changed. Icons
+    /// are loaded at the switcher surface's scale.
+    /// Callers must put it back in `self.icon_resolver` when done.
+    fn take_icon_resolver(&mut self, wmclass_index: WmClassIndex) -> IconResolver {
+        let mut resolver = match self.icon_resolver.take() {
+            Some(mut resolver) => {
+                resolver.set_wmclass_index(wmclass_index);
+                if self.icon_theme_changed.replace(false) {
+                    resolver.invalidate();
+                }
+                resolver
+            }
+            None => IconResolver::with_wmclass_index(ICON_SIZE, wmclass_index),
+        };
+        resolver.set_scale(self.window.scale_factor());
+        resolver
+    }
+
+    /// Resolve icons for the given windows ahead of time so the next show
+    /// finds them in the resolver's caches.
+    pub fn preload_icons(&mut self, windows: &[WindowInfo], wmclass_index: WmClassIndex) {
+        let mut icon_resolver = self.take_icon_resolver(wmclass_index);
+        for window in windows {
+            let _ = icon_resolver.resolve_window_icon(window);
+        }
+        self.icon_resolver = Some(icon_resolver);
+        debug!("Preloaded icons for {} windows", windows.len());
+    }
+
+    fn create_window_tile(
+        &self,
+        window: &WindowInfo,
+        target: SwitchTarget,
+        group_size: usize,
+        icon_resolver: &mut IconResolver,
+    ) -> Widget {
         let vbox = GtkBox::new(Orientation::Vertical, 5);
         vbox.set_margin_start(TILE_PADDING);
         vbox.set_margin_end(TILE_PADDING);
+        if target != SwitchTarget::Workspaces {
+            self.tint(&vbox, window);
+        }
+        if self.workspace_numbers && target == SwitchTarget::Windows {
+            let number = workspace_number(&window.workspace)
+                .map_or_else(|| window.workspace.clone(), |n| n.to_string());
+            let badge = Label::new(Some(&number));
+            badge.add_css_class("workspace-number");
+            vbox.append(&badge);
+        }
 
-        // Add icon - try app_id first, then window_class, then fallback
+        // Add icon - a rule's first, then app_id, then WM_CLASS class/instance, then fallback
         let pixbuf = icon_resolver
-            .resolve_icon(window.app_id.as_deref())
-            .or_else(|| icon_resolver.resolve_icon(window.window_class.as_deref()))
+            .resolve_window_icon(window)
             .or_else(|| icon_resolver.get_fallback_icon());
 
         if let Some(pb) = pixbuf {
             let icon = Image::from_pixbuf(Some(&pb));
             icon.set_pixel_size(ICON_SIZE);
-            vbox.append(&icon);
+            if group_size > 1 {
+                // How many windows a grouped entry stands for, on its icon
+                let overlay = Overlay::new();
+                overlay.set_child(Some(&icon));
+                let badge = Label::new(Some(&group_size.to_string()));
+                badge.add_css_class("group-count");
+                badge.set_halign(Align::End);
+                badge.set_valign(Align::Start);
+                overlay.add_overlay(&badge);
+                vbox.append(&overlay);
+            } else {
+                vbox.append(&icon);
+            }
         } else {
             // Absolute fallback: just a placeholder label
             let placeholder = Label::new(Some("□"));
@@ -195,15 +677,160 @@ impl SwitcherWindow {
         }
 
         // Add title
-        let title = truncate_string(&window.title, MAX_TITLE_LENGTH);
+        let title = truncate_string(&tile_label(window, target), MAX_TITLE_LENGTH);
         let label = Label::new(Some(&title));
         label.set_ellipsize(gtk4::pango::EllipsizeMode::End);
         label.set_max_width_chars(MAX_TITLE_LENGTH as i32);
+        label.add_css_class("tile-label");
+        if target == SwitchTarget::Windows && window.label.is_some() {
+            label.add_css_class("window-label");
+        }
         vbox.append(&label);
+        self.show_age(&vbox, window);
 
         vbox.upcast()
     }
 
+    /// Create a row for the per-application list: small icon and full title
+    fn create_list_row(&self, window: &WindowInfo, icon_resolver: &mut IconResolver) -> Widget {
+        let hbox = GtkBox::new(Orientation::Horizontal, TILE_PADDING);
+        hbox.set_margin_start(TILE_PADDING);
+        hbox.set_margin_end(TILE_PADDING);
+        self.tint(&hbox, window);
+
+        let pixels = LIST_ICON_SIZE * icon_resolver.scale();
+        let pixbuf = icon_resolver
+            .resolve_window_icon(window)
+            .or_else(|| icon_resolver.get_fallback_icon())
+            .and_then(|pb| pb.scale_simple(pixels, pixels, gtk4::gdk_pixbuf::InterpType::Bilinear));
+        if let Some(pb) = pixbuf {
+            let icon = Image::from_pixbuf(Some(&pb));
+            icon.set_pixel_size(LIST_ICON_SIZE);
+            hbox.append(&icon);
+        }
+
+        if let Some(ref nickname) = window.label {
+            let nickname = Label::new(Some(nickname));
+            nickname.add_css_class("window-label");
+            nickname.add_css_class("tile-label");
+            hbox.append(&nickname);
+        }
+
+        let title = truncate_string(&window.title, MAX_LIST_TITLE_LENGTH);
+        let label = Label::new(Some(&title));
+        label.set_xalign(0.0);
+        label.set_ellipsize(gtk4::pango::EllipsizeMode::End);
+        label.set_max_width_chars(MAX_LIST_TITLE_LENGTH as i32);
+        label.add_css_class("tile-label");
+        hbox.append(&label);
+        self.show_age(&hbox, window);
+
+        hbox.upcast()
+    }
+
+    /// Focus the tile's window on left click
+    fn add_click_to_select(&self, tile: &Widget, index: usize) {
+        let click = GestureClick::new();
+        click.set_button(gtk4::gdk::BUTTON_PRIMARY);
+        let input_tx = self.input_tx.clone();
+        // On release, so starting to drag the tile doesn't select it
+        click.connect_released(move |gesture, _, _, _| {
+            gesture.set_state(gtk4::EventSequenceState::Claimed);
+            send_input_command(&input_tx, InputCommand::SelectIndex(index));
+        });
+        tile.add_controller(click);
+    }
+
+    /// Open a menu of actions for the tile's window on right click. The
+    /// actions go to the daemon like their keys would.
+    fn add_context_menu(&self, tile: &Widget, index: usize, title: &str) {
+        let click = GestureClick::new();
+        click.set_button(gtk4::gdk::BUTTON_SECONDARY);
+        let tile_ref = tile.clone();
+        let input_tx = self.input_tx.clone();
+        let title = title.to_string();
+        click.connect_pressed(move |gesture, _, _, _| {
+            gesture.set_state(gtk4::EventSequenceState::Claimed);
+            let popover = Popover::new();
+            let menu = GtkBox::new(Orientation::Vertical, 0);
+            for (label, action) in TILE_MENU {
+                let button = Button::with_label(label);
+                button.add_css_class("flat");
+                // Weak, as the popover owns the button
+                let popover_ref = popover.downgrade();
+                let input_tx = input_tx.clone();
+                let title = title.clone();
+                button.connect_clicked(move |button| {
+                    if let Some(popover) = popover_ref.upgrade() {
+                        popover.popdown();
+                    }
+                    match action {
+                        Some(action) => {
+                            send_input_command(&input_tx, InputCommand::Tile { index, action })
+                        }
+                        None => button.clipboard().set_text(&title),
+                    }
+                });
+                menu.append(&button);
+            }
+            popover.set_child(Some(&menu));
+            popover.set_parent(&tile_ref);
+            popover.connect_closed(|popover| popover.unparent());
+            popover.popup();
+        });
+        tile.add_controller(click);
+    }
+
+    /// Let the tile be dragged onto another to move it there, which the
+    /// daemon pins as the order to keep
+    fn add_drag_and_drop(&self, tile: &Widget, index: usize) {
+        let source = DragSource::new();
+        source.set_actions(gtk4::gdk::DragAction::MOVE);
+        source.connect_prepare(move |_, _, _| {
+            Some(gtk4::gdk::ContentProvider::for_value(&(index as u32).to_value()))
+        });
+        tile.add_controller(source);
+
+        let target = DropTarget::new(u32::static_type(), gtk4::gdk::DragAction::MOVE);
+        let input_tx = self.input_tx.clone();
+        target.connect_drop(move |_, value, _, _| {
+            let Ok(from) = value.get::<u32>() else {
+                return false;
+            };
+            let from = from as usize;
+            if from != index {
+                send_input_command(&input_tx, InputCommand::Move { from, to: index });
+            }
+            true
+        });
+        tile.add_controller(target);
+    }
+
+    /// Add the "last used" caption and dim long-idle windows, as configured
+    fn show_age(&self, tile: &GtkBox, window: &WindowInfo) {
+        let Some(age) = window
+            .last_focused
+            .and_then(|at| SystemTime::now().duration_since(at).ok())
+        else {
+            return;
+        };
+        if self.last_used {
+            let caption = Label::new(Some(&format_age(age)));
+            caption.add_css_class("age");
+            tile.append(&caption);
+        }
+        if self.dim_idle_after.is_some_and(|idle| age >= idle) {
+            tile.add_css_class("idle");
+        }
+    }
+
+    /// Tint a tile with the color the rules gave its window
+    fn tint(&self, tile: &GtkBox, window: &WindowInfo) {
+        if let Some(ref color) = window.tint {
+            tile.add_css_class(&tint_class(color));
+        }
+    }
+
     fn highlight_tile(&self, tile: &Widget) {
         // Add CSS class for highlighting
         tile.add_css_class("selected");
@@ -213,6 +840,55 @@ impl SwitcherWindow {
         tile.remove_css_class("selected");
     }
 
+    /// Number of windows currently shown
+    pub fn window_count(&self) -> usize {
+        self.windows.len()
+    }
+
+    /// Ask the daemon for its current list after a selection update that
+    /// doesn't fit the one shown
+    pub fn request_resync(&self) {
+        send_input_command(&self.input_tx, InputCommand::Resync);
+    }
+
+    /// Set how many windows each entry of the next list shown stands for
+    pub fn set_group_sizes(&mut self, sizes: Vec<usize>) {
+        self.next_group_sizes = sizes;
+    }
+
+    /// Update the count badges of the grouped list shown, rebuilding the
+    /// tiles whose count changed
+    pub fn update_group_sizes(&mut self, sizes: Vec<usize>) {
+        let Some((layout, target, _)) = self.arrangement else {
+            return;
+        };
+        if sizes.len() != self.tiles.len() {
+            return;
+        }
+        let Some(mut icon_resolver) = self.icon_resolver.take() else {
+            return;
+        };
+        let old = std::mem::replace(&mut self.group_sizes, sizes);
+        for i in 0..self.tiles.len() {
+            if old.get(i) == self.group_sizes.get(i) {
+                continue;
+            }
+            let tile = self.build_tile(i, &self.windows[i], layout, target, &mut icon_resolver);
+            if i == self.current_index {
+                self.highlight_tile(&tile);
+            }
+            let old_tile = std::mem::replace(&mut self.tiles[i], tile);
+            replace_tile(&old_tile, &self.tiles[i]);
+        }
+        self.icon_resolver = Some(icon_resolver);
+    }
+
+    /// Mark the next list shown as the last known one, waiting for a
+    /// fresh one
+    pub fn set_refreshing(&mut self, refreshing: bool) {
+        self.refreshing = refreshing;
+    }
+
     /// Set the selection to a specific index
     /// (daemon owns the authoritative selection state, UI just reflects it)
     pub fn set_selection(&mut self, new_index: usize) {
@@ -233,26 +909,371 @@ impl SwitcherWindow {
             self.highlight_tile(new_tile);
         }
 
+        self.update_position();
+        self.update_preview();
+        self.scroll_to_selection();
+
         debug!(
             "Selection updated to window {}: {:?}",
             self.current_index, self.windows[self.current_index].title
         );
     }
 
-    /// Close the window switcher
+    /// Show the selected window's thumbnail, or nothing if none was captured yet
+    fn update_preview(&self) {
+        let Some(ref picture) = self.preview else {
+            return;
+        };
+        let path = self
+            .windows
+            .get(self.current_index)
+            .and_then(|window| preview_path(window.id))
+            .filter(|path| path.exists());
+        picture.set_visible(path.is_some());
+        picture.set_filename(path.as_deref());
+    }
+
+    /// Scroll the selected tile into view when the tiles don't fit
+    fn scroll_to_selection(&self) {
+        let Some(bounds) = self
+            .tiles
+            .get(self.current_index)
+            .and_then(|tile| tile.compute_bounds(&self.container))
+        else {
+            return;
+        };
+        let (x, y) = (f64::from(bounds.x()), f64::from(bounds.y()));
+        let (width, height) = (f64::from(bounds.width()), f64::from(bounds.height()));
+        for (adjustment, start, end) in [
+            (self.scroller.hadjustment(), x, x + width),
+            (self.scroller.vadjustment(), y, y + height),
+        ] {
+            adjustment.set_value(scroll_into_view(
+                adjustment.value(),
+                adjustment.page_size(),
+                start,
+                end,
+            ));
+        }
+    }
+
+    fn update_position(&self) {
+        self.position.set_text(&position_text(
+            self.current_index,
+            self.windows.len(),
+            self.refreshing,
+        ));
+        self.position.set_visible(position_visible(self.windows.len(), self.refreshing));
+    }
+
+    /// Briefly flash the selected tile
+    pub fn flash_selection(&self) {
+        let Some(duration) = wrap_cue_duration(self.window.has_css_class("reduced-motion")) else {
+            return;
+        };
+        let Some(tile) = self.tiles.get(self.current_index).cloned() else {
+            return;
+        };
+        tile.add_css_class("wrap-cue");
+        glib::timeout_add_local_once(duration, move || {
+            tile.remove_css_class("wrap-cue");
+        });
+    }
+
+    /// Hide the window, giving back the keyboard first. Unmapping destroys
+    /// the layer surface; only the pre-realized wl_surface is kept, which
+    /// holds no input or idle state.
     pub fn close(&self) {
         info!("Hiding window (not closing, so GTK app stays alive)");
+        if self.layer_shell {
+            self.window.set_keyboard_mode(KeyboardMode::None);
+            UI_RESOURCES.set_keyboard_grab(false);
+        }
         self.window.set_visible(false);
     }
 }
 
-/// Send an input command to the daemon via channel
+/// Send an input command to the daemon without blocking GTK
 fn send_input_command(tx: &InputSender, cmd: InputCommand) {
-    if let Err(e) = tx.send(cmd) {
+    if let Err(e) = tx.send_input(cmd) {
         warn!("Failed to send input command: {}", e);
     }
 }
 
+/// Pick the key to act on: the layout's keysym, or with `match_keycodes`
+/// the key at that position on a US layout (so e.g. Tab works wherever
+/// the layout puts it). Keys the switcher doesn't use keep their keysym.
+fn resolve_key(keyval: Key, keycode: u32, match_keycodes: bool) -> Key {
+    if match_keycodes {
+        key_for_keycode(keycode).unwrap_or(keyval)
+    } else {
+        keyval
+    }
+}
+
+/// The US layout key for the hardware keycodes the switcher responds to.
+/// Keycodes are evdev codes offset by 8, as reported by GDK on Wayland.
+fn key_for_keycode(keycode: u32) -> Option<Key> {
+    let key = match keycode {
+        9 => Key::Escape,
+        10 => Key::_1,
+        11 => Key::_2,
+        12 => Key::_3,
+        13 => Key::_4,
+        14 => Key::_5,
+        15 => Key::_6,
+        16 => Key::_7,
+        17 => Key::_8,
+        18 => Key::_9,
+        23 => Key::Tab,
+        36 => Key::Return,
+        64 => Key::Alt_L,
+        104 => Key::KP_Enter,
+        108 => Key::Alt_R,
+        111 => Key::Up,
+        113 => Key::Left,
+        114 => Key::Right,
+        116 => Key::Down,
+        _ => return None,
+    };
+    Some(key)
+}
+
+/// One section of the split layout, wrapped like the grid layout
+fn split_section() -> gtk4::Grid {
+    let section = gtk4::Grid::new();
+    section.set_row_spacing(TILE_PADDING as u32);
+    section.set_column_spacing(TILE_PADDING as u32);
+    section
+}
+
+/// Put `new` in the place of `old`, in a row of tiles or in a grid
+fn replace_tile(old: &Widget, new: &Widget) {
+    let Some(parent) = old.parent() else {
+        return;
+    };
+    if let Some(grid) = parent.downcast_ref::<gtk4::Grid>() {
+        let (column, row, width, height) = grid.query_child(old);
+        grid.remove(old);
+        grid.attach(new, column, row, width, height);
+    } else if let Some(row) = parent.downcast_ref::<GtkBox>() {
+        row.insert_child_after(new, Some(old));
+        row.remove(old);
+    }
+}
+
+/// A bound key, by keysym or by hardware keycode
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BoundKey {
+    /// Lowercase, like the keys it is compared with
+    Sym(Key),
+    Code(u32),
+}
+
+/// A key from `[keyboard.bindings]`, resolved to a keysym or keycode
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Binding {
+    key: BoundKey,
+    shift: bool,
+    ctrl: bool,
+    action: KeyAction,
+}
+
+/// Resolve the configured key bindings, skipping unknown key names
+fn key_bindings(bindings: &KeyBindings) -> Vec<Binding> {
+    bindings
+        .chords()
+        .into_iter()
+        .filter_map(|(chord, action)| {
+            let key = match chord.keycode() {
+                Some(code) => Some(BoundKey::Code(code)),
+                None => Key::from_name(&chord.key).map(|key| BoundKey::Sym(key.to_lower())),
+            };
+            let Some(key) = key else {
+                warn!("Unknown key '{}' in keyboard.bindings", chord.key);
+                return None;
+            };
+            Some(Binding {
+                key,
+                shift: chord.shift,
+                ctrl: chord.ctrl,
+                action,
+            })
+        })
+        .collect()
+}
+
+/// The action bound to `key` (lowercase) or `keycode` with the modifiers in
+/// `state`. A binding needs its modifiers held; the one naming the most wins,
+/// so `Shift+Tab` takes over from `Tab`.
+fn bound_action(
+    bindings: &[Binding],
+    key: Key,
+    keycode: u32,
+    state: ModifierType,
+) -> Option<KeyAction> {
+    let shift = state.contains(ModifierType::SHIFT_MASK);
+    let ctrl = state.contains(ModifierType::CONTROL_MASK);
+    let matches = |bound: BoundKey| match bound {
+        BoundKey::Sym(sym) => sym == key,
+        BoundKey::Code(code) => code == keycode,
+    };
+    bindings
+        .iter()
+        .filter(|b| matches(b.key) && (shift || !b.shift) && (ctrl || !b.ctrl))
+        .min_by_key(|b| std::cmp::Reverse(usize::from(b.shift) + usize::from(b.ctrl)))
+        .map(|b| b.action)
+}
+
+/// Resolve the keys of custom actions, paired with the action's index
+fn custom_action_keys(actions: &[CustomAction]) -> Vec<(Key, usize)> {
+    actions
+        .iter()
+        .enumerate()
+        .filter_map(|(index, action)| match Key::from_name(&action.key) {
+            Some(key) => Some((key.to_lower(), index)),
+            None => {
+                warn!("Unknown key '{}' for custom action", action.key);
+                None
+            }
+        })
+        .collect()
+}
+
+/// Sway rules that make the floating switcher window behave like the
+/// layer-shell overlay: floating, centered, on every workspace
+#[must_use]
+pub fn sway_rules() -> String {
+    format!(
+        "for_window [app_id=\"{}\"] floating enable, sticky enable, border none, move position center\n",
+        APPLICATION_ID
+    )
+}
+
+/// Whether to turn off motion: the config decides, and by default the
+/// desktop's `gtk-enable-animations` does
+#[must_use]
+fn wants_reduced_motion(mode: ReducedMotion, animations_enabled: bool) -> bool {
+    match mode {
+        ReducedMotion::Auto => !animations_enabled,
+        ReducedMotion::Always => true,
+        ReducedMotion::Never => false,
+    }
+}
+
+/// How long the wrap cue flashes the selected tile; with reduced motion
+/// it doesn't flash at all
+#[must_use]
+fn wrap_cue_duration(reduced_motion: bool) -> Option<Duration> {
+    (!reduced_motion).then_some(WRAP_CUE_DURATION)
+}
+
+/// Toggle the style class that turns off all transitions and animations
+fn set_reduced_motion(window: &ApplicationWindow, reduced: bool) {
+    if reduced {
+        window.add_css_class("reduced-motion");
+    } else {
+        window.remove_css_class("reduced-motion");
+    }
+}
+
+/// Toggle the style class for the high-contrast style
+fn set_high_contrast(window: &ApplicationWindow, high: bool) {
+    if high {
+        window.add_css_class("high-contrast");
+    } else {
+        window.remove_css_class("high-contrast");
+    }
+}
+
+/// Whether a GTK theme is a high-contrast one, like GTK's own
+/// HighContrast and HighContrastInverse
+#[must_use]
+fn is_high_contrast_theme(name: &str) -> bool {
+    name.to_ascii_lowercase().contains("highcontrast")
+}
+
+/// Give `window` the high-contrast style as configured; `auto` follows the
+/// GTK theme, which desktops switch for their high-contrast preference
+pub fn follow_high_contrast(window: &ApplicationWindow, mode: HighContrast) {
+    match mode {
+        HighContrast::Always => set_high_contrast(window, true),
+        HighContrast::Never => {}
+        HighContrast::Auto => {
+            let Some(settings) = gtk4::Settings::default() else {
+                return;
+            };
+            let theme = settings.gtk_theme_name().unwrap_or_default();
+            set_high_contrast(window, is_high_contrast_theme(&theme));
+            let window = window.clone();
+            settings.connect_gtk_theme_name_notify(move |settings| {
+                let theme = settings.gtk_theme_name().unwrap_or_default();
+                info!("GTK theme changed to {}", theme);
+                set_high_contrast(&window, is_high_contrast_theme(&theme));
+            });
+        }
+    }
+}
+
+/// Text shown under a tile: the window's label or title, or the app or
+/// workspace the tile stands for when the switcher is grouped.
+fn tile_label(window: &WindowInfo, target: SwitchTarget) -> String {
+    match target {
+        SwitchTarget::Windows => window.label.as_ref().unwrap_or(&window.title).clone(),
+        SwitchTarget::Apps => window.app_key().unwrap_or(&window.title).to_string(),
+        SwitchTarget::Workspaces => format!("Workspace {}", window.workspace),
+    }
+}
+
+/// Largest size the tiles may take up along an output side of `size`
+/// logical pixels
+fn max_content_size(size: i32) -> i32 {
+    (f64::from(size) * MAX_OUTPUT_FRACTION) as i32 - 2 * WINDOW_PADDING
+}
+
+/// Scroll offset that brings the span `start..end` into the view of an
+/// adjustment at `value` showing `page` pixels, moving as little as possible
+fn scroll_into_view(value: f64, page: f64, start: f64, end: f64) -> f64 {
+    if start < value {
+        start
+    } else if end > value + page {
+        end - page
+    } else {
+        value
+    }
+}
+
+/// For each window of `new`, whether the tile built for the window at the
+/// same position of `old` still shows it
+fn reusable_tiles(old: &[WindowInfo], new: &[WindowInfo]) -> Vec<bool> {
+    new.iter()
+        .enumerate()
+        .map(|(i, window)| old.get(i) == Some(window))
+        .collect()
+}
+
+/// Which way a wheel step cycles: down or right goes forward, up or left
+/// back. None for a step with no movement.
+fn scroll_direction(dx: f64, dy: f64) -> Option<bool> {
+    let delta = if dy != 0.0 { dy } else { dx };
+    (delta != 0.0).then_some(delta > 0.0)
+}
+
+/// One-based position of the selection and the total, e.g. "3 / 12"
+fn position_text(index: usize, total: usize, refreshing: bool) -> String {
+    if refreshing {
+        format!("{} / {} · refreshing…", index + 1, total)
+    } else {
+        format!("{} / {}", index + 1, total)
+    }
+}
+
+/// The position only says something with more than one window, unless it
+/// carries the refreshing hint
+fn position_visible(total: usize, refreshing: bool) -> bool {
+    total > 1 || refreshing
+}
+
 fn truncate_string(s: &str, max_chars: usize) -> String {
     let char_count = s.chars().count();
     if char_count <= max_chars {
@@ -267,17 +1288,131 @@ fn truncate_string(s: &str, max_chars: usize) -> String {
     }
 }
 
-/// Setup CSS styling for the window switcher
-pub fn setup_css() {
+/// Style class for tiles tinted `color`: `tint-` and the color's bytes in
+/// hex, so any CSS color gives a valid class name
+#[must_use]
+fn tint_class(color: &str) -> String {
+    color.bytes().fold("tint-".to_string(), |mut class, byte| {
+        let _ = write!(class, "{:02x}", byte);
+        class
+    })
+}
+
+/// A style class for each tint color
+#[must_use]
+fn tint_css(colors: &[String]) -> String {
+    colors
+        .iter()
+        .map(|color| {
+            format!(
+                ".{} {{ background-color: alpha({}, {}); }}\n",
+                tint_class(color),
+                color,
+                TINT_OPACITY
+            )
+        })
+        .collect()
+}
+
+/// Setup CSS styling for the window switcher, with a class for each of
+/// `tint_colors`
+pub fn setup_css(appearance: &Appearance, tint_colors: &[String]) {
     let provider = gtk4::CssProvider::new();
     // Minimal CSS - inherit colors from the user's GTK theme
-    provider.load_from_data(
+    provider.load_from_data(&format!(
         r#"
-        .selected {
+        window {{
+            background-color: alpha(@theme_bg_color, {opacity});
+        }}
+
+        {tints}
+        .selected {{
             background-color: alpha(@theme_selected_bg_color, 0.7);
-        }
+        }}
+
+        .selected.wrap-cue {{
+            background-color: @theme_selected_bg_color;
+        }}
+
+        .position {{
+            font-size: {position_em}em;
+            opacity: 0.7;
+        }}
+
+        .tile-label {{
+            font-size: {label_em}em;
+        }}
+
+        .window-label {{
+            font-weight: bold;
+        }}
+
+        .age {{
+            font-size: smaller;
+            opacity: 0.7;
+        }}
+
+        .idle {{
+            opacity: 0.5;
+        }}
+
+        .group-count {{
+            background-color: @theme_selected_bg_color;
+            color: @theme_selected_fg_color;
+            border-radius: 999px;
+            padding: 0 5px;
+            font-size: smaller;
+            font-weight: bold;
+        }}
+
+        .workspace-number {{
+            font-weight: bold;
+            font-size: larger;
+        }}
+
+        .dock-number {{
+            font-size: smaller;
+            opacity: 0.7;
+        }}
+
+        .reduced-motion * {{
+            transition: none;
+            animation: none;
+        }}
+
+        window.high-contrast {{
+            background-color: @theme_bg_color;
+        }}
+
+        .high-contrast label {{
+            font-size: larger;
+        }}
+
+        .high-contrast .tile-label {{
+            font-size: {high_contrast_label_em}em;
+        }}
+
+        .high-contrast .selected,
+        .high-contrast .selected.wrap-cue {{
+            background-color: @theme_selected_bg_color;
+            color: @theme_selected_fg_color;
+            outline: 3px solid @theme_fg_color;
+            outline-offset: -3px;
+        }}
+
+        .high-contrast .position,
+        .high-contrast .age,
+        .high-contrast .idle,
+        .high-contrast .dock-number {{
+            opacity: 1;
+        }}
         "#,
-    );
+        opacity = appearance.opacity,
+        tints = tint_css(tint_colors),
+        position_em = POSITION_FONT_EM * appearance.position_scale,
+        label_em = appearance.label_scale,
+        high_contrast_label_em = HIGH_CONTRAST_LABEL_SCALE * appearance.label_scale,
+    ));
 
     gtk4::style_context_add_provider_for_display(
         &gtk4::gdk::Display::default().expect("Failed to get default display"),
@@ -350,4 +1485,197 @@ mod tests {
         let result = truncate_string("hello", 2);
         assert_eq!(result, "...");
     }
+
+    // ==================== sway_rules tests ====================
+
+    #[test]
+    fn test_sway_rules_match_app_id() {
+        let rules = sway_rules();
+        assert!(rules.starts_with(&format!("for_window [app_id=\"{}\"]", APPLICATION_ID)));
+        assert!(rules.contains("floating enable"));
+    }
+
+    // ==================== layout and style tests ====================
+
+    #[test]
+    fn test_max_content_size() {
+        assert_eq!(max_content_size(1920), 1728 - 2 * WINDOW_PADDING);
+        // A portrait output limits the row to its narrow side
+        assert_eq!(max_content_size(1080), 972 - 2 * WINDOW_PADDING);
+    }
+
+    #[test]
+    fn test_scroll_into_view() {
+        // Already visible
+        assert_eq!(scroll_into_view(100.0, 500.0, 200.0, 300.0), 100.0);
+        // Off to the left/top
+        assert_eq!(scroll_into_view(100.0, 500.0, 50.0, 150.0), 50.0);
+        // Off to the right/bottom
+        assert_eq!(scroll_into_view(100.0, 500.0, 550.0, 700.0), 200.0);
+    }
+
+    #[test]
+    fn test_reduced_motion_follows_the_config_over_the_desktop() {
+        assert!(!wants_reduced_motion(ReducedMotion::Auto, true));
+        assert!(wants_reduced_motion(ReducedMotion::Auto, false));
+        assert!(wants_reduced_motion(ReducedMotion::Always, true));
+        assert!(!wants_reduced_motion(ReducedMotion::Never, false));
+    }
+
+    #[test]
+    fn test_wrap_cue_duration() {
+        assert_eq!(wrap_cue_duration(false), Some(WRAP_CUE_DURATION));
+        assert_eq!(wrap_cue_duration(true), None);
+    }
+
+    #[test]
+    fn test_tint_css() {
+        let colors = ["green".to_string(), "#3465a4".to_string()];
+        assert_eq!(
+            tint_css(&colors),
+            ".tint-677265656e { background-color: alpha(green, 0.3); }\n\
+             .tint-23333436356134 { background-color: alpha(#3465a4, 0.3); }\n"
+        );
+        assert_eq!(tint_css(&[]), "");
+    }
+
+    #[test]
+    fn test_is_high_contrast_theme() {
+        assert!(is_high_contrast_theme("HighContrast"));
+        assert!(is_high_contrast_theme("HighContrastInverse"));
+        assert!(!is_high_contrast_theme("Adwaita"));
+        assert!(!is_high_contrast_theme(""));
+    }
+
+    #[test]
+    fn test_scroll_direction() {
+        assert_eq!(scroll_direction(0.0, 1.0), Some(true));
+        assert_eq!(scroll_direction(0.0, -2.0), Some(false));
+        assert_eq!(scroll_direction(1.0, 0.0), Some(true));
+        assert_eq!(scroll_direction(-1.0, 0.0), Some(false));
+        assert_eq!(scroll_direction(0.0, 0.0), None);
+    }
+
+    #[test]
+    fn test_reusable_tiles() {
+        let window = |id: i64, title: &str| WindowInfo {
+            id,
+            title: title.to_string(),
+            ..Default::default()
+        };
+        let old = vec![window(1, "a"), window(2, "b"), window(3, "c")];
+        let new = vec![window(1, "a"), window(3, "c"), window(2, "b (renamed)"), window(4, "d")];
+        assert_eq!(reusable_tiles(&old, &new), vec![true, false, false, false]);
+        assert_eq!(reusable_tiles(&old, &old[..2]), vec![true, true]);
+        assert!(reusable_tiles(&[], &old).iter().all(|reused| !reused));
+    }
+
+    // ==================== tile_label tests ====================
+
+    #[test]
+    fn test_tile_label_per_target() {
+        let window = WindowInfo {
+            id: 1,
+            app_id: Some("firefox".to_string()),
+            title: "Mozilla Firefox".to_string(),
+            workspace: "3".to_string(),
+            output: "DP-1".to_string(),
+            window_class: None,
+            window_instance: None,
+            label: None,
+            last_focused: None,
+            ..Default::default()
+        };
+
+        assert_eq!(tile_label(&window, SwitchTarget::Windows), "Mozilla Firefox");
+        assert_eq!(tile_label(&window, SwitchTarget::Apps), "firefox");
+        assert_eq!(tile_label(&window, SwitchTarget::Workspaces), "Workspace 3");
+
+        let labelled = WindowInfo {
+            label: Some("Browser".to_string()),
+            ..window
+        };
+        assert_eq!(tile_label(&labelled, SwitchTarget::Windows), "Browser");
+        assert_eq!(tile_label(&labelled, SwitchTarget::Apps), "firefox");
+    }
+
+    #[test]
+    fn test_position_text() {
+        assert_eq!(position_text(0, 1, false), "1 / 1");
+        assert_eq!(position_text(2, 12, false), "3 / 12");
+        assert_eq!(position_text(11, 12, false), "12 / 12");
+        assert_eq!(position_text(0, 4, true), "1 / 4 · refreshing…");
+    }
+
+    #[test]
+    fn test_position_shown_for_more_than_one_window() {
+        assert!(!position_visible(0, false));
+        assert!(!position_visible(1, false));
+        assert!(position_visible(2, false));
+        // The refreshing hint shows even for a single window
+        assert!(position_visible(1, true));
+    }
+
+    // ==================== keycode tests ====================
+
+    #[test]
+    fn test_resolve_key_uses_keysym_by_default() {
+        // Keycode 23 is Tab on a US layout, but the layout says otherwise
+        assert_eq!(resolve_key(Key::a, 23, false), Key::a);
+    }
+
+    #[test]
+    fn test_resolve_key_matches_keycodes() {
+        assert_eq!(resolve_key(Key::a, 23, true), Key::Tab);
+        assert_eq!(resolve_key(Key::ampersand, 10, true), Key::_1);
+        assert_eq!(resolve_key(Key::ISO_Level3_Shift, 108, true), Key::Alt_R);
+        // Shift+Tab is still told apart by the Shift modifier
+        assert_eq!(resolve_key(Key::ISO_Left_Tab, 23, true), Key::Tab);
+    }
+
+    #[test]
+    fn test_bound_action_prefers_modifiers() {
+        let binding = |key, shift, action| Binding {
+            key: BoundKey::Sym(key),
+            shift,
+            ctrl: false,
+            action,
+        };
+        let bindings = [
+            binding(Key::Tab, false, KeyAction::Next),
+            binding(Key::j, false, KeyAction::Next),
+            binding(Key::Tab, true, KeyAction::Prev),
+        ];
+        let none = ModifierType::empty();
+        let shift = ModifierType::SHIFT_MASK;
+        assert_eq!(bound_action(&bindings, Key::Tab, 23, none), Some(KeyAction::Next));
+        assert_eq!(bound_action(&bindings, Key::Tab, 23, shift), Some(KeyAction::Prev));
+        // Modifiers not named don't stop a binding
+        assert_eq!(bound_action(&bindings, Key::j, 44, shift), Some(KeyAction::Next));
+        assert_eq!(bound_action(&bindings, Key::k, 45, none), None);
+    }
+
+    #[test]
+    fn test_bound_action_by_keycode() {
+        let binding = |key, action| Binding {
+            key,
+            shift: false,
+            ctrl: false,
+            action,
+        };
+        let bindings = [
+            binding(BoundKey::Sym(Key::Tab), KeyAction::Next),
+            binding(BoundKey::Code(24), KeyAction::Cancel),
+        ];
+        let none = ModifierType::empty();
+        // Whatever keysym the layout gives keycode 24, it cancels
+        assert_eq!(bound_action(&bindings, Key::a, 24, none), Some(KeyAction::Cancel));
+        assert_eq!(bound_action(&bindings, Key::q, 38, none), None);
+        assert_eq!(bound_action(&bindings, Key::Tab, 23, none), Some(KeyAction::Next));
+    }
+
+    #[test]
+    fn test_resolve_key_keeps_unknown_keycodes() {
+        assert_eq!(resolve_key(Key::q, 24, true), Key::q);
+    }
 }
@@ -1,5 +1,101 @@
+use crate::config::SwitchTarget;
 use crate::icon_resolver::WmClassIndex;
+use crate::ipc::SwitcherEvent;
 use crate::window_manager::WindowInfo;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Capacity of the channel carrying state from the daemon to the UI
+pub const UI_CHANNEL_CAPACITY: usize = 16;
+
+/// What the built-in UI currently holds from the compositor, for
+/// `debug-resources`. Written by the GTK thread, read by the daemon.
+#[derive(Debug, Default)]
+pub struct UiResources {
+    switcher_mapped: AtomicBool,
+    keyboard_grab: AtomicBool,
+    dock_mapped: AtomicBool,
+    taskbar_mapped: AtomicBool,
+}
+
+/// The built-in UI's resources; there is one UI per process
+pub static UI_RESOURCES: UiResources = UiResources {
+    switcher_mapped: AtomicBool::new(false),
+    keyboard_grab: AtomicBool::new(false),
+    dock_mapped: AtomicBool::new(false),
+    taskbar_mapped: AtomicBool::new(false),
+};
+
+impl UiResources {
+    pub fn set_switcher_mapped(&self, mapped: bool) {
+        self.switcher_mapped.store(mapped, Ordering::Relaxed);
+    }
+
+    pub fn set_keyboard_grab(&self, grabbed: bool) {
+        self.keyboard_grab.store(grabbed, Ordering::Relaxed);
+    }
+
+    pub fn set_dock_mapped(&self, mapped: bool) {
+        self.dock_mapped.store(mapped, Ordering::Relaxed);
+    }
+
+    pub fn set_taskbar_mapped(&self, mapped: bool) {
+        self.taskbar_mapped.store(mapped, Ordering::Relaxed);
+    }
+
+    /// The held resources as `key=value` lines
+    #[must_use]
+    pub fn report(&self) -> String {
+        let mapped = |flag: &AtomicBool| {
+            if flag.load(Ordering::Relaxed) {
+                "mapped"
+            } else {
+                "released"
+            }
+        };
+        format!(
+            "switcher-surface={}\nkeyboard-grab={}\ndock-surface={}\ntaskbar-surface={}\n",
+            mapped(&self.switcher_mapped),
+            if self.keyboard_grab.load(Ordering::Relaxed) {
+                "exclusive"
+            } else {
+                "none"
+            },
+            mapped(&self.dock_mapped),
+            mapped(&self.taskbar_mapped)
+        )
+    }
+}
+
+/// Where the daemon keeps the last preview captured of each window
+#[must_use]
+pub fn preview_dir() -> Option<PathBuf> {
+    dirs::runtime_dir()
+        .or_else(dirs::cache_dir)
+        .map(|dir| dir.join("sway-alttab-gui-previews"))
+}
+
+/// The preview file for a window
+#[must_use]
+pub fn preview_path(window_id: i64) -> Option<PathBuf> {
+    preview_dir().map(|dir| dir.join(format!("{}.png", window_id)))
+}
+
+/// How the switcher arranges its tiles
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SwitcherLayout {
+    /// A single horizontal row of icon tiles
+    #[default]
+    Row,
+    /// A vertical list of one application's windows with full titles
+    AppList,
+    /// Icon tiles wrapped into rows of `columns`, for lists too long for a row
+    Grid { columns: usize },
+    /// The current workspace's icon tiles, and the others from index
+    /// `first` below a divider, each wrapped into rows of `columns` (0 for
+    /// a single row)
+    Split { first: usize, columns: usize },
+}
 
 /// Commands sent from daemon to UI
 #[derive(Debug, Clone)]
@@ -9,10 +105,155 @@ pub enum UiCommand {
         windows: Vec<WindowInfo>,
         initial_index: usize,
         wmclass_index: WmClassIndex,
+        layout: SwitcherLayout,
+        /// What the entries stand for, which decides how they are labelled
+        target: SwitchTarget,
+        /// Index of the first entry after a separator (hybrid mode puts
+        /// one between current-workspace windows and the rest)
+        separator: Option<usize>,
+        /// Logical size of the output the switcher appears on, which
+        /// limits its size
+        output_size: Option<(i32, i32)>,
+        /// The list is the last known one; a fresh one follows shortly
+        refreshing: bool,
+        /// How many windows each entry stands for, when grouped by app or
+        /// workspace; empty otherwise
+        group_sizes: Vec<usize>,
     },
+    /// The open grouped switcher's window counts changed, one per entry
+    UpdateGroupSizes(Vec<usize>),
     /// Update the selected window to the given index
-    /// (daemon owns the authoritative selection state)
-    UpdateSelection { index: usize },
+    /// (daemon owns the authoritative selection state). `len` is the
+    /// length of the daemon's list, so the UI can tell when its own is stale.
+    UpdateSelection { index: usize, len: usize },
     /// Hide the window switcher
     Hide,
+    /// Flash the selected tile because cycling just wrapped around
+    WrapCue,
+    /// The selected window was closed before it could be focused, so the
+    /// next one was; tell the user why
+    WindowClosed { title: String },
+    /// Resolve icons for these windows in the background so the next
+    /// show doesn't have to
+    PreloadIcons {
+        windows: Vec<WindowInfo>,
+        wmclass_index: WmClassIndex,
+    },
+    /// Show these windows, most recently used first, in the dock
+    UpdateDock {
+        windows: Vec<WindowInfo>,
+        wmclass_index: WmClassIndex,
+    },
+    /// Show these windows, in the order they were opened, in the taskbar
+    UpdateTaskbar {
+        windows: Vec<WindowInfo>,
+        focused: Option<i64>,
+        wmclass_index: WmClassIndex,
+    },
+}
+
+impl UiCommand {
+    /// The state change this command represents for `watch` clients
+    #[must_use]
+    pub fn switcher_event(&self) -> Option<SwitcherEvent> {
+        match self {
+            UiCommand::Show {
+                windows,
+                initial_index,
+                target,
+                ..
+            } => Some(SwitcherEvent::Show {
+                windows: windows.clone(),
+                selected: *initial_index,
+                target: *target,
+            }),
+            UiCommand::UpdateSelection { index, .. } => {
+                Some(SwitcherEvent::Update { selected: *index })
+            }
+            UiCommand::Hide => Some(SwitcherEvent::Hide),
+            UiCommand::WrapCue
+            | UiCommand::WindowClosed { .. }
+            | UiCommand::UpdateGroupSizes(_)
+            | UiCommand::PreloadIcons { .. }
+            | UiCommand::UpdateDock { .. }
+            | UiCommand::UpdateTaskbar { .. } => None,
+        }
+    }
+}
+
+/// Whether a selection update fits the list the UI shows. A mismatch
+/// means the UI missed a show after the list changed, and setting the
+/// index would pick the wrong window or nothing at all.
+#[must_use]
+pub fn selection_in_sync(index: usize, daemon_len: usize, ui_len: usize) -> bool {
+    daemon_len == ui_len && index < ui_len
+}
+
+/// Queue a command, replacing a selection update at the end of the queue
+/// with a newer one (only the latest selection matters).
+pub fn push_coalesced(queue: &mut Vec<UiCommand>, command: UiCommand) {
+    if let UiCommand::UpdateSelection { .. } = command
+        && let Some(UiCommand::UpdateSelection { .. }) = queue.last()
+    {
+        queue.pop();
+    }
+    queue.push(command);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ui_resources_report() {
+        let resources = UiResources::default();
+        assert_eq!(
+            resources.report(),
+            "switcher-surface=released\nkeyboard-grab=none\ndock-surface=released\n\
+             taskbar-surface=released\n"
+        );
+        resources.set_switcher_mapped(true);
+        resources.set_keyboard_grab(true);
+        assert!(resources
+            .report()
+            .starts_with("switcher-surface=mapped\nkeyboard-grab=exclusive\n"));
+    }
+
+    fn selection(queue: &[UiCommand]) -> Vec<Option<usize>> {
+        queue
+            .iter()
+            .map(|c| match c {
+                UiCommand::UpdateSelection { index, .. } => Some(*index),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_selection_in_sync() {
+        assert!(selection_in_sync(2, 3, 3));
+        // The daemon's list grew or shrank since the last show
+        assert!(!selection_in_sync(2, 4, 3));
+        assert!(!selection_in_sync(0, 2, 3));
+        assert!(!selection_in_sync(3, 3, 3));
+    }
+
+    #[test]
+    fn test_push_coalesced_keeps_latest_selection() {
+        let mut queue = Vec::new();
+        for index in 0..5 {
+            push_coalesced(&mut queue, UiCommand::UpdateSelection { index, len: 5 });
+        }
+        assert_eq!(selection(&queue), vec![Some(4)]);
+    }
+
+    #[test]
+    fn test_push_coalesced_keeps_order_around_other_commands() {
+        let mut queue = Vec::new();
+        push_coalesced(&mut queue, UiCommand::UpdateSelection { index: 1, len: 5 });
+        push_coalesced(&mut queue, UiCommand::Hide);
+        push_coalesced(&mut queue, UiCommand::UpdateSelection { index: 2, len: 5 });
+        push_coalesced(&mut queue, UiCommand::UpdateSelection { index: 3, len: 5 });
+        assert_eq!(selection(&queue), vec![Some(1), None, Some(3)]);
+    }
 }
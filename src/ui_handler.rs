@@ -1,20 +1,35 @@
+use crate::dock::DockWindow;
+use crate::notify::notify_notice;
+use crate::taskbar::TaskbarWindow;
 use crate::ui::SwitcherWindow;
-use crate::ui_commands::UiCommand;
+use crate::ui_commands::{selection_in_sync, UiCommand};
 use std::cell::RefCell;
 use std::rc::Rc;
 use tokio::sync::mpsc;
-use tracing::{error, info};
+use tracing::{error, info, warn};
 
-/// Handles UI commands and dispatches them to the SwitcherWindow
+/// Handles UI commands and dispatches them to the SwitcherWindow, dock
+/// and taskbar
 pub fn handle_ui_commands(
     switcher: Rc<RefCell<SwitcherWindow>>,
-    mut ui_rx: mpsc::UnboundedReceiver<UiCommand>,
+    mut dock: Option<DockWindow>,
+    mut taskbar: Option<TaskbarWindow>,
+    mut ui_rx: mpsc::Receiver<UiCommand>,
 ) {
     info!("UI command handler started");
 
     // Use glib to handle commands on the GTK main thread
     glib::spawn_future_local(async move {
-        while let Some(command) = ui_rx.recv().await {
+        // A command read ahead while skipping stale selection updates
+        let mut next = None;
+        loop {
+            let command = match next.take() {
+                Some(command) => command,
+                None => match ui_rx.recv().await {
+                    Some(command) => command,
+                    None => break,
+                },
+            };
             info!("Received UI command: {:?}", command);
 
             match command {
@@ -22,18 +37,110 @@ pub fn handle_ui_commands(
                     windows,
                     initial_index,
                     wmclass_index,
+                    layout,
+                    target,
+                    separator,
+                    output_size,
+                    refreshing,
+                    group_sizes,
                 } => {
                     info!("Showing UI with {} windows, index {}", windows.len(), initial_index);
-                    switcher.borrow_mut().show(windows, initial_index, wmclass_index);
+                    switcher.borrow_mut().fit_to_output(output_size);
+                    switcher.borrow_mut().set_refreshing(refreshing);
+                    switcher.borrow_mut().set_group_sizes(group_sizes);
+                    switcher.borrow_mut().show(
+                        windows,
+                        initial_index,
+                        wmclass_index,
+                        layout,
+                        target,
+                        separator,
+                    );
                     info!("UI shown");
                 }
-                UiCommand::UpdateSelection { index } => {
-                    info!("Updating selection to index {}", index);
-                    switcher.borrow_mut().set_selection(index);
+                UiCommand::UpdateSelection { mut index, mut len } => {
+                    // Only the latest of a burst of queued updates matters
+                    while let Ok(command) = ui_rx.try_recv() {
+                        match command {
+                            UiCommand::UpdateSelection {
+                                index: newer,
+                                len: newer_len,
+                            } => {
+                                index = newer;
+                                len = newer_len;
+                            }
+                            command => {
+                                next = Some(command);
+                                break;
+                            }
+                        }
+                    }
+                    let shown = switcher.borrow().window_count();
+                    if selection_in_sync(index, len, shown) {
+                        info!("Updating selection to index {}", index);
+                        switcher.borrow_mut().set_selection(index);
+                    } else {
+                        warn!(
+                            "Selection {} of {} doesn't fit the {} shown windows, resyncing",
+                            index, len, shown
+                        );
+                        switcher.borrow().request_resync();
+                    }
                 }
                 UiCommand::Hide => {
-                    info!("Hiding UI");
-                    switcher.borrow().close();
+                    // A show right behind (cancel then show) updates the
+                    // window in place instead of flickering it
+                    match ui_rx.try_recv() {
+                        Ok(command @ UiCommand::Show { .. }) => {
+                            info!("Keeping the UI up for the show that follows");
+                            next = Some(command);
+                        }
+                        queued => {
+                            info!("Hiding UI");
+                            switcher.borrow().close();
+                            next = queued.ok();
+                        }
+                    }
+                }
+                UiCommand::UpdateGroupSizes(sizes) => {
+                    switcher.borrow_mut().update_group_sizes(sizes);
+                }
+                UiCommand::WrapCue => {
+                    switcher.borrow().flash_selection();
+                }
+                UiCommand::WindowClosed { title } => {
+                    // The switcher is gone by now, so this is a notification
+                    notify_notice(
+                        "Window closed",
+                        &format!("'{}' was closed, switched to the next window", title),
+                    );
+                }
+                UiCommand::PreloadIcons {
+                    windows,
+                    wmclass_index,
+                } => {
+                    // Run when GTK is idle so it never delays a show
+                    let switcher = switcher.clone();
+                    glib::idle_add_local_once(move || {
+                        switcher.borrow_mut().preload_icons(&windows, wmclass_index);
+                    });
+                }
+                UiCommand::UpdateDock {
+                    windows,
+                    wmclass_index,
+                } => {
+                    if let Some(ref mut dock) = dock {
+                        dock.update(&windows, wmclass_index);
+                    }
+                }
+                UiCommand::UpdateTaskbar {
+                    windows,
+                    focused,
+                    wmclass_index,
+                } => {
+                    if let Some(ref mut taskbar) = taskbar {
+                        taskbar.update(&windows, focused, wmclass_index);
+                    }
                 }
             }
         }
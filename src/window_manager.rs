@@ -1,83 +1,259 @@
-use anyhow::Result;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
 use std::borrow::Cow;
 use std::collections::{HashMap, HashSet};
+use std::time::SystemTime;
 use swayipc::{Node, NodeType};
 use tracing::debug;
 
-use crate::config::WorkspaceMode;
-use crate::sway_client::{RealSwayClient, SwayClient};
+use crate::config::{SortMode, SwitchTarget, WindowDetection, WorkspaceMode};
+use crate::focus_history::{FocusEntry, FocusHistory};
+use crate::compositor::{fetch_snapshot, Compositor, Snapshot};
+use crate::sway_client::RealSwayClient;
 
-#[derive(Debug, Clone)]
+/// Replaced by the app name in the title of untitled windows
+const UNTITLED_APP_PLACEHOLDER: &str = "{app}";
+
+/// Sway marks starting with this give the window a label, e.g. `label:mail`
+const LABEL_MARK_PREFIX: &str = "label:";
+
+/// Name sway gives the hidden workspace holding scratchpad windows
+const SCRATCHPAD_WORKSPACE: &str = "__i3_scratch";
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
 pub struct WindowInfo {
     pub id: i64,
     pub app_id: Option<String>,
     pub title: String,
     pub workspace: String,
-    pub window_class: Option<String>, // WM_CLASS for X11 windows
+    pub output: String,
+    pub window_class: Option<String>, // WM_CLASS class for X11 windows
+    pub window_instance: Option<String>, // WM_CLASS instance for X11 windows
+    /// Nickname shown on the tile, from a `label:` mark or a config rule
+    pub label: Option<String>,
+    /// Icon name or path a config rule shows instead of the app's icon
+    #[serde(default)]
+    pub icon: Option<String>,
+    /// Tile background tint from a config rule, a CSS color
+    #[serde(default)]
+    pub tint: Option<String>,
+    /// Listed before other windows because of a config rule
+    #[serde(default)]
+    pub pinned: bool,
+    /// When the window last had focus, if it did since the daemon started
+    #[serde(skip)]
+    pub last_focused: Option<SystemTime>,
+    /// Position and size in layout coordinates
+    #[serde(default)]
+    pub geometry: Geometry,
+    #[serde(default)]
+    pub floating: bool,
+    /// Fullscreen on its workspace or globally
+    #[serde(default)]
+    pub fullscreen: bool,
+    #[serde(default)]
+    pub urgent: bool,
+    #[serde(default)]
+    pub pid: Option<i32>,
 }
 
 impl WindowInfo {
-    pub fn from_node(node: &Node, workspace: String) -> Option<Self> {
+    pub fn from_node(
+        node: &Node,
+        workspace: String,
+        output: String,
+        detection: WindowDetection,
+    ) -> Option<Self> {
         // Only include actual windows (views), not containers
-        // Windows have a pid, containers don't
-        if node.node_type == NodeType::Con && node.pid.is_some() {
-            // Extract WM_CLASS from X11/XWayland window properties
-            let window_class = node
-                .window_properties
-                .as_ref()
-                .and_then(|props| props.class.clone());
+        if classify_node(node, detection) == NodeKind::Window {
+            // Extract the WM_CLASS pair from X11/XWayland window properties
+            let props = node.window_properties.as_ref();
+            let window_class = props.and_then(|props| props.class.clone());
+            let window_instance = props.and_then(|props| props.instance.clone());
+            let label = label_from_marks(&node.marks);
 
             Some(WindowInfo {
                 id: node.id,
                 app_id: node.app_id.clone(),
                 title: node.name.clone().unwrap_or_default(),
                 workspace,
+                output,
                 window_class,
+                window_instance,
+                label,
+                icon: None,
+                tint: None,
+                pinned: false,
+                last_focused: None,
+                geometry: Geometry::from(&node.rect),
+                floating: node.node_type == NodeType::FloatingCon,
+                fullscreen: node.fullscreen_mode.is_some_and(|mode| mode > 0),
+                urgent: node.urgent,
+                pid: node.pid,
             })
         } else {
             None
         }
     }
+
+    /// The title, or `untitled` with `{app}` replaced by the app if the
+    /// window has no title
+    pub fn display_title(&self, untitled: &str) -> Cow<'_, str> {
+        display_title(&self.title, self.app_key(), untitled)
+    }
+
+    /// Identifier of the application this window belongs to, used to
+    /// group windows of the same app (app_id, falling back to WM_CLASS)
+    pub fn app_key(&self) -> Option<&str> {
+        self.icon_candidates().first().copied()
+    }
+
+    /// Identifiers to try, in order, when resolving this window's icon:
+    /// the Wayland app_id, then the X11 WM_CLASS class and instance.
+    /// Empty and duplicate (case-insensitive) values are skipped.
+    pub fn icon_candidates(&self) -> Vec<&str> {
+        let mut candidates: Vec<&str> = Vec::with_capacity(3);
+        for id in [&self.app_id, &self.window_class, &self.window_instance]
+            .into_iter()
+            .flatten()
+        {
+            if !id.is_empty() && !candidates.iter().any(|c| c.eq_ignore_ascii_case(id)) {
+                candidates.push(id);
+            }
+        }
+        candidates
+    }
+}
+
+/// What a tree node is, as far as the window list is concerned
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum NodeKind {
+    Window,
+    /// Outputs, workspaces and split containers
+    Container,
+    /// A leaf container that failed detection, with the reason
+    Skipped(&'static str),
+}
+
+/// A node that looks like a window but was left out of the list
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SkippedNode {
+    pub id: i64,
+    pub name: Option<String>,
+    pub app_id: Option<String>,
+    pub reason: &'static str,
+}
+
+#[must_use]
+fn classify_node(node: &Node, detection: WindowDetection) -> NodeKind {
+    if !matches!(node.node_type, NodeType::Con | NodeType::FloatingCon) {
+        return NodeKind::Container;
+    }
+    let is_window = match detection {
+        WindowDetection::Pid => node.pid.is_some(),
+        WindowDetection::Lenient => {
+            node.pid.is_some() || node.app_id.is_some() || node.window_properties.is_some()
+        }
+    };
+    if is_window {
+        NodeKind::Window
+    } else if node.nodes.is_empty() && node.floating_nodes.is_empty() {
+        NodeKind::Skipped(match detection {
+            WindowDetection::Pid => "no pid",
+            WindowDetection::Lenient => "no pid, app_id or window properties",
+        })
+    } else {
+        NodeKind::Container
+    }
 }
 
 /// Manages window list and MRU ordering using Sway IPC.
-pub struct WindowManager<C: SwayClient = RealSwayClient> {
+pub struct WindowManager<C: Compositor = RealSwayClient> {
     client: C,
     windows: Vec<WindowInfo>,
+    focused_id: Option<i64>,
     current_workspace: Option<String>,
+    current_output: Option<String>,
+    /// Active output names ordered by layout position (left to right)
+    output_order: Vec<String>,
+    history: FocusHistory,
+    detection: WindowDetection,
+    /// Nodes left out at the last refresh
+    skipped: Vec<SkippedNode>,
+    /// Whether the focused window was fullscreen at the last refresh
+    focused_fullscreen: bool,
+    /// Logical size of the focused output at the last refresh
+    current_output_size: Option<(i32, i32)>,
 }
 
 impl WindowManager<RealSwayClient> {
     /// Create a new WindowManager with a real Sway connection
-    pub fn new() -> Result<Self> {
+    pub fn new(detection: WindowDetection) -> Result<Self> {
         let client = RealSwayClient::new()?;
-        Self::with_client(client)
+        Self::with_client(client, detection)
     }
 }
 
-impl<C: SwayClient> WindowManager<C> {
-    /// Create a WindowManager with a custom SwayClient (for testing)
-    pub fn with_client(client: C) -> Result<Self> {
+impl<C: Compositor> WindowManager<C> {
+    /// Create a WindowManager with a custom Compositor (for testing)
+    pub fn with_client(client: C, detection: WindowDetection) -> Result<Self> {
         let mut manager = WindowManager {
             client,
             windows: Vec::new(),
+            focused_id: None,
             current_workspace: None,
+            current_output: None,
+            output_order: Vec::new(),
+            history: FocusHistory::default(),
+            detection,
+            skipped: Vec::new(),
+            focused_fullscreen: false,
+            current_output_size: None,
         };
         manager.refresh()?;
         Ok(manager)
     }
 
+    /// The compositor this manager talks to, e.g. to open another
+    /// connection or subscribe to its events
+    pub fn compositor(&self) -> &C {
+        &self.client
+    }
+
     /// Refresh the window list from Sway
     /// This preserves the MRU order for existing windows
     pub fn refresh(&mut self) -> Result<()> {
-        let tree = self.client.get_tree()?;
+        let snapshot = fetch_snapshot(&mut self.client)?;
+        self.apply_snapshot(snapshot);
+        Ok(())
+    }
+
+    /// Bring the window list up to date with a snapshot read from sway,
+    /// preserving the MRU order for existing windows
+    pub fn apply_snapshot(&mut self, snapshot: Snapshot) {
+        let Snapshot {
+            tree,
+            workspaces,
+            outputs,
+        } = snapshot;
 
         // Find the currently focused window ID
         let focused_id = find_focused_window(&tree);
 
         // Save the current MRU order and collect new windows
         let old_windows = std::mem::take(&mut self.windows);
-        let current_windows = collect_windows(&tree, Cow::Borrowed(""));
+        let mut skipped = Vec::new();
+        let current_windows = collect_windows(
+            &tree,
+            Cow::Borrowed(""),
+            Cow::Borrowed(""),
+            self.detection,
+            &mut skipped,
+        );
+        if !skipped.is_empty() {
+            debug!("Skipped {} nodes that aren't windows", skipped.len());
+        }
+        self.skipped = skipped;
 
         // Preserve MRU order while merging old and new window lists
         self.windows = preserve_mru_order(old_windows, current_windows, focused_id);
@@ -86,25 +262,125 @@ impl<C: SwayClient> WindowManager<C> {
             self.windows.len(),
             focused_id
         );
-
-        // Get current workspace
-        if let Ok(workspaces) = self.client.get_workspaces() {
-            self.current_workspace = workspaces.iter().find(|w| w.focused).map(|w| w.name.clone());
+        self.focused_id = focused_id;
+        self.focused_fullscreen = focused_id.is_some_and(|id| is_fullscreen(&tree, id));
+
+        // Get current workspace and the output it is on
+        if let Some(workspaces) = workspaces
+            && let Some(focused) = workspaces.iter().find(|w| w.focused)
+        {
+            self.current_workspace = Some(focused.name.clone());
+            self.current_output = Some(focused.output.clone());
         }
 
-        Ok(())
+        // Get output layout positions for output-aware sorting
+        if let Some(outputs) = outputs {
+            self.current_output_size = outputs
+                .iter()
+                .find(|o| self.current_output.as_ref() == Some(&o.name))
+                .and_then(|o| match o.current_mode {
+                    Some(ref mode) => Some(logical_size(
+                        (mode.width, mode.height),
+                        o.transform.as_deref(),
+                        o.scale,
+                    )),
+                    None => (o.rect.width > 0).then_some((o.rect.width, o.rect.height)),
+                });
+            self.output_order = order_outputs(
+                outputs
+                    .iter()
+                    .filter(|o| o.active)
+                    .map(|o| (o.name.as_str(), o.rect.x, o.rect.y)),
+            );
+        }
     }
 
-    /// Move window to front of MRU list
+    /// Move window to front of MRU list and record it in the focus history
     pub fn on_focus(&mut self, window_id: i64) {
         if let Some(pos) = self.windows.iter().position(|w| w.id == window_id) {
-            let window = self.windows.remove(pos);
+            let mut window = self.windows.remove(pos);
+            window.last_focused = Some(SystemTime::now());
+            self.history.record(&window);
             self.windows.insert(0, window);
         }
     }
 
-    /// Get filtered windows based on workspace mode
-    pub fn get_filtered_windows(&self, mode: WorkspaceMode) -> Vec<WindowInfo> {
+    /// Focus changes, most recent first
+    pub fn history(&self) -> &FocusHistory {
+        &self.history
+    }
+
+    /// Take over the MRU order and focus history of a previous daemon.
+    /// IDs of windows that have since closed are dropped; windows the old
+    /// daemon didn't know go last. The focused window stays first.
+    pub fn restore(&mut self, mru: &[i64], history: Vec<FocusEntry>) {
+        let current = std::mem::take(&mut self.windows);
+        let restored: Vec<WindowInfo> = mru
+            .iter()
+            .filter_map(|id| current.iter().find(|w| w.id == *id).cloned())
+            .collect();
+        self.windows = preserve_mru_order(restored, current, self.focused_id);
+        for window in &mut self.windows {
+            window.last_focused = history
+                .iter()
+                .find(|entry| entry.window_id == window.id)
+                .map(|entry| entry.focused_at);
+        }
+        self.history.restore(history);
+    }
+
+    /// All known windows in MRU order
+    pub fn windows(&self) -> &[WindowInfo] {
+        &self.windows
+    }
+
+    /// Remove a window from the MRU list (e.g. after it was found to be closed)
+    pub fn remove_window(&mut self, window_id: i64) {
+        self.windows.retain(|w| w.id != window_id);
+    }
+
+    /// Get filtered windows based on workspace mode, ordered by sort mode
+    pub fn get_filtered_windows(&self, mode: WorkspaceMode, sort: SortMode) -> Vec<WindowInfo> {
+        let mut windows = self.filter_windows(mode);
+        match sort {
+            SortMode::Mru => {}
+            SortMode::Output => sort_by_output(&mut windows, &self.output_order),
+            SortMode::Workspace => sort_by_workspace_number(&mut windows),
+        }
+        if mode.current_first()
+            && let Some(ref current_ws) = self.current_workspace
+        {
+            workspace_first(&mut windows, current_ws);
+        }
+        windows
+    }
+
+    /// Nodes that looked like windows but were left out at the last refresh
+    pub fn skipped(&self) -> &[SkippedNode] {
+        &self.skipped
+    }
+
+    /// ID of the window that had focus at the last refresh
+    pub fn focused_window_id(&self) -> Option<i64> {
+        self.focused_id
+    }
+
+    /// Whether the focused window was fullscreen at the last refresh
+    pub fn focused_is_fullscreen(&self) -> bool {
+        self.focused_fullscreen
+    }
+
+    /// Size of the focused output in logical pixels, if known
+    pub fn current_output_size(&self) -> Option<(i32, i32)> {
+        self.current_output_size
+    }
+
+    /// Name of the focused workspace, if known
+    pub fn current_workspace(&self) -> Option<&str> {
+        self.current_workspace.as_deref()
+    }
+
+    fn filter_windows(&self, mode: WorkspaceMode) -> Vec<WindowInfo> {
         match mode {
             WorkspaceMode::Current => {
                 if let Some(ref current_ws) = self.current_workspace {
@@ -117,14 +393,90 @@ impl<C: SwayClient> WindowManager<C> {
                     self.windows.clone()
                 }
             }
-            WorkspaceMode::All => self.windows.clone(),
+            WorkspaceMode::All | WorkspaceMode::Hybrid | WorkspaceMode::Split => {
+                self.windows.clone()
+            }
         }
     }
 
-    /// Focus a window by ID
-    pub fn focus_window(&mut self, window_id: i64) -> Result<()> {
+    /// Focus a window by ID.
+    ///
+    /// If `workspace_first` is set and the window lives on another workspace,
+    /// switch to that workspace before focusing the window.
+    pub fn focus_window(&mut self, window_id: i64, workspace_first: bool) -> Result<()> {
+        if workspace_first {
+            let target = self
+                .windows
+                .iter()
+                .find(|w| w.id == window_id)
+                .map(|w| w.workspace.clone());
+            if let Some(target) = target
+                && needs_workspace_switch(&target, self.current_workspace.as_deref())
+            {
+                debug!("Switching to workspace '{}' before focusing {}", target, window_id);
+                return self.client.focus_window_on_workspace(window_id, &target);
+            }
+        }
         self.client.focus_window(window_id)
     }
+
+    /// Move a window to the focused workspace and focus it
+    pub fn move_window_here(&mut self, window_id: i64) -> Result<()> {
+        let workspace = self
+            .current_workspace
+            .clone()
+            .context("Focused workspace is unknown")?;
+        self.client.move_window_to_workspace(window_id, &workspace)?;
+
+        if let Some(window) = self.windows.iter_mut().find(|w| w.id == window_id) {
+            window.workspace = workspace;
+        }
+        Ok(())
+    }
+
+    /// Toggle a window between tiling and floating, and focus it
+    pub fn toggle_floating(&mut self, window_id: i64) -> Result<()> {
+        self.client.toggle_floating(window_id)
+    }
+
+    /// Close a window and forget it
+    pub fn close_window(&mut self, window_id: i64) -> Result<()> {
+        self.client.close_window(window_id)?;
+        self.remove_window(window_id);
+        Ok(())
+    }
+
+    /// Run a shell command through sway
+    pub fn exec(&mut self, command: &str) -> Result<()> {
+        self.client.exec(command)
+    }
+}
+
+/// A window's position and size in layout coordinates.
+/// Displays as grim's `x,y wxh`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Geometry {
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+}
+
+impl From<&swayipc::Rect> for Geometry {
+    fn from(rect: &swayipc::Rect) -> Self {
+        Geometry {
+            x: rect.x,
+            y: rect.y,
+            width: rect.width,
+            height: rect.height,
+        }
+    }
+}
+
+impl std::fmt::Display for Geometry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{},{} {}x{}", self.x, self.y, self.width, self.height)
+    }
 }
 
 /// Preserve MRU order while merging old and new window lists.
@@ -132,14 +484,22 @@ impl<C: SwayClient> WindowManager<C> {
 /// The resulting list has:
 /// 1. The focused window first (if any)
 /// 2. Previously known windows in their MRU order (if still present), with fresh data
-/// 3. Newly discovered windows at the end
+/// 3. Newly discovered windows at the end, in the order they were found
+///
+/// Focus times are kept from the old list.
 #[must_use]
 fn preserve_mru_order(
     old_windows: Vec<WindowInfo>,
     current_windows: Vec<WindowInfo>,
     focused_id: Option<i64>,
 ) -> Vec<WindowInfo> {
+    let focus_times: HashMap<i64, SystemTime> = old_windows
+        .iter()
+        .filter_map(|w| Some((w.id, w.last_focused?)))
+        .collect();
+
     // Build a map of current windows by ID for O(1) lookup with fresh data
+    let found_order: Vec<i64> = current_windows.iter().map(|w| w.id).collect();
     let current_by_id: HashMap<i64, WindowInfo> =
         current_windows.into_iter().map(|w| (w.id, w)).collect();
     let mut result = Vec::with_capacity(current_by_id.len());
@@ -163,24 +523,45 @@ fn preserve_mru_order(
     }
 
     // 3. Add any new windows not in the old list
-    for (id, new_win) in current_by_id {
-        if !added_ids.contains(&id) {
+    for id in found_order {
+        if added_ids.insert(id)
+            && let Some(new_win) = current_by_id.get(&id).cloned()
+        {
             result.push(new_win);
         }
     }
 
+    for window in &mut result {
+        window.last_focused = focus_times.get(&window.id).copied();
+    }
     result
 }
 
 /// Recursively collect all windows from a Sway node tree.
 /// Returns a flat list of WindowInfo structs.
 ///
-/// Uses `Cow<str>` to avoid cloning workspace names during traversal.
-/// The string is only cloned when a window is actually found.
+/// Uses `Cow<str>` to avoid cloning workspace and output names during traversal.
+/// The strings are only cloned when a window is actually found.
 #[must_use]
-fn collect_windows<'a>(node: &'a Node, current_workspace: Cow<'a, str>) -> Vec<WindowInfo> {
+fn collect_windows<'a>(
+    node: &'a Node,
+    current_workspace: Cow<'a, str>,
+    current_output: Cow<'a, str>,
+    detection: WindowDetection,
+    skipped: &mut Vec<SkippedNode>,
+) -> Vec<WindowInfo> {
     let mut windows = Vec::new();
 
+    // Update output name if we encounter an output node
+    let output: Cow<'a, str> = if node.node_type == NodeType::Output {
+        node.name
+            .as_deref()
+            .map(Cow::Borrowed)
+            .unwrap_or(current_output)
+    } else {
+        current_output
+    };
+
     // Update workspace name if we encounter a workspace node
     // Use Cow to avoid cloning unless necessary
     let workspace: Cow<'a, str> = if node.node_type == NodeType::Workspace {
@@ -192,23 +573,210 @@ fn collect_windows<'a>(node: &'a Node, current_workspace: Cow<'a, str>) -> Vec<W
         current_workspace
     };
 
-    // Add window if it's an actual window (has a pid)
+    // Add window if it's an actual window (see `classify_node`)
     // Only clone the workspace string when we actually create a WindowInfo
-    if let Some(window) = WindowInfo::from_node(node, workspace.clone().into_owned()) {
+    if let Some(window) = WindowInfo::from_node(
+        node,
+        workspace.clone().into_owned(),
+        output.clone().into_owned(),
+        detection,
+    ) {
         windows.push(window);
+    } else if let NodeKind::Skipped(reason) = classify_node(node, detection) {
+        skipped.push(SkippedNode {
+            id: node.id,
+            name: node.name.clone(),
+            app_id: node.app_id.clone(),
+            reason,
+        });
     }
 
-    // Recurse into children - borrow the workspace string
-    for child in &node.nodes {
-        windows.extend(collect_windows(child, Cow::Borrowed(&workspace)));
-    }
-    for child in &node.floating_nodes {
-        windows.extend(collect_windows(child, Cow::Borrowed(&workspace)));
+    // Recurse into children - borrow the workspace and output strings.
+    // Following the focus order makes the result an approximate MRU list.
+    for child in children_by_focus(node) {
+        windows.extend(collect_windows(
+            child,
+            Cow::Borrowed(&workspace),
+            Cow::Borrowed(&output),
+            detection,
+            skipped,
+        ));
     }
 
     windows
 }
 
+/// A node's tiling and floating children, most recently focused first
+#[must_use]
+fn children_by_focus(node: &Node) -> Vec<&Node> {
+    let mut children: Vec<&Node> = node.nodes.iter().chain(&node.floating_nodes).collect();
+    children.sort_by_key(|child| {
+        node.focus
+            .iter()
+            .position(|&id| id == child.id)
+            .unwrap_or(usize::MAX)
+    });
+    children
+}
+
+/// Order outputs by their layout position: left to right, then top to bottom.
+/// Takes `(name, x, y)` tuples and returns the output names in order.
+#[must_use]
+fn order_outputs<'a>(outputs: impl IntoIterator<Item = (&'a str, i32, i32)>) -> Vec<String> {
+    let mut outputs: Vec<_> = outputs.into_iter().collect();
+    outputs.sort_by_key(|&(name, x, y)| (x, y, name));
+    outputs.into_iter().map(|(name, _, _)| name.to_string()).collect()
+}
+
+/// An output's size in logical pixels, which surfaces are laid out in:
+/// the mode rotated by the transform, then divided by the scale
+#[must_use]
+fn logical_size(mode: (i32, i32), transform: Option<&str>, scale: Option<f64>) -> (i32, i32) {
+    let (width, height) = match transform {
+        Some("90" | "270" | "flipped-90" | "flipped-270") => (mode.1, mode.0),
+        _ => mode,
+    };
+    // Disabled outputs report a scale of -1
+    let scale = scale.filter(|&scale| scale > 0.0).unwrap_or(1.0);
+    (
+        (f64::from(width) / scale).round() as i32,
+        (f64::from(height) / scale).round() as i32,
+    )
+}
+
+/// Stable-sort windows by the layout position of their output.
+///
+/// MRU order is preserved within each output. Windows on outputs missing
+/// from `output_order` (e.g. the scratchpad) are placed last.
+fn sort_by_output(windows: &mut [WindowInfo], output_order: &[String]) {
+    windows.sort_by_key(|w| {
+        output_order
+            .iter()
+            .position(|o| o == &w.output)
+            .unwrap_or(usize::MAX)
+    });
+}
+
+/// The number sway gives a workspace: its name's leading digits, e.g. 3
+/// for `3: web`. Workspaces named without one have none.
+#[must_use]
+pub fn workspace_number(name: &str) -> Option<u32> {
+    let digits = name.len() - name.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+    name[..digits].parse().ok()
+}
+
+/// Stable-sort windows by workspace number, named workspaces last
+fn sort_by_workspace_number(windows: &mut [WindowInfo]) {
+    windows.sort_by_key(|w| workspace_number(&w.workspace).unwrap_or(u32::MAX));
+}
+
+/// Fill in the untitled format for a window of `app`
+#[must_use]
+pub fn untitled_title(format: &str, app: Option<&str>) -> String {
+    format.replace(UNTITLED_APP_PLACEHOLDER, app.unwrap_or("unknown"))
+}
+
+/// `title`, or the `untitled` format filled in for `app` if it is blank.
+/// Every list the daemon shows or prints goes through this.
+#[must_use]
+pub fn display_title<'a>(title: &'a str, app: Option<&str>, untitled: &str) -> Cow<'a, str> {
+    if title.trim().is_empty() {
+        Cow::Owned(untitled_title(untitled, app))
+    } else {
+        Cow::Borrowed(title)
+    }
+}
+
+/// Give untitled windows their display title in place
+pub fn use_display_titles(windows: &mut [WindowInfo], untitled: &str) {
+    for window in windows {
+        if let Cow::Owned(title) = window.display_title(untitled) {
+            window.title = title;
+        }
+    }
+}
+
+/// Stable-move the windows on `workspace` to the front of the list
+fn workspace_first(windows: &mut [WindowInfo], workspace: &str) {
+    windows.sort_by_key(|w| w.workspace != workspace);
+}
+
+/// The label from the first `label:` mark, if any
+#[must_use]
+fn label_from_marks(marks: &[String]) -> Option<String> {
+    marks
+        .iter()
+        .filter_map(|mark| mark.strip_prefix(LABEL_MARK_PREFIX))
+        .find(|label| !label.is_empty())
+        .map(str::to_string)
+}
+
+/// Reduce a window list to one entry per app or workspace, keeping the
+/// first (most recently used) window of each. Windows without an app key
+/// are kept as entries of their own.
+#[must_use]
+pub fn group_windows(windows: Vec<WindowInfo>, target: SwitchTarget) -> Vec<WindowInfo> {
+    let mut seen = HashSet::new();
+    match target {
+        SwitchTarget::Windows => windows,
+        SwitchTarget::Apps => windows
+            .into_iter()
+            .filter(|w| match w.app_key() {
+                Some(key) => seen.insert(key.to_lowercase()),
+                None => true,
+            })
+            .collect(),
+        SwitchTarget::Workspaces => windows
+            .into_iter()
+            .filter(|w| seen.insert(w.workspace.clone()))
+            .collect(),
+    }
+}
+
+/// How many of `windows` each entry of a list grouped by `target` stands
+/// for. Empty for `SwitchTarget::Windows`, where every entry is one window.
+#[must_use]
+pub fn group_sizes(
+    entries: &[WindowInfo],
+    windows: &[WindowInfo],
+    target: SwitchTarget,
+) -> Vec<usize> {
+    if target == SwitchTarget::Windows {
+        return Vec::new();
+    }
+    let key = |window: &WindowInfo| match target {
+        SwitchTarget::Workspaces => Some(window.workspace.clone()),
+        _ => window.app_key().map(str::to_lowercase),
+    };
+    let mut sizes: HashMap<String, usize> = HashMap::new();
+    for window in windows {
+        if let Some(key) = key(window) {
+            *sizes.entry(key).or_default() += 1;
+        }
+    }
+    entries
+        .iter()
+        .map(|entry| key(entry).and_then(|key| sizes.get(&key).copied()).unwrap_or(1))
+        .collect()
+}
+
+/// Decide whether to switch workspaces before focusing a window.
+///
+/// Switching to the workspace that is already focused would trigger sway's
+/// workspace_auto_back_and_forth, and the scratchpad is not a real workspace.
+#[must_use]
+fn needs_workspace_switch(target: &str, current: Option<&str>) -> bool {
+    !target.is_empty() && target != SCRATCHPAD_WORKSPACE && current != Some(target)
+}
+
+/// Whether the view with this ID is fullscreen, on its workspace or globally
+#[must_use]
+fn is_fullscreen(tree: &Node, window_id: i64) -> bool {
+    tree.find_as_ref(|node| node.id == window_id)
+        .and_then(|node| node.fullscreen_mode)
+        .is_some_and(|mode| mode > 0)
+}
+
 /// Find the currently focused window in a Sway node tree.
 #[must_use]
 fn find_focused_window(node: &Node) -> Option<i64> {
@@ -236,6 +804,7 @@ fn find_focused_window(node: &Node) -> Option<i64> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use proptest::prelude::*;
 
     fn make_window(id: i64, title: &str) -> WindowInfo {
         WindowInfo {
@@ -243,7 +812,12 @@ mod tests {
             app_id: Some(format!("app-{}", id)),
             title: title.to_string(),
             workspace: "1".to_string(),
+            output: "DP-1".to_string(),
             window_class: None,
+            window_instance: None,
+            label: None,
+            last_focused: None,
+            ..Default::default()
         }
     }
 
@@ -253,7 +827,12 @@ mod tests {
             app_id: Some(format!("app-{}", id)),
             title: title.to_string(),
             workspace: workspace.to_string(),
+            output: "DP-1".to_string(),
             window_class: None,
+            window_instance: None,
+            label: None,
+            last_focused: None,
+            ..Default::default()
         }
     }
 
@@ -299,10 +878,9 @@ mod tests {
 
         assert_eq!(result.len(), 3);
         assert_eq!(result[0].id, 1); // Existing window keeps position
-        // New windows at end (order may vary)
-        let new_ids: HashSet<_> = result[1..].iter().map(|w| w.id).collect();
-        assert!(new_ids.contains(&2));
-        assert!(new_ids.contains(&3));
+        // New windows at end, in the order they were found
+        assert_eq!(result[1].id, 2);
+        assert_eq!(result[2].id, 3);
     }
 
     #[test]
@@ -338,14 +916,24 @@ mod tests {
             app_id: Some("app".to_string()),
             title: "Old Title".to_string(),
             workspace: "1".to_string(),
+            output: "DP-1".to_string(),
             window_class: None,
+            window_instance: None,
+            label: None,
+            last_focused: None,
+            ..Default::default()
         }];
         let current = vec![WindowInfo {
             id: 1,
             app_id: Some("app".to_string()),
             title: "New Title".to_string(),
             workspace: "1".to_string(),
+            output: "DP-1".to_string(),
             window_class: None,
+            window_instance: None,
+            label: None,
+            last_focused: None,
+            ..Default::default()
         }];
 
         let result = preserve_mru_order(old, current, None);
@@ -354,10 +942,444 @@ mod tests {
         assert_eq!(result[0].title, "New Title"); // Should use fresh data
     }
 
+    #[test]
+    fn test_preserve_mru_order_keeps_focus_times() {
+        let focused_at = SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(100);
+        let old = vec![
+            WindowInfo {
+                last_focused: Some(focused_at),
+                ..make_window(1, "A")
+            },
+            make_window(2, "B"),
+        ];
+        let current = vec![make_window(1, "A"), make_window(2, "B")];
+
+        let result = preserve_mru_order(old, current, Some(1));
+
+        assert_eq!(result[0].last_focused, Some(focused_at));
+        assert_eq!(result[1].last_focused, None);
+    }
+
+    // ==================== preserve_mru_order properties ====================
+
+    /// An old MRU list, the windows now in the tree, and a focused ID
+    /// that may or may not be among them
+    fn mru_inputs() -> impl Strategy<Value = (Vec<i64>, Vec<i64>, Option<i64>)> {
+        (
+            prop::collection::hash_set(0i64..50, 0..20),
+            prop::collection::hash_set(0i64..50, 0..20),
+            prop::option::of(0i64..50),
+        )
+            .prop_map(|(old, current, focused)| {
+                (old.into_iter().collect(), current.into_iter().collect(), focused)
+            })
+    }
+
+    fn windows_with_ids(ids: &[i64]) -> Vec<WindowInfo> {
+        ids.iter().map(|&id| make_window(id, "W")).collect()
+    }
+
+    proptest! {
+        #[test]
+        fn prop_preserve_mru_order_keeps_exactly_live_windows(
+            (old, current, focused) in mru_inputs()
+        ) {
+            let result = preserve_mru_order(windows_with_ids(&old), windows_with_ids(&current), focused);
+            let ids: Vec<i64> = result.iter().map(|w| w.id).collect();
+
+            let unique: HashSet<i64> = ids.iter().copied().collect();
+            prop_assert_eq!(unique.len(), ids.len(), "duplicate windows in {:?}", ids);
+            prop_assert_eq!(unique, current.iter().copied().collect::<HashSet<_>>());
+        }
+
+        #[test]
+        fn prop_preserve_mru_order_puts_focused_first(
+            (old, current, focused) in mru_inputs()
+        ) {
+            let result = preserve_mru_order(windows_with_ids(&old), windows_with_ids(&current), focused);
+            if let Some(focused) = focused.filter(|id| current.contains(id)) {
+                prop_assert_eq!(result[0].id, focused);
+            }
+        }
+
+        #[test]
+        fn prop_preserve_mru_order_keeps_relative_mru_order(
+            (old, current, focused) in mru_inputs()
+        ) {
+            let result = preserve_mru_order(windows_with_ids(&old), windows_with_ids(&current), focused);
+            let position = |id: i64| result.iter().position(|w| w.id == id);
+
+            // Surviving windows from the old list stay in their old order
+            let survivors: Vec<usize> = old
+                .iter()
+                .filter(|&&id| Some(id) != focused)
+                .filter_map(|&id| position(id))
+                .collect();
+            prop_assert!(survivors.is_sorted(), "MRU order lost: {:?}", survivors);
+        }
+    }
+
+    // ==================== output ordering tests ====================
+
+    fn make_window_on_output(id: i64, output: &str) -> WindowInfo {
+        WindowInfo {
+            output: output.to_string(),
+            ..make_window(id, "W")
+        }
+    }
+
+    #[test]
+    fn test_order_outputs_left_to_right() {
+        let order = order_outputs([("HDMI-A-1", 1920, 0), ("eDP-1", 0, 0), ("DP-2", 3840, 0)]);
+        assert_eq!(order, vec!["eDP-1", "HDMI-A-1", "DP-2"]);
+    }
+
+    #[test]
+    fn test_order_outputs_stacked_top_to_bottom() {
+        let order = order_outputs([("bottom", 0, 1080), ("top", 0, 0)]);
+        assert_eq!(order, vec!["top", "bottom"]);
+    }
+
+    #[test]
+    fn test_sort_by_output_preserves_mru_within_output() {
+        let mut windows = vec![
+            make_window_on_output(1, "right"),
+            make_window_on_output(2, "left"),
+            make_window_on_output(3, "right"),
+            make_window_on_output(4, "left"),
+        ];
+        sort_by_output(&mut windows, &["left".to_string(), "right".to_string()]);
+
+        let ids: Vec<i64> = windows.iter().map(|w| w.id).collect();
+        assert_eq!(ids, vec![2, 4, 1, 3]);
+    }
+
+    #[test]
+    fn test_sort_by_output_unknown_outputs_last() {
+        let mut windows = vec![
+            make_window_on_output(1, "__i3"),
+            make_window_on_output(2, "left"),
+        ];
+        sort_by_output(&mut windows, &["left".to_string()]);
+
+        assert_eq!(windows[0].id, 2);
+        assert_eq!(windows[1].id, 1);
+    }
+
+    #[test]
+    fn test_workspace_number() {
+        assert_eq!(workspace_number("3"), Some(3));
+        assert_eq!(workspace_number("3: web"), Some(3));
+        assert_eq!(workspace_number("10:mail"), Some(10));
+        assert_eq!(workspace_number("web"), None);
+        assert_eq!(workspace_number(""), None);
+    }
+
+    #[test]
+    fn test_sort_by_workspace_number() {
+        let mut windows = vec![
+            make_window_in_workspace(1, "A", "web"),
+            make_window_in_workspace(2, "B", "10"),
+            make_window_in_workspace(3, "C", "2: code"),
+            make_window_in_workspace(4, "D", "10"),
+        ];
+        sort_by_workspace_number(&mut windows);
+
+        let ids: Vec<i64> = windows.iter().map(|w| w.id).collect();
+        assert_eq!(ids, vec![3, 2, 4, 1]);
+    }
+
+    #[test]
+    fn test_workspace_first_keeps_order_within_groups() {
+        let mut windows = vec![
+            make_window_in_workspace(1, "A", "2"),
+            make_window_in_workspace(2, "B", "1"),
+            make_window_in_workspace(3, "C", "3"),
+            make_window_in_workspace(4, "D", "1"),
+        ];
+        workspace_first(&mut windows, "1");
+
+        let ids: Vec<i64> = windows.iter().map(|w| w.id).collect();
+        assert_eq!(ids, vec![2, 4, 1, 3]);
+    }
+
+    // ==================== display_title tests ====================
+
+    #[test]
+    fn test_display_title_falls_back_for_untitled_windows() {
+        let mut window = make_window(1, "Editor");
+        assert_eq!(window.display_title("Untitled — {app}"), "Editor");
+
+        window.title = "  ".to_string();
+        assert_eq!(window.display_title("Untitled — {app}"), "Untitled — app-1");
+
+        window.app_id = None;
+        assert_eq!(window.display_title("({app})"), "(unknown)");
+
+        // The X11 class stands in for a missing app_id
+        window.window_class = Some("XTerm".to_string());
+        assert_eq!(window.display_title("({app})"), "(XTerm)");
+    }
+
+    #[test]
+    fn test_use_display_titles() {
+        let mut windows = vec![make_window(1, "Editor"), make_window(2, "")];
+        use_display_titles(&mut windows, "Untitled — {app}");
+        assert_eq!(windows[0].title, "Editor");
+        assert_eq!(windows[1].title, "Untitled — app-2");
+    }
+
+    // ==================== label_from_marks tests ====================
+
+    #[test]
+    fn test_label_from_marks() {
+        let marks = vec!["_x".to_string(), "label:".to_string(), "label:mail".to_string()];
+        assert_eq!(label_from_marks(&marks), Some("mail".to_string()));
+        assert_eq!(label_from_marks(&["work".to_string()]), None);
+        assert_eq!(label_from_marks(&[]), None);
+    }
+
+    // ==================== group_windows tests ====================
+
+    #[test]
+    fn test_group_windows_by_app_keeps_mru_window() {
+        let mut a = make_window(1, "first");
+        a.app_id = Some("firefox".to_string());
+        let mut b = make_window(2, "other");
+        b.app_id = Some("foot".to_string());
+        let mut c = make_window(3, "second");
+        c.app_id = Some("Firefox".to_string());
+        let mut d = make_window(4, "no key");
+        d.app_id = None;
+        let mut e = make_window(5, "no key either");
+        e.app_id = None;
+
+        let grouped = group_windows(vec![a, b, c, d, e], SwitchTarget::Apps);
+        let ids: Vec<i64> = grouped.iter().map(|w| w.id).collect();
+        assert_eq!(ids, vec![1, 2, 4, 5]);
+    }
+
+    #[test]
+    fn test_group_windows_by_workspace() {
+        let windows = vec![
+            make_window_in_workspace(1, "a", "2"),
+            make_window_in_workspace(2, "b", "1"),
+            make_window_in_workspace(3, "c", "2"),
+        ];
+        let grouped = group_windows(windows, SwitchTarget::Workspaces);
+        let ids: Vec<i64> = grouped.iter().map(|w| w.id).collect();
+        assert_eq!(ids, vec![1, 2]);
+    }
+
+    #[test]
+    fn test_group_windows_windows_is_identity() {
+        let windows = vec![make_window(1, "a"), make_window(2, "b")];
+        assert_eq!(group_windows(windows, SwitchTarget::Windows).len(), 2);
+    }
+
+    #[test]
+    fn test_group_sizes() {
+        let mut a = make_window_in_workspace(1, "a", "2");
+        a.app_id = Some("firefox".to_string());
+        let mut b = make_window_in_workspace(2, "b", "1");
+        b.app_id = Some("foot".to_string());
+        let mut c = make_window_in_workspace(3, "c", "2");
+        c.app_id = Some("Firefox".to_string());
+        let mut d = make_window_in_workspace(4, "d", "2");
+        d.app_id = None;
+        let windows = vec![a, b, c, d];
+
+        let apps = group_windows(windows.clone(), SwitchTarget::Apps);
+        assert_eq!(group_sizes(&apps, &windows, SwitchTarget::Apps), vec![2, 1, 1]);
+        let workspaces = group_windows(windows.clone(), SwitchTarget::Workspaces);
+        assert_eq!(
+            group_sizes(&workspaces, &windows, SwitchTarget::Workspaces),
+            vec![3, 1]
+        );
+        assert!(group_sizes(&windows, &windows, SwitchTarget::Windows).is_empty());
+        // A window closed since the list was built leaves its entry at 1
+        assert_eq!(group_sizes(&apps, &windows[1..2], SwitchTarget::Apps), vec![1, 1, 1]);
+    }
+
+    // ==================== needs_workspace_switch tests ====================
+
+    #[test]
+    fn test_needs_workspace_switch_other_workspace() {
+        assert!(needs_workspace_switch("2", Some("1")));
+    }
+
+    #[test]
+    fn test_needs_workspace_switch_same_workspace() {
+        assert!(!needs_workspace_switch("1", Some("1")));
+    }
+
+    #[test]
+    fn test_needs_workspace_switch_unknown_current() {
+        assert!(needs_workspace_switch("1", None));
+    }
+
+    #[test]
+    fn test_needs_workspace_switch_skips_scratchpad_and_empty() {
+        assert!(!needs_workspace_switch(SCRATCHPAD_WORKSPACE, Some("1")));
+        assert!(!needs_workspace_switch("", Some("1")));
+    }
+
+    // ==================== classify_node tests ====================
+
+    /// swayipc::Node is #[non_exhaustive], so build one from sway's JSON
+    fn make_node(node_type: &str, extra: serde_json::Value) -> Node {
+        let rect = serde_json::json!({"x": 0, "y": 0, "width": 0, "height": 0});
+        let mut node = serde_json::json!({
+            "id": 7,
+            "name": "node",
+            "type": node_type,
+            "border": "none",
+            "current_border_width": 0,
+            "layout": "splith",
+            "rect": rect,
+            "window_rect": rect,
+            "deco_rect": rect,
+            "geometry": rect,
+            "urgent": false,
+            "focused": false,
+            "focus": [],
+            "floating_nodes": [],
+            "sticky": false,
+        });
+        node.as_object_mut()
+            .unwrap()
+            .extend(extra.as_object().unwrap().clone());
+        serde_json::from_value(node).unwrap()
+    }
+
+    #[test]
+    fn test_classify_node_by_pid() {
+        let view = make_node("con", serde_json::json!({"pid": 42}));
+        assert_eq!(classify_node(&view, WindowDetection::Pid), NodeKind::Window);
+
+        let floating = make_node("floating_con", serde_json::json!({"pid": 42}));
+        assert_eq!(classify_node(&floating, WindowDetection::Pid), NodeKind::Window);
+
+        let workspace = make_node("workspace", serde_json::json!({}));
+        assert_eq!(classify_node(&workspace, WindowDetection::Pid), NodeKind::Container);
+    }
+
+    #[test]
+    fn test_classify_node_without_pid() {
+        let view = make_node("con", serde_json::json!({"app_id": "popup"}));
+        assert_eq!(classify_node(&view, WindowDetection::Pid), NodeKind::Skipped("no pid"));
+        assert_eq!(classify_node(&view, WindowDetection::Lenient), NodeKind::Window);
+
+        let empty = make_node("con", serde_json::json!({}));
+        assert!(matches!(
+            classify_node(&empty, WindowDetection::Lenient),
+            NodeKind::Skipped(_)
+        ));
+
+        // Split containers have children and are not reported
+        let split = make_node("con", serde_json::json!({"nodes": [view_json()]}));
+        assert_eq!(classify_node(&split, WindowDetection::Pid), NodeKind::Container);
+    }
+
+    #[test]
+    fn test_logical_size() {
+        assert_eq!(logical_size((1920, 1080), Some("normal"), Some(1.0)), (1920, 1080));
+        assert_eq!(logical_size((1920, 1080), Some("90"), Some(1.0)), (1080, 1920));
+        assert_eq!(logical_size((3840, 2160), Some("flipped-270"), Some(2.0)), (1080, 1920));
+        assert_eq!(logical_size((2560, 1440), None, Some(1.25)), (2048, 1152));
+        assert_eq!(logical_size((1280, 720), Some("180"), Some(-1.0)), (1280, 720));
+    }
+
+    #[test]
+    fn test_is_fullscreen() {
+        let mut tree = make_node("workspace", serde_json::json!({"id": 1}));
+        tree.nodes = vec![
+            make_node("con", serde_json::json!({"id": 2, "pid": 1, "fullscreen_mode": 0})),
+            make_node("con", serde_json::json!({"id": 3, "pid": 1, "fullscreen_mode": 1})),
+        ];
+        assert!(!is_fullscreen(&tree, 2));
+        assert!(is_fullscreen(&tree, 3));
+        assert!(!is_fullscreen(&tree, 4));
+    }
+
+    fn view_json() -> serde_json::Value {
+        serde_json::to_value(make_node("con", serde_json::json!({"pid": 1}))).unwrap()
+    }
+
+    #[test]
+    fn test_collect_windows_reports_skipped_nodes() {
+        let mut tree = make_node(
+            "workspace",
+            serde_json::json!({"name": "1", "nodes": [view_json()]}),
+        );
+        tree.floating_nodes = vec![make_node(
+            "floating_con",
+            serde_json::json!({"id": 9, "name": "odd", "app_id": "odd"}),
+        )];
+
+        let mut skipped = Vec::new();
+        let windows = collect_windows(
+            &tree,
+            Cow::Borrowed(""),
+            Cow::Borrowed(""),
+            WindowDetection::Pid,
+            &mut skipped,
+        );
+        assert_eq!(windows.len(), 1);
+        assert_eq!(windows[0].workspace, "1");
+        assert_eq!(skipped.len(), 1);
+        assert_eq!(skipped[0].id, 9);
+        assert_eq!(skipped[0].app_id.as_deref(), Some("odd"));
+
+        let mut skipped = Vec::new();
+        let windows = collect_windows(
+            &tree,
+            Cow::Borrowed(""),
+            Cow::Borrowed(""),
+            WindowDetection::Lenient,
+            &mut skipped,
+        );
+        assert_eq!(windows.len(), 2);
+        assert!(skipped.is_empty());
+    }
+
+    #[test]
+    fn test_from_node_state() {
+        let node = make_node(
+            "floating_con",
+            serde_json::json!({
+                "pid": 42,
+                "urgent": true,
+                "fullscreen_mode": 1,
+                "rect": {"x": 10, "y": 20, "width": 640, "height": 480},
+            }),
+        );
+        let window = WindowInfo::from_node(
+            &node,
+            "1".to_string(),
+            "DP-1".to_string(),
+            WindowDetection::Pid,
+        )
+        .unwrap();
+        assert_eq!(window.geometry.to_string(), "10,20 640x480");
+        assert!(window.floating && window.fullscreen && window.urgent);
+        assert_eq!(window.pid, Some(42));
+    }
+
+    #[test]
+    fn test_children_by_focus() {
+        let mut tree = make_node("workspace", serde_json::json!({"focus": [3, 1]}));
+        tree.nodes = vec![
+            make_node("con", serde_json::json!({"id": 1})),
+            make_node("con", serde_json::json!({"id": 2})),
+        ];
+        tree.floating_nodes = vec![make_node("floating_con", serde_json::json!({"id": 3}))];
+
+        let ids: Vec<i64> = children_by_focus(&tree).iter().map(|n| n.id).collect();
+        assert_eq!(ids, vec![3, 1, 2]);
+    }
+
     // ==================== WindowInfo tests ====================
-    // Note: swayipc::Node is #[non_exhaustive] so we cannot construct it directly in tests.
-    // WindowInfo::from_node is tested via integration tests with a real Sway connection.
-    // The logic is simple: check node_type == Con && pid.is_some()
 
     #[test]
     fn test_window_info_fields() {
@@ -366,7 +1388,12 @@ mod tests {
             app_id: Some("alacritty".to_string()),
             title: "Terminal".to_string(),
             workspace: "1".to_string(),
+            output: "DP-1".to_string(),
             window_class: Some("Alacritty".to_string()),
+            window_instance: Some("Alacritty".to_string()),
+            label: None,
+            last_focused: None,
+            ..Default::default()
         };
 
         assert_eq!(info.id, 42);
@@ -374,6 +1401,7 @@ mod tests {
         assert_eq!(info.title, "Terminal");
         assert_eq!(info.workspace, "1");
         assert_eq!(info.window_class, Some("Alacritty".to_string()));
+        assert_eq!(info.window_instance, Some("Alacritty".to_string()));
     }
 
     #[test]
@@ -383,7 +1411,12 @@ mod tests {
             app_id: None,
             title: String::new(),
             workspace: "2".to_string(),
+            output: "DP-1".to_string(),
             window_class: None,
+            window_instance: None,
+            label: None,
+            last_focused: None,
+            ..Default::default()
         };
 
         assert!(info.app_id.is_none());
@@ -391,11 +1424,69 @@ mod tests {
         assert!(info.title.is_empty());
     }
 
+    #[test]
+    fn test_icon_candidates_order() {
+        let info = WindowInfo {
+            app_id: None,
+            window_class: Some("Steam".to_string()),
+            window_instance: Some("steamwebhelper".to_string()),
+            ..make_window(1, "Steam")
+        };
+
+        assert_eq!(info.icon_candidates(), vec!["Steam", "steamwebhelper"]);
+    }
+
+    #[test]
+    fn test_icon_candidates_app_id_first() {
+        let info = WindowInfo {
+            window_class: Some("Firefox".to_string()),
+            ..make_window(1, "Browser")
+        };
+
+        assert_eq!(info.icon_candidates(), vec!["app-1", "Firefox"]);
+    }
+
+    #[test]
+    fn test_icon_candidates_skips_empty_and_duplicates() {
+        let info = WindowInfo {
+            app_id: Some(String::new()),
+            window_class: Some("Gimp".to_string()),
+            window_instance: Some("gimp".to_string()),
+            ..make_window(1, "GIMP")
+        };
+
+        assert_eq!(info.icon_candidates(), vec!["Gimp"]);
+    }
+
+    #[test]
+    fn test_app_key_prefers_app_id_then_class() {
+        let wayland = make_window(1, "A");
+        assert_eq!(wayland.app_key(), Some("app-1"));
+
+        let x11 = WindowInfo {
+            app_id: None,
+            window_class: Some("Gimp".to_string()),
+            ..make_window(2, "B")
+        };
+        assert_eq!(x11.app_key(), Some("Gimp"));
+    }
+
+    #[test]
+    fn test_icon_candidates_none() {
+        let info = WindowInfo {
+            app_id: None,
+            ..make_window(1, "Unknown")
+        };
+
+        assert!(info.icon_candidates().is_empty());
+    }
+
     // ==================== get_filtered_windows tests ====================
-    // Note: Full WindowManager tests would require mocking SwayClient.
+    // Note: Full WindowManager tests would require mocking Compositor.
     // These tests focus on the pure helper functions and filtering logic.
 
     #[test]
+    #[allow(clippy::useless_vec, clippy::op_ref)]
     fn test_window_info_workspace_filter_logic() {
         // Test the filtering logic directly
         let windows = vec![
@@ -418,6 +1509,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::useless_vec)]
     fn test_window_info_no_filter_all_workspaces() {
         let windows = vec![
             make_window_in_workspace(1, "A", "1"),
@@ -12,6 +12,8 @@ use crate::window_manager::WindowInfo;
 pub struct WindowSwitcher {
     windows: Vec<WindowInfo>,
     current_index: usize,
+    /// Full window list, stashed while expanded to a single application
+    collapsed: Option<Vec<WindowInfo>>,
 }
 
 impl WindowSwitcher {
@@ -29,6 +31,7 @@ impl WindowSwitcher {
         WindowSwitcher {
             windows,
             current_index,
+            collapsed: None,
         }
     }
 
@@ -56,6 +59,118 @@ impl WindowSwitcher {
         self.windows.is_empty()
     }
 
+    /// Check if the list is currently expanded to a single application.
+    #[must_use]
+    pub fn is_expanded(&self) -> bool {
+        self.collapsed.is_some()
+    }
+
+    /// Narrow the list to the windows of the selected window's application,
+    /// keeping the selection on the same window.
+    ///
+    /// Returns false if already expanded or the selection has no app identity.
+    pub fn expand_app(&mut self) -> bool {
+        if self.is_expanded() {
+            return false;
+        }
+        let Some(selected) = self.current() else {
+            return false;
+        };
+        let Some(app_key) = selected.app_key().map(str::to_string) else {
+            return false;
+        };
+        let selected_id = selected.id;
+
+        let app_windows: Vec<WindowInfo> = self
+            .windows
+            .iter()
+            .filter(|w| w.app_key() == Some(app_key.as_str()))
+            .cloned()
+            .collect();
+
+        self.collapsed = Some(std::mem::replace(&mut self.windows, app_windows));
+        self.select_window(selected_id);
+        true
+    }
+
+    /// Restore the full window list, keeping the selection on the same window.
+    ///
+    /// Returns false if not expanded.
+    pub fn collapse(&mut self) -> bool {
+        let Some(windows) = self.collapsed.take() else {
+            return false;
+        };
+        let selected_id = self.current().map(|w| w.id);
+
+        self.windows = windows;
+        self.current_index = 0;
+        if let Some(id) = selected_id {
+            self.select_window(id);
+        }
+        true
+    }
+
+    /// Move the selection to the window with the given ID.
+    ///
+    /// Returns false (leaving the selection unchanged) if it isn't in the list.
+    pub fn select_window(&mut self, window_id: i64) -> bool {
+        match self.windows.iter().position(|w| w.id == window_id) {
+            Some(index) => {
+                self.current_index = index;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Move the selection to the given index.
+    ///
+    /// Returns false (leaving the selection unchanged) if it is out of range.
+    pub fn select_index(&mut self, index: usize) -> bool {
+        if index < self.windows.len() {
+            self.current_index = index;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Take the selected window out of the list (and out of the stashed
+    /// full list when expanded), selecting the one that took its place
+    pub fn remove_current(&mut self) -> Option<WindowInfo> {
+        if self.current_index >= self.windows.len() {
+            return None;
+        }
+        let removed = self.windows.remove(self.current_index);
+        if let Some(ref mut collapsed) = self.collapsed {
+            collapsed.retain(|w| w.id != removed.id);
+        }
+        self.current_index = self.current_index.min(self.windows.len().saturating_sub(1));
+        Some(removed)
+    }
+
+    /// Iterate over the windows to try when finalizing a selection.
+    ///
+    /// Yields the current window first, followed by the remaining windows
+    /// in MRU order (wrapping around), so a vanished selection can fall
+    /// back to the next candidate.
+    pub fn selection_candidates(&self) -> impl Iterator<Item = &WindowInfo> {
+        let len = self.windows.len();
+        (0..len).map(move |offset| &self.windows[(self.current_index + offset) % len])
+    }
+
+    /// Whether cycling in this direction would wrap past the end of the list
+    #[must_use]
+    pub fn would_wrap(&self, forward: bool) -> bool {
+        let len = self.windows.len();
+        len > 1
+            && if forward {
+                self.current_index == len - 1
+            } else {
+                self.current_index == 0
+            }
+    }
+
     /// Cycle to the next or previous window.
     ///
     /// Returns the new current index.
@@ -80,6 +195,7 @@ impl WindowSwitcher {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use proptest::prelude::*;
 
     fn make_window(id: i64, title: &str) -> WindowInfo {
         WindowInfo {
@@ -87,7 +203,12 @@ mod tests {
             app_id: Some(format!("app-{}", id)),
             title: title.to_string(),
             workspace: "1".to_string(),
+            output: "DP-1".to_string(),
             window_class: None,
+            window_instance: None,
+            label: None,
+            last_focused: None,
+            ..Default::default()
         }
     }
 
@@ -150,6 +271,22 @@ mod tests {
         assert_eq!(switcher.current_index(), 0);
     }
 
+    #[test]
+    fn test_would_wrap() {
+        let windows = vec![make_window(1, "A"), make_window(2, "B"), make_window(3, "C")];
+        let mut switcher = WindowSwitcher::new(windows, false);
+
+        assert!(!switcher.would_wrap(true));
+        assert!(switcher.would_wrap(false));
+        switcher.cycle(false);
+        assert!(switcher.would_wrap(true));
+        assert!(!switcher.would_wrap(false));
+
+        let single = WindowSwitcher::new(vec![make_window(1, "A")], false);
+        assert!(!single.would_wrap(true));
+        assert!(!single.would_wrap(false));
+    }
+
     #[test]
     fn test_cycle_empty() {
         let mut switcher = WindowSwitcher::new(vec![], false);
@@ -157,4 +294,206 @@ mod tests {
         assert_eq!(switcher.cycle(false), 0);
     }
 
+    #[test]
+    fn test_selection_candidates_start_at_current_and_wrap() {
+        let windows = vec![make_window(1, "A"), make_window(2, "B"), make_window(3, "C")];
+        let mut switcher = WindowSwitcher::new(windows, false);
+        switcher.cycle(true);
+
+        let ids: Vec<i64> = switcher.selection_candidates().map(|w| w.id).collect();
+        assert_eq!(ids, vec![2, 3, 1]);
+    }
+
+    #[test]
+    fn test_select_window() {
+        let windows = vec![make_window(1, "A"), make_window(2, "B"), make_window(3, "C")];
+        let mut switcher = WindowSwitcher::new(windows, false);
+
+        assert!(switcher.select_window(3));
+        assert_eq!(switcher.current_index(), 2);
+    }
+
+    #[test]
+    fn test_select_window_missing_keeps_selection() {
+        let windows = vec![make_window(1, "A"), make_window(2, "B")];
+        let mut switcher = WindowSwitcher::new(windows, true);
+
+        assert!(!switcher.select_window(42));
+        assert_eq!(switcher.current_index(), 1);
+    }
+
+    #[test]
+    fn test_select_index() {
+        let windows = vec![make_window(1, "A"), make_window(2, "B")];
+        let mut switcher = WindowSwitcher::new(windows, false);
+
+        assert!(switcher.select_index(1));
+        assert_eq!(switcher.current_index(), 1);
+        assert!(!switcher.select_index(2));
+        assert_eq!(switcher.current_index(), 1);
+    }
+
+    #[test]
+    fn test_remove_current_selects_neighbour() {
+        let windows = vec![make_window(1, "A"), make_window(2, "B"), make_window(3, "C")];
+        let mut switcher = WindowSwitcher::new(windows, false);
+
+        switcher.select_index(2);
+        assert_eq!(switcher.remove_current().map(|w| w.id), Some(3));
+        assert_eq!(switcher.current().map(|w| w.id), Some(2));
+        switcher.select_index(0);
+        assert_eq!(switcher.remove_current().map(|w| w.id), Some(1));
+        assert_eq!(switcher.current().map(|w| w.id), Some(2));
+        assert_eq!(switcher.remove_current().map(|w| w.id), Some(2));
+        assert!(switcher.remove_current().is_none());
+    }
+
+    fn make_app_window(id: i64, app_id: &str) -> WindowInfo {
+        WindowInfo {
+            app_id: Some(app_id.to_string()),
+            ..make_window(id, "W")
+        }
+    }
+
+    #[test]
+    fn test_expand_app_keeps_only_same_app() {
+        let windows = vec![
+            make_app_window(1, "firefox"),
+            make_app_window(2, "alacritty"),
+            make_app_window(3, "firefox"),
+            make_app_window(4, "alacritty"),
+        ];
+        let mut switcher = WindowSwitcher::new(windows, true);
+
+        assert!(switcher.expand_app());
+        assert!(switcher.is_expanded());
+        let ids: Vec<i64> = switcher.windows().iter().map(|w| w.id).collect();
+        assert_eq!(ids, vec![2, 4]);
+        assert_eq!(switcher.current().unwrap().id, 2);
+    }
+
+    #[test]
+    fn test_expand_app_twice_is_noop() {
+        let windows = vec![make_app_window(1, "firefox"), make_app_window(2, "firefox")];
+        let mut switcher = WindowSwitcher::new(windows, false);
+
+        assert!(switcher.expand_app());
+        assert!(!switcher.expand_app());
+    }
+
+    #[test]
+    fn test_collapse_restores_list_and_selection() {
+        let windows = vec![
+            make_app_window(1, "firefox"),
+            make_app_window(2, "alacritty"),
+            make_app_window(3, "firefox"),
+        ];
+        let mut switcher = WindowSwitcher::new(windows, false);
+
+        switcher.expand_app();
+        switcher.cycle(true); // Select window 3 within firefox windows
+        assert!(switcher.collapse());
+
+        assert!(!switcher.is_expanded());
+        assert_eq!(switcher.windows().len(), 3);
+        assert_eq!(switcher.current().unwrap().id, 3);
+        assert_eq!(switcher.current_index(), 2);
+    }
+
+    #[test]
+    fn test_collapse_when_not_expanded() {
+        let mut switcher = WindowSwitcher::new(vec![make_window(1, "A")], false);
+        assert!(!switcher.collapse());
+    }
+
+    #[test]
+    fn test_selection_candidates_empty() {
+        let switcher = WindowSwitcher::new(vec![], false);
+        assert_eq!(switcher.selection_candidates().count(), 0);
+    }
+
+    // ==================== properties ====================
+
+    #[derive(Debug, Clone, Copy)]
+    enum Op {
+        Cycle(bool),
+        Expand,
+        Collapse,
+        Select(i64),
+    }
+
+    fn op() -> impl Strategy<Value = Op> {
+        prop_oneof![
+            any::<bool>().prop_map(Op::Cycle),
+            Just(Op::Expand),
+            Just(Op::Collapse),
+            (0i64..30).prop_map(Op::Select),
+        ]
+    }
+
+    /// Windows with unique IDs, a few apps shared between them
+    fn windows() -> impl Strategy<Value = Vec<WindowInfo>> {
+        prop::collection::hash_set(0i64..30, 0..15).prop_map(|ids| {
+            ids.into_iter()
+                .map(|id| WindowInfo {
+                    app_id: Some(format!("app-{}", id % 4)),
+                    ..make_window(id, "W")
+                })
+                .collect()
+        })
+    }
+
+    proptest! {
+        #[test]
+        fn prop_selection_stays_in_bounds(
+            windows in windows(),
+            start_at_next in any::<bool>(),
+            ops in prop::collection::vec(op(), 0..40),
+        ) {
+            let all_ids: Vec<i64> = windows.iter().map(|w| w.id).collect();
+            let mut switcher = WindowSwitcher::new(windows, start_at_next);
+
+            for op in ops {
+                match op {
+                    Op::Cycle(forward) => {
+                        let index = switcher.cycle(forward);
+                        prop_assert_eq!(index, switcher.current_index());
+                    }
+                    Op::Expand => {
+                        switcher.expand_app();
+                    }
+                    Op::Collapse => {
+                        switcher.collapse();
+                    }
+                    Op::Select(id) => {
+                        switcher.select_window(id);
+                    }
+                }
+
+                if switcher.is_empty() {
+                    prop_assert_eq!(switcher.current_index(), 0);
+                } else {
+                    prop_assert!(switcher.current_index() < switcher.windows().len());
+                    prop_assert!(switcher.current().is_some());
+                }
+                prop_assert_eq!(switcher.selection_candidates().count(), switcher.windows().len());
+            }
+
+            // Collapsing always gets back the full list in its original order
+            switcher.collapse();
+            let ids: Vec<i64> = switcher.windows().iter().map(|w| w.id).collect();
+            prop_assert_eq!(ids, all_ids);
+        }
+
+        #[test]
+        fn prop_full_cycle_returns_to_start(windows in windows(), forward in any::<bool>()) {
+            let len = windows.len();
+            let mut switcher = WindowSwitcher::new(windows, true);
+            let start = switcher.current_index();
+            for _ in 0..len {
+                switcher.cycle(forward);
+            }
+            prop_assert_eq!(switcher.current_index(), start);
+        }
+    }
 }